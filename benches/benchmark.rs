@@ -1,41 +1,132 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use font::{Node, Rectangle};
 use font::atlas::{AtlasGenerator, AtlasGeneratorOption, AtlasLoadMode, Padding};
-
-use font::*;
+use font::layout::TextLayout;
+use font::loader::{FontLoader, FontSystem};
+use font::multi_atlas::MultiFontAtlasGenerator;
+use font::pixels::expand_gray;
 
 const FONT_SIZE: u32 = 24 * 64;
-const GLYPHS: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789\\|/?.>,<`!@#$%^&*()_-=+[]{};:'\" ";
+
+/// The OFL licensed font shipped in the repository, so that the benches run from any checkout.
+const FONT_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/fonts/EBGaramond-Regular.ttf");
+
+const PARAGRAPH: &str = "The quick brown fox jumps over the lazy dog. Pack my box with five dozen liquor jugs! \
+    How vexingly quick daft zebras jump; the five boxing wizards jump quickly. \
+    Sphinx of black quartz, judge my vow: 0123456789 (){}[]<>.";
 
 fn benchmark_1024_subpixel(c: &mut Criterion) {
 
+    let font_system = FontSystem::new().unwrap();
+
     let generator = AtlasGenerator::new(
-	&"/home/corendos/dev/rust/font/resources/fonts/EBGaramond-Regular.ttf",
-	AtlasGeneratorOption::new(1024, 1024, 72, Padding::new(1, 1, 1, 1)),
+	&font_system,
+	FONT_PATH,
+	AtlasGeneratorOption::new(1024, 1024, 72, Padding::uniform(1)),
 	AtlasLoadMode::LCD
-    );
+    ).unwrap();
 
     c.bench_function("1024_1024_subpixel", |b| b.iter(|| {
-	let font_atlas = generator.generate(FONT_SIZE).unwrap();
+	black_box(generator.generate(FONT_SIZE).unwrap());
     }));
 }
 
 
 fn benchmark_1024_gray(c: &mut Criterion) {
 
+    let font_system = FontSystem::new().unwrap();
+
     let generator = AtlasGenerator::new(
-	&"/home/corendos/dev/rust/font/resources/fonts/EBGaramond-Regular.ttf",
-	AtlasGeneratorOption::new(1024, 1024, 72, Padding::new(1, 1, 1, 1)),
+	&font_system,
+	FONT_PATH,
+	AtlasGeneratorOption::new(1024, 1024, 72, Padding::uniform(1)),
 	AtlasLoadMode::Gray
-    );
+    ).unwrap();
 
     c.bench_function("1024_1024_gray", |b| b.iter(|| {
-	let font_atlas = generator.generate(FONT_SIZE).unwrap();
+	black_box(generator.generate(FONT_SIZE).unwrap());
+    }));
+}
+
+/// Generates a 1024x1024 atlas rendering every glyph again, so that each one goes through the bitmap conversion.
+fn benchmark_1024_uncached(c: &mut Criterion, name: &str, load_mode: AtlasLoadMode) {
+    let font_system = FontSystem::new().unwrap();
+
+    let font_loader = FontLoader::new(&font_system, FONT_PATH).unwrap();
+    font_loader.set_cache_capacity(Some(0));
+
+    let mut generator = MultiFontAtlasGenerator::new(AtlasGeneratorOption::new(1024, 1024, 72, Padding::uniform(1)), load_mode);
+    let charset: String = (' '..='\u{24f}').filter(|c| !c.is_control()).collect();
+    generator.add_font(font_loader, &charset);
+
+    c.bench_function(name, |b| b.iter(|| {
+	black_box(generator.generate(FONT_SIZE).unwrap());
+    }));
+}
+
+fn benchmark_1024_subpixel_uncached(c: &mut Criterion) {
+    benchmark_1024_uncached(c, "1024_1024_subpixel_uncached", AtlasLoadMode::LCD);
+}
+
+fn benchmark_1024_gray_uncached(c: &mut Criterion) {
+    benchmark_1024_uncached(c, "1024_1024_gray_uncached", AtlasLoadMode::Gray);
+}
+
+/// Expands a 1024x1024 gray bitmap to RGB one pixel at a time, as glyphs were converted before, and by blocks.
+fn benchmark_gray_expansion(c: &mut Criterion) {
+    let gray: Vec<u8> = (0..1024 * 1024u32).map(|i| (i * 31 % 251) as u8).collect();
+    let mut rgb = vec![0u8; gray.len() * 3];
+
+    c.bench_function("gray_expansion_per_pixel", |b| b.iter(|| {
+	for (pixel, value) in rgb.chunks_exact_mut(3).zip(black_box(&gray)) {
+	    pixel[0] = *value;
+	    pixel[1] = *value;
+	    pixel[2] = *value;
+	}
+	black_box(&rgb);
+    }));
+
+    c.bench_function("gray_expansion_blocks", |b| b.iter(|| {
+	expand_gray(black_box(&gray), &mut rgb);
+	black_box(&rgb);
+    }));
+}
+
+/// Packs 2000 rectangles of glyph-like sizes in a 2048x2048 tree, without rendering anything.
+fn benchmark_packing(c: &mut Criterion) {
+    let sizes: Vec<Rectangle> = (0..2000u32).map(|i| Rectangle::new(0, 0, 4 + i * 7 % 29, 10 + i * 13 % 23)).collect();
+
+    c.bench_function("packing_2000", |b| b.iter(|| {
+	let mut node = Node::new(Rectangle::new(0, 0, 2048, 2048));
+	for size in sizes.iter() {
+	    black_box(node.insert(size).unwrap());
+	}
+    }));
+}
+
+/// Lays a paragraph out in lines of 400 pixels with an atlas generated once.
+fn benchmark_layout(c: &mut Criterion) {
+    let font_system = FontSystem::new().unwrap();
+
+    let generator = AtlasGenerator::new(
+	&font_system,
+	FONT_PATH,
+	AtlasGeneratorOption::new(512, 512, 72, Padding::uniform(1)),
+	AtlasLoadMode::Gray
+    ).unwrap();
+    let atlas = generator.generate(FONT_SIZE).unwrap();
+
+    c.bench_function("layout_paragraph", |b| b.iter(|| {
+	black_box(TextLayout::new(black_box(PARAGRAPH), &atlas, Some(400)));
     }));
 }
 
 criterion_group!{
-    name = benches;
+    name = generation;
     config = Criterion::default().measurement_time(std::time::Duration::from_secs(30));
     targets = benchmark_1024_subpixel, benchmark_1024_gray
 }
-criterion_main!(benches);
+criterion_group!(conversion, benchmark_1024_subpixel_uncached, benchmark_1024_gray_uncached, benchmark_gray_expansion);
+criterion_group!(packing, benchmark_packing);
+criterion_group!(layout, benchmark_layout);
+criterion_main!(generation, conversion, packing, layout);
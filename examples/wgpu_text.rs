@@ -0,0 +1,304 @@
+use std::borrow::Cow;
+
+use wgpu::util::DeviceExt;
+
+use font::atlas::{AtlasGenerator, AtlasGeneratorOption, AtlasLoadMode, FontAtlas, Padding};
+use font::layout::{LayoutConfig, TextLayout};
+use font::loader::FontSystem;
+
+const WIDTH: u32 = 640;
+const HEIGHT: u32 = 360;
+const MARGIN: i32 = 24;
+const FONT_SIZE: u32 = 24 * 64;
+const OUTPUT_PATH: &str = "wgpu_text.png";
+
+const TEXT: &str = "The quick brown fox jumps over the lazy dog. \
+    This paragraph is laid out with the crate geometry API, wrapped at word boundaries, \
+    and drawn with subpixel coverage from an LCD atlas.\n\
+    Sphinx of black quartz, judge my vow!";
+
+const BACKGROUND: [f32; 4] = [0.08, 0.09, 0.12, 1.0];
+const FOREGROUND: [f32; 4] = [0.95, 0.85, 0.60, 1.0];
+
+// The layout is generated with the wgpu conventions: positions are in pixels with a y-down axis,
+// and the atlas UVs have their origin at the top-left corner.
+//
+// LCD coverage differs per channel, which can't be expressed with a single alpha. Instead of
+// relying on dual-source blending, the text is drawn twice: the first pass multiplies the target
+// by (1 - coverage) and the second one adds color * coverage.
+const SHADER: &str = r#"
+struct Uniforms {
+    viewport: vec2<f32>,
+    color: vec4<f32>,
+};
+
+@group(0) @binding(0) var<uniform> uniforms: Uniforms;
+@group(0) @binding(1) var atlas_texture: texture_2d<f32>;
+@group(0) @binding(2) var atlas_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@location(0) position: vec2<f32>, @location(1) uv: vec2<f32>) -> VertexOutput {
+    var out: VertexOutput;
+    let ndc = position / uniforms.viewport * 2.0 - 1.0;
+    out.position = vec4<f32>(ndc.x, -ndc.y, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+@fragment
+fn fs_coverage(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(textureSample(atlas_texture, atlas_sampler, in.uv).rgb, 1.0);
+}
+
+@fragment
+fn fs_color(in: VertexOutput) -> @location(0) vec4<f32> {
+    let coverage = textureSample(atlas_texture, atlas_sampler, in.uv).rgb;
+    return vec4<f32>(uniforms.color.rgb * coverage, 1.0);
+}
+"#;
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct Vertex {
+    position: [f32; 2],
+    uv: [f32; 2],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct Uniforms {
+    viewport: [f32; 2],
+    _padding: [f32; 2],
+    color: [f32; 4],
+}
+
+fn generate_atlas() -> FontAtlas {
+    let font_system = FontSystem::new().unwrap();
+    let font_path = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/fonts/EBGaramond-Regular.ttf");
+
+    let generator = AtlasGenerator::new(
+	&font_system,
+	font_path,
+	AtlasGeneratorOption::new(512, 512, 72, Padding::uniform(1)),
+	AtlasLoadMode::LCD
+    ).unwrap();
+
+    generator.generate(FONT_SIZE).unwrap()
+}
+
+fn create_pipeline(device: &wgpu::Device, layout: &wgpu::PipelineLayout, module: &wgpu::ShaderModule, fragment_entry: &str, blend: wgpu::BlendComponent) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+	label: Some(fragment_entry),
+	layout: Some(layout),
+	vertex: wgpu::VertexState {
+	    module,
+	    entry_point: "vs_main",
+	    compilation_options: Default::default(),
+	    buffers: &[wgpu::VertexBufferLayout {
+		array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
+		step_mode: wgpu::VertexStepMode::Vertex,
+		attributes: &wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2],
+	    }],
+	},
+	primitive: wgpu::PrimitiveState::default(),
+	depth_stencil: None,
+	multisample: wgpu::MultisampleState::default(),
+	fragment: Some(wgpu::FragmentState {
+	    module,
+	    entry_point: fragment_entry,
+	    compilation_options: Default::default(),
+	    targets: &[Some(wgpu::ColorTargetState {
+		format: wgpu::TextureFormat::Rgba8UnormSrgb,
+		blend: Some(wgpu::BlendState {
+		    color: blend,
+		    alpha: wgpu::BlendComponent::OVER,
+		}),
+		write_mask: wgpu::ColorWrites::ALL,
+	    })],
+	}),
+	multiview: None,
+	cache: None,
+    })
+}
+
+async fn run() {
+    let mut atlas = generate_atlas();
+
+    let layout = TextLayout::new(TEXT, &atlas, Some(WIDTH - 2 * MARGIN as u32)).with_config(LayoutConfig::top_left());
+    let vertices: Vec<Vertex> = layout.generate_buffers(&atlas, MARGIN, MARGIN)
+	.iter()
+	.map(|vertex| Vertex { position: vertex.position, uv: vertex.uv })
+	.collect();
+
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+    let adapter = instance.request_adapter(&wgpu::RequestAdapterOptions::default())
+	.await
+	.expect("No suitable GPU adapter found");
+    let (device, queue) = adapter.request_device(&wgpu::DeviceDescriptor::default(), None)
+	.await
+	.expect("Failed to create device");
+
+    let atlas_texture = atlas.create_texture(&device, &queue);
+
+    // Glyphs are drawn at integer positions with their exact size, so nearest filtering is enough
+    // and never samples the padding of neighbouring glyphs.
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor::default());
+
+    let uniforms = Uniforms { viewport: [WIDTH as f32, HEIGHT as f32], _padding: [0.0; 2], color: FOREGROUND };
+    let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+	label: Some("uniforms"),
+	contents: bytemuck::bytes_of(&uniforms),
+	usage: wgpu::BufferUsages::UNIFORM,
+    });
+    let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+	label: Some("vertices"),
+	contents: bytemuck::cast_slice(&vertices),
+	usage: wgpu::BufferUsages::VERTEX,
+    });
+
+    let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+	label: Some("text"),
+	entries: &[
+	    wgpu::BindGroupLayoutEntry {
+		binding: 0,
+		visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+		ty: wgpu::BindingType::Buffer {
+		    ty: wgpu::BufferBindingType::Uniform,
+		    has_dynamic_offset: false,
+		    min_binding_size: None,
+		},
+		count: None,
+	    },
+	    wgpu::BindGroupLayoutEntry {
+		binding: 1,
+		visibility: wgpu::ShaderStages::FRAGMENT,
+		ty: wgpu::BindingType::Texture {
+		    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+		    view_dimension: wgpu::TextureViewDimension::D2,
+		    multisampled: false,
+		},
+		count: None,
+	    },
+	    wgpu::BindGroupLayoutEntry {
+		binding: 2,
+		visibility: wgpu::ShaderStages::FRAGMENT,
+		ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+		count: None,
+	    },
+	],
+    });
+    let atlas_view = atlas_texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+	label: Some("text"),
+	layout: &bind_group_layout,
+	entries: &[
+	    wgpu::BindGroupEntry { binding: 0, resource: uniform_buffer.as_entire_binding() },
+	    wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(&atlas_view) },
+	    wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::Sampler(&sampler) },
+	],
+    });
+
+    let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+	label: Some("text"),
+	source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(SHADER)),
+    });
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+	label: Some("text"),
+	bind_group_layouts: &[&bind_group_layout],
+	push_constant_ranges: &[],
+    });
+    let coverage_pipeline = create_pipeline(&device, &pipeline_layout, &module, "fs_coverage", wgpu::BlendComponent {
+	src_factor: wgpu::BlendFactor::Zero,
+	dst_factor: wgpu::BlendFactor::OneMinusSrc,
+	operation: wgpu::BlendOperation::Add,
+    });
+    let color_pipeline = create_pipeline(&device, &pipeline_layout, &module, "fs_color", wgpu::BlendComponent {
+	src_factor: wgpu::BlendFactor::One,
+	dst_factor: wgpu::BlendFactor::One,
+	operation: wgpu::BlendOperation::Add,
+    });
+
+    let target_size = wgpu::Extent3d { width: WIDTH, height: HEIGHT, depth_or_array_layers: 1 };
+    let target = device.create_texture(&wgpu::TextureDescriptor {
+	label: Some("target"),
+	size: target_size,
+	mip_level_count: 1,
+	sample_count: 1,
+	dimension: wgpu::TextureDimension::D2,
+	format: wgpu::TextureFormat::Rgba8UnormSrgb,
+	usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+	view_formats: &[],
+    });
+    let target_view = target.create_view(&wgpu::TextureViewDescriptor::default());
+
+    // Rows copied to a buffer must be aligned on 256 bytes
+    let unpadded_bytes_per_row = WIDTH * 4;
+    let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    let padded_bytes_per_row = unpadded_bytes_per_row.div_ceil(align) * align;
+    let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+	label: Some("readback"),
+	size: (padded_bytes_per_row * HEIGHT) as wgpu::BufferAddress,
+	usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+	mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("text") });
+    {
+	let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+	    label: Some("text"),
+	    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+		view: &target_view,
+		resolve_target: None,
+		ops: wgpu::Operations {
+		    load: wgpu::LoadOp::Clear(wgpu::Color {
+			r: BACKGROUND[0] as f64,
+			g: BACKGROUND[1] as f64,
+			b: BACKGROUND[2] as f64,
+			a: BACKGROUND[3] as f64,
+		    }),
+		    store: wgpu::StoreOp::Store,
+		},
+	    })],
+	    depth_stencil_attachment: None,
+	    timestamp_writes: None,
+	    occlusion_query_set: None,
+	});
+	render_pass.set_bind_group(0, &bind_group, &[]);
+	render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+
+	render_pass.set_pipeline(&coverage_pipeline);
+	render_pass.draw(0..vertices.len() as u32, 0..1);
+	render_pass.set_pipeline(&color_pipeline);
+	render_pass.draw(0..vertices.len() as u32, 0..1);
+    }
+    encoder.copy_texture_to_buffer(
+	target.as_image_copy(),
+	wgpu::ImageCopyBuffer {
+	    buffer: &readback_buffer,
+	    layout: wgpu::ImageDataLayout { offset: 0, bytes_per_row: Some(padded_bytes_per_row), rows_per_image: None },
+	},
+	target_size
+    );
+    queue.submit(Some(encoder.finish()));
+
+    let slice = readback_buffer.slice(..);
+    slice.map_async(wgpu::MapMode::Read, |result| result.expect("Failed to map readback buffer"));
+    device.poll(wgpu::Maintain::Wait);
+
+    let pixels: Vec<u8> = slice.get_mapped_range()
+	.chunks(padded_bytes_per_row as usize)
+	.flat_map(|row| row[..unpadded_bytes_per_row as usize].to_vec())
+	.collect();
+
+    image::RgbaImage::from_raw(WIDTH, HEIGHT, pixels).unwrap().save(OUTPUT_PATH).unwrap();
+    println!("Saved {}", OUTPUT_PATH);
+}
+
+fn main() {
+    pollster::block_on(run());
+}
@@ -0,0 +1,53 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use font::{Node, Rectangle};
+use font::atlas::FontAtlas;
+
+fn overlap(a: &Rectangle, b: &Rectangle) -> bool {
+    a.left < b.left + b.width && b.left < a.left + a.width && a.top < b.top + b.height && b.top < a.top + a.height
+}
+
+/// Panics if a rectangle is outside the `width` by `height` area or overlaps another one.
+fn check_packing(rectangles: &[Rectangle], width: u32, height: u32) {
+    for (index, rectangle) in rectangles.iter().enumerate() {
+	assert!(rectangle.left + rectangle.width <= width && rectangle.top + rectangle.height <= height);
+	assert!(rectangles[index + 1..].iter().all(|other| !overlap(rectangle, other)));
+    }
+}
+
+// The first two bytes give the size of the area, and each following pair of bytes the size of a rectangle to insert
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 2 {
+	return;
+    }
+
+    let (width, height) = (data[0] as u32 + 1, data[1] as u32 + 1);
+    let sizes: Vec<(u32, u32)> = data[2..].chunks_exact(2).map(|size| (size[0] as u32 % 64, size[1] as u32 % 64)).collect();
+
+    let mut node = Node::new(Rectangle::new(0, 0, width, height));
+    let mut packed = Vec::new();
+    for (rectangle_width, rectangle_height) in sizes.iter().copied().filter(|(width, height)| *width > 0 && *height > 0) {
+	if let Ok(rectangle) = node.insert(&Rectangle::new(0, 0, rectangle_width, rectangle_height)) {
+	    assert_eq!((rectangle.width, rectangle.height), (rectangle_width, rectangle_height));
+	    packed.push(rectangle);
+	}
+    }
+    check_packing(&packed, width, height);
+
+    // The regions reserved in an atlas follow the same rules, before and after repacking
+    let mut atlas = FontAtlas::new((width, height));
+    for (region_width, region_height) in sizes {
+	let _ = atlas.reserve(region_width, region_height);
+    }
+    check_packing(atlas.reserved(), width, height);
+
+    let reserved = atlas.reserved().to_vec();
+    if let Ok(remap) = atlas.repack() {
+	check_packing(atlas.reserved(), width, height);
+	for (before, after) in reserved.iter().zip(atlas.reserved()) {
+	    assert_eq!(remap.get(before), Some(after));
+	}
+    }
+});
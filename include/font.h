@@ -0,0 +1,192 @@
+#ifndef FONT_H
+#define FONT_H
+
+/* Generated by cbindgen from src/ffi.rs, do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Renders the glyphs in grayscale, matching AtlasLoadMode::Gray.
+ */
+#define FONT_LOAD_MODE_GRAY 0
+
+/**
+ * Renders the glyphs with subpixel antialiasing, matching AtlasLoadMode::LCD.
+ */
+#define FONT_LOAD_MODE_LCD 1
+
+/**
+ * A struct representing a FontAtlas generator
+ */
+typedef struct FontGenerator FontGenerator;
+
+/**
+ * An atlas containing glyphs of a given font.
+ */
+typedef struct FontAtlas FontAtlas;
+
+/**
+ * The configuration of a generator.
+ */
+typedef struct FontGeneratorOptions {
+  /**
+   * The size of the atlas, in pixels.
+   */
+  uint32_t width;
+  uint32_t height;
+  /**
+   * The resolution the glyphs are rendered at.
+   */
+  uint32_t dpi;
+  /**
+   * The empty pixels around each glyph.
+   */
+  uint32_t padding;
+  /**
+   * How the glyphs are rendered, one of the FONT_LOAD_MODE constants. A plain integer, as C can store any value in an enum.
+   */
+  uint32_t load_mode;
+  /**
+   * Bakes the .notdef glyph, used for the characters missing from the atlas.
+   */
+  bool notdef;
+  /**
+   * Lets the glyphs that don't fit upright be packed rotated.
+   */
+  bool rotate;
+  /**
+   * The characters baked, as a nul terminated UTF-8 string. Null bakes the default characters.
+   */
+  const char *charset;
+} FontGeneratorOptions;
+
+/**
+ * The position of a glyph in the atlas along with its metrics, in pixels.
+ */
+typedef struct FontGlyph {
+  uint32_t x;
+  uint32_t y;
+  uint32_t width;
+  uint32_t height;
+  int32_t bearing_x;
+  int32_t bearing_y;
+  int32_t advance;
+  /**
+   * Whether the bitmap is stored rotated 90° clockwise.
+   */
+  bool rotated;
+} FontGlyph;
+
+/**
+ * The vertical metrics of the font of an atlas, in pixels.
+ */
+typedef struct FontLineMetrics {
+  int32_t ascender;
+  int32_t descender;
+  int32_t line_height;
+} FontLineMetrics;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Returns the default options: a 256x256 atlas at 72 dpi without padding, rendering the default characters in LCD mode.
+ */
+struct FontGeneratorOptions font_generator_options_default(void);
+
+/**
+ * Creates a generator rendering the font file at `path` with `options`. Returns null on error.
+ *
+ * # Safety
+ * `path` must point to a nul terminated string and `options` to valid options.
+ */
+struct FontGenerator *font_generator_new(const char *path,
+                                         const struct FontGeneratorOptions *options);
+
+/**
+ * Creates a generator rendering the font file content of `length` bytes at `data` with `options`, which is copied. Returns null on error.
+ *
+ * # Safety
+ * `data` must point to `length` readable bytes and `options` to valid options.
+ */
+struct FontGenerator *font_generator_new_from_memory(const uint8_t *data,
+                                                     size_t length,
+                                                     const struct FontGeneratorOptions *options);
+
+/**
+ * Frees a generator. Null is ignored.
+ *
+ * # Safety
+ * `generator` must be null or come from `font_generator_new` or `font_generator_new_from_memory`, and not be used afterwards.
+ */
+void font_generator_free(struct FontGenerator *generator);
+
+/**
+ * Generates an atlas with the font of `generator` at `size`, in 26.6 points. Returns null on error.
+ *
+ * # Safety
+ * `generator` must be a generator that wasn't freed.
+ */
+struct FontAtlas *font_generator_generate(const struct FontGenerator *generator, uint32_t size);
+
+/**
+ * Frees an atlas. Null is ignored.
+ *
+ * # Safety
+ * `atlas` must be null or come from `font_generator_generate`, and not be used afterwards.
+ */
+void font_atlas_free(struct FontAtlas *atlas);
+
+/**
+ * Writes the width and height of `atlas` in pixels to the non null pointers.
+ *
+ * # Safety
+ * `atlas` must be an atlas that wasn't freed, and `width` and `height` null or writable.
+ */
+void font_atlas_size(const struct FontAtlas *atlas, uint32_t *width, uint32_t *height);
+
+/**
+ * Returns the pixels of `atlas`, 3 bytes per pixel in RGB order row after row, and writes their byte count to `length` if not null.
+ * The pixels live as long as the atlas.
+ *
+ * # Safety
+ * `atlas` must be an atlas that wasn't freed, and `length` null or writable.
+ */
+const uint8_t *font_atlas_pixels(const struct FontAtlas *atlas,
+                                 size_t *length);
+
+/**
+ * Writes the entry of the character `codepoint` to `glyph`, falling back to the .notdef glyph if it was baked.
+ * Returns false if the atlas has no entry for it.
+ *
+ * # Safety
+ * `atlas` must be an atlas that wasn't freed, and `glyph` writable.
+ */
+bool font_atlas_glyph(const struct FontAtlas *atlas,
+                      uint32_t codepoint,
+                      struct FontGlyph *glyph);
+
+/**
+ * Writes the vertical metrics of the font of `atlas` to `line_metrics`.
+ *
+ * # Safety
+ * `atlas` must be an atlas that wasn't freed, and `line_metrics` null or writable.
+ */
+void font_atlas_line_metrics(const struct FontAtlas *atlas, struct FontLineMetrics *line_metrics);
+
+/**
+ * Returns the message of the last error of a function called from this thread, or null if there was none.
+ * The message lives until the next error on this thread.
+ */
+const char *font_last_error(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* FONT_H */
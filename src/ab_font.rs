@@ -0,0 +1,286 @@
+use std::cell::{Cell, OnceCell};
+use std::fmt::{Debug, Display};
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+use std::sync::Arc;
+
+use ab_glyph::{point, Font, FontVec, GlyphId, OutlineCurve, Point};
+
+use super::{Glyph, GlyphMetrics, Transform};
+use super::atlas::{AtlasGeneratorError, AtlasLoadMode, LineMetrics};
+use super::pixels::{PixelFormat, Pixels};
+use super::rasterizer::{hash_font_data, synthesize_box, Rasterizer};
+
+/// The weights of the filter spreading LCD coverage over the neighbouring subpixels, the default one of FreeType.
+const LCD_FILTER: [u32; 5] = [8, 77, 86, 77, 8];
+
+/// A pure Rust backend rendering the glyphs of a font face with ab_glyph, without FreeType.
+/// Glyphs aren't hinted, so their bitmaps can be a pixel larger on each side than FreeType ones.
+pub struct AbFont {
+    font: FontVec,
+    underline: Option<(f32, f32)>,
+    strikeout: Option<(f32, f32)>,
+    transform: Cell<Option<Transform>>,
+    char_size: Cell<(u32, u32)>,
+    face_index: u32,
+    font_hash: OnceCell<u64>,
+}
+
+impl AbFont {
+    /// Creates a backend from the given font filepath.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn new<P>(font_filepath: P) -> Result<AbFont, AbFontError> where P: AsRef<Path> {
+	Self::with_face_index(font_filepath, 0)
+    }
+
+    /// Creates a backend from the face at `face_index` in the given font file, for font collections.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_face_index<P>(font_filepath: P, face_index: u32) -> Result<AbFont, AbFontError> where P: AsRef<Path> {
+	let data = std::fs::read(font_filepath).map_err(AbFontError::IoError)?;
+
+	Self::from_vec(data, face_index)
+    }
+
+    /// Creates a backend from the face at `face_index` in the font file loaded in `data`.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(bytes = data.len(), face_index), err(Display)))]
+    pub fn from_vec(data: Vec<u8>, face_index: u32) -> Result<AbFont, AbFontError> {
+	// ab_glyph doesn't expose the decoration metrics, so they are read from the face tables once
+	let face = ttf_parser::Face::parse(&data, face_index).map_err(|_| AbFontError::FaceError)?;
+	let to_pair = |metrics: ttf_parser::LineMetrics| (metrics.position as f32, metrics.thickness as f32);
+	let (underline, strikeout) = (face.underline_metrics().map(to_pair), face.strikeout_metrics().map(to_pair));
+
+	let font = FontVec::try_from_vec_and_index(data, face_index).map_err(|_| AbFontError::FaceError)?;
+
+	Ok(AbFont {
+	    font,
+	    underline,
+	    strikeout,
+	    transform: Cell::new(None),
+	    char_size: Cell::new((0, 0)),
+	    face_index,
+	    font_hash: OnceCell::new(),
+	})
+    }
+
+    /// Creates a backend from the face at `face_index` in the font file content `data`, which is copied.
+    pub fn from_bytes(data: &[u8], face_index: u32) -> Result<AbFont, AbFontError> {
+	Self::from_vec(data.to_vec(), face_index)
+    }
+
+    /// Returns the underlying ab_glyph font.
+    pub fn font(&self) -> &FontVec {
+	&self.font
+    }
+
+    /// Returns the number of pixels per font unit at the current size, a 26.6 point size being as many pixels per em as with FreeType.
+    fn scale(&self) -> f32 {
+	let (size, dpi) = self.char_size.get();
+	let pixels_per_em = size as f32 / 64.0 * dpi as f32 / 72.0;
+
+	pixels_per_em / self.font.units_per_em().unwrap_or(1000.0)
+    }
+
+    /// Returns the outline of the glyph at `glyph_id` in pixels, with the metrics of its gray bitmap, without rendering it.
+    fn outline(&self, glyph_id: GlyphId) -> (Vec<OutlineCurve>, GlyphMetrics) {
+	let scale = self.scale();
+	let transform = self.transform.get().unwrap_or_default();
+	let to_pixels = |p: Point| point(
+	    (transform.xx * p.x + transform.xy * p.y) * scale,
+	    (transform.yx * p.x + transform.yy * p.y) * scale
+	);
+
+	// The advance is transformed like the outline, as FreeType does
+	let advance = (transform.xx * self.font.h_advance_unscaled(glyph_id) * scale).round() as i32;
+	let blank = GlyphMetrics::new(0, 0, 0, 0, advance);
+
+	let curves: Vec<OutlineCurve> = match self.font.outline(glyph_id) {
+	    Some(outline) => outline.curves.into_iter().map(|curve| match curve {
+		OutlineCurve::Line(p0, p1) => OutlineCurve::Line(to_pixels(p0), to_pixels(p1)),
+		OutlineCurve::Quad(p0, p1, p2) => OutlineCurve::Quad(to_pixels(p0), to_pixels(p1), to_pixels(p2)),
+		OutlineCurve::Cubic(p0, p1, p2, p3) => OutlineCurve::Cubic(to_pixels(p0), to_pixels(p1), to_pixels(p2), to_pixels(p3)),
+	    }).collect(),
+	    None => return (Vec::new(), blank),
+	};
+
+	// The bitmap covers the control box of the outline, like FreeType's
+	let points = curves.iter().flat_map(|curve| match curve {
+	    OutlineCurve::Line(p0, p1) => vec![*p0, *p1],
+	    OutlineCurve::Quad(p0, p1, p2) => vec![*p0, *p1, *p2],
+	    OutlineCurve::Cubic(p0, p1, p2, p3) => vec![*p0, *p1, *p2, *p3],
+	});
+	let (min, max) = points.fold((point(f32::MAX, f32::MAX), point(f32::MIN, f32::MIN)), |(min, max), p| {
+	    (point(min.x.min(p.x), min.y.min(p.y)), point(max.x.max(p.x), max.y.max(p.y)))
+	});
+
+	let (left, top) = (min.x.floor() as i32, max.y.ceil() as i32);
+	let (width, height) = ((max.x.ceil() as i32 - left).max(0) as u32, (top - min.y.floor() as i32).max(0) as u32);
+	if width == 0 || height == 0 {
+	    return (Vec::new(), blank);
+	}
+
+	(curves, GlyphMetrics::new(width, height, left, top, advance))
+    }
+
+    /// Returns the metrics of the glyph at `glyph_id` rendered in `load_mode`, without rendering it.
+    fn measure_glyph(&self, glyph_id: GlyphId, load_mode: AtlasLoadMode) -> GlyphMetrics {
+	let (_, metrics) = self.outline(glyph_id);
+
+	match load_mode {
+	    AtlasLoadMode::LCD if metrics.width > 0 => GlyphMetrics::new(metrics.width + 2, metrics.height, metrics.bearing_x - 1, metrics.bearing_y, metrics.advance),
+	    _ => metrics,
+	}
+    }
+
+    /// Renders the glyph at `glyph_id` in the face.
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "rasterize", level = "trace", skip(self)))]
+    fn render(&self, glyph_id: GlyphId, load_mode: AtlasLoadMode) -> Glyph {
+	let (curves, metrics) = self.outline(glyph_id);
+	let GlyphMetrics { width, height, bearing_x: left, bearing_y: top, advance } = metrics;
+	if width == 0 || height == 0 {
+	    return Glyph::new(metrics, Pixels::new(0, 0, PixelFormat::Rgb8));
+	}
+
+	match load_mode {
+	    // Color glyphs aren't supported, so outlines are drawn in gray like FreeType does for them
+	    AtlasLoadMode::Gray | AtlasLoadMode::Color => {
+		let coverage = rasterize_curves(&curves, (width, height), |p| point(p.x - left as f32, top as f32 - p.y));
+		let bitmap = Pixels::from_fn(width, height, PixelFormat::Rgb8, |x, y| {
+		    let gray = coverage[(y * width + x) as usize];
+		    [gray, gray, gray, 255]
+		});
+
+		Glyph::new(GlyphMetrics::new(width, height, left, top, advance), bitmap)
+	    },
+	    AtlasLoadMode::LCD => {
+		// Rendered 3 times wider with a pixel of margin on both sides, which the filter spreads the coverage over
+		let subpixels = (width + 2) * 3;
+		let coverage = rasterize_curves(&curves, (subpixels, height), |p| point((p.x - left as f32) * 3.0 + 3.0, top as f32 - p.y));
+
+		let filtered: Vec<u8> = coverage.chunks_exact(subpixels as usize).flat_map(|row| {
+		    (0..row.len()).map(move |x| {
+			let sum: u32 = LCD_FILTER.iter().enumerate()
+			    .filter_map(|(tap, weight)| row.get((x + tap).checked_sub(2)?).map(|value| weight * *value as u32))
+			    .sum();
+			(sum / 256) as u8
+		    })
+		}).collect();
+
+		let bitmap = Pixels::from_raw(width + 2, height, PixelFormat::Rgb8, filtered).unwrap_or_else(|| Pixels::new(width + 2, height, PixelFormat::Rgb8));
+
+		Glyph::new(GlyphMetrics::new(width + 2, height, left - 1, top, advance), bitmap)
+	    },
+	}
+    }
+}
+
+/// Fills the outline made of `curves` in a `size` coverage map, `to_bitmap` moving their points to bitmap coordinates.
+fn rasterize_curves<F>(curves: &[OutlineCurve], size: (u32, u32), to_bitmap: F) -> Vec<u8> where F: Fn(Point) -> Point {
+    let mut rasterizer = ab_glyph_rasterizer::Rasterizer::new(size.0 as usize, size.1 as usize);
+
+    for curve in curves {
+	match curve {
+	    OutlineCurve::Line(p0, p1) => rasterizer.draw_line(to_bitmap(*p0), to_bitmap(*p1)),
+	    OutlineCurve::Quad(p0, p1, p2) => rasterizer.draw_quad(to_bitmap(*p0), to_bitmap(*p1), to_bitmap(*p2)),
+	    OutlineCurve::Cubic(p0, p1, p2, p3) => rasterizer.draw_cubic(to_bitmap(*p0), to_bitmap(*p1), to_bitmap(*p2), to_bitmap(*p3)),
+	}
+    }
+
+    let mut coverage = vec![0; (size.0 * size.1) as usize];
+    rasterizer.for_each_pixel(|index, alpha| coverage[index] = (alpha.min(1.0) * 255.0).round() as u8);
+
+    coverage
+}
+
+impl Rasterizer for AbFont {
+    fn set_char_size(&self, size: u32, dpi: u32) -> Result<(), AtlasGeneratorError> {
+	self.char_size.set((size, dpi));
+	Ok(())
+    }
+
+    fn set_transform(&self, transform: Option<Transform>) {
+	self.transform.set(transform);
+    }
+
+    fn glyph_index(&self, c: char) -> u32 {
+	self.font.glyph_id(c).0 as u32
+    }
+
+    fn line_metrics(&self) -> LineMetrics {
+	let scale = self.scale();
+	let to_pixels = |units: f32| (units * scale).round() as i32;
+
+	// Rounded outward like the FreeType size metrics
+	let ascender = (self.font.ascent_unscaled() * scale).ceil() as i32;
+	let descender = (self.font.descent_unscaled() * scale).floor() as i32;
+	let line_height = to_pixels(self.font.ascent_unscaled() - self.font.descent_unscaled() + self.font.line_gap_unscaled());
+
+	// FreeType moves the underline position of the post table to the middle of the stroke, and so does the OS/2 strikeout here
+	let (underline_position, underline_thickness) = match self.underline {
+	    Some((position, thickness)) => (to_pixels(position - thickness / 2.0), to_pixels(thickness).max(1)),
+	    None => (descender / 2, 1),
+	};
+	let (strikeout_position, strikeout_thickness) = match self.strikeout {
+	    Some((position, thickness)) => (to_pixels(position - thickness / 2.0), to_pixels(thickness).max(1)),
+	    None => (ascender / 3, underline_thickness),
+	};
+
+	LineMetrics::new(ascender, descender, line_height)
+	    .with_underline(underline_position, underline_thickness)
+	    .with_strikeout(strikeout_position, strikeout_thickness)
+    }
+
+    fn font_hash(&self) -> Option<u64> {
+	Some(*self.font_hash.get_or_init(|| hash_font_data(self.font.as_slice(), self.face_index)))
+    }
+
+    fn rasterize(&self, c: char, load_mode: AtlasLoadMode) -> Result<Arc<Glyph>, AtlasGeneratorError> {
+	Ok(Arc::new(self.render(self.font.glyph_id(c), load_mode)))
+    }
+
+    fn measure(&self, c: char, load_mode: AtlasLoadMode) -> Result<GlyphMetrics, AtlasGeneratorError> {
+	Ok(self.measure_glyph(self.font.glyph_id(c), load_mode))
+    }
+
+    fn rasterize_notdef(&self, load_mode: AtlasLoadMode) -> Result<Glyph, AtlasGeneratorError> {
+	let glyph = self.render(GlyphId(0), load_mode);
+
+	if glyph.bitmap.width > 0 && glyph.bitmap.height > 0 {
+	    return Ok(glyph);
+	}
+
+	Ok(synthesize_box(&self.line_metrics()))
+    }
+}
+
+/// An enum representing all the error that could happen opening a font with ab_glyph.
+pub enum AbFontError {
+    IoError(std::io::Error),
+    FaceError,
+}
+
+impl Display for AbFontError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+	match self {
+	    AbFontError::IoError(_) => write!(f, "Can't read font file"),
+	    AbFontError::FaceError => write!(f, "Can't load font face"),
+	}
+    }
+}
+
+impl Debug for AbFontError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+	match self {
+	    AbFontError::IoError(_) => write!(f, "Can't read font file"),
+	    AbFontError::FaceError => write!(f, "Can't load font face"),
+	}
+    }
+}
+
+impl std::error::Error for AbFontError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+	match self {
+	    AbFontError::IoError(error) => Some(error),
+	    AbFontError::FaceError => None,
+	}
+    }
+}
@@ -1,347 +1,2512 @@
 use std::collections::HashMap;
+use std::ops::{Range, RangeInclusive};
+use std::str::CharIndices;
+#[cfg(not(feature = "freetype"))]
+use std::marker::PhantomData;
+#[cfg(all(any(feature = "freetype", feature = "image"), not(target_arch = "wasm32")))]
 use std::path::Path;
+#[cfg(all(feature = "freetype", not(target_arch = "wasm32")))]
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::fmt::{Debug, Display};
 
-use freetype::face::{Face, LoadFlag};
-use freetype::{Bitmap, Library, LcdFilter};
-use image::{ImageBuffer, Rgb, GenericImage};
-
-use super::{Glyph, GlyphMetrics, Node, Rectangle, NodeInsertError};
-
-const GLYPHS: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789\\|/?.>,<`!@#$%^&*()_-=+[]{};:'\" ";
+#[cfg(feature = "freetype")]
+use freetype::face::LoadFlag;
+
+use super::{Glyph, GlyphMetrics, Node, Rectangle, NodeInsertError, Transform};
+use super::pixels::{to_rgba, Background, PixelFormat, PixelFormatError, Pixels};
+#[cfg(feature = "freetype")]
+use super::loader::{FontLoader, FontLoaderError, FontSystem};
+#[cfg(feature = "ab-glyph")]
+use super::ab_font::AbFontError;
+#[cfg(all(feature = "ab-glyph", not(feature = "freetype")))]
+use super::ab_font::AbFont;
+use super::layout::{Anchor, LayoutConfig, TextLayout, UvOrigin, UvUnits, YAxis, NO_BREAK_SPACE, SOFT_HYPHEN};
+use super::multi_atlas::FontId;
+use super::rasterizer::{DriverProperty, Rasterizer};
+use super::rich_text::Color;
+use super::scaled_atlas::ScaledAtlas;
+use super::script::{is_default_ignorable, EMOJI_PRESENTATION};
+
+/// The characters baked in an atlas by default.
+pub const GLYPHS: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789\\|/?.>,<`!@#$%^&*()_-=+[]{};:'\" ";
+
+/// The width of a tab, in spaces.
+const TAB_WIDTH: i32 = 4;
+
+/// The resolution at which a point is a pixel, sizes given in 26.6 points then being 64 times their height in pixels.
+pub const PIXEL_DPI: u32 = 72;
+
+/// The empty pixels around images inserted in an atlas, so that filtering doesn't bleed the neighbouring glyphs in.
+const IMAGE_PADDING: u32 = 1;
+
+/// The source of the generations of every atlas of the process, so that no two states of any atlases share one.
+static NEXT_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Returns a generation no atlas had yet, for the implementations of `GlyphAtlas::generation`.
+pub fn next_generation() -> u64 {
+    NEXT_GENERATION.fetch_add(1, Ordering::Relaxed)
+}
 
 /// An atlas containing glyphs of a given font.
 pub struct FontAtlas {
     pub map: HashMap<char, FontAtlasEntry>,
-    pub buffer: ImageBuffer<Rgb<u8>, Vec<u8>>,
+    pub buffer: Pixels,
     pub width: u32,
     pub height: u32,
+    pub line_metrics: LineMetrics,
+    pub notdef: Option<FontAtlasEntry>,
+    /// Draws the characters missing from an atlas without .notdef glyph with the space glyph rather than leaving them out.
+    pub space_fallback: bool,
+    pub images: HashMap<String, FontAtlasEntry>,
+    /// The glyphs drawn for whole sequences of characters, like emoji ZWJ sequences, by sequence without emoji presentation selectors.
+    pub sequences: HashMap<String, FontAtlasEntry>,
+    pub load_mode: AtlasLoadMode,
+    pub font_size: u32,
+    /// The resolution `font_size` is given at, `PIXEL_DPI` for atlases generated by pixel height. None for atlases not
+    /// made by a generator, `AtlasGenerator::add_glyphs` then using the DPI of its options.
+    pub dpi: Option<u32>,
+    /// The entries of the charset baked at the extra sizes of the generator, by character and size.
+    pub sized_map: HashMap<(char, u32), FontAtlasEntry>,
+    /// The vertical metrics of the font at each extra size.
+    pub sized_line_metrics: HashMap<u32, LineMetrics>,
+    /// The characters that couldn't be packed by a lenient generator, at any size, with the reason.
+    pub failures: Vec<(char, AtlasGeneratorError)>,
+    /// The value the buffer is cleared to where nothing is drawn, kept when the atlas is repacked.
+    pub background: Background,
+    fingerprint: Option<u64>,
+    /// Identifies the state of the entries, renewed when they change to invalidate what was computed from them.
+    generation: u64,
+    node: Node,
+    dirty_regions: Vec<Rectangle>,
+    reserved: Vec<Rectangle>,
+    padding: Padding,
+    padding_ranges: Vec<(RangeInclusive<char>, Padding)>,
+}
+
+impl FontAtlas {
+    /// Create a font atlas of given `atlas_size`.
+    pub fn new(atlas_size: (u32, u32)) -> Self {
+	Self {
+	    map: HashMap::new(),
+	    buffer: Pixels::new(atlas_size.0, atlas_size.1, PixelFormat::Rgb8),
+	    width: atlas_size.0,
+	    height: atlas_size.1,
+	    line_metrics: LineMetrics::default(),
+	    notdef: None,
+	    space_fallback: false,
+	    images: HashMap::new(),
+	    sequences: HashMap::new(),
+	    load_mode: AtlasLoadMode::default(),
+	    font_size: 0,
+	    dpi: None,
+	    sized_map: HashMap::new(),
+	    sized_line_metrics: HashMap::new(),
+	    failures: Vec::new(),
+	    background: Background::Transparent,
+	    fingerprint: None,
+	    generation: next_generation(),
+	    node: Node::new(Rectangle::new(0, 0, atlas_size.0, atlas_size.1)),
+	    dirty_regions: Vec::new(),
+	    reserved: Vec::new(),
+	    padding: Padding::default(),
+	    padding_ranges: Vec::new(),
+	}
+    }
+
+    /// Reserves an empty region of `width` by `height` pixels next to the glyphs, for images drawn from the same texture
+    /// like icons or a white pixel. No padding is added around it, and an empty size gets an empty rectangle.
+    /// The application draws in the returned region of the buffer and marks it dirty.
+    pub fn reserve(&mut self, width: u32, height: u32) -> Result<Rectangle, NodeInsertError> {
+	if width == 0 || height == 0 {
+	    return Ok(Rectangle::default());
+	}
+
+	let region = self.node.insert(&Rectangle::new(0, 0, width, height))?;
+	self.reserved.push(region);
+
+	Ok(region)
+    }
+
+    /// Returns the regions reserved with `reserve`, in the order they were reserved.
+    pub fn reserved(&self) -> &[Rectangle] {
+	&self.reserved
+    }
+
+    /// Packs `image` in the atlas and returns its entry, also given by `image(key)`, and marks its position dirty.
+    /// The entry sits on the baseline and advances by the image width, so it can be drawn like a glyph, for instance an inline emoji.
+    /// The colors of RGBA images are premultiplied by their alpha, which is dropped unless the atlas is RGBA, like those rendered
+    /// in `AtlasLoadMode::Color`. An image of the same size as the one already inserted under `key` replaces its pixels in place.
+    /// Atlases without pixels, made by `AtlasGenerator::measure`, can't take images, which are reported as not fitting.
+    pub fn insert_image(&mut self, key: &str, image: &Pixels) -> Result<&FontAtlasEntry, NodeInsertError> {
+	if !self.has_pixels() {
+	    return Err(NodeInsertError(Rectangle::new(0, 0, image.width, image.height)));
+	}
+
+	let bitmap = Pixels::from_fn(image.width, image.height, self.buffer.format, |x, y| match *image.get_pixel(x, y) {
+	    [r, g, b, a] => {
+		let premultiply = |channel: u8| (channel as u32 * a as u32 / 255) as u8;
+		[premultiply(r), premultiply(g), premultiply(b), a]
+	    },
+	    [r, g, b] => [r, g, b, 255],
+	    _ => [0, 0, 0, 0],
+	});
+
+	let position = match self.images.get(key) {
+	    Some(entry) if entry.position.width == bitmap.width && entry.position.height == bitmap.height => {
+		self.buffer.copy_from(&bitmap, entry.position.left, entry.position.top);
+		entry.position
+	    },
+	    _ => {
+		let padding = Padding::uniform(IMAGE_PADDING);
+		pack_glyph(&mut self.node, &mut self.buffer, &bitmap, &padding, false)?.0
+	    },
+	};
+	self.mark_dirty(position);
+
+	let metrics = GlyphMetrics::new(bitmap.width, bitmap.height, 0, bitmap.height as i32, bitmap.width as i32);
+	self.generation = next_generation();
+	self.images.insert(String::from(key), FontAtlasEntry::new(position, metrics).with_color(true));
+
+	Ok(&self.images[key])
+    }
+
+    /// Returns the entry of the image inserted under `key`.
+    pub fn image(&self, key: &str) -> Option<&FontAtlasEntry> {
+	self.images.get(key)
+    }
+
+    /// Packs again the glyphs, images and reserved regions left in the atlas, largest first, reclaiming the room of the entries
+    /// removed from it and of the glyphs packed in a poor order. Their pixels are moved to their new positions and the whole buffer
+    /// is marked dirty. Returns the new position of each non-empty entry by its previous position, to patch uvs computed before.
+    /// If they don't fit anymore, the atlas is left untouched.
+    pub fn repack(&mut self) -> Result<HashMap<Rectangle, Rectangle>, NodeInsertError> {
+	let no_padding = Padding::default();
+	let image_padding = Padding::uniform(IMAGE_PADDING);
+
+	let padding = |c: char| padding_for(&self.padding, &self.padding_ranges, c);
+	let glyphs = self.map.iter().map(|(c, entry)| (entry.position, padding(*c)))
+	    .chain(self.notdef.iter().map(|entry| (entry.position, &self.padding)))
+	    .chain(self.sized_map.iter().map(|((c, _), entry)| (entry.position, padding(*c))))
+	    .chain(self.sequences.values().map(|entry| (entry.position, &self.padding)));
+	let images = self.images.values().map(|entry| (entry.position, &image_padding));
+	let reserved = self.reserved.iter().map(|region| (*region, &no_padding));
+	let mut regions: Vec<(Rectangle, &Padding)> = glyphs.chain(images).chain(reserved)
+	    .filter(|(region, _)| region.width > 0 && region.height > 0)
+	    .collect();
+
+	// Sorting by the previous position as well makes the result independent of the order of the maps
+	regions.sort_by_key(|(region, _)| (std::cmp::Reverse((region.height, region.width)), region.top, region.left));
+
+	// Atlases without pixels only have their boxes moved
+	let has_pixels = self.has_pixels();
+	let mut node = Node::new(Rectangle::new(0, 0, self.width, self.height));
+	let mut buffer = match has_pixels {
+	    true => Pixels::from_background(self.width, self.height, self.buffer.format, &self.background),
+	    false => Pixels::new(0, 0, self.buffer.format),
+	};
+	let mut remap = HashMap::with_capacity(regions.len());
+	for (region, padding) in regions {
+	    let position = match has_pixels {
+		true => insert_bitmap(&mut node, &mut buffer, &self.buffer.crop(&region), padding)?,
+		false => insert_padded(&mut node, region.width, region.height, padding)?,
+	    };
+	    remap.insert(region, position);
+	}
+
+	let entries = self.map.values_mut().chain(self.notdef.iter_mut()).chain(self.sized_map.values_mut()).chain(self.images.values_mut())
+	    .chain(self.sequences.values_mut());
+	for entry in entries {
+	    if let Some(position) = remap.get(&entry.position) {
+		entry.position = *position;
+	    }
+	}
+	for region in self.reserved.iter_mut() {
+	    if let Some(position) = remap.get(region) {
+		*region = *position;
+	    }
+	}
+
+	self.node = node;
+	self.buffer = buffer;
+	self.generation = next_generation();
+	self.dirty_regions.clear();
+	self.mark_dirty(Rectangle::new(0, 0, self.width, self.height));
+
+	Ok(remap)
+    }
+
+    /// Returns the generation of the entries, unique to this atlas and renewed each time they are added or moved, by repacks,
+    /// added glyphs and inserted images, so that what was computed from them, like cached layouts and vertices, can be
+    /// computed again.
+    pub fn generation(&self) -> u64 {
+	self.generation
+    }
+
+    /// Returns false for the atlases made by `AtlasGenerator::measure`, which have the entries but not the pixels of the glyphs.
+    pub fn has_pixels(&self) -> bool {
+	self.buffer.dimensions() == (self.width, self.height)
+    }
+
+    /// Returns the bytes of the buffer with its width, height, stride in bytes and pixel format, to upload it as is.
+    pub fn as_raw(&self) -> (&[u8], u32, u32, usize, PixelFormat) {
+	(&self.buffer.data, self.buffer.width, self.buffer.height, self.buffer.stride(), self.buffer.format)
+    }
+
+    /// Copies the rows of the buffer into `staging`, for instance a mapped GPU staging buffer, each row starting `row_pitch`
+    /// bytes after the previous one, the bytes past the end of a row being left untouched. Returns false without copying anything
+    /// if the pitch is shorter than a row or `staging` can't hold every row.
+    pub fn write_into(&self, staging: &mut [u8], row_pitch: usize) -> bool {
+	let stride = self.buffer.stride();
+	let rows = self.buffer.height as usize;
+	if row_pitch < stride || (rows > 0 && staging.len() < row_pitch * (rows - 1) + stride) {
+	    return false;
+	}
+
+	for (row, source) in self.buffer.data.chunks_exact(stride.max(1)).enumerate().take(rows) {
+	    staging[row * row_pitch..row * row_pitch + stride].copy_from_slice(source);
+	}
+
+	true
+    }
+
+    /// Records that `region` of the buffer changed and must be uploaded again.
+    pub fn mark_dirty(&mut self, region: Rectangle) {
+	if !region.is_empty() && !self.dirty_regions.iter().any(|dirty| dirty.contains(&region)) {
+	    self.dirty_regions.push(region);
+	}
+    }
+
+    /// Returns the regions of the buffer changed since the last call, leaving none dirty.
+    pub fn take_dirty_regions(&mut self) -> Vec<Rectangle> {
+	std::mem::take(&mut self.dirty_regions)
+    }
+
+    /// Returns the entry of the character `c`, falling back to the .notdef glyph. Without one, missing characters have no entry
+    /// unless `space_fallback` is set, which gives them the space glyph.
+    /// Control characters such as tabs and newlines have no entry, nor soft hyphens, and no-break spaces fall back to the space glyph.
+    pub fn lookup(&self, c: char) -> Option<&FontAtlasEntry> {
+	match c {
+	    c if c.is_control() || is_default_ignorable(c) => return None,
+	    // Soft hyphens are invisible unless a layout breaks a line at them, and no-break spaces are drawn as spaces
+	    SOFT_HYPHEN => return None,
+	    NO_BREAK_SPACE if !self.map.contains_key(&c) => return self.map.get(&' '),
+	    _ => {},
+	}
+
+	self.map.get(&c)
+	    .or(self.notdef.as_ref())
+	    .or_else(|| self.map.get(&' ').filter(|_| self.space_fallback))
+    }
+
+    /// Returns the horizontal advance of the given character, with the same fallbacks as `lookup`.
+    /// A tab advances by several spaces and other control characters don't advance.
+    pub fn advance(&self, c: char) -> i32 {
+	match c {
+	    '\t' => TAB_WIDTH * self.advance(' '),
+	    c => self.lookup(c).map_or(0, |entry| entry.metrics.advance),
+	}
+    }
+
+    /// Returns the sizes the charset was baked at in 26.6 points, `font_size` along with the extra sizes, in increasing order.
+    pub fn sizes(&self) -> Vec<u32> {
+	let mut sizes: Vec<u32> = std::iter::once(self.font_size).chain(self.sized_line_metrics.keys().copied()).collect();
+	sizes.sort_unstable();
+	sizes
+    }
+
+    /// Returns the baked size closest to `size`, the larger one on a tie, so that text is drawn at it.
+    pub fn nearest_size(&self, size: u32) -> u32 {
+	self.sizes().into_iter()
+	    .min_by_key(|baked| (baked.abs_diff(size), std::cmp::Reverse(*baked)))
+	    .unwrap_or(self.font_size)
+    }
+
+    /// Returns the entry of the character `c` baked at `size`, with the fallbacks of `lookup` at the main size and falling back
+    /// to the space glyph at extra sizes if `space_fallback` is set.
+    pub fn lookup_sized(&self, c: char, size: u32) -> Option<&FontAtlasEntry> {
+	if size == self.font_size {
+	    return self.lookup(c);
+	}
+	if c.is_control() || is_default_ignorable(c) || c == SOFT_HYPHEN {
+	    return None;
+	}
+
+	self.sized_map.get(&(c, size)).or_else(|| self.sized_map.get(&(' ', size)).filter(|_| self.space_fallback))
+    }
+
+    /// Returns the glyph drawn for the whole `sequence`, like an emoji ZWJ sequence, whether or not it has emoji presentation selectors.
+    pub fn lookup_sequence(&self, sequence: &str) -> Option<&FontAtlasEntry> {
+	match sequence.contains(EMOJI_PRESENTATION) {
+	    true => self.sequences.get(&sequence.replace(EMOJI_PRESENTATION, "")),
+	    false => self.sequences.get(sequence),
+	}
+    }
+
+    /// Returns the vertical metrics of the font at `size`, or None if the charset wasn't baked at it.
+    pub fn line_metrics_sized(&self, size: u32) -> Option<LineMetrics> {
+	match size == self.font_size {
+	    true => Some(self.line_metrics),
+	    false => self.sized_line_metrics.get(&size).copied(),
+	}
+    }
+
+    /// Returns a view of the glyphs baked at `size`, to lay out and draw text at it. Characters have no entry if it wasn't baked.
+    pub fn at_size(&self, size: u32) -> SizedAtlas<'_> {
+	SizedAtlas { atlas: self, size }
+    }
+
+    /// Returns true if the charset was baked at sizes other than `font_size`.
+    fn has_extra_sizes(&self) -> bool {
+	!self.sized_line_metrics.is_empty()
+    }
+
+    /// Returns true if the atlas has an entry for `c` at `size`, without fallbacks.
+    fn has_entry(&self, c: char, size: u32) -> bool {
+	match size == self.font_size {
+	    true => self.map.contains_key(&c),
+	    false => self.sized_map.contains_key(&(c, size)),
+	}
+    }
+
+    /// Returns the fingerprint of the fonts, size and options the atlas was generated from, given by `AtlasGenerator::fingerprint`.
+    /// It is None for atlases not made by `AtlasGenerator::generate` or changed by `AtlasGenerator::add_glyphs` since.
+    pub fn fingerprint(&self) -> Option<u64> {
+	self.fingerprint
+    }
+
+    /// Returns a hash of the pixels, entries and metrics of the atlas, the same across runs and platforms, to tell whether
+    /// a baked atlas changed. Entries are hashed by character, images by key and sequences by sequence, whatever the order of the maps.
+    pub fn content_hash(&self) -> u64 {
+	let mut hasher = ContentHasher::new();
+
+	hasher.write_u32(self.width);
+	hasher.write_u32(self.height);
+	hasher.write_u32(self.load_mode as u32);
+	hasher.write_u32(self.font_size);
+	let line_metrics = &self.line_metrics;
+	for metric in [line_metrics.ascender, line_metrics.descender, line_metrics.line_height, line_metrics.underline_position,
+		       line_metrics.underline_thickness, line_metrics.strikeout_position, line_metrics.strikeout_thickness] {
+	    hasher.write_i32(metric);
+	}
+	hasher.write(&self.buffer);
+
+	let mut entries: Vec<(&char, &FontAtlasEntry)> = self.map.iter().collect();
+	entries.sort_by_key(|(c, _)| **c);
+	hasher.write_u32(entries.len() as u32);
+	for (c, entry) in entries {
+	    hasher.write_u32(*c as u32);
+	    hasher.write_entry(entry);
+	}
+
+	let mut sized_entries: Vec<(&(char, u32), &FontAtlasEntry)> = self.sized_map.iter().collect();
+	sized_entries.sort_by_key(|(key, _)| **key);
+	hasher.write_u32(sized_entries.len() as u32);
+	for ((c, size), entry) in sized_entries {
+	    hasher.write_u32(*c as u32);
+	    hasher.write_u32(*size);
+	    hasher.write_entry(entry);
+	}
+
+	hasher.write_u32(self.notdef.is_some() as u32);
+	if let Some(notdef) = &self.notdef {
+	    hasher.write_entry(notdef);
+	}
+
+	let mut images: Vec<(&String, &FontAtlasEntry)> = self.images.iter().collect();
+	images.sort_by_key(|(key, _)| *key);
+	hasher.write_u32(images.len() as u32);
+	for (key, entry) in images {
+	    hasher.write_u32(key.len() as u32);
+	    hasher.write(key.as_bytes());
+	    hasher.write_entry(entry);
+	}
+
+	let mut sequences: Vec<(&String, &FontAtlasEntry)> = self.sequences.iter().collect();
+	sequences.sort_by_key(|(sequence, _)| *sequence);
+	hasher.write_u32(sequences.len() as u32);
+	for (sequence, entry) in sequences {
+	    hasher.write_u32(sequence.len() as u32);
+	    hasher.write(sequence.as_bytes());
+	    hasher.write_entry(entry);
+	}
+
+	hasher.write_u32(self.reserved.len() as u32);
+	for region in self.reserved.iter() {
+	    hasher.write_rectangle(region);
+	}
+
+	hasher.finish()
+    }
+}
+
+/// A 64-bit FNV-1a hasher. Unlike the std hashers, its values are specified and don't depend on the platform or the Rust version.
+pub(crate) struct ContentHasher(u64);
+
+impl ContentHasher {
+    pub(crate) fn new() -> Self {
+	Self(0xcbf2_9ce4_8422_2325)
+    }
+
+    pub(crate) fn write(&mut self, bytes: &[u8]) {
+	for byte in bytes {
+	    self.0 = (self.0 ^ *byte as u64).wrapping_mul(0x0100_0000_01b3);
+	}
+    }
+
+    pub(crate) fn write_u32(&mut self, value: u32) {
+	self.write(&value.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, value: u64) {
+	self.write(&value.to_le_bytes());
+    }
+
+    fn write_i32(&mut self, value: i32) {
+	self.write(&value.to_le_bytes());
+    }
+
+    fn write_rectangle(&mut self, rectangle: &Rectangle) {
+	for value in [rectangle.left, rectangle.top, rectangle.width, rectangle.height] {
+	    self.write_u32(value);
+	}
+    }
+
+    fn write_entry(&mut self, entry: &FontAtlasEntry) {
+	self.write_rectangle(&entry.position);
+	self.write_u32(entry.metrics.width);
+	self.write_u32(entry.metrics.height);
+	self.write_i32(entry.metrics.bearing_x);
+	self.write_i32(entry.metrics.bearing_y);
+	self.write_i32(entry.metrics.advance);
+	self.write_u32(entry.page as u32);
+	self.write_u32(entry.font.0 as u32);
+	self.write_u32(entry.glyph_id);
+	self.write_u32(entry.rotated as u32);
+	self.write_u32(entry.color as u32);
+    }
+
+    pub(crate) fn finish(&self) -> u64 {
+	self.0
+    }
+}
+
+/// A trait giving the layout the glyphs and metrics of the fonts of an atlas.
+pub trait GlyphAtlas {
+    /// Returns the size of the atlas, or of each of its pages.
+    fn page_size(&self) -> (u32, u32);
+
+    /// Returns the entry of the character `c` of the font `font`, with the fallbacks of the atlas.
+    fn glyph(&self, font: FontId, c: char) -> Option<&FontAtlasEntry>;
+
+    /// Returns true if the font `font` itself has a glyph for the character `c`, without the fallbacks of the atlas.
+    fn has_glyph(&self, font: FontId, c: char) -> bool {
+	self.glyph(font, c).is_some()
+    }
+
+    /// Returns the single glyph of the font `font` drawing the whole `sequence`, like an emoji ZWJ sequence, if the atlas has one.
+    fn sequence_glyph(&self, _font: FontId, _sequence: &str) -> Option<&FontAtlasEntry> {
+	None
+    }
+
+    /// Returns the horizontal advance of the character `c` of the font `font`. Tabs advance by several spaces.
+    fn glyph_advance(&self, font: FontId, c: char) -> i32 {
+	match c {
+	    '\t' => TAB_WIDTH * self.glyph_advance(font, ' '),
+	    c => self.glyph(font, c).map_or(0, |entry| entry.metrics.advance),
+	}
+    }
+
+    /// Returns the vertical metrics of the font `font`.
+    fn font_metrics(&self, font: FontId) -> LineMetrics;
+
+    /// Returns the pixels of the page `page`, gray coverage being repeated in the 3 channels.
+    fn page_buffer(&self, page: usize) -> Option<&Pixels>;
+
+    /// Returns a number identifying the state of the entries, taken from `next_generation` so that it isn't shared with any
+    /// other atlas, even one dropped, and renewed each time entries are added or moved. The default, 0, suits atlases whose
+    /// entries never change, but a LayoutCache can't tell two of them apart.
+    fn generation(&self) -> u64 {
+	0
+    }
+}
+
+/// An atlas holds a single font, with its fallbacks merged in, so the font is ignored.
+impl GlyphAtlas for FontAtlas {
+    fn page_size(&self) -> (u32, u32) {
+	(self.width, self.height)
+    }
+
+    fn glyph(&self, _font: FontId, c: char) -> Option<&FontAtlasEntry> {
+	self.lookup(c)
+    }
+
+    fn sequence_glyph(&self, _font: FontId, sequence: &str) -> Option<&FontAtlasEntry> {
+	self.lookup_sequence(sequence)
+    }
+
+    fn font_metrics(&self, _font: FontId) -> LineMetrics {
+	self.line_metrics
+    }
+
+    fn page_buffer(&self, page: usize) -> Option<&Pixels> {
+	(page == 0).then_some(&self.buffer)
+    }
+
+    fn generation(&self) -> u64 {
+	self.generation
+    }
+}
+
+/// The glyphs of a FontAtlas baked at one of its sizes, given by `FontAtlas::at_size`.
+#[derive(Copy, Clone)]
+pub struct SizedAtlas<'a> {
+    atlas: &'a FontAtlas,
+    size: u32,
+}
+
+impl SizedAtlas<'_> {
+    /// Returns the size of the glyphs, in 26.6 points.
+    pub fn size(&self) -> u32 {
+	self.size
+    }
+}
+
+/// Like its atlas, the view holds a single font, so the font is ignored.
+impl GlyphAtlas for SizedAtlas<'_> {
+    fn page_size(&self) -> (u32, u32) {
+	self.atlas.page_size()
+    }
+
+    fn glyph(&self, _font: FontId, c: char) -> Option<&FontAtlasEntry> {
+	self.atlas.lookup_sized(c, self.size)
+    }
+
+    fn font_metrics(&self, _font: FontId) -> LineMetrics {
+	self.atlas.line_metrics_sized(self.size).unwrap_or(self.atlas.line_metrics)
+    }
+
+    fn page_buffer(&self, page: usize) -> Option<&Pixels> {
+	self.atlas.page_buffer(page)
+    }
+
+    fn generation(&self) -> u64 {
+	self.atlas.generation
+    }
+}
+
+/// Vertical metrics of the font the atlas was generated from, in pixels.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct LineMetrics {
+    pub ascender: i32,
+    pub descender: i32,
+    pub line_height: i32,
+    /// Distance from the baseline up to the middle of the underline, negative below the baseline.
+    pub underline_position: i32,
+    pub underline_thickness: i32,
+    /// Distance from the baseline up to the middle of the strikeout.
+    pub strikeout_position: i32,
+    pub strikeout_thickness: i32,
+}
+
+impl LineMetrics {
+    /// Creates line metrics from the ascender, descender and line height, without decoration metrics.
+    pub fn new(ascender: i32, descender: i32, line_height: i32) -> Self {
+	Self {
+	    ascender,
+	    descender,
+	    line_height,
+	    ..Self::default()
+	}
+    }
+
+    /// Sets the position and thickness of the underline.
+    pub fn with_underline(mut self, position: i32, thickness: i32) -> Self {
+	self.underline_position = position;
+	self.underline_thickness = thickness;
+	self
+    }
+
+    /// Sets the position and thickness of the strikeout.
+    pub fn with_strikeout(mut self, position: i32, thickness: i32) -> Self {
+	self.strikeout_position = position;
+	self.strikeout_thickness = thickness;
+	self
+    }
+}
+
+/// An entry to the font atlas. It contains the glyph metrics and its position in the atlas.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct FontAtlasEntry {
+    metrics: GlyphMetrics,
+    position: Rectangle,
+    page: usize,
+    font: FontId,
+    glyph_id: u32,
+    rotated: bool,
+    color: bool,
+}
+
+impl FontAtlasEntry {
+    /// Creates an entry from the glyph metrics and position in an atlas.
+    pub fn new(position: Rectangle, metrics: GlyphMetrics) -> Self {
+	Self {
+	    position,
+	    metrics,
+	    page: 0,
+	    font: FontId(0),
+	    glyph_id: 0,
+	    rotated: false,
+	    color: false,
+	}
+    }
+
+    /// Sets whether the glyph is drawn with its own colors, like color emoji and images.
+    pub fn with_color(mut self, color: bool) -> Self {
+	self.color = color;
+	self
+    }
+
+    /// Sets whether the glyph is stored rotated 90° clockwise in the atlas.
+    pub fn with_rotation(mut self, rotated: bool) -> Self {
+	self.rotated = rotated;
+	self
+    }
+
+    /// Sets the index of the glyph in the face it was rendered from.
+    pub fn with_glyph_id(mut self, glyph_id: u32) -> Self {
+	self.glyph_id = glyph_id;
+	self
+    }
+
+    /// Sets the font the glyph was rendered from.
+    pub fn with_font(mut self, font: FontId) -> Self {
+	self.font = font;
+	self
+    }
+
+    /// Sets the index of the atlas page containing the glyph.
+    pub fn with_page(mut self, page: usize) -> Self {
+	self.page = page;
+	self
+    }
+
+    /// Returns the metrics of the glyph.
+    pub fn metrics(&self) -> &GlyphMetrics {
+	&self.metrics
+    }
+
+    /// Returns the position of the glyph in the atlas. The width and height are swapped for a rotated glyph.
+    pub fn position(&self) -> &Rectangle {
+	&self.position
+    }
+
+    /// Returns the uv of the top-left and bottom-right corners of the glyph position, normalized to `atlas_size`, with the
+    /// v axis going down. With `inset_half_texel`, the corners are moved half a texel inwards, to the centers of the edge texels,
+    /// so that bilinear filtering never samples the neighbouring texels.
+    pub fn uv_rect(&self, atlas_size: (u32, u32), inset_half_texel: bool) -> [f32; 4] {
+	let [left, top, right, bottom] = self.texel_rect(inset_half_texel);
+	let (width, height) = (atlas_size.0 as f32, atlas_size.1 as f32);
+
+	[left / width, top / height, right / width, bottom / height]
+    }
+
+    /// Returns the left, top, right and bottom edges of the glyph position in texels, moved half a texel inwards if
+    /// `inset_half_texel` is set and the glyph isn't blank.
+    fn texel_rect(&self, inset_half_texel: bool) -> [f32; 4] {
+	let inset = if inset_half_texel && !self.is_blank() { 0.5 } else { 0.0 };
+	let position = &self.position;
+
+	[
+	    position.left as f32 + inset,
+	    position.top as f32 + inset,
+	    (position.left + position.width) as f32 - inset,
+	    (position.top + position.height) as f32 - inset,
+	]
+    }
+
+    /// Returns true if the glyph is stored rotated 90° clockwise in the atlas, its top edge running down the right of its position.
+    pub fn is_rotated(&self) -> bool {
+	self.rotated
+    }
+
+    /// Returns true if the glyph is drawn with its own colors, premultiplied by their alpha, rather than with its coverage of the
+    /// text color. Quads and instances give it a white color.
+    pub fn is_color(&self) -> bool {
+	self.color
+    }
+
+    /// Returns true if the glyph has no pixels, like a space, and takes no room in the atlas.
+    pub fn is_blank(&self) -> bool {
+	self.position.width == 0 || self.position.height == 0
+    }
+
+    /// Returns the index of the atlas page containing the glyph.
+    pub fn page(&self) -> usize {
+	self.page
+    }
+
+    /// Returns the font the glyph was rendered from, `FontId(0)` being the primary font of the generator.
+    pub fn font(&self) -> FontId {
+	self.font
+    }
+
+    /// Returns the index of the glyph in the face it was rendered from, 0 for the .notdef glyph.
+    pub fn glyph_id(&self) -> u32 {
+	self.glyph_id
+    }
+
+    /// Returns the bottom-left, bottom-right, top-left and top-right corners of the quad drawing the entry with its origin at (`x`, `y`),
+    /// `atlas_size` being the size of its atlas page.
+    pub fn quad(&self, atlas_size: (u32, u32), x: i32, y: i32, color: Color, config: &LayoutConfig) -> [TextVertex; 4] {
+	glyph_quad(self, atlas_size, x, y, color, config)
+    }
+}
+
+// @Temporary
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TextVertex {
+    pub position: [f32; 2],
+    pub uv: [f32; 2],
+    pub color: [f32; 4],
+    /// The page of the atlas the glyph is on.
+    pub layer: u32,
+}
+
+/// A glyph quad described by a single instance, to be expanded to its 4 corners by the vertex shader.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct GlyphInstance {
+    /// The corner of the quad with the lowest coordinates: bottom-left with y up, top-left with y down.
+    pub position: [f32; 2],
+    pub size: [f32; 2],
+    /// The uv of the `position` corner followed by the uv of the opposite corner.
+    pub uv_rect: [f32; 4],
+    pub color: [f32; 4],
+    /// The page of the atlas the glyph is on.
+    pub layer: u32,
+    /// 1 if the glyph is rotated in the atlas, u then following the y axis of the quad and v its x axis.
+    pub rotated: u32,
+}
+
+/// The quad drawing a glyph, handed to a VertexEmitter to build vertices of its own type.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct GlyphQuad {
+    /// The byte index of the glyph in the text.
+    pub index: usize,
+    /// The bottom-left, bottom-right, top-left and top-right corners of the quad, like `FontAtlasEntry::quad`.
+    pub positions: [[f32; 2]; 4],
+    /// The uvs of the corners, in the same order.
+    pub uvs: [[f32; 2]; 4],
+    /// The color of the glyph with straight alpha, white for color glyphs.
+    pub color: [f32; 4],
+    /// The page of the atlas the glyph is on.
+    pub page: u32,
+}
+
+impl GlyphQuad {
+    /// Creates the quad of the glyph at the byte `index` from its 4 corners, as returned by `FontAtlasEntry::quad`.
+    pub fn new(index: usize, corners: &[TextVertex; 4]) -> Self {
+	Self {
+	    index,
+	    positions: corners.map(|corner| corner.position),
+	    uvs: corners.map(|corner| corner.uv),
+	    color: corners[0].color,
+	    page: corners[0].layer,
+	}
+    }
+
+    /// Returns the corners of the quad as TextVertex.
+    pub fn vertices(&self) -> [TextVertex; 4] {
+	[0, 1, 2, 3].map(|corner| TextVertex {
+	    position: self.positions[corner],
+	    uv: self.uvs[corner],
+	    color: self.color,
+	    layer: self.page,
+	})
+    }
+
+    /// Returns the quad moved by less than a device pixel so that its bottom-left corner lands on a whole device pixel,
+    /// `pixels_per_unit` being the device pixels per unit of its positions. Quads of unrotated glyphs, a whole number of
+    /// pixels wide and tall, then have all their corners on whole pixels, and their texels map to pixels one to one instead
+    /// of being blurred by interpolation.
+    pub fn snapped(&self, pixels_per_unit: f32) -> GlyphQuad {
+	let snap = |value: f32| (value * pixels_per_unit).round() / pixels_per_unit;
+	let [[left, bottom], [_, bottom_right], [top_left, _], _] = self.positions;
+	let unrotated = (bottom - bottom_right).abs() * pixels_per_unit < 1e-3 && (left - top_left).abs() * pixels_per_unit < 1e-3;
+
+	// Rounding each corner of an unrotated quad translates it without the rounding errors of adding an offset
+	let positions = match unrotated {
+	    true => self.positions.map(|[x, y]| [snap(x), snap(y)]),
+	    false => {
+		let offset = [snap(left) - left, snap(bottom) - bottom];
+		self.positions.map(|[x, y]| [x + offset[0], y + offset[1]])
+	    },
+	};
+
+	GlyphQuad { positions, ..*self }
+    }
+
+    /// Returns the indices of the corners making the two triangles of the quad, counter-clockwise with y up.
+    pub fn triangle_corners() -> [u32; 6] {
+	QUAD_INDICES
+    }
+}
+
+/// Builds vertices of any type from the glyph quads of a layout, so that engines with their own vertex layout, like 3D positions,
+/// packed uvs or interleaved colors, don't go through TextVertex. Closures taking a `&GlyphQuad` are emitters.
+pub trait VertexEmitter {
+    /// Called with the quad of each visible glyph, in the order of the layout.
+    fn emit_quad(&mut self, quad: &GlyphQuad);
+}
+
+impl<F> VertexEmitter for F where F: FnMut(&GlyphQuad) {
+    fn emit_quad(&mut self, quad: &GlyphQuad) {
+	self(quad)
+    }
+}
+
+impl TextVertex {
+    pub fn new(x: f32, y: f32, u: f32, v: f32) -> TextVertex {
+	TextVertex {
+	    position: [x, y],
+	    uv: [u, v],
+	    color: Color::default().to_f32(),
+	    layer: 0,
+	}
+    }
+}
+
+pub fn generate_buffers_from_text(text: &str, font_atlas: &FontAtlas, x: i32, y: i32) -> Vec<TextVertex> {
+    generate_colored_buffers_from_text(text, font_atlas, x, y, &[])
+}
+
+/// Same as `generate_buffers_from_text`, (`x`, `y`) being the `anchor` of the text rather than the start of its first baseline.
+/// The text is as tall as its lines, the first starting an ascender above its baseline, and as wide as its longest line.
+pub fn generate_anchored_buffers_from_text(text: &str, font_atlas: &FontAtlas, x: i32, y: i32, anchor: Anchor) -> Vec<TextVertex> {
+    let metrics = &font_atlas.line_metrics;
+    let width = text.split('\n').map(|line| line.chars().map(|c| font_atlas.advance(c)).sum::<i32>()).max().unwrap_or(0);
+    let height = text.split('\n').count() as i32 * metrics.line_height;
+    let (anchor_x, anchor_y) = anchor.position(width, height, metrics.ascender);
+
+    generate_buffers_from_text(text, font_atlas, x - anchor_x, y + anchor_y - metrics.ascender)
+}
+
+/// Same as `generate_buffers_from_text`, the vertices of the glyphs in each byte range of `colors` getting its color.
+/// Glyphs outside of all ranges are white.
+pub fn generate_colored_buffers_from_text(text: &str, font_atlas: &FontAtlas, x: i32, y: i32, colors: &[(Range<usize>, Color)]) -> Vec<TextVertex> {
+    let mut vertex_buffer = Vec::<TextVertex>::with_capacity(text.len() * 6);
+
+    for_each_glyph(text, font_atlas, x, y, |index, glyph, x, y| {
+	push_glyph_quad(&mut vertex_buffer, glyph, (font_atlas.width, font_atlas.height), x, y, color_at(colors, index), &LayoutConfig::default())
+    });
+
+    vertex_buffer
+}
+
+/// Same as `generate_buffers_from_text`, but with 4 unique vertices per glyph and an index buffer made of 2 triangles per glyph.
+pub fn generate_indexed_buffers_from_text(text: &str, font_atlas: &FontAtlas, x: i32, y: i32) -> (Vec<TextVertex>, Vec<u32>) {
+    let mut vertex_buffer = Vec::<TextVertex>::with_capacity(text.len() * 4);
+
+    for_each_glyph(text, font_atlas, x, y, |_, glyph, x, y| push_indexed_glyph_quad(&mut vertex_buffer, glyph, (font_atlas.width, font_atlas.height), x, y, Color::default(), &LayoutConfig::default()));
+
+    let index_buffer = quad_indices(vertex_buffer.len() / 4);
+    (vertex_buffer, index_buffer)
+}
+
+/// Returns the indices of `quad_count` quads made of 4 consecutive vertices, as emitted by the indexed buffer generators.
+/// The pattern only depends on the number of quads, so a single buffer sized for the largest text can be shared.
+pub fn quad_indices(quad_count: usize) -> Vec<u32> {
+    (0..quad_count as u32)
+	.flat_map(|quad| QUAD_INDICES.iter().map(move |index| quad * 4 + index))
+	.collect()
+}
+
+/// Generates one instance per visible glyph of `text`, drawn in `color`, for instanced quad rendering.
+pub fn generate_instances_from_text(text: &str, font_atlas: &FontAtlas, x: i32, y: i32, color: Color) -> Vec<GlyphInstance> {
+    let mut instances = Vec::<GlyphInstance>::with_capacity(text.len());
+
+    for_each_glyph(text, font_atlas, x, y, |_, glyph, x, y| push_glyph_instance(&mut instances, glyph, (font_atlas.width, font_atlas.height), x, y, color, &LayoutConfig::default()));
+
+    instances
+}
+
+/// Returns the color of the first range of `colors` containing the byte `index`, or white.
+pub(crate) fn color_at(colors: &[(Range<usize>, Color)], index: usize) -> Color {
+    colors.iter()
+	.find(|(range, _)| range.contains(&index))
+	.map_or_else(Color::default, |(_, color)| *color)
+}
+
+/// Calls `f` with the byte index of each glyph of `text`, its entry and the pen position it is drawn at, starting at (`x`, `y`).
+fn for_each_glyph<F>(text: &str, font_atlas: &FontAtlas, x: i32, y: i32, mut f: F) where F: FnMut(usize, &FontAtlasEntry, i32, i32) {
+    for (index, glyph, x, y) in PlacedGlyphs::new(text, font_atlas, x, y) {
+	f(index, glyph, x, y);
+    }
+}
+
+/// Yields the byte index of each glyph of a text, its entry and the pen position it is drawn at, lines going down from the first baseline.
+struct PlacedGlyphs<'a> {
+    chars: CharIndices<'a>,
+    font_atlas: &'a FontAtlas,
+    x: i32,
+    advance: i32,
+    baseline: i32,
+}
+
+impl<'a> PlacedGlyphs<'a> {
+    fn new(text: &'a str, font_atlas: &'a FontAtlas, x: i32, y: i32) -> Self {
+	Self {
+	    chars: text.char_indices(),
+	    font_atlas,
+	    x,
+	    advance: 0,
+	    baseline: y,
+	}
+    }
+}
+
+impl<'a> Iterator for PlacedGlyphs<'a> {
+    type Item = (usize, &'a FontAtlasEntry, i32, i32);
+
+    fn next(&mut self) -> Option<Self::Item> {
+	for (index, c) in self.chars.by_ref() {
+	    if c == '\n' {
+		self.advance = 0;
+		self.baseline -= self.font_atlas.line_metrics.line_height;
+		continue;
+	    }
+
+	    let pen = self.x + self.advance;
+	    self.advance += self.font_atlas.advance(c);
+
+	    if let Some(glyph) = self.font_atlas.lookup(c) {
+		return Some((index, glyph, pen, self.baseline));
+	    }
+	}
+
+	None
+    }
+}
+
+/// Returns the quads of the visible glyphs of `text`, placed like `generate_buffers_from_text` does starting at (`x`, `y`), computed one at a
+/// time as the iterator advances. Nothing is allocated, so dynamic text can be laid out every frame straight into a mapped vertex buffer.
+pub fn layout_quads<'a>(text: &'a str, font_atlas: &'a FontAtlas, x: i32, y: i32) -> GlyphQuads<'a> {
+    GlyphQuads {
+	glyphs: PlacedGlyphs::new(text, font_atlas, x, y),
+	color: Color::default(),
+	config: LayoutConfig::default(),
+    }
+}
+
+/// The iterator over the glyph quads of a text returned by `layout_quads`.
+pub struct GlyphQuads<'a> {
+    glyphs: PlacedGlyphs<'a>,
+    color: Color,
+    config: LayoutConfig,
+}
+
+impl<'a> GlyphQuads<'a> {
+    /// Sets the color of the glyphs, white by default.
+    pub fn with_color(mut self, color: Color) -> Self {
+	self.color = color;
+	self
+    }
+
+    /// Sets the coordinate conventions of the quads, y up with uvs starting at the bottom-left corner by default.
+    pub fn with_config(mut self, config: LayoutConfig) -> Self {
+	self.config = config;
+	self
+    }
+}
+
+impl<'a> Iterator for GlyphQuads<'a> {
+    type Item = GlyphQuad;
+
+    fn next(&mut self) -> Option<GlyphQuad> {
+	let atlas_size = (self.glyphs.font_atlas.width, self.glyphs.font_atlas.height);
+
+	self.glyphs.by_ref()
+	    .find(|(_, glyph, _, _)| !glyph.is_blank())
+	    .map(|(index, glyph, x, y)| GlyphQuad::new(index, &glyph_quad(glyph, atlas_size, x, y, self.color, &self.config)))
+    }
+}
+
+/// Pushes the two triangles drawing `glyph` with its origin at (`x`, `y`), `atlas_size` being the size of its atlas page.
+pub(crate) fn push_glyph_quad(vertex_buffer: &mut Vec<TextVertex>, glyph: &FontAtlasEntry, atlas_size: (u32, u32), x: i32, y: i32, color: Color, config: &LayoutConfig) {
+    if glyph.is_blank() {
+	return;
+    }
+
+    let corners = glyph_quad(glyph, atlas_size, x, y, color, config);
+    vertex_buffer.extend(QUAD_INDICES.iter().map(|index| corners[*index as usize]));
+}
+
+/// Pushes the 4 corners of the glyph quad, in the order expected by `quad_indices`.
+pub(crate) fn push_indexed_glyph_quad(vertex_buffer: &mut Vec<TextVertex>, glyph: &FontAtlasEntry, atlas_size: (u32, u32), x: i32, y: i32, color: Color, config: &LayoutConfig) {
+    if glyph.is_blank() {
+	return;
+    }
+
+    vertex_buffer.extend_from_slice(&glyph_quad(glyph, atlas_size, x, y, color, config));
+}
+
+/// Pushes the instance drawing `glyph` with its origin at (`x`, `y`).
+pub(crate) fn push_glyph_instance(instances: &mut Vec<GlyphInstance>, glyph: &FontAtlasEntry, atlas_size: (u32, u32), x: i32, y: i32, color: Color, config: &LayoutConfig) {
+    if glyph.is_blank() {
+	return;
+    }
+
+    let [bottom_left, bottom_right, top_left, top_right] = glyph_quad(glyph, atlas_size, x, y, color, config);
+    let (origin, opposite) = match config.y_axis {
+	YAxis::Up => (bottom_left, top_right),
+	YAxis::Down => (top_left, bottom_right),
+    };
+
+    instances.push(GlyphInstance {
+	position: origin.position,
+	size: [glyph.metrics.width as f32, glyph.metrics.height as f32],
+	uv_rect: [origin.uv[0], origin.uv[1], opposite.uv[0], opposite.uv[1]],
+	color: origin.color,
+	layer: origin.layer,
+	rotated: glyph.rotated as u32,
+    });
+}
+
+/// Pushes the two triangles of an untextured quad whose corners are (`left`, `bottom`) and (`right`, `top`), in the order of glyph quads.
+pub(crate) fn push_solid_quad(vertex_buffer: &mut Vec<TextVertex>, left: f32, bottom: f32, right: f32, top: f32, color: Color) {
+    let corner = |x: f32, y: f32| TextVertex {
+	position: [x, y],
+	uv: [0.0, 0.0],
+	color: color.to_f32(),
+	layer: 0,
+    };
+
+    let corners = [corner(left, bottom), corner(right, bottom), corner(left, top), corner(right, top)];
+    vertex_buffer.extend(QUAD_INDICES.iter().map(|index| corners[*index as usize]));
+}
+
+/// The two triangles, counter-clockwise with y up, of a quad whose corners are bottom-left, bottom-right, top-left and top-right.
+const QUAD_INDICES: [u32; 6] = [0, 1, 2, 1, 3, 2];
+
+/// Returns the bottom-left, bottom-right, top-left and top-right corners of the glyph quad drawn with its origin at (`x`, `y`).
+fn glyph_quad(glyph: &FontAtlasEntry, atlas_size: (u32, u32), x: i32, y: i32, color: Color, config: &LayoutConfig) -> [TextVertex; 4] {
+    // The quad is computed with y up, then mirrored for a y down space
+    let (y, y_sign) = match config.y_axis {
+	YAxis::Up => (y, 1.0),
+	YAxis::Down => (-y, -1.0),
+    };
+
+    let left = (x + glyph.metrics.bearing_x) as f32;
+    let right = (x + glyph.metrics.bearing_x + glyph.metrics.width as i32) as f32;
+    let top = (y + glyph.metrics.bearing_y) as f32 * y_sign;
+    let bottom = (y + glyph.metrics.bearing_y - glyph.metrics.height as i32) as f32 * y_sign;
+
+    let (width, height) = match config.uv_units {
+	UvUnits::Normalized => (atlas_size.0 as f32, atlas_size.1 as f32),
+	UvUnits::Texels => (1.0, 1.0),
+    };
+    let [texel_left, texel_top, texel_right, texel_bottom] = glyph.texel_rect(false);
+    let (v_top, v_bottom) = match config.uv_origin {
+	UvOrigin::BottomLeft => (atlas_size.1 as f32 - texel_top, atlas_size.1 as f32 - texel_bottom),
+	UvOrigin::TopLeft => (texel_top, texel_bottom),
+    };
+
+    let uv_left = texel_left / width;
+    let uv_right = texel_right / width;
+    let uv_top = v_top / height;
+    let uv_bottom = v_bottom / height;
+
+    let color = if glyph.color { Color::default() } else { color };
+    let corner = |x: f32, y: f32, u: f32, v: f32| TextVertex {
+	position: [x, y],
+	uv: [u, v],
+	color: color.to_f32(),
+	layer: glyph.page as u32,
+    };
+
+    // A rotated glyph has its top edge on the right of its rectangle and its left edge on the top
+    if glyph.rotated {
+	[
+	    corner(left, bottom, uv_left, uv_top),
+	    corner(right, bottom, uv_left, uv_bottom),
+	    corner(left, top, uv_right, uv_top),
+	    corner(right, top, uv_right, uv_bottom),
+	]
+    } else {
+	[
+	    corner(left, bottom, uv_left, uv_bottom),
+	    corner(right, bottom, uv_right, uv_bottom),
+	    corner(left, top, uv_left, uv_top),
+	    corner(right, top, uv_right, uv_top),
+	]
+    }
+}
+
+/// Saves an image of `s` in white over black, cropped to the glyphs drawn. LCD coverage is composited channel by channel.
+#[cfg(all(feature = "image", not(target_arch = "wasm32")))]
+pub fn generate_text_img<P>(s: &str, font_atlas: &FontAtlas, save_path: P) -> std::io::Result<()> where P: AsRef<Path> {
+    generate_text_img_with_options(s, font_atlas, save_path, &TextImageOptions::new(Color::rgb(0, 0, 0), Color::default()))
+}
+
+/// The colors of the images generated by `generate_text_img_with_options`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TextImageOptions {
+    /// The color the image is filled with before drawing the text, transparent by default.
+    pub background: Color,
+    /// The color of the text, its coverage being used as alpha. White by default.
+    pub foreground: Color,
+}
+
+impl TextImageOptions {
+    /// Creates options drawing `foreground` text over a `background` filled image.
+    pub fn new(background: Color, foreground: Color) -> Self {
+	Self { background, foreground }
+    }
+}
+
+impl Default for TextImageOptions {
+    fn default() -> Self {
+	Self::new(Color::new(0, 0, 0, 0), Color::default())
+    }
+}
+
+/// Saves an RGBA image of `s` laid out with `font_atlas`, with the colors of `options`, cropped to the glyphs drawn.
+#[cfg(all(feature = "image", not(target_arch = "wasm32")))]
+pub fn generate_text_img_with_options<P>(s: &str, font_atlas: &FontAtlas, save_path: P, options: &TextImageOptions) -> std::io::Result<()> where P: AsRef<Path> {
+    render_text_img(s, font_atlas, options).save(save_path)
+}
+
+/// Returns an RGBA image of `s` laid out with `font_atlas`, with the colors of `options`, cropped to the glyphs drawn.
+pub fn render_text_img(s: &str, font_atlas: &FontAtlas, options: &TextImageOptions) -> Pixels {
+    let layout = TextLayout::new(s, font_atlas, None);
+
+    let mut bounds: Option<(i32, i32, i32, i32)> = None;
+    for placement in layout.glyphs() {
+	if let Some(glyph) = layout.glyph_entry(font_atlas, placement).filter(|glyph| !glyph.is_blank()) {
+	    let left = placement.x + glyph.metrics.bearing_x;
+	    let top = placement.y - glyph.metrics.bearing_y;
+	    let right = left + glyph.metrics.width as i32;
+	    let bottom = top + glyph.metrics.height as i32;
+
+	    bounds = Some(match bounds {
+		Some((l, t, r, b)) => (l.min(left), t.min(top), r.max(right), b.max(bottom)),
+		None => (left, top, right, bottom),
+	    });
+	}
+    }
+
+    let (left, top, right, bottom) = bounds.unwrap_or((0, 0, 1, 1));
+    let background = options.background;
+    let mut buffer = Pixels::from_pixel((right - left) as u32, (bottom - top) as u32, PixelFormat::Rgba8, &[background.r, background.g, background.b, background.a]);
+
+    blend_text(&mut buffer, -left, -top, &layout, font_atlas, options.foreground);
+
+    buffer
+}
+
+/// Draws the glyphs of `layout` in `color` over the RGBA `image`, (`x`, `y`) being the top-left corner of the paragraph.
+/// The coverage of each glyph is alpha blended with the image channel by channel, so that the subpixel coverage of LCD atlases
+/// weights each color channel on its own like a dual-source blend would. Color glyphs are drawn with their own colors and alpha,
+/// faded by the alpha of `color`. Pixels outside the image are skipped. Returns an error without drawing anything if the image isn't RGBA.
+pub fn draw_text<A: GlyphAtlas>(image: &mut Pixels, x: i32, y: i32, layout: &TextLayout, atlas: &A, color: Color) -> Result<(), PixelFormatError> {
+    if image.format != PixelFormat::Rgba8 {
+	return Err(PixelFormatError::new(PixelFormat::Rgba8, image.format));
+    }
+
+    blend_text(image, x, y, layout, atlas, color);
+    Ok(())
+}
+
+/// Same as `draw_text`, `image` being known to be RGBA.
+fn blend_text<A: GlyphAtlas>(image: &mut Pixels, x: i32, y: i32, layout: &TextLayout, atlas: &A, color: Color) {
+    let text_alpha = color.a as f32 / 255.0;
+    let foreground = [color.r as f32, color.g as f32, color.b as f32];
+
+    for placement in layout.glyphs() {
+	let glyph = match layout.glyph_entry(atlas, placement) {
+	    Some(glyph) if !glyph.is_blank() => glyph,
+	    _ => continue,
+	};
+	let page = match atlas.page_buffer(glyph.page) {
+	    Some(page) => page,
+	    None => continue,
+	};
+
+	let glyph_left = x + placement.x + glyph.metrics.bearing_x;
+	let glyph_top = y + placement.y - glyph.metrics.bearing_y;
+	let (width, height) = match glyph.rotated {
+	    true => (glyph.position.height, glyph.position.width),
+	    false => (glyph.position.width, glyph.position.height),
+	};
+
+	for source_y in 0..height {
+	    for source_x in 0..width {
+		let dest_x = glyph_left + source_x as i32;
+		let dest_y = glyph_top + source_y as i32;
+		if dest_x < 0 || dest_y < 0 || dest_x >= image.width as i32 || dest_y >= image.height as i32 {
+		    continue;
+		}
+
+		let coverage = match glyph.rotated {
+		    true => page.get_pixel(glyph.position.left + height - 1 - source_y, glyph.position.top + source_x),
+		    false => page.get_pixel(glyph.position.left + source_x, glyph.position.top + source_y),
+		};
+		if coverage.iter().all(|value| *value == 0) {
+		    continue;
+		}
+
+		let pixel = image.get_pixel_mut(dest_x as u32, dest_y as u32);
+		let dest_alpha = pixel[3] as f32 / 255.0;
+		// Coverage, and color glyphs in RGB atlases, cover the image as much as their brightest channel
+		let source_alpha = to_rgba(coverage)[3] as f32 / 255.0 * text_alpha;
+		let alpha = source_alpha + dest_alpha * (1.0 - source_alpha);
+		if alpha == 0.0 {
+		    continue;
+		}
+
+		// Gray coverage is the same in all channels, while LCD coverage blends each of them with its own weight.
+		// Color glyphs hold premultiplied colors, blended with their alpha
+		for channel in 0..3 {
+		    let (premultiplied, channel_alpha) = match glyph.color {
+			true => (coverage[channel] as f32 * text_alpha, source_alpha),
+			false => {
+			    let channel_alpha = coverage[channel] as f32 / 255.0 * text_alpha;
+			    (foreground[channel] * channel_alpha, channel_alpha)
+			},
+		    };
+		    let blended = premultiplied + pixel[channel] as f32 * dest_alpha * (1.0 - channel_alpha);
+		    pixel[channel] = (blended / alpha).round().min(255.0) as u8;
+		}
+		pixel[3] = (alpha * 255.0).round() as u8;
+	    }
+	}
+    }
+}
+
+/// A struct representing a padding area around a rectangle.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Padding {
+    left: u32,
+    right: u32,
+    top: u32,
+    bottom: u32,
+}
+
+impl Padding {
+    /// Creates a Padding object from the padding on all sides
+    pub fn new(left: u32, right: u32, top: u32, bottom: u32) -> Self {
+	Self { left, right, top, bottom }
+    }
+
+    /// Creates a padding of `padding` pixels on all sides.
+    pub fn uniform(padding: u32) -> Self {
+	Self::new(padding, padding, padding, padding)
+    }
+
+    /// Creates a padding of `horizontal` pixels on the left and right, and `vertical` pixels on the top and bottom.
+    pub fn symmetric(horizontal: u32, vertical: u32) -> Self {
+	Self::new(horizontal, horizontal, vertical, vertical)
+    }
+
+    /// Returns the padding on the left side.
+    pub fn left(&self) -> u32 {
+	self.left
+    }
+
+    /// Returns the padding on the right side.
+    pub fn right(&self) -> u32 {
+	self.right
+    }
+
+    /// Returns the padding on the top side.
+    pub fn top(&self) -> u32 {
+	self.top
+    }
+
+    /// Returns the padding on the bottom side.
+    pub fn bottom(&self) -> u32 {
+	self.bottom
+    }
+
+    /// Returns the padding on the left and right sides together.
+    pub fn horizontal(&self) -> u32 {
+	self.left + self.right
+    }
+
+    /// Returns the padding on the top and bottom sides together.
+    pub fn vertical(&self) -> u32 {
+	self.top + self.bottom
+    }
+}
+
+/// An enum telling the AtlasGenerator how to load the glyphs.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum AtlasLoadMode {
+    Gray,
+    #[default]
+    LCD,
+    /// Gray, with the glyphs of color fonts, like emoji, in their colors premultiplied by their alpha. The atlases are RGBA,
+    /// coverage being stored as premultiplied white.
+    Color,
+}
+
+impl AtlasLoadMode {
+    /// Returns the format of the atlases rendered in this mode, RGBA for color glyphs to keep their alpha and RGB otherwise.
+    pub fn pixel_format(&self) -> PixelFormat {
+	match self {
+	    AtlasLoadMode::Gray | AtlasLoadMode::LCD => PixelFormat::Rgb8,
+	    AtlasLoadMode::Color => PixelFormat::Rgba8,
+	}
+    }
+
+    /// Returns the FreeType load flags rendering glyphs in this mode.
+    #[cfg(feature = "freetype")]
+    pub fn load_flags(&self) -> LoadFlag {
+	match self {
+	    AtlasLoadMode::Gray => LoadFlag::RENDER,
+	    AtlasLoadMode::LCD => LoadFlag::RENDER | LoadFlag::TARGET_LCD,
+	    AtlasLoadMode::Color => LoadFlag::RENDER | LoadFlag::COLOR,
+	}
+    }
+}
+
+/// What a generator does when the glyphs don't fit in the atlas. Sizes are in 26.6 points, like the size given to `generate`.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum OverflowPolicy {
+    /// Returns the packing error.
+    #[default]
+    Fail,
+    /// Retries with the font size reduced by `step` each time, down to `min_size`. The extra sizes of the options are kept.
+    ShrinkFont { step: u32, min_size: u32 },
+    /// Retries with the atlas width and height doubled each time, up to `max_width` by `max_height`.
+    GrowAtlas { max_width: u32, max_height: u32 },
+}
+
+impl OverflowPolicy {
+    /// Returns the font and atlas sizes to retry with after an overflow at `size` in an atlas of `atlas_size`, or None to give up.
+    pub fn next(&self, size: u32, atlas_size: (u32, u32)) -> Option<(u32, (u32, u32))> {
+	let next = match *self {
+	    OverflowPolicy::Fail => return None,
+	    OverflowPolicy::ShrinkFont { step, min_size } => (size.saturating_sub(step).max(min_size), atlas_size),
+	    OverflowPolicy::GrowAtlas { max_width, max_height } => {
+		let (width, height) = atlas_size;
+		(size, (width.saturating_mul(2).min(max_width).max(width), height.saturating_mul(2).min(max_height).max(height)))
+	    },
+	};
+
+	(next != (size, atlas_size)).then_some(next)
+    }
+
+    /// Writes the policy to `hasher`.
+    fn hash(&self, hasher: &mut ContentHasher) {
+	let (kind, first, second) = match *self {
+	    OverflowPolicy::Fail => (0, 0, 0),
+	    OverflowPolicy::ShrinkFont { step, min_size } => (1, step, min_size),
+	    OverflowPolicy::GrowAtlas { max_width, max_height } => (2, max_width, max_height),
+	};
+
+	for value in [kind, first, second] {
+	    hasher.write_u32(value);
+	}
+    }
+}
+
+/// The function wrapped by a GlyphHook.
+type GlyphHookFn = dyn Fn(&mut Pixels, &GlyphMetrics, char) + Send + Sync;
+
+/// A function post-processing the bitmap of each glyph once rendered and before it is packed, given its metrics and character,
+/// for effects like tinting, thresholds or palettes. Shared by the options, it is equal to its clones only.
+#[derive(Clone)]
+pub struct GlyphHook(Arc<GlyphHookFn>);
+
+impl GlyphHook {
+    /// Wraps `hook` to be set in generator options. The bitmap must keep its size, which the metrics describe, and format,
+    /// generators failing with `AtlasGeneratorError::HookError` otherwise.
+    pub fn new<F>(hook: F) -> Self where F: Fn(&mut Pixels, &GlyphMetrics, char) + Send + Sync + 'static {
+	Self(Arc::new(hook))
+    }
+
+    /// Runs the hook on the rendered `glyph` of `c`, returning the processed glyph, or an error if the hook changed the size
+    /// or format of the bitmap or left it without as many bytes as its pixels need.
+    pub(crate) fn apply(&self, glyph: &Glyph, c: char) -> Result<Glyph, AtlasGeneratorError> {
+	let mut processed = glyph.clone();
+	(self.0)(&mut processed.bitmap, &processed.metrics, c);
+
+	let (bitmap, original) = (&processed.bitmap, &glyph.bitmap);
+	let length = bitmap.width as usize * bitmap.height as usize * bitmap.format.channels();
+	if bitmap.dimensions() != original.dimensions() || bitmap.format != original.format || bitmap.data.len() != length {
+	    return Err(AtlasGeneratorError::HookError(c));
+	}
+
+	Ok(processed)
+    }
+}
+
+impl Debug for GlyphHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+	write!(f, "GlyphHook")
+    }
+}
+
+impl PartialEq for GlyphHook {
+    fn eq(&self, other: &Self) -> bool {
+	Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// A struct representing the AtlasGenerator options.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AtlasGeneratorOption {
+    pub dpi: u32,
+    pub size: (u32, u32),
+    pub padding: Padding,
+    pub transform: Option<Transform>,
+    pub notdef: bool,
+    pub charset: String,
+    pub rotate: bool,
+    pub lenient: bool,
+    pub overflow: OverflowPolicy,
+    pub deterministic: bool,
+    pub extra_sizes: Vec<u32>,
+    /// Paddings replacing `padding` around the characters of a range, the last range containing a character winning.
+    pub padding_ranges: Vec<(RangeInclusive<char>, Padding)>,
+    pub crop: bool,
+    /// Sequences of characters drawn by a single glyph, like emoji ZWJ sequences, baked if a font of the generator has one.
+    pub sequences: Vec<String>,
+    /// Darkens the stems of the glyphs, for thin fonts coming out too light at small sizes.
+    pub stem_darkening: bool,
+    /// The gamma the coverage of the glyphs is raised to the inverse of, above 1 to make them darker and below to make them lighter.
+    pub gamma: f32,
+    /// Properties of the font drivers set before rendering, in order.
+    pub driver_properties: Vec<DriverProperty>,
+    /// Post-processes the bitmap of each character glyph before it is packed.
+    pub glyph_hook: Option<GlyphHook>,
+    /// The value the atlas is cleared to where no glyph is drawn.
+    pub background: Background,
+}
+
+impl AtlasGeneratorOption {
+    /// Creates an AtlasGeneratorOption object from its components.
+    pub fn new(width: u32, height: u32, dpi: u32, padding: Padding) -> Self {
+	Self {
+	    dpi,
+	    size: (width, height),
+	    padding,
+	    transform: None,
+	    notdef: false,
+	    charset: String::from(GLYPHS),
+	    rotate: false,
+	    lenient: false,
+	    overflow: OverflowPolicy::Fail,
+	    deterministic: false,
+	    extra_sizes: Vec::new(),
+	    padding_ranges: Vec::new(),
+	    crop: false,
+	    sequences: Vec::new(),
+	    stem_darkening: false,
+	    gamma: 1.0,
+	    driver_properties: Vec::new(),
+	    glyph_hook: None,
+	    background: Background::Transparent,
+	}
+    }
+
+    /// Sets the characters baked in the atlas, instead of GLYPHS.
+    pub fn with_charset(mut self, charset: &str) -> Self {
+	self.charset = String::from(charset);
+	self
+    }
+
+    /// Bakes the .notdef glyph in the atlas, used for characters missing from it.
+    pub fn with_notdef(mut self) -> Self {
+	self.notdef = true;
+	self
+    }
+
+    /// Sets the transform applied to every glyph before rasterization.
+    pub fn with_transform(mut self, transform: Transform) -> Self {
+	self.transform = Some(transform);
+	self
+    }
+
+    /// Lets the glyphs that don't fit upright be packed rotated 90° clockwise.
+    pub fn with_rotation(mut self) -> Self {
+	self.rotate = true;
+	self
+    }
+
+    /// Skips the characters that can't be rendered or packed instead of failing, listing them in `FontAtlas::failures`.
+    pub fn with_lenient(mut self) -> Self {
+	self.lenient = true;
+	self
+    }
+
+    /// Sets what happens when the glyphs don't fit in the atlas, instead of failing.
+    pub fn with_overflow(mut self, overflow: OverflowPolicy) -> Self {
+	self.overflow = overflow;
+	self
+    }
+
+    /// Packs the characters sorted by codepoint and without duplicates, so that the atlas doesn't depend on the charset order.
+    pub fn with_deterministic(mut self) -> Self {
+	self.deterministic = true;
+	self
+    }
+
+    /// Also bakes the charset at each of `sizes`, in 26.6 points, their entries going to `FontAtlas::sized_map`.
+    pub fn with_extra_sizes(mut self, sizes: &[u32]) -> Self {
+	self.extra_sizes = sizes.to_vec();
+	self
+    }
+
+    /// Pads the characters of `range` with `padding` instead, like CJK glyphs needing a wider gutter or glyphs drawn with
+    /// effects a larger margin. Overrides the ranges given before for the characters they share.
+    pub fn with_padding_range(mut self, range: RangeInclusive<char>, padding: Padding) -> Self {
+	self.padding_ranges.push((range, padding));
+	self
+    }
+
+    /// Crops each bitmap to the pixels it inks before packing it, saving the room of the empty rows and columns some fonts
+    /// and hinting modes leave around their glyphs. The bearings are moved so that glyphs are drawn at the same place.
+    pub fn with_cropping(mut self) -> Self {
+	self.crop = true;
+	self
+    }
+
+    /// Also bakes the glyphs fonts draw for whole `sequences`, like the emoji ZWJ sequence 👩‍💻, to `FontAtlas::sequences`.
+    /// The sequences no font has a glyph for are left out, layouts drawing their characters instead. Glyphs are looked up by
+    /// name rather than shaped, so only the fonts naming them like Noto Color Emoji does are found, see `sequence_glyph_name`.
+    pub fn with_sequences(mut self, sequences: &[&str]) -> Self {
+	self.sequences = sequences.iter().map(|sequence| String::from(*sequence)).collect();
+	self
+    }
+
+    /// Darkens the stems of the glyphs where the fonts support it, which FreeType does for CFF fonts and the glyphs hinted by
+    /// its autohinter. Thin fonts at 10 to 12 pixels then come out less light.
+    pub fn with_stem_darkening(mut self) -> Self {
+	self.stem_darkening = true;
+	self
+    }
+
+    /// Raises the coverage of the glyphs to the power 1 / `gamma`, which must be positive, once rendered. Gammas above 1
+    /// darken the antialiased edges, giving more contrast to small glyphs, and color glyphs are left as they are.
+    pub fn with_gamma(mut self, gamma: f32) -> Self {
+	self.gamma = gamma;
+	self
+    }
+
+    /// Sets `property` on its font driver before rendering, like `DriverProperty::InterpreterVersion(35)`, 38 or 40, choosing
+    /// how TrueType fonts are hinted. With FreeType, the properties are shared by every font of the same FontSystem.
+    pub fn with_driver_property(mut self, property: DriverProperty) -> Self {
+	self.driver_properties.push(property);
+	self
+    }
+
+    /// Runs `hook` on the bitmap of each character glyph after it is rendered, and cropped, and before it is packed, with the
+    /// glyph metrics and character. The bitmap must keep its size and format, generation failing with
+    /// `AtlasGeneratorError::HookError` otherwise. Generators with a hook can't fingerprint their atlases.
+    pub fn with_glyph_hook<F>(mut self, hook: F) -> Self where F: Fn(&mut Pixels, &GlyphMetrics, char) + Send + Sync + 'static {
+	self.glyph_hook = Some(GlyphHook::new(hook));
+	self
+    }
+
+    /// Clears the atlas to `background` instead of zero, like `Background::MAGENTA` or a checkerboard, to spot the padding
+    /// and the pixels bleeding around the glyphs during development.
+    pub fn with_background(mut self, background: Background) -> Self {
+	self.background = background;
+	self
+    }
+
+    /// Returns the padding around the character `c`, from the last range containing it or `padding`.
+    pub fn padding_for(&self, c: char) -> &Padding {
+	padding_for(&self.padding, &self.padding_ranges, c)
+    }
+}
+
+/// A 256x256 atlas at 72 dpi without padding, baking GLYPHS.
+impl Default for AtlasGeneratorOption {
+    fn default() -> Self {
+	Self::new(256, 256, 72, Padding::default())
+    }
+}
+
+/// Returns the padding of the last of `ranges` containing `c`, or `padding` if none does.
+fn padding_for<'a>(padding: &'a Padding, ranges: &'a [(RangeInclusive<char>, Padding)], c: char) -> &'a Padding {
+    ranges.iter().rev().find(|(range, _)| range.contains(&c)).map_or(padding, |(_, padding)| padding)
+}
+
+/// Inserts the padded bitmap in the packing tree and copies it in `buffer`, returning its position without padding
+/// and whether it was rotated. When `rotate` is set, a bitmap that doesn't fit upright is packed rotated 90° clockwise.
+/// A bitmap of another format is converted to the one of `buffer`. Blank bitmaps aren't inserted and get an empty rectangle.
+pub(crate) fn pack_glyph(node: &mut Node, buffer: &mut Pixels, bitmap: &Pixels, padding: &Padding, rotate: bool) -> Result<(Rectangle, bool), NodeInsertError> {
+    let (position, rotated) = pack_rectangle(node, bitmap.width, bitmap.height, padding, rotate)?;
+
+    let converted;
+    let bitmap = match bitmap.format == buffer.format {
+	true => bitmap,
+	false => {
+	    converted = bitmap.to_format(buffer.format);
+	    &converted
+	},
+    };
+
+    if rotated {
+	buffer.copy_from(&bitmap.rotate90(), position.left, position.top);
+    } else if position.width > 0 && position.height > 0 {
+	buffer.copy_from(bitmap, position.left, position.top);
+    }
+
+    Ok((position, rotated))
+}
+
+/// Raises the coverage of the glyph at `position` in `buffer` to the power 1 / `gamma`, see `AtlasGeneratorOption::with_gamma`.
+pub(crate) fn apply_gamma(buffer: &mut Pixels, position: &Rectangle, gamma: f32) {
+    if gamma == 1.0 || gamma <= 0.0 {
+	return;
+    }
+
+    let exponent = 1.0 / gamma;
+    let table: Vec<u8> = (0..=255).map(|value| ((value as f32 / 255.0).powf(exponent) * 255.0).round() as u8).collect();
+    for y in position.top..position.top + position.height {
+	for x in position.left..position.left + position.width {
+	    for channel in buffer.get_pixel_mut(x, y) {
+		*channel = table[*channel as usize];
+	    }
+	}
+    }
 }
 
-impl FontAtlas {
-    /// Create a font atlas of given `atlas_size`.
-    pub fn new(atlas_size: (u32, u32)) -> Self {
-	Self {
-	    map: HashMap::new(),
-	    buffer: ImageBuffer::new(atlas_size.0, atlas_size.1),
-	    width: atlas_size.0,
-	    height: atlas_size.1,
+/// Returns the pixels of `region` of `page`, an atlas page rendered in `load_mode`, in premultiplied sRGBA row after row.
+/// Coverage becomes white with the coverage as alpha, so that it is tinted by the vertex color. LCD coverage is averaged to
+/// gray, blending a single alpha. Color glyphs keep their colors and alpha.
+#[cfg(any(feature = "egui", feature = "bevy"))]
+pub(crate) fn premultiplied_pixels(page: &Pixels, region: &Rectangle, load_mode: AtlasLoadMode) -> Vec<[u8; 4]> {
+    let mut pixels = Vec::with_capacity(region.width as usize * region.height as usize);
+
+    for y in region.top..region.top + region.height {
+	for x in region.left..region.left + region.width {
+	    let pixel = page.get_pixel(x, y);
+	    let pixel = match load_mode {
+		AtlasLoadMode::LCD => {
+		    let gray = ((pixel[0] as u32 + pixel[1] as u32 + pixel[2] as u32) / 3) as u8;
+		    [gray; 4]
+		},
+		AtlasLoadMode::Gray | AtlasLoadMode::Color => to_rgba(pixel),
+	    };
+	    pixels.push(pixel);
 	}
     }
 
+    pixels
+}
+
+/// Inserts a padded rectangle of `width` by `height` pixels in the packing tree, returning its position without padding
+/// and whether it was rotated, like `pack_glyph` but leaving the pixels to the caller.
+pub(crate) fn pack_rectangle(node: &mut Node, width: u32, height: u32, padding: &Padding, rotate: bool) -> Result<(Rectangle, bool), NodeInsertError> {
+    if width == 0 || height == 0 {
+	return Ok((Rectangle::default(), false));
+    }
 
+    match insert_padded(node, width, height, padding) {
+	Err(_) if rotate && width != height => insert_padded(node, height, width, padding).map(|position| (position, true)),
+	result => result.map(|position| (position, false)),
+    }
 }
 
-/// An entry to the font atlas. It contains the glyph metrics and its position in the atlas.
-pub struct FontAtlasEntry {
-    metrics: GlyphMetrics,
-    position: Rectangle
+/// Inserts the padded bitmap in the packing tree and copies it in `buffer`, returning its position without padding.
+fn insert_bitmap(node: &mut Node, buffer: &mut Pixels, bitmap: &Pixels, padding: &Padding) -> Result<Rectangle, NodeInsertError> {
+    let position = insert_padded(node, bitmap.width, bitmap.height, padding)?;
+    buffer.copy_from(bitmap, position.left, position.top);
+
+    Ok(position)
 }
 
-impl FontAtlasEntry {
-    /// Creates an entry from the glyph metrics and position in an atlas.
-    pub fn new(position: Rectangle, metrics: GlyphMetrics) -> Self {
-	Self {
-	    position,
-	    metrics
-	}
-    }
+/// Inserts a rectangle of `width` by `height` pixels surrounded by `padding` in the packing tree, returning its position without padding.
+fn insert_padded(node: &mut Node, width: u32, height: u32, padding: &Padding) -> Result<Rectangle, NodeInsertError> {
+    let padded_rectangle = Rectangle::new(
+	0,
+	0,
+	width + padding.horizontal(),
+	height + padding.vertical()
+    );
+
+    let inserted = node.insert(&padded_rectangle)?;
+
+    Ok(Rectangle::new(
+	inserted.top + padding.top,
+	inserted.left + padding.left,
+	inserted.width - padding.horizontal(),
+	inserted.height - padding.vertical()
+    ))
 }
 
-// @Temporary
-#[derive(Debug)]
-pub struct TextVertex {
-    pub position: [f32; 2],
-    pub uv: [f32; 2],
+/// A struct representing a FontAtlas generator
+pub struct AtlasGenerator {
+    rasterizer: Box<dyn Rasterizer>,
+    fallbacks: Vec<Box<dyn Rasterizer>>,
+    load_mode: AtlasLoadMode,
+    options: AtlasGeneratorOption
 }
 
-impl TextVertex {
-    pub fn new(x: f32, y: f32, u: f32, v: f32) -> TextVertex {
-	TextVertex {
-	    position: [x, y],
-	    uv: [u, v],
+impl AtlasGenerator {
+    /// Returns a builder configuring a generator step by step.
+    pub fn builder<'a>() -> AtlasGeneratorBuilder<'a> {
+	AtlasGeneratorBuilder::new()
+    }
+
+    /// Creates a generator from the given font filepath, options and load mode, using the library of `font_system`.
+    #[cfg(all(feature = "freetype", not(target_arch = "wasm32")))]
+    pub fn new<P>(font_system: &FontSystem, font_filepath: P, options: AtlasGeneratorOption, load_mode: AtlasLoadMode) -> Result<AtlasGenerator, AtlasGeneratorError> where P: AsRef<Path> {
+	Ok(Self::from_font_loader(FontLoader::new(font_system, font_filepath)?, options, load_mode))
+    }
+
+    /// Creates a generator rendering the glyphs of an existing font loader.
+    #[cfg(feature = "freetype")]
+    pub fn from_font_loader(font_loader: FontLoader, options: AtlasGeneratorOption, load_mode: AtlasLoadMode) -> AtlasGenerator {
+	Self::from_rasterizer(font_loader, options, load_mode)
+    }
+
+    /// Creates a generator rendering the glyphs with the given backend.
+    pub fn from_rasterizer<R>(rasterizer: R, options: AtlasGeneratorOption, load_mode: AtlasLoadMode) -> AtlasGenerator where R: Rasterizer + 'static {
+	AtlasGenerator {
+	    rasterizer: Box::new(rasterizer),
+	    fallbacks: Vec::new(),
+	    load_mode,
+	    options,
 	}
     }
-}
 
-pub fn generate_buffers_from_text(text: &str, font_atlas: &FontAtlas, x: i32, y: i32) -> Vec<TextVertex> {
-    let mut advance = 0i32;
+    /// Returns the backend rendering the primary font.
+    pub fn rasterizer(&self) -> &dyn Rasterizer {
+	self.rasterizer.as_ref()
+    }
 
-    let mut vertex_buffer = Vec::<TextVertex>::with_capacity(text.len() * 4 * 6);
+    /// Returns the options the atlases are generated with.
+    pub fn options(&self) -> &AtlasGeneratorOption {
+	&self.options
+    }
 
-    for c in text.chars() {
-	let glyph = font_atlas.map.get(&c).unwrap_or_else(|| {
-	    font_atlas.map.get(&' ').unwrap()
-	});
+    /// Returns the options mutably, to generate other atlases from the same fonts without loading them again.
+    pub fn options_mut(&mut self) -> &mut AtlasGeneratorOption {
+	&mut self.options
+    }
 
-	let left = (x + advance + glyph.metrics.bearing_x) as f32;
-	let right = (x + advance + glyph.metrics.bearing_x + glyph.metrics.width as i32) as f32;
-	let top = (y + glyph.metrics.bearing_y) as f32;
-	let bottom = (y + glyph.metrics.bearing_y - glyph.metrics.height as i32) as f32;
+    /// Replaces the options the next atlases are generated with.
+    pub fn set_options(&mut self, options: AtlasGeneratorOption) {
+	self.options = options;
+    }
 
-	let uv_left = glyph.position.left as f32 / font_atlas.width as f32;
-	let uv_right = (glyph.position.left + glyph.position.width) as f32 / font_atlas.width as f32;
-	let uv_top = (font_atlas.height -  glyph.position.top) as f32 / font_atlas.height as f32;
-	let uv_bottom = (font_atlas.height - (glyph.position.top + glyph.position.height)) as f32 / font_atlas.height as f32;
+    /// Sets the resolution the next atlases are rendered at.
+    pub fn set_dpi(&mut self, dpi: u32) {
+	self.options.dpi = dpi;
+    }
 
-	let v1 = TextVertex::new(left, bottom, uv_left, uv_bottom);
-	let v2 = TextVertex::new(right, bottom, uv_right, uv_bottom);
-	let v3 = TextVertex::new(left, top, uv_left, uv_top);
-	let v4 = TextVertex::new(right, bottom, uv_right, uv_bottom);
-	let v5 = TextVertex::new(right, top, uv_right, uv_top);
-	let v6 = TextVertex::new(left, top, uv_left, uv_top);
+    /// Sets the characters baked in the next atlases.
+    pub fn set_charset(&mut self, charset: &str) {
+	self.options.charset = String::from(charset);
+    }
 
-	vertex_buffer.push(v1);
-	vertex_buffer.push(v2);
-	vertex_buffer.push(v3);
-	vertex_buffer.push(v4);
-	vertex_buffer.push(v5);
-	vertex_buffer.push(v6);
+    /// Returns how the glyphs are rendered.
+    pub fn load_mode(&self) -> AtlasLoadMode {
+	self.load_mode
+    }
 
-	advance += glyph.metrics.advance;
+    /// Sets how the glyphs of the next atlases are rendered.
+    pub fn set_load_mode(&mut self, load_mode: AtlasLoadMode) {
+	self.load_mode = load_mode;
     }
 
-    vertex_buffer
-}
+    /// Adds a font used for the characters missing from the primary font and the previous fallbacks.
+    /// Entries rendered from it are given the returned id.
+    pub fn add_fallback<R>(&mut self, rasterizer: R) -> FontId where R: Rasterizer + 'static {
+	self.fallbacks.push(Box::new(rasterizer));
+	FontId(self.fallbacks.len())
+    }
 
-pub fn generate_text_img<P>(s: &str, font_atlas: &FontAtlas, save_path: P) where P: AsRef<Path> {
-    let mut advance = 0i32;
-    let mut top = 0i32;
-    let mut left = 0i32;
-    let mut right = 0i32;
-    let mut bottom = 0i32;
+    /// Returns the primary font followed by the fallbacks.
+    fn fonts(&self) -> impl Iterator<Item = &dyn Rasterizer> {
+	std::iter::once(self.rasterizer.as_ref()).chain(self.fallbacks.iter().map(|fallback| fallback.as_ref()))
+    }
 
-    for c in s.chars() {
-	let glyph = font_atlas.map.get(&c).unwrap();
+    /// Returns the first font of the fallback chain containing `c`, or the primary font if none does.
+    fn font_for(&self, c: char) -> (FontId, &dyn Rasterizer) {
+	self.fonts()
+	    .enumerate()
+	    .find(|(_, rasterizer)| rasterizer.has_glyph(c))
+	    .map_or((FontId(0), self.rasterizer.as_ref()), |(index, rasterizer)| (FontId(index), rasterizer))
+    }
 
-	top = std::cmp::max(top, glyph.metrics.bearing_y);
-	bottom = std::cmp::max(bottom, glyph.metrics.height as i32 - glyph.metrics.bearing_y);
-	left = std::cmp::max(left, -(advance + glyph.metrics.bearing_x));
-	right = std::cmp::max(right, advance + glyph.metrics.bearing_x + glyph.metrics.width as i32);
+    /// Generate an atlas with the associated font of size `size`. When the glyphs don't fit, the overflow policy of the options
+    /// may retry at other sizes, the `font_size`, `width` and `height` of the atlas telling the ones that succeeded. If every
+    /// retry overflows, the result of the last one is returned.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "info", skip(self), fields(chars = self.options.charset.chars().count()), err(Display)))]
+    pub fn generate(&self, size: u32) -> Result<FontAtlas, AtlasGeneratorError>{
+	self.generate_sized(size, self.options.size, &self.options.extra_sizes, self.options.dpi)
+    }
 
-	advance += glyph.metrics.advance;
+    /// Generates an atlas with the font em `pixel_height` pixels high, ignoring the DPI of the options. The atlas `font_size`,
+    /// the extra sizes and the overflow policy steps are then in 26.6 pixels, 64 times the height in pixels.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "info", skip(self), err(Display)))]
+    pub fn generate_px(&self, pixel_height: u32) -> Result<FontAtlas, AtlasGeneratorError> {
+	self.generate_sized(pixel_height * 64, self.options.size, &self.options.extra_sizes, PIXEL_DPI)
     }
 
-    let buffer_width = right + left + 1;
-    let buffer_height = top + bottom + 1;
+    /// Generates an atlas for each of `scales`, with the font size, extra sizes and atlas size of the options multiplied by
+    /// the scale, for displays of several DPI. Scales that aren't positive are skipped.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "info", skip(self), err(Display)))]
+    pub fn generate_scaled(&self, size: u32, scales: &[f32]) -> Result<ScaledAtlas, AtlasGeneratorError> {
+	let scale_size = |size: u32, scale: f32| (size as f32 * scale).round() as u32;
+	let mut atlases = Vec::with_capacity(scales.len());
 
-    let mut buffer: ImageBuffer<Rgb<u8>, _> = ImageBuffer::new(buffer_width as u32, buffer_height as u32);
+	for scale in scales.iter().copied().filter(|scale| *scale > 0.0 && scale.is_finite()) {
+	    let atlas_size = ((self.options.size.0 as f32 * scale).ceil() as u32, (self.options.size.1 as f32 * scale).ceil() as u32);
+	    let extra_sizes: Vec<u32> = self.options.extra_sizes.iter().map(|extra_size| scale_size(*extra_size, scale)).collect();
 
-    advance = 0;
-    for c in s.chars() {
-	let glyph = font_atlas.map.get(&c).unwrap();
+	    atlases.push((scale, self.generate_sized(scale_size(size, scale), atlas_size, &extra_sizes, self.options.dpi)?));
+	}
 
-	for x in 0..glyph.position.width {
-	    for y in 0..glyph.position.height {
-		let source_x = x + glyph.position.left;
-		let source_y = y + glyph.position.top;
+	Ok(ScaledAtlas::new(atlases))
+    }
 
-		let dest_x = x as i32 + left + advance + glyph.metrics.bearing_x;
-		let dest_y = y as i32 + top - glyph.metrics.bearing_y;
+    /// Generates an atlas of `atlas_size` pixels with the font at `size` and `extra_sizes` at `dpi`, applying the overflow policy.
+    fn generate_sized(&self, size: u32, atlas_size: (u32, u32), extra_sizes: &[u32], dpi: u32) -> Result<FontAtlas, AtlasGeneratorError> {
+	let fingerprint = self.fingerprint_sized(size, atlas_size, extra_sizes, dpi);
+	let (mut size, mut atlas_size) = (size, atlas_size);
+
+	loop {
+	    let result = self.generate_at(size, atlas_size, extra_sizes, dpi).map(|mut atlas| {
+		atlas.fingerprint = fingerprint;
+		atlas
+	    });
+
+	    // Lenient generators don't fail on overflow but leave the glyphs that didn't fit out
+	    let overflowed = match &result {
+		Ok(atlas) => atlas.failures.iter().any(|(_, error)| matches!(error, AtlasGeneratorError::InsertError(_))),
+		Err(error) => matches!(error, AtlasGeneratorError::InsertError(_)),
+	    };
 
-		buffer.put_pixel(dest_x as u32, dest_y as u32, *font_atlas.buffer.get_pixel(source_x, source_y));
+	    match self.options.overflow.next(size, atlas_size) {
+		Some(next) if overflowed => {
+		    #[cfg(feature = "tracing")]
+		    tracing::debug!(size = next.0, width = next.1.0, height = next.1.1, "retrying after overflow");
+		    (size, atlas_size) = next;
+		},
+		_ => return result,
 	    }
 	}
+    }
 
-
-	advance += glyph.metrics.advance;
+    /// Returns a hash of the fonts, the options and `size`, the same across runs and platforms, which changes whenever
+    /// `generate(size)` could give another atlas, new versions of the crate included. Build systems can skip baking an atlas
+    /// again while it doesn't change. Returns None if a font can't be hashed, see `Rasterizer::font_hash`.
+    pub fn fingerprint(&self, size: u32) -> Option<u64> {
+	self.fingerprint_sized(size, self.options.size, &self.options.extra_sizes, self.options.dpi)
     }
 
-    buffer.save(save_path).unwrap();
-}
+    /// Returns the fingerprint of an atlas of `atlas_size` pixels generated at `size` and `extra_sizes` at `dpi`.
+    fn fingerprint_sized(&self, size: u32, atlas_size: (u32, u32), extra_sizes: &[u32], dpi: u32) -> Option<u64> {
+	// What a hook does to the bitmaps can't be hashed
+	if self.options.glyph_hook.is_some() {
+	    return None;
+	}
 
-/// A struct representing a padding area around a rectangle.
-#[allow(dead_code)]
-pub struct Padding {
-    left: u32,
-    right: u32,
-    top: u32,
-    bottom: u32,
-    horizontal: u32,
-    vertical: u32,
-}
+	let mut hasher = ContentHasher::new();
 
-impl Padding {
-    /// Creates a Padding object from the padding on all sides
-    pub fn new(left: u32, right: u32, top: u32, bottom: u32) -> Self {
-	Self {
-	    left, right, top, bottom,
-	    horizontal: left + right, vertical: top + bottom
+	hasher.write(env!("CARGO_PKG_VERSION").as_bytes());
+	for rasterizer in self.fonts() {
+	    hasher.write_u64(rasterizer.font_hash()?);
 	}
-    }
-}
 
-/// An enum telling the AtlasGenerator how to load the glyphs.
-pub enum AtlasLoadMode {
-    Gray,
-    LCD,
-}
+	let options = &self.options;
+	hasher.write_u32(size);
+	hasher.write_u32(self.load_mode as u32);
+	for value in [atlas_size.0, atlas_size.1, dpi] {
+	    hasher.write_u32(value);
+	}
+	let paddings = std::iter::once((None, &options.padding))
+	    .chain(options.padding_ranges.iter().map(|(range, padding)| (Some(range), padding)));
+	hasher.write_u32(options.padding_ranges.len() as u32);
+	for (range, padding) in paddings {
+	    if let Some(range) = range {
+		hasher.write_u32(*range.start() as u32);
+		hasher.write_u32(*range.end() as u32);
+	    }
+	    for value in [padding.left, padding.right, padding.top, padding.bottom] {
+		hasher.write_u32(value);
+	    }
+	}
+	match options.transform {
+	    Some(transform) => {
+		hasher.write_u32(1);
+		for value in [transform.xx, transform.xy, transform.yx, transform.yy] {
+		    hasher.write_u32(value.to_bits());
+		}
+	    },
+	    None => hasher.write_u32(0),
+	}
+	for flag in [options.notdef, options.rotate, options.lenient, options.deterministic, options.crop, options.stem_darkening] {
+	    hasher.write_u32(flag as u32);
+	}
+	hasher.write_u32(options.gamma.to_bits());
+	match options.background {
+	    Background::Transparent => hasher.write_u32(0),
+	    Background::Color(color) => {
+		hasher.write_u32(1);
+		hasher.write(&color);
+	    },
+	    Background::Checkerboard { size, colors } => {
+		hasher.write_u32(2);
+		hasher.write_u32(size);
+		hasher.write(&colors.concat());
+	    },
+	}
+	hasher.write_u32(options.driver_properties.len() as u32);
+	for property in options.driver_properties.iter() {
+	    for name in [property.module().name(), property.name()] {
+		hasher.write_u32(name.len() as u32);
+		hasher.write(name.as_bytes());
+	    }
+	    match property {
+		DriverProperty::InterpreterVersion(number) | DriverProperty::HintingEngine(_, number) => hasher.write_u32(*number),
+		DriverProperty::NoStemDarkening(_, flag) => hasher.write_u32(*flag as u32),
+		DriverProperty::DarkeningParameters(_, parameters) => {
+		    for parameter in parameters.iter() {
+			hasher.write_i32(*parameter);
+		    }
+		},
+	    }
+	}
+	options.overflow.hash(&mut hasher);
+	hasher.write_u32(extra_sizes.len() as u32);
+	for extra_size in extra_sizes.iter() {
+	    hasher.write_u32(*extra_size);
+	}
 
-impl AtlasLoadMode {
-    pub fn default() -> AtlasLoadMode {
-	AtlasLoadMode::LCD
+	hasher.write_u32(options.sequences.len() as u32);
+	for sequence in options.sequences.iter() {
+	    hasher.write_u32(sequence.len() as u32);
+	    hasher.write(sequence.as_bytes());
+	}
+
+	// The characters are hashed in the order they are packed, so that the charset order only counts when it changes the atlas
+	let chars = self.pack_order(&options.charset);
+	hasher.write_u32(chars.len() as u32);
+	for c in chars {
+	    hasher.write_u32(c as u32);
+	}
+
+	Some(hasher.finish())
     }
-}
 
-/// A struct representing the AtlasGenerator options.
-pub struct AtlasGeneratorOption {
-    pub dpi: u32,
-    pub size: (u32, u32),
-    pub padding: Padding,
-}
+    /// Generates an atlas of `atlas_size` pixels with the font at `size`, the charset being baked again at `extra_sizes`.
+    fn generate_at(&self, size: u32, atlas_size: (u32, u32), extra_sizes: &[u32], dpi: u32) -> Result<FontAtlas, AtlasGeneratorError> {
+	self.set_char_size(size, dpi)?;
 
-impl AtlasGeneratorOption {
-    /// Creates an AtlasGeneratorOption object from its components.
-    pub fn new(width: u32, height: u32, dpi: u32, padding: Padding) -> Self {
-	Self {
-	    dpi,
-	    size: (width, height),
-	    padding,
+	let mut atlas = FontAtlas::new(atlas_size);
+	if self.options.background != Background::Transparent || self.load_mode.pixel_format() != atlas.buffer.format {
+	    atlas.background = self.options.background;
+	    atlas.buffer = Pixels::from_background(atlas.width, atlas.height, self.load_mode.pixel_format(), &atlas.background);
+	}
+	atlas.line_metrics = self.rasterizer.line_metrics();
+	atlas.load_mode = self.load_mode;
+	atlas.font_size = size;
+	atlas.dpi = Some(dpi);
+	atlas.padding = self.options.padding;
+	atlas.padding_ranges = self.options.padding_ranges.clone();
+
+	let chars = self.pack_order(&self.options.charset);
+	for c in chars.iter() {
+	    self.try_pack_char(&mut atlas, *c, size)?;
 	}
-    }
-}
 
-/// A struct representing a FontAtlas generator
-pub struct AtlasGenerator {
-    ft_font_face: Face,
-    load_mode: AtlasLoadMode,
-    options: AtlasGeneratorOption
-}
+	for sequence in self.options.sequences.iter() {
+	    self.try_pack_sequence(&mut atlas, sequence)?;
+	}
 
-impl AtlasGenerator {
-    /// Creates a generator from the given font filepath, options and load mode.
-    pub fn new<P>(font_filepath: P, options: AtlasGeneratorOption, load_mode: AtlasLoadMode) -> AtlasGenerator where P: AsRef<Path> {
-	let library = Library::init().expect("Failed to init freetype library");
+	for extra_size in extra_sizes.iter().filter(|extra_size| **extra_size != size) {
+	    self.set_char_size(*extra_size, dpi)?;
+	    atlas.sized_line_metrics.insert(*extra_size, self.rasterizer.line_metrics());
 
-	library.set_lcd_filter(LcdFilter::LcdFilterDefault).expect("Failed to set LCD Filter");
+	    for c in chars.iter() {
+		self.try_pack_char(&mut atlas, *c, *extra_size)?;
+	    }
+	}
+	if atlas.has_extra_sizes() {
+	    // The fonts are left at the main size, for load_glyph
+	    self.set_char_size(size, dpi)?;
+	}
 
-	let face = library.new_face(font_filepath.as_ref(), 0).expect("Failed to load font");
+	#[cfg(feature = "tracing")]
+	let _span = tracing::debug_span!("finalize", glyphs = atlas.map.len(), failures = atlas.failures.len()).entered();
 
-	AtlasGenerator {
-	    ft_font_face: face,
-	    load_mode,
-	    options,
+	if self.options.notdef {
+	    let mut glyph = self.rasterizer.rasterize_notdef(self.load_mode)?;
+	    if self.options.crop {
+		glyph = glyph.cropped();
+	    }
+
+	    let (position, rotated) = pack_glyph(&mut atlas.node, &mut atlas.buffer, &glyph.bitmap, &self.options.padding, self.options.rotate)?;
+	    apply_gamma(&mut atlas.buffer, &position, self.options.gamma);
+
+	    atlas.notdef = Some(FontAtlasEntry::new(position, glyph.metrics).with_rotation(rotated));
 	}
+
+	// The whole buffer is new, so it is uploaded at once rather than glyph by glyph
+	atlas.mark_dirty(Rectangle::new(0, 0, atlas.width, atlas.height));
+
+	Ok(atlas)
     }
 
-    /// Generate an atlas with the associated font of size `size`.
-    pub fn generate(&self, size: u32) -> Result<FontAtlas, AtlasGeneratorError>{
-	self.ft_font_face.set_char_size(0, size as isize, 0, self.options.dpi).unwrap();
+    /// Measures the characters of `charset` with the fonts at `size` and packs their boxes as `generate` would, without rendering
+    /// them, for tools only needing the advances and extents of the glyphs, like server-side layout. The atlas has the entries
+    /// and line metrics but no pixels, see `FontAtlas::has_pixels`, and the positions follow the metrics, which may be a bit
+    /// smaller than rendered bitmaps. Repacking it moves the boxes, `add_glyphs` measures the new glyphs and images are refused.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "info", skip(self, charset), fields(chars = charset.chars().count()), err(Display)))]
+    pub fn measure(&self, charset: &str, size: u32) -> Result<FontAtlas, AtlasGeneratorError> {
+	self.set_char_size(size, self.options.dpi)?;
 
 	let mut atlas = FontAtlas::new(self.options.size);
+	atlas.buffer = Pixels::new(0, 0, self.load_mode.pixel_format());
+	atlas.line_metrics = self.rasterizer.line_metrics();
+	atlas.load_mode = self.load_mode;
+	atlas.font_size = size;
+	atlas.dpi = Some(self.options.dpi);
+	atlas.padding = self.options.padding;
+	atlas.padding_ranges = self.options.padding_ranges.clone();
+
+	for c in self.pack_order(charset) {
+	    match self.measure_char(&mut atlas, c) {
+		Err(error) if self.options.lenient => atlas.failures.push((c, error)),
+		result => result?,
+	    }
+	}
 
-	let mut node = Node::new(Rectangle::new(0, 0, atlas.width, atlas.height));
+	Ok(atlas)
+    }
 
-	for c in GLYPHS.chars() {
-	    let load_flags = match self.load_mode {
-		AtlasLoadMode::Gray => LoadFlag::RENDER,
-		AtlasLoadMode::LCD => LoadFlag::RENDER | LoadFlag::TARGET_LCD
-	    };
+    /// Measures `c` with the first font containing it, already set to the atlas size, and packs its box in `atlas`.
+    fn measure_char(&self, atlas: &mut FontAtlas, c: char) -> Result<(), AtlasGeneratorError> {
+	let (font_id, rasterizer) = self.font_for(c);
+	let metrics = rasterizer.measure(c, self.load_mode)?;
+	let (position, rotated) = pack_rectangle(&mut atlas.node, metrics.width, metrics.height, self.options.padding_for(c), self.options.rotate)?;
+
+	let entry = FontAtlasEntry::new(position, metrics)
+	    .with_font(font_id)
+	    .with_glyph_id(rasterizer.glyph_index(c))
+	    .with_rotation(rotated);
+	atlas.map.insert(c, entry);
 
-	    let glyph = self.load_glyph(c, load_flags)?;
+	Ok(())
+    }
+
+    /// Packs the characters of `chars` missing from an atlas previously generated by this generator, at each of its sizes,
+    /// marking their positions dirty. On error, the characters packed so far are kept.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(chars = chars.chars().count()), err(Display)))]
+    pub fn add_glyphs(&self, atlas: &mut FontAtlas, chars: &str) -> Result<(), AtlasGeneratorError> {
+	let chars = self.pack_order(chars);
 
-	    let bitmap_rectangle = Rectangle::new(
-		0,
-		0,
-		glyph.bitmap.width() + self.options.padding.horizontal,
-		glyph.bitmap.height() + self.options.padding.vertical
-	    );
+	// The main size comes last, so that the fonts are left at it
+	let mut sizes = atlas.sizes();
+	sizes.retain(|size| *size != atlas.font_size);
+	sizes.push(atlas.font_size);
 
-	    let inserted = node.insert(&bitmap_rectangle)?;
+	for size in sizes {
+	    self.set_char_size(size, atlas.dpi.unwrap_or(self.options.dpi))?;
+
+	    for c in chars.iter() {
+		if c.is_control() || atlas.has_entry(*c, size) {
+		    continue;
+		}
 
-	    let inserted_without_padding = Rectangle::new(
-		inserted.top + self.options.padding.top,
-		inserted.left + self.options.padding.left,
-		inserted.width - self.options.padding.horizontal,
-		inserted.height - self.options.padding.vertical
-	    );
+		atlas.fingerprint = None;
+		atlas.generation = next_generation();
 
-	    let entry = FontAtlasEntry::new(inserted_without_padding, glyph.metrics);
+		// Measured atlases only get the boxes of the new glyphs
+		if !atlas.has_pixels() {
+		    match self.measure_char(atlas, *c) {
+			Err(error) if self.options.lenient => atlas.failures.push((*c, error)),
+			result => result?,
+		    }
+		    continue;
+		}
+		if let Some(position) = self.try_pack_char(atlas, *c, size)? {
+		    atlas.mark_dirty(position);
+		}
+	    }
+	}
 
-	    atlas.map.insert(c, entry);
+	Ok(())
+    }
 
-	    let mut atlas_view = atlas.buffer.sub_image(
-		inserted_without_padding.left,
-		inserted_without_padding.top,
-		inserted_without_padding.width,
-		inserted_without_padding.height
-	    );
-	    atlas_view.copy_from(&glyph.bitmap, 0, 0);
+    /// Sets the size and transform of every font of the generator. At `PIXEL_DPI`, whole sizes are set in pixels.
+    fn set_char_size(&self, size: u32, dpi: u32) -> Result<(), AtlasGeneratorError> {
+	for rasterizer in self.fonts() {
+	    match dpi == PIXEL_DPI && size % 64 == 0 {
+		true => rasterizer.set_pixel_sizes(size / 64)?,
+		false => rasterizer.set_char_size(size, dpi)?,
+	    }
+	    rasterizer.set_transform(self.options.transform);
+	    rasterizer.set_stem_darkening(self.options.stem_darkening)?;
+	    for property in self.options.driver_properties.iter() {
+		rasterizer.set_driver_property(property)?;
+	    }
 	}
 
-	Ok(atlas)
+	Ok(())
     }
 
-    fn convert_bitmap(&self, bitmap: &Bitmap) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
-	let (width, height, pitch) = match self.load_mode {
-	    AtlasLoadMode::Gray => (bitmap.width() as u32, bitmap.rows() as u32, bitmap.pitch()),
-	    AtlasLoadMode::LCD => (bitmap.width() as u32 / 3, bitmap.rows() as u32, bitmap.pitch()),
+    /// Returns the characters of `chars` in the order they are packed, sorted and deduplicated in deterministic mode.
+    fn pack_order(&self, chars: &str) -> Vec<char> {
+	let mut chars: Vec<char> = chars.chars().collect();
+
+	if self.options.deterministic {
+	    chars.sort_unstable();
+	    chars.dedup();
+	}
+
+	chars
+    }
+
+    /// Packs `c` at `size` in `atlas` and returns its position. In lenient mode, a failure is added to the atlas failures and gives None.
+    fn try_pack_char(&self, atlas: &mut FontAtlas, c: char, size: u32) -> Result<Option<Rectangle>, AtlasGeneratorError> {
+	match self.pack_char(atlas, c, size) {
+	    Ok(position) => Ok(Some(position)),
+	    Err(error) if self.options.lenient => {
+		atlas.failures.push((c, error));
+		Ok(None)
+	    },
+	    Err(error) => Err(error),
+	}
+    }
+
+    /// Renders `c` with the first font containing it, already set to `size`, and packs it in `atlas`, returning its position.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip(self, atlas), err(Display)))]
+    fn pack_char(&self, atlas: &mut FontAtlas, c: char, size: u32) -> Result<Rectangle, AtlasGeneratorError> {
+	let (font_id, rasterizer) = self.font_for(c);
+
+	// Blitting doesn't tell color glyphs apart nor runs the hook, so they go through a rendered glyph
+	let (metrics, (position, rotated), color) = if self.options.crop || self.load_mode == AtlasLoadMode::Color || self.options.glyph_hook.is_some() {
+	    let mut glyph = rasterizer.rasterize(c, self.load_mode)?;
+	    if self.options.crop {
+		glyph = Arc::new(glyph.cropped());
+	    }
+	    if let Some(hook) = &self.options.glyph_hook {
+		glyph = Arc::new(hook.apply(&glyph, c)?);
+	    }
+	    (glyph.metrics, pack_glyph(&mut atlas.node, &mut atlas.buffer, &glyph.bitmap, self.options.padding_for(c), self.options.rotate)?, glyph.color)
+	} else {
+	    // The bitmap goes straight from the backend to the atlas buffer, at the position packed from its size
+	    let node = &mut atlas.node;
+	    let (metrics, placement) = rasterizer.blit(c, self.load_mode, &mut atlas.buffer, &mut |width, height| {
+		pack_rectangle(node, width, height, self.options.padding_for(c), self.options.rotate).map_err(AtlasGeneratorError::from)
+	    })?;
+	    (metrics, placement, false)
+	};
+	if !color {
+	    apply_gamma(&mut atlas.buffer, &position, self.options.gamma);
+	}
+
+	let entry = FontAtlasEntry::new(position, metrics)
+	    .with_font(font_id)
+	    .with_glyph_id(rasterizer.glyph_index(c))
+	    .with_rotation(rotated)
+	    .with_color(color);
+	match size == atlas.font_size {
+	    true => atlas.map.insert(c, entry),
+	    false => atlas.sized_map.insert((c, size), entry),
 	};
 
-	let pixel_count = (width * height) as usize;
-
-	let mut vec_buffer = Vec::<u8>::with_capacity(pixel_count * 3);
-	vec_buffer.resize(pixel_count * 3, 0);
-
-	for y in 0..height as usize {
-	    for x in 0..width as usize {
-		match self.load_mode {
-		    AtlasLoadMode::Gray => {
-			let src = y * pitch as usize + x;
-			let dst = y * (width * 3) as usize + x * 3;
-			let gray = bitmap.buffer()[src];
-			vec_buffer[dst] = gray;
-			vec_buffer[dst + 1] = gray;
-			vec_buffer[dst + 2] = gray;
-		    },
-		    AtlasLoadMode::LCD => {
-			let src = y * pitch as usize + x * 3;
-			let dst = y * (width * 3) as usize + x * 3;
-
-			let r = bitmap.buffer()[src];
-			let g = bitmap.buffer()[src + 1];
-			let b = bitmap.buffer()[src + 2];
-			vec_buffer[dst] = r;
-			vec_buffer[dst + 1] = g;
-			vec_buffer[dst + 2] = b;
-		    },
-		}
+	#[cfg(feature = "tracing")]
+	tracing::trace!(font = font_id.0, left = position.left, top = position.top, width = position.width, height = position.height, rotated, "glyph packed");
+
+	Ok(position)
+    }
+
+    /// Packs the glyph of `sequence` from the first font having one, like `try_pack_char`. Sequences no font has are skipped.
+    fn try_pack_sequence(&self, atlas: &mut FontAtlas, sequence: &str) -> Result<(), AtlasGeneratorError> {
+	match self.pack_sequence(atlas, sequence) {
+	    Err(error) if self.options.lenient => {
+		atlas.failures.push((sequence.chars().next().unwrap_or_default(), error));
+		Ok(())
+	    },
+	    result => result,
+	}
+    }
 
+    /// Renders the glyph of `sequence` with the first font having one and packs it in `atlas` at its main size.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip(self, atlas), err(Display)))]
+    fn pack_sequence(&self, atlas: &mut FontAtlas, sequence: &str) -> Result<(), AtlasGeneratorError> {
+	for (index, rasterizer) in self.fonts().enumerate() {
+	    let (glyph_id, mut glyph) = match rasterizer.rasterize_sequence(sequence, self.load_mode)? {
+		Some(sequence_glyph) => sequence_glyph,
+		None => continue,
+	    };
+	    if self.options.crop {
+		glyph = glyph.cropped();
 	    }
+
+	    let (position, rotated) = pack_glyph(&mut atlas.node, &mut atlas.buffer, &glyph.bitmap, &self.options.padding, self.options.rotate)?;
+	    let entry = FontAtlasEntry::new(position, glyph.metrics)
+		.with_font(FontId(index))
+		.with_glyph_id(glyph_id)
+		.with_rotation(rotated)
+		.with_color(glyph.color);
+	    atlas.sequences.insert(sequence.replace(EMOJI_PRESENTATION, ""), entry);
+
+	    return Ok(());
 	}
 
-	ImageBuffer::from_vec(width, height, vec_buffer).unwrap()
+	Ok(())
+    }
+
+    /// Renders a glyph of the primary font in the given mode.
+    pub fn load_glyph(&self, c: char, load_mode: AtlasLoadMode) -> Result<Arc<Glyph>, AtlasGeneratorError> {
+	self.rasterizer.rasterize(c, load_mode)
     }
+}
+
+/// The font an AtlasGeneratorBuilder renders, either a file or an already loaded font.
+enum FontSource {
+    #[cfg(all(feature = "freetype", not(target_arch = "wasm32")))]
+    File(PathBuf),
+    #[cfg(any(feature = "freetype", feature = "ab-glyph"))]
+    Bytes(Vec<u8>),
+    Loader(Box<dyn Rasterizer>),
+}
 
-    /// Loads a glyph from the associated font file.
-    pub fn load_glyph(&self, c: char, load_flags: LoadFlag) -> Result<Glyph, AtlasGeneratorError> {
-	if let Err(_) = self.ft_font_face.load_char(c as usize, load_flags) {
-	    return Err(AtlasGeneratorError::LoadError(c));
+/// A builder configuring an AtlasGenerator, starting from a 256x256 atlas at 72 dpi without padding.
+pub struct AtlasGeneratorBuilder<'a> {
+    #[cfg(feature = "freetype")]
+    font_system: Option<&'a FontSystem>,
+    #[cfg(not(feature = "freetype"))]
+    font_system: PhantomData<&'a ()>,
+    font: Option<FontSource>,
+    fallbacks: Vec<Box<dyn Rasterizer>>,
+    load_mode: AtlasLoadMode,
+    options: AtlasGeneratorOption,
+}
+
+impl<'a> AtlasGeneratorBuilder<'a> {
+    /// Creates a builder with the default configuration and no font.
+    pub fn new() -> Self {
+	Self {
+	    #[cfg(feature = "freetype")]
+	    font_system: None,
+	    #[cfg(not(feature = "freetype"))]
+	    font_system: PhantomData,
+	    font: None,
+	    fallbacks: Vec::new(),
+	    load_mode: AtlasLoadMode::default(),
+	    options: AtlasGeneratorOption::default(),
 	}
+    }
+
+    /// Sets the font system used to open font files, instead of a dedicated one.
+    #[cfg(feature = "freetype")]
+    pub fn font_system(mut self, font_system: &'a FontSystem) -> Self {
+	self.font_system = Some(font_system);
+	self
+    }
+
+    /// Sets the path of the font file to render.
+    #[cfg(all(feature = "freetype", not(target_arch = "wasm32")))]
+    pub fn font<P>(mut self, font_filepath: P) -> Self where P: AsRef<Path> {
+	self.font = Some(FontSource::File(font_filepath.as_ref().to_path_buf()));
+	self
+    }
+
+    /// Sets the content of the font file to render, with FreeType if enabled or ab_glyph otherwise.
+    #[cfg(any(feature = "freetype", feature = "ab-glyph"))]
+    pub fn font_bytes(mut self, data: &[u8]) -> Self {
+	self.font = Some(FontSource::Bytes(data.to_vec()));
+	self
+    }
+
+    /// Sets an already loaded font to render.
+    #[cfg(feature = "freetype")]
+    pub fn font_loader(self, font_loader: FontLoader) -> Self {
+	self.rasterizer(font_loader)
+    }
+
+    /// Sets the backend rendering the font.
+    pub fn rasterizer<R>(mut self, rasterizer: R) -> Self where R: Rasterizer + 'static {
+	self.font = Some(FontSource::Loader(Box::new(rasterizer)));
+	self
+    }
+
+    /// Adds a font used for the characters missing from the previous ones.
+    pub fn fallback<R>(mut self, rasterizer: R) -> Self where R: Rasterizer + 'static {
+	self.fallbacks.push(Box::new(rasterizer));
+	self
+    }
+
+    /// Sets the size of the atlas, in pixels.
+    pub fn size(mut self, width: u32, height: u32) -> Self {
+	self.options.size = (width, height);
+	self
+    }
+
+    /// Sets the resolution the glyphs are rendered at.
+    pub fn dpi(mut self, dpi: u32) -> Self {
+	self.options.dpi = dpi;
+	self
+    }
+
+    /// Sets the padding around each glyph.
+    pub fn padding(mut self, padding: Padding) -> Self {
+	self.options.padding = padding;
+	self
+    }
+
+    /// Pads the characters of `range` with `padding` instead, see `AtlasGeneratorOption::with_padding_range`.
+    pub fn padding_range(mut self, range: RangeInclusive<char>, padding: Padding) -> Self {
+	self.options.padding_ranges.push((range, padding));
+	self
+    }
+
+    /// Sets how the glyphs are rendered.
+    pub fn mode(mut self, load_mode: AtlasLoadMode) -> Self {
+	self.load_mode = load_mode;
+	self
+    }
+
+    /// Sets the characters baked in the atlas.
+    pub fn charset(mut self, charset: &str) -> Self {
+	self.options.charset = String::from(charset);
+	self
+    }
+
+    /// Sets the transform applied to every glyph before rasterization.
+    pub fn transform(mut self, transform: Transform) -> Self {
+	self.options.transform = Some(transform);
+	self
+    }
+
+    /// Bakes the .notdef glyph in the atlas.
+    pub fn notdef(mut self) -> Self {
+	self.options.notdef = true;
+	self
+    }
+
+    /// Lets the glyphs that don't fit upright be packed rotated.
+    pub fn rotate(mut self) -> Self {
+	self.options.rotate = true;
+	self
+    }
+
+    /// Skips the characters that can't be rendered or packed instead of failing, listing them in `FontAtlas::failures`.
+    pub fn lenient(mut self) -> Self {
+	self.options.lenient = true;
+	self
+    }
+
+    /// Sets what happens when the glyphs don't fit in the atlas, instead of failing.
+    pub fn overflow(mut self, overflow: OverflowPolicy) -> Self {
+	self.options.overflow = overflow;
+	self
+    }
+
+    /// Packs the characters sorted by codepoint and without duplicates, so that the atlas doesn't depend on the charset order.
+    pub fn deterministic(mut self) -> Self {
+	self.options.deterministic = true;
+	self
+    }
+
+    /// Also bakes the charset at each of `sizes`, in 26.6 points, their entries going to `FontAtlas::sized_map`.
+    pub fn extra_sizes(mut self, sizes: &[u32]) -> Self {
+	self.options.extra_sizes = sizes.to_vec();
+	self
+    }
+
+    /// Crops each bitmap to the pixels it inks, see `AtlasGeneratorOption::with_cropping`.
+    pub fn crop(mut self) -> Self {
+	self.options.crop = true;
+	self
+    }
+
+    /// Also bakes the glyphs of whole sequences, see `AtlasGeneratorOption::with_sequences`.
+    pub fn sequences(mut self, sequences: &[&str]) -> Self {
+	self.options.sequences = sequences.iter().map(|sequence| String::from(*sequence)).collect();
+	self
+    }
+
+    /// Darkens the stems of the glyphs, see `AtlasGeneratorOption::with_stem_darkening`.
+    pub fn stem_darkening(mut self) -> Self {
+	self.options.stem_darkening = true;
+	self
+    }
+
+    /// Raises the coverage of the glyphs to the power 1 / `gamma`, see `AtlasGeneratorOption::with_gamma`.
+    pub fn gamma(mut self, gamma: f32) -> Self {
+	self.options.gamma = gamma;
+	self
+    }
 
-	let ft_glyph = self.ft_font_face.glyph();
-	let raw_bitmap = ft_glyph.bitmap();
+    /// Sets a property of a font driver before rendering, see `AtlasGeneratorOption::with_driver_property`.
+    pub fn driver_property(mut self, property: DriverProperty) -> Self {
+	self.options.driver_properties.push(property);
+	self
+    }
 
-	let bitmap = self.convert_bitmap(&raw_bitmap);
+    /// Post-processes the bitmap of each character glyph before it is packed, see `AtlasGeneratorOption::with_glyph_hook`.
+    pub fn glyph_hook<F>(mut self, hook: F) -> Self where F: Fn(&mut Pixels, &GlyphMetrics, char) + Send + Sync + 'static {
+	self.options.glyph_hook = Some(GlyphHook::new(hook));
+	self
+    }
 
+    /// Clears the atlas to `background`, see `AtlasGeneratorOption::with_background`.
+    pub fn background(mut self, background: Background) -> Self {
+	self.options.background = background;
+	self
+    }
 
-	let metrics = GlyphMetrics::new(
-	    ft_glyph.metrics().width as u32 / 64,
-	    ft_glyph.metrics().height as u32 / 64,
-	    ft_glyph.metrics().horiBearingX as i32 / 64,
-	    ft_glyph.metrics().horiBearingY as i32 / 64,
-	    ft_glyph.metrics().horiAdvance as i32 / 64
-	);
+    /// Opens the font and creates the generator.
+    pub fn build(self) -> Result<AtlasGenerator, AtlasGeneratorError> {
+	let rasterizer: Box<dyn Rasterizer> = match self.font {
+	    Some(FontSource::Loader(rasterizer)) => rasterizer,
+	    #[cfg(all(feature = "freetype", not(target_arch = "wasm32")))]
+	    Some(FontSource::File(path)) => match self.font_system {
+		Some(font_system) => Box::new(FontLoader::new(font_system, path)?),
+		// The face keeps its library alive, so the temporary font system can be dropped
+		None => Box::new(FontLoader::new(&FontSystem::new()?, path)?),
+	    },
+	    #[cfg(feature = "freetype")]
+	    Some(FontSource::Bytes(data)) => match self.font_system {
+		Some(font_system) => Box::new(FontLoader::from_vec(font_system, data, 0)?),
+		None => Box::new(FontLoader::from_vec(&FontSystem::new()?, data, 0)?),
+	    },
+	    #[cfg(all(feature = "ab-glyph", not(feature = "freetype")))]
+	    Some(FontSource::Bytes(data)) => Box::new(AbFont::from_vec(data, 0)?),
+	    None => return Err(AtlasGeneratorError::NoFont),
+	};
 
+	Ok(AtlasGenerator {
+	    rasterizer,
+	    fallbacks: self.fallbacks,
+	    load_mode: self.load_mode,
+	    options: self.options,
+	})
+    }
+}
 
-	Ok(Glyph::new(metrics, bitmap))
+impl Default for AtlasGeneratorBuilder<'_> {
+    fn default() -> Self {
+	Self::new()
     }
 }
 
 /// An enum representing all the error that could happen using the generator.
 pub enum AtlasGeneratorError{
     InsertError(NodeInsertError),
-    LoadError(char)
+    #[cfg(feature = "freetype")]
+    LoadError(FontLoaderError),
+    #[cfg(feature = "ab-glyph")]
+    FontError(AbFontError),
+    NoFont,
+    Cancelled,
+    HookError(char),
 }
 
 impl From<NodeInsertError> for AtlasGeneratorError {
@@ -350,11 +2515,31 @@ impl From<NodeInsertError> for AtlasGeneratorError {
     }
 }
 
+#[cfg(feature = "freetype")]
+impl From<FontLoaderError> for AtlasGeneratorError {
+    fn from(e: FontLoaderError) -> Self {
+	AtlasGeneratorError::LoadError(e)
+    }
+}
+
+#[cfg(feature = "ab-glyph")]
+impl From<AbFontError> for AtlasGeneratorError {
+    fn from(e: AbFontError) -> Self {
+	AtlasGeneratorError::FontError(e)
+    }
+}
+
 impl Display for AtlasGeneratorError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
 	match self {
 	    AtlasGeneratorError::InsertError(node_error) => write!(f, "{}", node_error),
-	    AtlasGeneratorError::LoadError(c) => write!(f, "Can't load character {}", c),
+	    #[cfg(feature = "freetype")]
+	    AtlasGeneratorError::LoadError(loader_error) => write!(f, "{}", loader_error),
+	    #[cfg(feature = "ab-glyph")]
+	    AtlasGeneratorError::FontError(font_error) => write!(f, "{}", font_error),
+	    AtlasGeneratorError::NoFont => write!(f, "No font was given to the generator"),
+	    AtlasGeneratorError::Cancelled => write!(f, "The generation task was cancelled before it completed"),
+	    AtlasGeneratorError::HookError(c) => write!(f, "The glyph hook changed the size or format of the bitmap of {:?}", c),
 	}
     }
 }
@@ -363,7 +2548,26 @@ impl Debug for AtlasGeneratorError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
 	match self {
 	    AtlasGeneratorError::InsertError(node_error) => write!(f, "{}", node_error),
-	    AtlasGeneratorError::LoadError(c) => write!(f, "Can't load character {}", c),
+	    #[cfg(feature = "freetype")]
+	    AtlasGeneratorError::LoadError(loader_error) => write!(f, "{}", loader_error),
+	    #[cfg(feature = "ab-glyph")]
+	    AtlasGeneratorError::FontError(font_error) => write!(f, "{}", font_error),
+	    AtlasGeneratorError::NoFont => write!(f, "No font was given to the generator"),
+	    AtlasGeneratorError::Cancelled => write!(f, "The generation task was cancelled before it completed"),
+	    AtlasGeneratorError::HookError(c) => write!(f, "The glyph hook changed the size or format of the bitmap of {:?}", c),
+	}
+    }
+}
+
+impl std::error::Error for AtlasGeneratorError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+	match self {
+	    AtlasGeneratorError::InsertError(node_error) => Some(node_error),
+	    #[cfg(feature = "freetype")]
+	    AtlasGeneratorError::LoadError(loader_error) => Some(loader_error),
+	    #[cfg(feature = "ab-glyph")]
+	    AtlasGeneratorError::FontError(font_error) => Some(font_error),
+	    AtlasGeneratorError::NoFont | AtlasGeneratorError::Cancelled | AtlasGeneratorError::HookError(_) => None,
 	}
     }
 }
@@ -0,0 +1,280 @@
+use std::collections::BTreeMap;
+
+use ::bevy::prelude::{App, Assets, Changed, Commands, Component, Entity, Handle, Image, IntoSystemConfigs, Mesh, Or, Plugin, PostUpdate, Query, Res, ResMut, Resource, Without};
+use ::bevy::render::mesh::Indices;
+use ::bevy::render::render_asset::RenderAssetUsages;
+use ::bevy::render::render_resource::{Extent3d, PrimitiveTopology, TextureDimension, TextureFormat};
+use ::bevy::sprite::{ColorMaterial, Mesh2dHandle};
+
+use super::Rectangle;
+use super::atlas::{premultiplied_pixels, FontAtlas, GlyphAtlas};
+use super::layout::{LayoutConfig, LayoutOptions, TextLayout, UvOrigin, UvUnits, YAxis};
+use super::rich_text::Color;
+
+// FontAtlasPlugin draws text from baked atlases: they are added to the `AtlasAssets` resource, whose `texture_events` are
+// turned each frame into the `Image` assets of `AtlasTextures`, and the entities carrying a `Text2dAtlas` get the mesh of
+// their text and a `ColorMaterial` sampling their atlas.
+
+/// Adds the AtlasAssets and AtlasTextures resources and the systems uploading the atlases and building the text meshes,
+/// run in `PostUpdate`. It needs the `Image`, `Mesh` and `ColorMaterial` assets of the default plugins.
+pub struct FontAtlasPlugin;
+
+impl Plugin for FontAtlasPlugin {
+    fn build(&self, app: &mut App) {
+	app.init_resource::<AtlasAssets>()
+	    .init_resource::<AtlasTextures>()
+	    .add_systems(PostUpdate, (upload_atlas_textures, build_text_meshes).chain());
+    }
+}
+
+/// The key of an atlas in AtlasAssets, standing for both the atlas and its texture like a Bevy `Handle<Image>`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct AtlasHandle(pub u32);
+
+/// A change of the texture of an atlas, with pixels in premultiplied sRGBA like `TextureFormat::Rgba8UnormSrgb`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextureEvent {
+    /// The texture of a new atlas is to be created with the `width` x `height` pixels of `data`.
+    Created { handle: AtlasHandle, width: u32, height: u32, data: Vec<u8> },
+    /// The `region` of the texture is to be overwritten with the pixels of `data`.
+    Updated { handle: AtlasHandle, region: Rectangle, data: Vec<u8> },
+    /// The atlas was removed and its texture is to be dropped.
+    Released { handle: AtlasHandle },
+}
+
+/// The atlases registered by the application, tracking the state of their textures.
+#[derive(Default, Resource)]
+pub struct AtlasAssets {
+    atlases: BTreeMap<AtlasHandle, FontAtlas>,
+    created: Vec<AtlasHandle>,
+    released: Vec<AtlasHandle>,
+    next: u32,
+}
+
+impl AtlasAssets {
+    /// Creates a registry without any atlas.
+    pub fn new() -> Self {
+	Self::default()
+    }
+
+    /// Registers `atlas`, its texture being created on the next call to texture_events.
+    pub fn add(&mut self, mut atlas: FontAtlas) -> AtlasHandle {
+	let handle = AtlasHandle(self.next);
+	self.next += 1;
+
+	// The whole buffer goes with the creation
+	atlas.take_dirty_regions();
+	self.atlases.insert(handle, atlas);
+	self.created.push(handle);
+	handle
+    }
+
+    /// Returns the atlas of `handle`, if it wasn't removed.
+    pub fn get(&self, handle: AtlasHandle) -> Option<&FontAtlas> {
+	self.atlases.get(&handle)
+    }
+
+    /// Returns the atlas of `handle` to draw in it or insert images, the regions marked dirty being uploaded on the next
+    /// call to texture_events.
+    pub fn get_mut(&mut self, handle: AtlasHandle) -> Option<&mut FontAtlas> {
+	self.atlases.get_mut(&handle)
+    }
+
+    /// Removes the atlas of `handle` and returns it, its texture being released on the next call to texture_events.
+    pub fn remove(&mut self, handle: AtlasHandle) -> Option<FontAtlas> {
+	let atlas = self.atlases.remove(&handle)?;
+
+	match self.created.iter().position(|created| *created == handle) {
+	    // The texture was never created, so there is nothing to release
+	    Some(index) => { self.created.remove(index); },
+	    None => self.released.push(handle),
+	}
+
+	Some(atlas)
+    }
+
+    /// Returns the number of registered atlases.
+    pub fn len(&self) -> usize {
+	self.atlases.len()
+    }
+
+    /// Returns true if no atlas is registered.
+    pub fn is_empty(&self) -> bool {
+	self.atlases.is_empty()
+    }
+
+    /// Returns the texture changes since the last call: the textures of the added atlases, the dirty regions of the others
+    /// and the textures of the removed ones, leaving no region dirty.
+    pub fn texture_events(&mut self) -> Vec<TextureEvent> {
+	let mut events: Vec<TextureEvent> = self.released.drain(..)
+	    .map(|handle| TextureEvent::Released { handle })
+	    .collect();
+	let created = std::mem::take(&mut self.created);
+
+	for (handle, atlas) in self.atlases.iter_mut() {
+	    let handle = *handle;
+	    if created.contains(&handle) {
+		let whole = Rectangle::new(0, 0, atlas.width, atlas.height);
+		let data = premultiplied_pixels(&atlas.buffer, &whole, atlas.load_mode).concat();
+		events.push(TextureEvent::Created { handle, width: atlas.width, height: atlas.height, data });
+		continue;
+	    }
+
+	    for region in atlas.take_dirty_regions() {
+		let data = premultiplied_pixels(&atlas.buffer, &region, atlas.load_mode).concat();
+		events.push(TextureEvent::Updated { handle, region, data });
+	    }
+	}
+
+	events
+    }
+}
+
+/// The `Image` assets holding the textures of the atlases of AtlasAssets, kept up to date by the plugin.
+#[derive(Default, Resource)]
+pub struct AtlasTextures {
+    images: BTreeMap<AtlasHandle, Handle<Image>>,
+}
+
+impl AtlasTextures {
+    /// Returns the image of the atlas of `handle`, once its texture was created.
+    pub fn get(&self, handle: AtlasHandle) -> Option<&Handle<Image>> {
+	self.images.get(&handle)
+    }
+}
+
+/// Creates, patches and removes the images of the atlases from their texture events.
+fn upload_atlas_textures(mut assets: ResMut<AtlasAssets>, mut textures: ResMut<AtlasTextures>, mut images: ResMut<Assets<Image>>) {
+    for event in assets.texture_events() {
+	match event {
+	    TextureEvent::Created { handle, width, height, data } => {
+		let size = Extent3d { width, height, depth_or_array_layers: 1 };
+		let image = Image::new(size, TextureDimension::D2, data, TextureFormat::Rgba8UnormSrgb, RenderAssetUsages::default());
+		textures.images.insert(handle, images.add(image));
+	    },
+	    TextureEvent::Updated { handle, region, data } => {
+		let image = match textures.images.get(&handle).and_then(|image| images.get_mut(image)) {
+		    Some(image) => image,
+		    None => continue,
+		};
+		let stride = image.width() as usize * 4;
+		let row_length = region.width as usize * 4;
+
+		for (row, source) in data.chunks_exact(row_length.max(1)).enumerate() {
+		    let start = (region.top as usize + row) * stride + region.left as usize * 4;
+		    image.data[start..start + row_length].copy_from_slice(source);
+		}
+	    },
+	    TextureEvent::Released { handle } => {
+		if let Some(image) = textures.images.remove(&handle) {
+		    images.remove(&image);
+		}
+	    },
+	}
+    }
+}
+
+/// The entities whose Text2dAtlas changed or which have no mesh yet.
+type TextsToMesh<'w, 's> = Query<'w, 's, (Entity, &'static Text2dAtlas), Or<(Changed<Text2dAtlas>, Without<Mesh2dHandle>)>>;
+
+/// Gives the entities whose Text2dAtlas changed, or which have no mesh yet, the mesh of their text and a material sampling
+/// their atlas. Those whose atlas has no texture are left for a later frame.
+fn build_text_meshes(mut commands: Commands, assets: Res<AtlasAssets>, textures: Res<AtlasTextures>, mut meshes: ResMut<Assets<Mesh>>, mut materials: ResMut<Assets<ColorMaterial>>, texts: TextsToMesh) {
+    for (entity, text) in texts.iter() {
+	let (mesh, texture) = match (text.mesh(&assets), textures.get(text.atlas)) {
+	    (Some(mesh), Some(texture)) => (mesh, texture.clone()),
+	    _ => continue,
+	};
+
+	commands.entity(entity).insert((
+	    Mesh2dHandle(meshes.add(Mesh::from(mesh))),
+	    materials.add(ColorMaterial::from(texture)),
+	));
+    }
+}
+
+/// The mesh of a Text2dAtlas, converting into a Bevy `Mesh` in a `TriangleList`.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct Text2dMesh {
+    pub positions: Vec<[f32; 3]>,
+    pub uvs: Vec<[f32; 2]>,
+    /// Linear colors with straight alpha, like `Mesh::ATTRIBUTE_COLOR`.
+    pub colors: Vec<[f32; 4]>,
+    pub indices: Vec<u32>,
+}
+
+impl From<Text2dMesh> for Mesh {
+    fn from(mesh: Text2dMesh) -> Self {
+	Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::default())
+	    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, mesh.positions)
+	    .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, mesh.uvs)
+	    .with_inserted_attribute(Mesh::ATTRIBUTE_COLOR, mesh.colors)
+	    .with_inserted_indices(Indices::U32(mesh.indices))
+    }
+}
+
+/// The component of an entity drawing `text` with the glyphs of an atlas, in world units from its transform with y up. The
+/// entity is spawned with a `SpatialBundle`, the plugin inserting its mesh and material.
+#[derive(Debug, Clone, PartialEq, Component)]
+pub struct Text2dAtlas {
+    pub text: String,
+    pub atlas: AtlasHandle,
+    pub color: Color,
+    pub options: LayoutOptions,
+}
+
+impl Text2dAtlas {
+    /// Creates a component drawing `text` in white with the glyphs of `atlas`.
+    pub fn new(text: &str, atlas: AtlasHandle) -> Self {
+	Self {
+	    text: String::from(text),
+	    atlas,
+	    color: Color::rgb(255, 255, 255),
+	    options: LayoutOptions::new(),
+	}
+    }
+
+    /// Sets the color of the glyphs.
+    pub fn with_color(mut self, color: Color) -> Self {
+	self.color = color;
+	self
+    }
+
+    /// Sets the options of the layout.
+    pub fn with_options(mut self, options: LayoutOptions) -> Self {
+	self.options = options;
+	self
+    }
+
+    /// Lays out the text and returns its mesh, the top-left corner of the paragraph at the origin, or None if the atlas was
+    /// removed from `assets`.
+    pub fn mesh(&self, assets: &AtlasAssets) -> Option<Text2dMesh> {
+	let atlas = assets.get(self.atlas)?;
+	Some(self.mesh_with(atlas))
+    }
+
+    /// Lays out the text with the glyphs of `atlas` and returns its mesh.
+    pub fn mesh_with<A: GlyphAtlas>(&self, atlas: &A) -> Text2dMesh {
+	// Bevy draws 2D with y up and samples textures from their top-left corner
+	let config = LayoutConfig::new(YAxis::Up, UvOrigin::TopLeft, UvUnits::Normalized);
+	let layout = TextLayout::with_options(&self.text, atlas, &self.options).with_config(config);
+	let (vertices, indices) = layout.generate_colored_indexed_buffers(atlas, 0, 0, &[(0..self.text.len(), self.color)]);
+
+	Text2dMesh {
+	    positions: vertices.iter().map(|vertex| [vertex.position[0], vertex.position[1], 0.0]).collect(),
+	    uvs: vertices.iter().map(|vertex| vertex.uv).collect(),
+	    colors: vertices.iter().map(|vertex| linear_color(vertex.color)).collect(),
+	    indices,
+	}
+    }
+}
+
+/// Converts the sRGB channels of a vertex color to linear ones, leaving its alpha.
+fn linear_color([r, g, b, a]: [f32; 4]) -> [f32; 4] {
+    let linear = |channel: f32| match channel <= 0.04045 {
+	true => channel / 12.92,
+	false => ((channel + 0.055) / 1.055).powf(2.4),
+    };
+
+    [linear(r), linear(g), linear(b), a]
+}
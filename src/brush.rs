@@ -0,0 +1,139 @@
+use super::Rectangle;
+use super::atlas::{AtlasGenerator, AtlasGeneratorError, FontAtlas};
+use super::layout::TextLayout;
+use super::rich_text::Color;
+
+/// A piece of text queued in a TextBrush, drawn with a single color and scale.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Section {
+    pub text: String,
+    /// The top-left corner of the text, in the Y-up space of the vertices.
+    pub position: (f32, f32),
+    pub color: Color,
+    /// The factor applied to the size the atlas was rendered at.
+    pub scale: f32,
+    /// The width the text is wrapped at, before scaling.
+    pub max_width: Option<u32>,
+}
+
+impl Section {
+    /// Creates a white section at the origin, at the atlas size and without wrapping.
+    pub fn new(text: &str) -> Self {
+	Self {
+	    text: String::from(text),
+	    position: (0.0, 0.0),
+	    color: Color::default(),
+	    scale: 1.0,
+	    max_width: None,
+	}
+    }
+
+    /// Sets the top-left corner of the text.
+    pub fn with_position(mut self, x: f32, y: f32) -> Self {
+	self.position = (x, y);
+	self
+    }
+
+    /// Sets the color of the text.
+    pub fn with_color(mut self, color: Color) -> Self {
+	self.color = color;
+	self
+    }
+
+    /// Sets the factor applied to the size the atlas was rendered at.
+    pub fn with_scale(mut self, scale: f32) -> Self {
+	self.scale = scale;
+	self
+    }
+
+    /// Wraps the text at `max_width`, before scaling.
+    pub fn with_max_width(mut self, max_width: u32) -> Self {
+	self.max_width = Some(max_width);
+	self
+    }
+}
+
+/// A vertex of a glyph quad emitted by a TextBrush.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct BrushVertex {
+    pub position: [f32; 2],
+    pub uv: [f32; 2],
+    pub color: [f32; 4],
+}
+
+/// What a frame of queued sections needs to be drawn.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrushOutput {
+    /// The quads of all the sections, 6 vertices per glyph.
+    pub vertices: Vec<BrushVertex>,
+    /// The regions of the atlas to upload before drawing, see `FontAtlas::update_texture`.
+    pub uploads: Vec<Rectangle>,
+}
+
+/// A type queuing sections every frame and turning them into a single vertex buffer,
+/// adding the glyphs they need to its atlas on the way.
+pub struct TextBrush {
+    generator: AtlasGenerator,
+    atlas: FontAtlas,
+    queue: Vec<Section>,
+}
+
+impl TextBrush {
+    /// Creates a brush whose atlas is generated by `generator` at `font_size`.
+    pub fn new(generator: AtlasGenerator, font_size: u32) -> Result<Self, AtlasGeneratorError> {
+	let atlas = generator.generate(font_size)?;
+
+	Ok(Self {
+	    generator,
+	    atlas,
+	    queue: Vec::new(),
+	})
+    }
+
+    /// Returns the atlas the glyphs are drawn from.
+    pub fn atlas(&self) -> &FontAtlas {
+	&self.atlas
+    }
+
+    /// Returns the atlas the glyphs are drawn from, to create its texture.
+    pub fn atlas_mut(&mut self) -> &mut FontAtlas {
+	&mut self.atlas
+    }
+
+    /// Queues a section to be drawn this frame.
+    pub fn queue(&mut self, section: Section) {
+	self.queue.push(section);
+    }
+
+    /// Drops the sections queued so far.
+    pub fn clear_queue(&mut self) {
+	self.queue.clear();
+    }
+
+    /// Adds the missing glyphs of the queued sections to the atlas and builds their vertices, emptying the queue.
+    /// If the atlas runs out of room, the error is returned and the queue is kept.
+    pub fn process_queued(&mut self) -> Result<BrushOutput, AtlasGeneratorError> {
+	for section in self.queue.iter() {
+	    self.generator.add_glyphs(&mut self.atlas, &section.text)?;
+	}
+
+	let mut vertices = Vec::new();
+
+	for section in self.queue.drain(..) {
+	    let layout = TextLayout::new(&section.text, &self.atlas, section.max_width);
+	    let color = section.color.to_f32();
+	    let (x, y) = section.position;
+
+	    vertices.extend(layout.generate_buffers(&self.atlas, 0, 0).iter().map(|vertex| BrushVertex {
+		position: [x + vertex.position[0] * section.scale, y + vertex.position[1] * section.scale],
+		uv: vertex.uv,
+		color,
+	    }));
+	}
+
+	Ok(BrushOutput {
+	    vertices,
+	    uploads: self.atlas.take_dirty_regions(),
+	})
+    }
+}
@@ -0,0 +1,124 @@
+use std::collections::BTreeSet;
+use std::fmt::Display;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+
+/// A set of characters to bake, deduplicated and sorted by codepoint. Control characters are never added.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Charset {
+    chars: BTreeSet<char>,
+}
+
+impl Charset {
+    /// Creates an empty charset.
+    pub fn new() -> Self {
+	Self::default()
+    }
+
+    /// Creates a charset containing the characters of `text`.
+    pub fn from_text(text: &str) -> Self {
+	let mut charset = Self::new();
+	charset.add_text(text);
+	charset
+    }
+
+    /// Adds the characters of `text`.
+    pub fn add_text(&mut self, text: &str) {
+	self.chars.extend(text.chars().filter(|c| !c.is_control()));
+    }
+
+    /// Adds the characters of the string values of the JSON document `json`, escapes decoded. Object keys are skipped,
+    /// as they are usually identifiers rather than displayed text. Malformed documents are scanned as far as possible.
+    pub fn add_json_strings(&mut self, json: &str) {
+	let mut chars = json.chars().peekable();
+
+	while let Some(c) = chars.next() {
+	    if c != '"' {
+		continue;
+	    }
+
+	    let mut string = String::new();
+	    while let Some(c) = chars.next() {
+		match c {
+		    '"' => break,
+		    '\\' => match chars.next() {
+			Some('n') => string.push('\n'),
+			Some('t') => string.push('\t'),
+			Some('r') => string.push('\r'),
+			Some('b') => string.push('\u{8}'),
+			Some('f') => string.push('\u{c}'),
+			Some('u') => {
+			    let code: String = chars.by_ref().take(4).collect();
+			    let code = u32::from_str_radix(&code, 16).unwrap_or(0xfffd);
+
+			    // Characters outside of the BMP are escaped as a surrogate pair
+			    let mut next = chars.clone();
+			    let code = if (0xd800..0xdc00).contains(&code) && next.next() == Some('\\') && next.next() == Some('u') {
+				let low: String = chars.by_ref().skip(2).take(4).collect();
+				let low = u32::from_str_radix(&low, 16).unwrap_or(0xdc00);
+				0x10000 + ((code - 0xd800) << 10) + (low.saturating_sub(0xdc00) & 0x3ff)
+			    } else {
+				code
+			    };
+			    string.push(char::from_u32(code).unwrap_or('\u{fffd}'));
+			},
+			Some(c) => string.push(c),
+			None => break,
+		    },
+		    c => string.push(c),
+		}
+	    }
+
+	    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+	    if chars.peek() != Some(&':') {
+		self.add_text(&string);
+	    }
+	}
+    }
+
+    /// Adds the characters of the file at `path`, or of its string values if it is a `.json` file.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn add_file<P>(&mut self, path: P) -> std::io::Result<()> where P: AsRef<Path> {
+	let path = path.as_ref();
+	let content = std::fs::read_to_string(path)?;
+
+	match path.extension().and_then(|extension| extension.to_str()) {
+	    Some("json") => self.add_json_strings(&content),
+	    _ => self.add_text(&content),
+	}
+
+	Ok(())
+    }
+
+    /// Adds the characters of another charset.
+    pub fn merge(&mut self, other: &Charset) {
+	self.chars.extend(other.chars.iter());
+    }
+
+    /// Returns true if the charset contains `c`.
+    pub fn contains(&self, c: char) -> bool {
+	self.chars.contains(&c)
+    }
+
+    /// Returns the number of characters of the charset.
+    pub fn len(&self) -> usize {
+	self.chars.len()
+    }
+
+    /// Returns true if the charset has no character.
+    pub fn is_empty(&self) -> bool {
+	self.chars.is_empty()
+    }
+
+    /// Returns the characters of the charset by increasing codepoint.
+    pub fn chars(&self) -> impl Iterator<Item = char> + '_ {
+	self.chars.iter().copied()
+    }
+}
+
+/// Writes the characters by increasing codepoint, in the form taken by `AtlasGeneratorOption::with_charset`.
+impl Display for Charset {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+	self.chars.iter().try_for_each(|c| write!(f, "{}", c))
+    }
+}
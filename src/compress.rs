@@ -0,0 +1,219 @@
+use super::atlas::{AtlasLoadMode, FontAtlas};
+use super::pixels::{PixelFormat, Pixels};
+
+/// The modifiers of the 16 tables of EAC blocks, by table index.
+const EAC_MODIFIERS: [[i32; 8]; 16] = [
+    [-3, -6, -9, -15, 2, 5, 8, 14],
+    [-3, -7, -10, -13, 2, 6, 9, 12],
+    [-2, -5, -8, -13, 1, 4, 7, 12],
+    [-2, -4, -6, -13, 1, 3, 5, 12],
+    [-3, -6, -8, -12, 2, 5, 7, 11],
+    [-3, -7, -9, -11, 2, 6, 8, 10],
+    [-4, -7, -8, -11, 3, 6, 7, 10],
+    [-3, -5, -8, -11, 2, 4, 7, 10],
+    [-2, -6, -8, -10, 1, 5, 7, 9],
+    [-2, -5, -8, -10, 1, 4, 7, 9],
+    [-2, -4, -8, -10, 1, 3, 7, 9],
+    [-2, -5, -7, -10, 1, 4, 6, 9],
+    [-3, -4, -7, -10, 2, 3, 6, 9],
+    [-1, -2, -3, -10, 0, 1, 2, 9],
+    [-4, -6, -8, -9, 3, 5, 7, 8],
+    [-3, -5, -7, -9, 2, 4, 6, 8],
+];
+
+/// A block compressed format of single channel textures, each 4 by 4 pixel block being stored in 8 bytes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Compression {
+    /// BC4 unsigned, also known as RGTC1 or ATI1, supported by desktop GPUs.
+    Bc4,
+    /// EAC R11 unsigned, part of ETC2 and supported by mobile GPUs.
+    EacR11,
+}
+
+impl Compression {
+    /// Returns the number of bytes of a block.
+    pub fn block_size(&self) -> usize {
+	8
+    }
+
+    /// Returns the number of bytes of a `width` by `height` texture, partial blocks on the right and bottom edges being stored whole.
+    pub fn compressed_size(&self, width: u32, height: u32) -> usize {
+	blocks(width) * blocks(height) * self.block_size()
+    }
+}
+
+/// Returns the number of blocks covering `length` pixels.
+fn blocks(length: u32) -> usize {
+    (length as usize).div_ceil(4)
+}
+
+/// Compresses the first channel of `pixels` with `compression`, blocks being stored row after row. The pixels of partial blocks
+/// past the edges repeat the last row and column.
+pub fn compress(pixels: &Pixels, compression: Compression) -> Vec<u8> {
+    let mut data = Vec::with_capacity(compression.compressed_size(pixels.width, pixels.height));
+    if pixels.width == 0 || pixels.height == 0 {
+	return data;
+    }
+
+    for block_y in 0..blocks(pixels.height) as u32 {
+	for block_x in 0..blocks(pixels.width) as u32 {
+	    let mut block = [0; 16];
+	    for (index, value) in block.iter_mut().enumerate() {
+		let x = (block_x * 4 + index as u32 % 4).min(pixels.width - 1);
+		let y = (block_y * 4 + index as u32 / 4).min(pixels.height - 1);
+		*value = pixels.get_pixel(x, y)[0];
+	    }
+
+	    let encoded = match compression {
+		Compression::Bc4 => encode_bc4(&block),
+		Compression::EacR11 => encode_eac_r11(&block),
+	    };
+	    data.extend_from_slice(&encoded);
+	}
+    }
+
+    data
+}
+
+/// Decompresses a `width` by `height` texture compressed with `compression` into a gray bitmap, or returns None if `data` doesn't
+/// have as many blocks as the texture needs.
+pub fn decompress(data: &[u8], width: u32, height: u32, compression: Compression) -> Option<Pixels> {
+    if data.len() != compression.compressed_size(width, height) {
+	return None;
+    }
+
+    let columns = blocks(width);
+    Some(Pixels::from_fn(width, height, PixelFormat::Rgb8, |x, y| {
+	let index = y as usize / 4 * columns + x as usize / 4;
+	let block = &data[index * 8..index * 8 + 8];
+	let value = match compression {
+	    Compression::Bc4 => decode_bc4(block)[(y % 4 * 4 + x % 4) as usize],
+	    Compression::EacR11 => decode_eac_r11(block)[(y % 4 * 4 + x % 4) as usize],
+	};
+	[value, value, value, 255]
+    }))
+}
+
+impl FontAtlas {
+    /// Compresses the buffer of a gray atlas with `compression`, cutting the memory it takes on the GPU. Returns None for LCD and
+    /// color atlases, whose channels differ.
+    pub fn compressed(&self, compression: Compression) -> Option<Vec<u8>> {
+	(self.load_mode == AtlasLoadMode::Gray).then(|| compress(&self.buffer, compression))
+    }
+}
+
+/// Returns the 8 values of a BC4 block with the given endpoints.
+fn bc4_palette(red0: u8, red1: u8) -> [u8; 8] {
+    let (red0, red1) = (red0 as u32, red1 as u32);
+    let mut palette = [red0 as u8, red1 as u8, 0, 0, 0, 0, 0, 255];
+
+    if red0 > red1 {
+	for (index, value) in palette[2..].iter_mut().enumerate() {
+	    let weight = index as u32 + 1;
+	    *value = (((7 - weight) * red0 + weight * red1 + 3) / 7) as u8;
+	}
+    } else {
+	for (index, value) in palette[2..6].iter_mut().enumerate() {
+	    let weight = index as u32 + 1;
+	    *value = (((5 - weight) * red0 + weight * red1 + 2) / 5) as u8;
+	}
+    }
+
+    palette
+}
+
+/// Encodes a block of 16 values, row after row, as a BC4 block interpolating between its extremes.
+fn encode_bc4(block: &[u8; 16]) -> [u8; 8] {
+    let max = *block.iter().max().unwrap_or(&0);
+    let min = *block.iter().min().unwrap_or(&0);
+    let palette = bc4_palette(max, min);
+
+    let mut indices = 0u64;
+    for (pixel, value) in block.iter().enumerate() {
+	let nearest = (0..8).min_by_key(|index| (palette[*index] as i32 - *value as i32).abs()).unwrap_or(0);
+	indices |= (nearest as u64) << (3 * pixel);
+    }
+
+    let mut encoded = [max, min, 0, 0, 0, 0, 0, 0];
+    encoded[2..].copy_from_slice(&indices.to_le_bytes()[..6]);
+    encoded
+}
+
+/// Decodes a BC4 block into its 16 values, row after row.
+fn decode_bc4(block: &[u8]) -> [u8; 16] {
+    let palette = bc4_palette(block[0], block[1]);
+    let mut bytes = [0; 8];
+    bytes[..6].copy_from_slice(&block[2..8]);
+    let indices = u64::from_le_bytes(bytes);
+
+    let mut values = [0; 16];
+    for (pixel, value) in values.iter_mut().enumerate() {
+	*value = palette[((indices >> (3 * pixel)) & 7) as usize];
+    }
+
+    values
+}
+
+/// Returns the 11 bit value of an EAC R11 block for a modifier.
+fn eac_value(base: i32, multiplier: i32, modifier: i32) -> i32 {
+    match multiplier {
+	0 => (base * 8 + 4 + modifier).clamp(0, 2047),
+	multiplier => (base * 8 + 4 + modifier * multiplier * 8).clamp(0, 2047),
+    }
+}
+
+/// Encodes a block of 16 values, row after row, as an EAC R11 block, searching the tables and multipliers spanning its range.
+fn encode_eac_r11(block: &[u8; 16]) -> [u8; 8] {
+    let targets = block.map(|value| value as i32 * 2047 / 255);
+    let max = *block.iter().max().unwrap_or(&0) as i32;
+    let min = *block.iter().min().unwrap_or(&0) as i32;
+
+    // (error, base, multiplier, table, indices)
+    let mut best = (i64::MAX, 0, 0, 0, 0u64);
+    for (table, modifiers) in EAC_MODIFIERS.iter().enumerate() {
+	let span = modifiers[7] - modifiers[3];
+	let multiplier = ((max - min + span / 2) / span).clamp(1, 15);
+
+	for multiplier in (multiplier - 1).max(1)..=(multiplier + 1).min(15) {
+	    let center = ((max + min - (modifiers[3] + modifiers[7]) * multiplier) / 2).clamp(0, 255);
+	    for base in (center - 1).max(0)..=(center + 1).min(255) {
+		let mut error = 0;
+		let mut indices = 0u64;
+		for (pixel, target) in targets.iter().enumerate() {
+		    let (index, difference) = modifiers.iter().enumerate()
+			.map(|(index, modifier)| (index, (eac_value(base, multiplier, *modifier) - target).abs() as i64))
+			.min_by_key(|(_, difference)| *difference)
+			.unwrap_or((0, 0));
+		    error += difference * difference;
+		    // Indices are stored column after column
+		    indices |= (index as u64) << (45 - 3 * (pixel % 4 * 4 + pixel / 4));
+		}
+
+		if error < best.0 {
+		    best = (error, base, multiplier, table, indices);
+		}
+	    }
+	}
+    }
+
+    let (_, base, multiplier, table, indices) = best;
+    (((base as u64) << 56) | ((multiplier as u64) << 52) | ((table as u64) << 48) | indices).to_be_bytes()
+}
+
+/// Decodes an EAC R11 block into its 16 values, row after row, keeping the 8 most significant bits.
+fn decode_eac_r11(block: &[u8]) -> [u8; 16] {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&block[..8]);
+    let bits = u64::from_be_bytes(bytes);
+    let base = (bits >> 56) as i32;
+    let multiplier = ((bits >> 52) & 15) as i32;
+    let modifiers = &EAC_MODIFIERS[((bits >> 48) & 15) as usize];
+
+    let mut values = [0; 16];
+    for (pixel, value) in values.iter_mut().enumerate() {
+	let index = (bits >> (45 - 3 * (pixel % 4 * 4 + pixel / 4))) & 7;
+	*value = ((eac_value(base, multiplier, modifiers[index as usize]) * 255 + 1023) / 2047) as u8;
+    }
+
+    values
+}
@@ -0,0 +1,225 @@
+use std::ops::Range;
+
+use epaint::{Color32, ColorImage, ImageDelta, Mesh, TextureId, Vertex};
+use epaint::textures::TextureOptions;
+
+use super::Rectangle;
+use super::atlas::{premultiplied_pixels, quad_indices, AtlasLoadMode, FontAtlas, GlyphAtlas, TextVertex};
+use super::layout::{LayoutConfig, LayoutOptions, TextLayout};
+use super::pixels::Pixels;
+use super::rich_text::Color;
+
+// The types below hold what egui needs from an atlas and convert into the epaint ones. Atlases are drawn from user textures,
+// registered with `TextureId::User`, the page of a mesh telling which texture it samples.
+
+/// A change of the egui texture holding an atlas page, converting into an `epaint::ImageDelta`: the `size` pixels to write
+/// at `pos`, or the whole texture if `pos` is None. Pixels are premultiplied sRGBA like `Color32`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextureDelta {
+    pub pos: Option<[usize; 2]>,
+    pub size: [usize; 2],
+    pub pixels: Vec<[u8; 4]>,
+}
+
+impl TextureDelta {
+    /// Returns the delta replacing the whole texture with `page`, an atlas page rendered in `load_mode`.
+    pub fn full(page: &Pixels, load_mode: AtlasLoadMode) -> Self {
+	Self {
+	    pos: None,
+	    ..Self::region(page, &Rectangle::new(0, 0, page.width, page.height), load_mode)
+	}
+    }
+
+    /// Returns the delta writing `region` of `page`, an atlas page rendered in `load_mode`, at its place in the texture.
+    /// Coverage becomes white with the coverage as alpha, so that egui tints it with the vertex color. egui blends a single
+    /// alpha, so LCD coverage is averaged to gray. Color glyphs keep their colors and alpha.
+    pub fn region(page: &Pixels, region: &Rectangle, load_mode: AtlasLoadMode) -> Self {
+	Self {
+	    pos: Some([region.left as usize, region.top as usize]),
+	    size: [region.width as usize, region.height as usize],
+	    pixels: premultiplied_pixels(page, region, load_mode),
+	}
+    }
+}
+
+impl From<TextureDelta> for ImageDelta {
+    /// Converts the delta to the one to give to `TextureManager::set`, sampled linearly.
+    fn from(delta: TextureDelta) -> Self {
+	let image = ColorImage {
+	    size: delta.size,
+	    pixels: delta.pixels.into_iter().map(|[r, g, b, a]| Color32::from_rgba_premultiplied(r, g, b, a)).collect(),
+	};
+
+	match delta.pos {
+	    Some(pos) => ImageDelta::partial(pos, image, TextureOptions::LINEAR),
+	    None => ImageDelta::full(image, TextureOptions::LINEAR),
+	}
+    }
+}
+
+impl FontAtlas {
+    /// Returns the deltas updating the egui texture of the atlas with the regions changed since the last call, leaving none
+    /// dirty. A whole atlas, like a newly generated one, gives a single delta replacing the texture.
+    pub fn egui_texture_deltas(&mut self) -> Vec<TextureDelta> {
+	let whole = Rectangle::new(0, 0, self.width, self.height);
+
+	self.take_dirty_regions().iter()
+	    .map(|region| match *region == whole {
+		true => TextureDelta::full(&self.buffer, self.load_mode),
+		false => TextureDelta::region(&self.buffer, region, self.load_mode),
+	    })
+	    .collect()
+    }
+}
+
+/// A vertex of an egui mesh, converting into an `epaint::Vertex`: position in points, normalized uv from the top-left corner
+/// and premultiplied sRGBA color.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct MeshVertex {
+    pub pos: [f32; 2],
+    pub uv: [f32; 2],
+    pub color: [u8; 4],
+}
+
+impl From<MeshVertex> for Vertex {
+    fn from(vertex: MeshVertex) -> Self {
+	let [r, g, b, a] = vertex.color;
+
+	Self {
+	    pos: vertex.pos.into(),
+	    uv: vertex.uv.into(),
+	    color: Color32::from_rgba_premultiplied(r, g, b, a),
+	}
+    }
+}
+
+/// The triangles drawing the glyphs of an atlas page, like an `epaint::Mesh` with the page in place of the texture id.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct TextMesh {
+    pub indices: Vec<u32>,
+    pub vertices: Vec<MeshVertex>,
+    pub page: usize,
+}
+
+impl TextMesh {
+    /// Returns the epaint mesh sampling `texture_id`, the texture the page of the mesh was registered as.
+    pub fn to_mesh(&self, texture_id: TextureId) -> Mesh {
+	Mesh {
+	    indices: self.indices.clone(),
+	    vertices: self.vertices.iter().map(|vertex| Vertex::from(*vertex)).collect(),
+	    texture_id,
+	}
+    }
+}
+
+/// A line of a Galley, like an `epaint::Row`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GalleyRow {
+    /// The minimum and maximum corners of the line, in points from the top-left corner of the paragraph.
+    pub rect: [f32; 4],
+    pub byte_range: Range<usize>,
+    /// The range of the line in characters, the unit of egui cursors.
+    pub char_range: Range<usize>,
+}
+
+/// A paragraph laid out and ready to be painted by egui, like `epaint::Galley`, positioned in points from its top-left corner.
+/// Layouts are computed in atlas pixels, which are `pixels_per_point` points.
+pub struct Galley {
+    /// One mesh per atlas page holding glyphs of the paragraph, by increasing page.
+    pub meshes: Vec<TextMesh>,
+    pub rows: Vec<GalleyRow>,
+    /// The width and height of the paragraph.
+    pub size: [f32; 2],
+    pub layout: TextLayout,
+    pub pixels_per_point: f32,
+    text: String,
+}
+
+impl Galley {
+    /// Lays out `text` with the glyphs of `atlas` and `options`, every glyph drawn in `color`.
+    pub fn new<A: GlyphAtlas>(text: &str, atlas: &A, options: &LayoutOptions, color: Color, pixels_per_point: f32) -> Self {
+	let layout = TextLayout::with_options(text, atlas, options).with_config(LayoutConfig::top_left());
+	let (vertices, _) = layout.generate_colored_indexed_buffers(atlas, 0, 0, &[(0..text.len(), color)]);
+
+	Self::from_vertices(layout, text, &vertices, pixels_per_point)
+    }
+
+    /// Creates a galley from a layout of `text`, the glyphs of a rich text being drawn in the color of their span.
+    pub fn from_layout<A: GlyphAtlas>(layout: TextLayout, text: &str, atlas: &A, pixels_per_point: f32) -> Self {
+	let layout = layout.with_config(LayoutConfig::top_left());
+	let (vertices, _) = layout.generate_indexed_buffers(atlas, 0, 0);
+
+	Self::from_vertices(layout, text, &vertices, pixels_per_point)
+    }
+
+    /// Splits the indexed `vertices` of `layout`, 4 per glyph, in meshes by page and computes the rows.
+    fn from_vertices(layout: TextLayout, text: &str, vertices: &[TextVertex], pixels_per_point: f32) -> Self {
+	let mut meshes: Vec<TextMesh> = Vec::new();
+
+	for quad in vertices.chunks_exact(4) {
+	    let page = quad[0].layer as usize;
+	    let mesh = match meshes.iter().position(|mesh| mesh.page == page) {
+		Some(index) => &mut meshes[index],
+		None => {
+		    meshes.push(TextMesh { page, ..TextMesh::default() });
+		    meshes.last_mut().unwrap()
+		},
+	    };
+	    mesh.vertices.extend(quad.iter().map(|vertex| mesh_vertex(vertex, pixels_per_point)));
+	}
+	for mesh in meshes.iter_mut() {
+	    mesh.indices = quad_indices(mesh.vertices.len() / 4);
+	}
+	meshes.sort_by_key(|mesh| mesh.page);
+
+	let char_index = |byte_index: usize| text[..byte_index.min(text.len())].chars().count();
+	let rows: Vec<GalleyRow> = layout.line_boundaries().iter()
+	    .map(|line| {
+		let rect = line.rectangle;
+		GalleyRow {
+		    rect: [rect.x, rect.y, rect.x + rect.width, rect.y + rect.height].map(|value| value / pixels_per_point),
+		    byte_range: line.range.clone(),
+		    char_range: char_index(line.range.start)..char_index(line.range.end),
+		}
+	    })
+	    .collect();
+
+	let width = rows.iter().fold(0.0f32, |width, row| width.max(row.rect[2]));
+	let height = rows.last().map_or(0.0, |row| row.rect[3]);
+
+	Self {
+	    meshes,
+	    rows,
+	    size: [width, height],
+	    layout,
+	    pixels_per_point,
+	    text: String::from(text),
+	}
+    }
+
+    /// Returns the character index of the cursor closest to (`x`, `y`), in points from the top-left corner of the paragraph.
+    pub fn cursor_at(&self, x: f32, y: f32) -> usize {
+	let byte_index = self.layout.hit_test(x * self.pixels_per_point, y * self.pixels_per_point);
+	self.text[..byte_index.min(self.text.len())].chars().count()
+    }
+
+    /// Returns the minimum and maximum corners of the caret before the character at `char_index`, in points.
+    pub fn cursor_rect(&self, char_index: usize) -> [f32; 4] {
+	let byte_index = self.text.char_indices().nth(char_index).map_or(self.text.len(), |(index, _)| index);
+	let rect = self.layout.caret_position(byte_index);
+
+	[rect.x, rect.y, rect.x + rect.width, rect.y + rect.height].map(|value| value / self.pixels_per_point)
+    }
+}
+
+/// Converts a vertex in pixels with a straight alpha color to an egui vertex in points with a premultiplied color.
+fn mesh_vertex(vertex: &TextVertex, pixels_per_point: f32) -> MeshVertex {
+    let [r, g, b, a] = vertex.color;
+    let premultiplied = [r * a, g * a, b * a, a].map(|channel| (channel * 255.0).round() as u8);
+
+    MeshVertex {
+	pos: [vertex.position[0] / pixels_per_point, vertex.position[1] / pixels_per_point],
+	uv: vertex.uv,
+	color: premultiplied,
+    }
+}
@@ -0,0 +1,347 @@
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+
+use super::atlas::{AtlasGenerator, AtlasGeneratorBuilder, AtlasLoadMode, FontAtlas, Padding};
+
+thread_local! {
+    /// The message of the last error of a function called from this thread.
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Stores `message` as the last error of the calling thread.
+fn set_last_error(message: String) {
+    // Interior nul bytes can't be given to C, so the message is cut at the first one
+    let message = message.split('\0').next().unwrap_or_default().to_string();
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = CString::new(message).ok());
+}
+
+/// Runs `f`, turning a panic into the last error and `fallback`, as unwinding out of a C function aborts the host process.
+fn catch_panic<R>(fallback: R, f: impl FnOnce() -> R) -> R {
+    match std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)) {
+	Ok(result) => result,
+	Err(panic) => {
+	    let message = match (panic.downcast_ref::<&str>(), panic.downcast_ref::<String>()) {
+		(Some(message), _) => message.to_string(),
+		(_, Some(message)) => message.clone(),
+		_ => String::from("unknown cause"),
+	    };
+	    set_last_error(format!("Panicked: {}", message));
+	    fallback
+	},
+    }
+}
+
+/// Renders the glyphs in grayscale, matching AtlasLoadMode::Gray.
+pub const FONT_LOAD_MODE_GRAY: u32 = 0;
+/// Renders the glyphs with subpixel antialiasing, matching AtlasLoadMode::LCD.
+pub const FONT_LOAD_MODE_LCD: u32 = 1;
+
+/// The configuration of a generator.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct FontGeneratorOptions {
+    /// The size of the atlas, in pixels.
+    pub width: u32,
+    pub height: u32,
+    /// The resolution the glyphs are rendered at.
+    pub dpi: u32,
+    /// The empty pixels around each glyph.
+    pub padding: u32,
+    /// How the glyphs are rendered, one of the FONT_LOAD_MODE constants. A plain integer, as C can store any value in an enum.
+    pub load_mode: u32,
+    /// Bakes the .notdef glyph, used for the characters missing from the atlas.
+    pub notdef: bool,
+    /// Lets the glyphs that don't fit upright be packed rotated.
+    pub rotate: bool,
+    /// The characters baked, as a nul terminated UTF-8 string. Null bakes the default characters.
+    pub charset: *const c_char,
+}
+
+/// The position of a glyph in the atlas along with its metrics, in pixels.
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct FontGlyph {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub bearing_x: i32,
+    pub bearing_y: i32,
+    pub advance: i32,
+    /// Whether the bitmap is stored rotated 90° clockwise.
+    pub rotated: bool,
+}
+
+/// The vertical metrics of the font of an atlas, in pixels.
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct FontLineMetrics {
+    pub ascender: i32,
+    pub descender: i32,
+    pub line_height: i32,
+}
+
+/// Returns the default options: a 256x256 atlas at 72 dpi without padding, rendering the default characters in LCD mode.
+#[no_mangle]
+pub extern "C" fn font_generator_options_default() -> FontGeneratorOptions {
+    FontGeneratorOptions {
+	width: 256,
+	height: 256,
+	dpi: 72,
+	padding: 0,
+	load_mode: FONT_LOAD_MODE_LCD,
+	notdef: false,
+	rotate: false,
+	charset: std::ptr::null(),
+    }
+}
+
+/// Applies `options` to `builder`, or returns None if the load mode is unknown or the charset isn't valid UTF-8.
+///
+/// # Safety
+/// `options.charset` must be null or point to a nul terminated string.
+unsafe fn configure<'a>(mut builder: AtlasGeneratorBuilder<'a>, options: &FontGeneratorOptions) -> Option<AtlasGeneratorBuilder<'a>> {
+    let load_mode = match options.load_mode {
+	FONT_LOAD_MODE_GRAY => AtlasLoadMode::Gray,
+	FONT_LOAD_MODE_LCD => AtlasLoadMode::LCD,
+	load_mode => {
+	    set_last_error(format!("Unknown load mode {}", load_mode));
+	    return None;
+	},
+    };
+    let padding = options.padding;
+
+    builder = builder
+	.size(options.width, options.height)
+	.dpi(options.dpi)
+	.padding(Padding::uniform(padding))
+	.mode(load_mode);
+
+    if !options.charset.is_null() {
+	match CStr::from_ptr(options.charset).to_str() {
+	    Ok(charset) => builder = builder.charset(charset),
+	    Err(_) => {
+		set_last_error(String::from("The charset isn't valid UTF-8"));
+		return None;
+	    },
+	}
+    }
+    if options.notdef {
+	builder = builder.notdef();
+    }
+    if options.rotate {
+	builder = builder.rotate();
+    }
+
+    Some(builder)
+}
+
+/// Builds the generator configured in `builder`, or returns null and sets the last error.
+fn build(builder: AtlasGeneratorBuilder) -> *mut AtlasGenerator {
+    match builder.build() {
+	Ok(generator) => Box::into_raw(Box::new(generator)),
+	Err(error) => {
+	    set_last_error(error.to_string());
+	    std::ptr::null_mut()
+	},
+    }
+}
+
+/// Creates a generator rendering the font file at `path` with `options`. Returns null on error.
+///
+/// # Safety
+/// `path` must point to a nul terminated string and `options` to valid options.
+#[cfg(not(target_arch = "wasm32"))]
+#[no_mangle]
+pub unsafe extern "C" fn font_generator_new(path: *const c_char, options: *const FontGeneratorOptions) -> *mut AtlasGenerator {
+    catch_panic(std::ptr::null_mut(), || {
+	if path.is_null() || options.is_null() {
+	    set_last_error(String::from("The path and options can't be null"));
+	    return std::ptr::null_mut();
+	}
+
+	let path = match CStr::from_ptr(path).to_str() {
+	    Ok(path) => path,
+	    Err(_) => {
+		set_last_error(String::from("The path isn't valid UTF-8"));
+		return std::ptr::null_mut();
+	    },
+	};
+
+	match configure(AtlasGenerator::builder().font(path), &*options) {
+	    Some(builder) => build(builder),
+	    None => std::ptr::null_mut(),
+	}
+    })
+}
+
+/// Creates a generator rendering the font file content of `length` bytes at `data` with `options`, which is copied. Returns null on error.
+///
+/// # Safety
+/// `data` must point to `length` readable bytes and `options` to valid options.
+#[no_mangle]
+pub unsafe extern "C" fn font_generator_new_from_memory(data: *const u8, length: usize, options: *const FontGeneratorOptions) -> *mut AtlasGenerator {
+    catch_panic(std::ptr::null_mut(), || {
+	if data.is_null() || options.is_null() {
+	    set_last_error(String::from("The data and options can't be null"));
+	    return std::ptr::null_mut();
+	}
+
+	let data = std::slice::from_raw_parts(data, length);
+
+	match configure(AtlasGenerator::builder().font_bytes(data), &*options) {
+	    Some(builder) => build(builder),
+	    None => std::ptr::null_mut(),
+	}
+    })
+}
+
+/// Frees a generator. Null is ignored.
+///
+/// # Safety
+/// `generator` must be null or come from `font_generator_new` or `font_generator_new_from_memory`, and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn font_generator_free(generator: *mut AtlasGenerator) {
+    catch_panic((), || {
+	if !generator.is_null() {
+	    drop(Box::from_raw(generator));
+	}
+    })
+}
+
+/// Generates an atlas with the font of `generator` at `size`, in 26.6 points. Returns null on error.
+///
+/// # Safety
+/// `generator` must be a generator that wasn't freed.
+#[no_mangle]
+pub unsafe extern "C" fn font_generator_generate(generator: *const AtlasGenerator, size: u32) -> *mut FontAtlas {
+    catch_panic(std::ptr::null_mut(), || {
+	let generator = match generator.as_ref() {
+	    Some(generator) => generator,
+	    None => {
+		set_last_error(String::from("The generator can't be null"));
+		return std::ptr::null_mut();
+	    },
+	};
+
+	match generator.generate(size) {
+	    Ok(atlas) => Box::into_raw(Box::new(atlas)),
+	    Err(error) => {
+		set_last_error(error.to_string());
+		std::ptr::null_mut()
+	    },
+	}
+    })
+}
+
+/// Frees an atlas. Null is ignored.
+///
+/// # Safety
+/// `atlas` must be null or come from `font_generator_generate`, and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn font_atlas_free(atlas: *mut FontAtlas) {
+    catch_panic((), || {
+	if !atlas.is_null() {
+	    drop(Box::from_raw(atlas));
+	}
+    })
+}
+
+/// Writes the width and height of `atlas` in pixels to the non null pointers.
+///
+/// # Safety
+/// `atlas` must be an atlas that wasn't freed, and `width` and `height` null or writable.
+#[no_mangle]
+pub unsafe extern "C" fn font_atlas_size(atlas: *const FontAtlas, width: *mut u32, height: *mut u32) {
+    catch_panic((), || {
+	if let Some(atlas) = atlas.as_ref() {
+	    if let Some(width) = width.as_mut() {
+		*width = atlas.width;
+	    }
+	    if let Some(height) = height.as_mut() {
+		*height = atlas.height;
+	    }
+	}
+    })
+}
+
+/// Returns the pixels of `atlas`, 3 bytes per pixel in RGB order row after row, and writes their byte count to `length` if not null.
+/// The pixels live as long as the atlas.
+///
+/// # Safety
+/// `atlas` must be an atlas that wasn't freed, and `length` null or writable.
+#[no_mangle]
+pub unsafe extern "C" fn font_atlas_pixels(atlas: *const FontAtlas, length: *mut usize) -> *const u8 {
+    catch_panic(std::ptr::null(), || {
+	let pixels: &[u8] = match atlas.as_ref() {
+	    Some(atlas) => &atlas.buffer,
+	    None => &[],
+	};
+
+	if let Some(length) = length.as_mut() {
+	    *length = pixels.len();
+	}
+
+	pixels.as_ptr()
+    })
+}
+
+/// Writes the entry of the character `codepoint` to `glyph`, falling back to the .notdef glyph if it was baked.
+/// Returns false if the atlas has no entry for it.
+///
+/// # Safety
+/// `atlas` must be an atlas that wasn't freed, and `glyph` writable.
+#[no_mangle]
+pub unsafe extern "C" fn font_atlas_glyph(atlas: *const FontAtlas, codepoint: u32, glyph: *mut FontGlyph) -> bool {
+    catch_panic(false, || {
+	let entry = match (atlas.as_ref(), char::from_u32(codepoint)) {
+	    (Some(atlas), Some(c)) => atlas.lookup(c),
+	    _ => None,
+	};
+
+	match (entry, glyph.as_mut()) {
+	    (Some(entry), Some(glyph)) => {
+		let (position, metrics) = (entry.position(), entry.metrics());
+		*glyph = FontGlyph {
+		    x: position.left,
+		    y: position.top,
+		    width: position.width,
+		    height: position.height,
+		    bearing_x: metrics.bearing_x,
+		    bearing_y: metrics.bearing_y,
+		    advance: metrics.advance,
+		    rotated: entry.is_rotated(),
+		};
+		true
+	    },
+	    _ => false,
+	}
+    })
+}
+
+/// Writes the vertical metrics of the font of `atlas` to `line_metrics`.
+///
+/// # Safety
+/// `atlas` must be an atlas that wasn't freed, and `line_metrics` null or writable.
+#[no_mangle]
+pub unsafe extern "C" fn font_atlas_line_metrics(atlas: *const FontAtlas, line_metrics: *mut FontLineMetrics) {
+    catch_panic((), || {
+	if let (Some(atlas), Some(line_metrics)) = (atlas.as_ref(), line_metrics.as_mut()) {
+	    *line_metrics = FontLineMetrics {
+		ascender: atlas.line_metrics.ascender,
+		descender: atlas.line_metrics.descender,
+		line_height: atlas.line_metrics.line_height,
+	    };
+	}
+    })
+}
+
+/// Returns the message of the last error of a function called from this thread, or null if there was none.
+/// The message lives until the next error on this thread.
+#[no_mangle]
+pub extern "C" fn font_last_error() -> *const c_char {
+    catch_panic(std::ptr::null(), || {
+	LAST_ERROR.with(|last_error| last_error.borrow().as_ref().map_or(std::ptr::null(), |message| message.as_ptr()))
+    })
+}
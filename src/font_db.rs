@@ -0,0 +1,247 @@
+use std::path::{Path, PathBuf};
+
+/// The slant of a font face.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Style {
+    #[default]
+    Normal,
+    Italic,
+    Oblique,
+}
+
+impl From<fontdb::Style> for Style {
+    fn from(style: fontdb::Style) -> Self {
+	match style {
+	    fontdb::Style::Normal => Style::Normal,
+	    fontdb::Style::Italic => Style::Italic,
+	    fontdb::Style::Oblique => Style::Oblique,
+	}
+    }
+}
+
+impl From<Style> for fontdb::Style {
+    fn from(style: Style) -> Self {
+	match style {
+	    Style::Normal => fontdb::Style::Normal,
+	    Style::Italic => fontdb::Style::Italic,
+	    Style::Oblique => fontdb::Style::Oblique,
+	}
+    }
+}
+
+/// The weight of a font face, from the thinnest to the boldest.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Weight {
+    Thin,
+    ExtraLight,
+    Light,
+    #[default]
+    Normal,
+    Medium,
+    SemiBold,
+    Bold,
+    ExtraBold,
+    Black,
+}
+
+impl Weight {
+    /// Returns the numeric weight, as used by OpenType and CSS.
+    pub fn value(&self) -> u16 {
+	match self {
+	    Weight::Thin => 100,
+	    Weight::ExtraLight => 200,
+	    Weight::Light => 300,
+	    Weight::Normal => 400,
+	    Weight::Medium => 500,
+	    Weight::SemiBold => 600,
+	    Weight::Bold => 700,
+	    Weight::ExtraBold => 800,
+	    Weight::Black => 900,
+	}
+    }
+
+    /// Returns the named weight closest to the numeric `value`.
+    pub fn from_value(value: u16) -> Self {
+	match value {
+	    0..=149 => Weight::Thin,
+	    150..=249 => Weight::ExtraLight,
+	    250..=349 => Weight::Light,
+	    350..=449 => Weight::Normal,
+	    450..=549 => Weight::Medium,
+	    550..=649 => Weight::SemiBold,
+	    650..=749 => Weight::Bold,
+	    750..=849 => Weight::ExtraBold,
+	    _ => Weight::Black,
+	}
+    }
+}
+
+/// A font face installed on the system.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontFace {
+    pub path: PathBuf,
+    pub face_index: u32,
+    pub family: String,
+    pub postscript_name: String,
+    pub style: Style,
+    pub weight: Weight,
+    pub monospaced: bool,
+}
+
+/// A query selecting a font face by family, weight and style.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontQuery {
+    pub families: Vec<String>,
+    pub weight: Weight,
+    pub style: Style,
+}
+
+impl FontQuery {
+    /// Creates a query for a normal face of the first available family.
+    pub fn new(families: &[&str]) -> Self {
+	Self {
+	    families: families.iter().map(|family| String::from(*family)).collect(),
+	    weight: Weight::default(),
+	    style: Style::default(),
+	}
+    }
+
+    /// Sets the requested weight.
+    pub fn with_weight(mut self, weight: Weight) -> Self {
+	self.weight = weight;
+	self
+    }
+
+    /// Sets the requested style.
+    pub fn with_style(mut self, style: Style) -> Self {
+	self.style = style;
+	self
+    }
+
+    /// Parses a description like `"DejaVu Sans, Bold Italic"`: comma separated families, the last item
+    /// being read as weight and style keywords if it only contains such keywords.
+    pub fn parse(description: &str) -> Self {
+	let mut items: Vec<&str> = description.split(',').map(str::trim).filter(|item| !item.is_empty()).collect();
+	let mut query = Self::new(&[]);
+
+	if items.len() > 1 {
+	    let modifiers = items[items.len() - 1];
+	    if modifiers.split_whitespace().all(|word| query.apply_modifier(word)) {
+		items.pop();
+	    } else {
+		query = Self::new(&[]);
+	    }
+	}
+
+	query.families = items.into_iter().map(String::from).collect();
+	query
+    }
+
+    /// Applies a weight or style keyword, returning false if it isn't one.
+    fn apply_modifier(&mut self, word: &str) -> bool {
+	match word.to_ascii_lowercase().replace('-', "").as_str() {
+	    "thin" | "hairline" => self.weight = Weight::Thin,
+	    "extralight" | "ultralight" => self.weight = Weight::ExtraLight,
+	    "light" => self.weight = Weight::Light,
+	    "regular" | "normal" | "book" => self.weight = Weight::Normal,
+	    "medium" => self.weight = Weight::Medium,
+	    "semibold" | "demibold" => self.weight = Weight::SemiBold,
+	    "bold" => self.weight = Weight::Bold,
+	    "extrabold" | "ultrabold" => self.weight = Weight::ExtraBold,
+	    "black" | "heavy" => self.weight = Weight::Black,
+	    "italic" => self.style = Style::Italic,
+	    "oblique" => self.style = Style::Oblique,
+	    _ => return false,
+	}
+	true
+    }
+}
+
+/// A database of font faces, usually filled with the fonts installed on the system.
+pub struct FontDatabase {
+    db: fontdb::Database,
+}
+
+impl FontDatabase {
+    /// Creates an empty database.
+    pub fn new() -> Self {
+	Self {
+	    db: fontdb::Database::new(),
+	}
+    }
+
+    /// Creates a database containing the fonts installed on the system.
+    pub fn system() -> Self {
+	let mut database = Self::new();
+	database.db.load_system_fonts();
+	database
+    }
+
+    /// Adds the faces of the given font file.
+    pub fn load_font_file<P>(&mut self, font_filepath: P) -> Result<(), std::io::Error> where P: AsRef<Path> {
+	self.db.load_font_file(font_filepath)
+    }
+
+    /// Adds the faces of all the font files in the given directory and its subdirectories.
+    pub fn load_fonts_dir<P>(&mut self, directory: P) where P: AsRef<Path> {
+	self.db.load_fonts_dir(directory)
+    }
+
+    /// Returns all the faces of the database backed by a file.
+    pub fn faces(&self) -> Vec<FontFace> {
+	self.db.faces().filter_map(Self::font_face).collect()
+    }
+
+    /// Returns the face best matching the query, trying each family in order.
+    /// The generic `serif`, `sans-serif`, `monospace`, `cursive` and `fantasy` families are supported.
+    pub fn query(&self, query: &FontQuery) -> Option<FontFace> {
+	let families: Vec<fontdb::Family> = query.families.iter().map(|family| {
+	    match family.to_ascii_lowercase().as_str() {
+		"serif" => fontdb::Family::Serif,
+		"sans-serif" | "sans" => fontdb::Family::SansSerif,
+		"monospace" => fontdb::Family::Monospace,
+		"cursive" => fontdb::Family::Cursive,
+		"fantasy" => fontdb::Family::Fantasy,
+		_ => fontdb::Family::Name(family),
+	    }
+	}).collect();
+
+	let id = self.db.query(&fontdb::Query {
+	    families: &families,
+	    weight: fontdb::Weight(query.weight.value()),
+	    stretch: fontdb::Stretch::Normal,
+	    style: query.style.into(),
+	})?;
+
+	self.db.face(id).and_then(Self::font_face)
+    }
+
+    /// Returns the face best matching a description like `"DejaVu Sans, Bold"`.
+    pub fn query_str(&self, description: &str) -> Option<FontFace> {
+	self.query(&FontQuery::parse(description))
+    }
+
+    fn font_face(face_info: &fontdb::FaceInfo) -> Option<FontFace> {
+	let path = match &face_info.source {
+	    fontdb::Source::File(path) => path.clone(),
+	    fontdb::Source::SharedFile(path, _) => path.clone(),
+	    fontdb::Source::Binary(_) => return None,
+	};
+
+	Some(FontFace {
+	    path,
+	    face_index: face_info.index,
+	    family: face_info.families.first().map(|(family, _)| family.clone()).unwrap_or_default(),
+	    postscript_name: face_info.post_script_name.clone(),
+	    style: face_info.style.into(),
+	    weight: Weight::from_value(face_info.weight.0),
+	    monospaced: face_info.monospaced,
+	})
+    }
+}
+
+impl Default for FontDatabase {
+    fn default() -> Self {
+	Self::new()
+    }
+}
@@ -0,0 +1,121 @@
+use glow::HasContext;
+
+use super::Rectangle;
+use super::pixels::to_rgba;
+use super::atlas::{AtlasLoadMode, FontAtlas};
+
+impl AtlasLoadMode {
+    /// Returns the internal format and pixel format of OpenGL textures holding atlases rendered in this mode.
+    /// Color atlases hold premultiplied alpha, to be blended with `glBlendFunc(GL_ONE, GL_ONE_MINUS_SRC_ALPHA)`.
+    pub fn gl_formats(&self) -> (i32, u32) {
+	match self {
+	    AtlasLoadMode::Gray => (glow::R8 as i32, glow::RED),
+	    AtlasLoadMode::LCD => (glow::RGB8 as i32, glow::RGB),
+	    AtlasLoadMode::Color => (glow::RGBA8 as i32, glow::RGBA),
+	}
+    }
+}
+
+impl FontAtlas {
+    /// Returns the pixels of `region` laid out for `gl_formats()`, row by row and without row padding.
+    pub fn gl_texture_data(&self, region: &Rectangle) -> Vec<u8> {
+	let mut data = Vec::new();
+
+	for y in region.top..region.top + region.height {
+	    for x in region.left..region.left + region.width {
+		let pixel = self.buffer.get_pixel(x, y);
+		match self.load_mode {
+		    AtlasLoadMode::Gray => data.push(pixel[0]),
+		    AtlasLoadMode::LCD => data.extend_from_slice(&[pixel[0], pixel[1], pixel[2]]),
+		    AtlasLoadMode::Color => data.extend_from_slice(&to_rgba(pixel)),
+		}
+	    }
+	}
+
+	data
+    }
+
+    /// Creates a 2D texture of the atlas size, with nearest filtering and no mipmaps, and uploads the whole atlas in it,
+    /// which leaves no region dirty. The texture is left bound to `TEXTURE_2D`.
+    ///
+    /// # Safety
+    ///
+    /// The context of `gl` must be current on the calling thread.
+    pub unsafe fn create_gl_texture<G: HasContext>(&mut self, gl: &G) -> Result<G::Texture, String> {
+	let texture = gl.create_texture()?;
+	let (internal_format, format) = self.load_mode.gl_formats();
+
+	gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+	gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MIN_FILTER, glow::NEAREST as i32);
+	gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MAG_FILTER, glow::NEAREST as i32);
+	gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_S, glow::CLAMP_TO_EDGE as i32);
+	gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_T, glow::CLAMP_TO_EDGE as i32);
+
+	let data = self.gl_texture_data(&Rectangle::new(0, 0, self.width, self.height));
+	with_tight_unpacking(gl, || {
+	    gl.tex_image_2d(
+		glow::TEXTURE_2D,
+		0,
+		internal_format,
+		self.width as i32,
+		self.height as i32,
+		0,
+		format,
+		glow::UNSIGNED_BYTE,
+		Some(&data)
+	    );
+	});
+	self.take_dirty_regions();
+
+	Ok(texture)
+    }
+
+    /// Uploads the regions of the atlas changed since the last upload to `texture`.
+    ///
+    /// # Safety
+    ///
+    /// Same as `update_gl_texture`.
+    pub unsafe fn upload_dirty_gl_regions<G: HasContext>(&mut self, gl: &G, texture: G::Texture) {
+	for region in self.take_dirty_regions() {
+	    self.update_gl_texture(gl, texture, &region);
+	}
+    }
+
+    /// Uploads `region` of the atlas to the same region of `texture`, for instance the position of new entries.
+    /// The texture is left bound to `TEXTURE_2D`.
+    ///
+    /// # Safety
+    ///
+    /// The context of `gl` must be current on the calling thread and `texture` must have been created by `create_gl_texture`.
+    pub unsafe fn update_gl_texture<G: HasContext>(&self, gl: &G, texture: G::Texture, region: &Rectangle) {
+	if region.width == 0 || region.height == 0 {
+	    return;
+	}
+
+	let (_, format) = self.load_mode.gl_formats();
+	let data = self.gl_texture_data(region);
+
+	gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+	with_tight_unpacking(gl, || {
+	    gl.tex_sub_image_2d(
+		glow::TEXTURE_2D,
+		0,
+		region.left as i32,
+		region.top as i32,
+		region.width as i32,
+		region.height as i32,
+		format,
+		glow::UNSIGNED_BYTE,
+		glow::PixelUnpackData::Slice(&data)
+	    );
+	});
+    }
+}
+
+/// Runs `upload` with an unpack alignment of 1, since rows of 1 or 3 byte texels are rarely 4 byte aligned, then restores it.
+unsafe fn with_tight_unpacking<G: HasContext>(gl: &G, upload: impl FnOnce()) {
+    let alignment = gl.get_parameter_i32(glow::UNPACK_ALIGNMENT);
+    gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 1);
+    upload();
+    gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, alignment);
+}
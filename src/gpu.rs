@@ -0,0 +1,97 @@
+use super::Rectangle;
+use super::pixels::to_rgba;
+use super::atlas::{AtlasLoadMode, FontAtlas};
+
+impl AtlasLoadMode {
+    /// Returns the texture format holding atlases rendered in this mode.
+    /// Coverage is linear, so LCD atlases use a non-sRGB format to be sampled unchanged. Color atlases hold premultiplied alpha,
+    /// to be drawn with `wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING`.
+    pub fn texture_format(&self) -> wgpu::TextureFormat {
+	match self {
+	    AtlasLoadMode::Gray => wgpu::TextureFormat::R8Unorm,
+	    AtlasLoadMode::LCD | AtlasLoadMode::Color => wgpu::TextureFormat::Rgba8Unorm,
+	}
+    }
+
+    /// Returns the size of a texel of `texture_format()`, in bytes.
+    fn texel_size(&self) -> u32 {
+	match self {
+	    AtlasLoadMode::Gray => 1,
+	    AtlasLoadMode::LCD | AtlasLoadMode::Color => 4,
+	}
+    }
+}
+
+impl FontAtlas {
+    /// Returns the texture format matching the load mode of the atlas.
+    pub fn texture_format(&self) -> wgpu::TextureFormat {
+	self.load_mode.texture_format()
+    }
+
+    /// Returns the pixels of `region` laid out for `texture_format()`, row by row.
+    pub fn texture_data(&self, region: &Rectangle) -> Vec<u8> {
+	let mut data = Vec::with_capacity((region.width * region.height * self.load_mode.texel_size()) as usize);
+
+	for y in region.top..region.top + region.height {
+	    for x in region.left..region.left + region.width {
+		let pixel = self.buffer.get_pixel(x, y);
+		match self.load_mode {
+		    AtlasLoadMode::Gray => data.push(pixel[0]),
+		    AtlasLoadMode::LCD => data.extend_from_slice(&[pixel[0], pixel[1], pixel[2], 255]),
+		    AtlasLoadMode::Color => data.extend_from_slice(&to_rgba(pixel)),
+		}
+	    }
+	}
+
+	data
+    }
+
+    /// Creates a texture of the atlas size and uploads the whole atlas in it, which leaves no region dirty.
+    pub fn create_texture(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) -> wgpu::Texture {
+	let texture = device.create_texture(&wgpu::TextureDescriptor {
+	    label: Some("font atlas"),
+	    size: wgpu::Extent3d { width: self.width, height: self.height, depth_or_array_layers: 1 },
+	    mip_level_count: 1,
+	    sample_count: 1,
+	    dimension: wgpu::TextureDimension::D2,
+	    format: self.texture_format(),
+	    usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+	    view_formats: &[],
+	});
+
+	self.update_texture(queue, &texture, &Rectangle::new(0, 0, self.width, self.height));
+	self.take_dirty_regions();
+
+	texture
+    }
+
+    /// Uploads the regions of the atlas changed since the last upload to `texture`.
+    pub fn upload_dirty_regions(&mut self, queue: &wgpu::Queue, texture: &wgpu::Texture) {
+	for region in self.take_dirty_regions() {
+	    self.update_texture(queue, texture, &region);
+	}
+    }
+
+    /// Uploads `region` of the atlas to the same region of `texture`, for instance the position of new entries.
+    pub fn update_texture(&self, queue: &wgpu::Queue, texture: &wgpu::Texture, region: &Rectangle) {
+	if region.width == 0 || region.height == 0 {
+	    return;
+	}
+
+	queue.write_texture(
+	    wgpu::ImageCopyTexture {
+		texture,
+		mip_level: 0,
+		origin: wgpu::Origin3d { x: region.left, y: region.top, z: 0 },
+		aspect: wgpu::TextureAspect::All,
+	    },
+	    &self.texture_data(region),
+	    wgpu::ImageDataLayout {
+		offset: 0,
+		bytes_per_row: Some(region.width * self.load_mode.texel_size()),
+		rows_per_image: None,
+	    },
+	    wgpu::Extent3d { width: region.width, height: region.height, depth_or_array_layers: 1 }
+	);
+    }
+}
@@ -0,0 +1,1299 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Debug;
+use std::ops::Range;
+use std::sync::Arc;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::atlas::{color_at, push_glyph_instance, push_glyph_quad, push_indexed_glyph_quad, push_solid_quad, quad_indices, FontAtlasEntry, GlyphAtlas, GlyphInstance, GlyphQuad, LineMetrics, TextVertex, VertexEmitter};
+use super::multi_atlas::FontId;
+use super::path::TextPath;
+use super::rich_text::{Color, Decoration, RichText, Theme};
+use super::script::{select_fonts, Script, ZERO_WIDTH_JOINER};
+
+/// The soft hyphen, a break opportunity inside a word only drawn, as a hyphen, when a line is broken at it.
+pub const SOFT_HYPHEN: char = '\u{ad}';
+
+/// The no-break space, advancing like a space without being a break opportunity.
+pub const NO_BREAK_SPACE: char = '\u{a0}';
+
+/// Returns true if lines can be broken after the whitespace `c`, which excludes the no-break spaces.
+fn is_breaking_space(c: char) -> bool {
+    c.is_whitespace() && !matches!(c, NO_BREAK_SPACE | '\u{2007}' | '\u{202f}')
+}
+
+/// A rectangle in layout space, whose origin is the top-left corner of the paragraph and whose y axis points down.
+#[derive(Default, Debug, Copy, Clone, PartialEq)]
+pub struct LayoutRect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl LayoutRect {
+    /// Creates a rectangle at (x, y) with (width, height) dimensions.
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+	Self { x, y, width, height }
+    }
+
+    /// Returns true if the point (x, y) is inside the rectangle.
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+	x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+}
+
+/// The direction of the y axis of the generated vertices.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum YAxis {
+    #[default]
+    Up,
+    Down,
+}
+
+/// The corner of the atlas texture where uv coordinates start.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum UvOrigin {
+    /// OpenGL convention.
+    #[default]
+    BottomLeft,
+    /// Vulkan, wgpu, Metal and Direct3D convention.
+    TopLeft,
+}
+
+/// The unit of the generated uv coordinates.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum UvUnits {
+    /// Between 0 and 1 across the atlas.
+    #[default]
+    Normalized,
+    /// In atlas pixels, for unnormalized samplers and texel fetches.
+    Texels,
+}
+
+/// The point of a paragraph placed at the position given to the generators.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Anchor {
+    #[default]
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+    /// The start of the baseline of the first line.
+    BaselineLeft,
+    /// The middle of the baseline of the first line.
+    Baseline,
+    /// The end of the baseline of the first line.
+    BaselineRight,
+}
+
+impl Anchor {
+    /// Returns the position of the anchor from the top-left corner, with y down, of a paragraph of `width` x `height` pixels
+    /// whose first baseline is `baseline` pixels below its top.
+    pub fn position(&self, width: i32, height: i32, baseline: i32) -> (i32, i32) {
+	let x = match self {
+	    Anchor::TopLeft | Anchor::Left | Anchor::BottomLeft | Anchor::BaselineLeft => 0,
+	    Anchor::Top | Anchor::Center | Anchor::Bottom | Anchor::Baseline => width / 2,
+	    Anchor::TopRight | Anchor::Right | Anchor::BottomRight | Anchor::BaselineRight => width,
+	};
+	let y = match self {
+	    Anchor::TopLeft | Anchor::Top | Anchor::TopRight => 0,
+	    Anchor::Left | Anchor::Center | Anchor::Right => height / 2,
+	    Anchor::BottomLeft | Anchor::Bottom | Anchor::BottomRight => height,
+	    Anchor::BaselineLeft | Anchor::Baseline | Anchor::BaselineRight => baseline,
+	};
+
+	(x, y)
+    }
+}
+
+/// The coordinate conventions of the vertices and instances generated from a layout.
+/// The default is y up with normalized uvs starting at the bottom-left corner, the position given being the top-left corner.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct LayoutConfig {
+    pub y_axis: YAxis,
+    pub uv_origin: UvOrigin,
+    pub uv_units: UvUnits,
+    pub anchor: Anchor,
+    /// Whether glyphs placed on fractional positions, like along a path, are moved to whole pixels. Other layouts are always
+    /// on whole pixels, metrics being baked in pixels.
+    pub pixel_snap: bool,
+}
+
+impl LayoutConfig {
+    /// Creates a configuration from its components.
+    pub fn new(y_axis: YAxis, uv_origin: UvOrigin, uv_units: UvUnits) -> Self {
+	Self { y_axis, uv_origin, uv_units, anchor: Anchor::TopLeft, pixel_snap: false }
+    }
+
+    /// Returns the conventions of wgpu and Vulkan in a y down screen space: y down with normalized uvs starting at the top-left corner.
+    pub fn top_left() -> Self {
+	Self::new(YAxis::Down, UvOrigin::TopLeft, UvUnits::Normalized)
+    }
+
+    /// Sets the direction of the y axis.
+    pub fn with_y_axis(mut self, y_axis: YAxis) -> Self {
+	self.y_axis = y_axis;
+	self
+    }
+
+    /// Sets the corner of the texture where uv coordinates start.
+    pub fn with_uv_origin(mut self, uv_origin: UvOrigin) -> Self {
+	self.uv_origin = uv_origin;
+	self
+    }
+
+    /// Sets the unit of the uv coordinates.
+    pub fn with_uv_units(mut self, uv_units: UvUnits) -> Self {
+	self.uv_units = uv_units;
+	self
+    }
+
+    /// Sets whether glyphs placed on fractional positions are moved to whole pixels.
+    pub fn with_pixel_snap(mut self, pixel_snap: bool) -> Self {
+	self.pixel_snap = pixel_snap;
+	self
+    }
+
+    /// Sets the point of the paragraph placed at the position given to the generators.
+    pub fn with_anchor(mut self, anchor: Anchor) -> Self {
+	self.anchor = anchor;
+	self
+    }
+}
+
+/// The distance between the tops of two consecutive lines.
+#[derive(Default, Debug, Copy, Clone, PartialEq)]
+pub enum LineHeight {
+    /// The line height of the font.
+    #[default]
+    Font,
+    /// A line height in pixels.
+    Absolute(i32),
+    /// A factor applied to the line height of the font.
+    Multiplier(f32),
+}
+
+impl LineHeight {
+    /// Returns the line height in pixels for a font whose line height is `font_line_height`.
+    pub fn resolve(&self, font_line_height: i32) -> i32 {
+	match self {
+	    LineHeight::Font => font_line_height,
+	    LineHeight::Absolute(line_height) => *line_height,
+	    LineHeight::Multiplier(factor) => (font_line_height as f32 * factor).round() as i32,
+	}
+    }
+}
+
+/// The positions a tab advances to, relative to the start of the line.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TabStops {
+    /// A stop every given number of space advances.
+    Spaces(u32),
+    /// A stop every given number of pixels.
+    Fixed(i32),
+    /// Stops at the given increasing positions in pixels. Past the last one, a tab advances like a space.
+    Positions(Vec<i32>),
+}
+
+impl TabStops {
+    /// Returns the first stop after `pen_x`, spaces being those of the font `font`.
+    fn next_stop<A: GlyphAtlas>(&self, atlas: &A, font: FontId, pen_x: i32) -> i32 {
+	let interval = match self {
+	    TabStops::Spaces(spaces) => *spaces as i32 * atlas.glyph_advance(font, ' '),
+	    TabStops::Fixed(interval) => *interval,
+	    TabStops::Positions(positions) => {
+		return positions.iter()
+		    .copied()
+		    .find(|position| *position > pen_x)
+		    .unwrap_or_else(|| pen_x + atlas.glyph_advance(font, ' '));
+	    },
+	};
+
+	if interval <= 0 {
+	    return pen_x;
+	}
+
+	(pen_x.div_euclid(interval) + 1) * interval
+    }
+}
+
+impl Default for TabStops {
+    fn default() -> Self {
+	TabStops::Spaces(4)
+    }
+}
+
+/// Finds where words may be broken with a hyphen. Closures taking a word and returning the points are hyphenators.
+pub trait Hyphenator {
+    /// Returns the byte offsets in `word` where it may be broken, a hyphen ending the line before each of them.
+    fn hyphenation_points(&self, word: &str) -> Vec<usize>;
+}
+
+impl<F> Hyphenator for F where F: Fn(&str) -> Vec<usize> {
+    fn hyphenation_points(&self, word: &str) -> Vec<usize> {
+	self(word)
+    }
+}
+
+/// A hyphenator shared by layout options, equal to its clones only.
+#[derive(Clone)]
+pub struct Hyphenation(Arc<dyn Hyphenator + Send + Sync>);
+
+impl Hyphenation {
+    /// Wraps `hyphenator` to be set in layout options.
+    pub fn new<H: Hyphenator + Send + Sync + 'static>(hyphenator: H) -> Self {
+	Self(Arc::new(hyphenator))
+    }
+
+    /// Returns the hyphenation points of `word` that are character boundaries strictly inside it, in increasing order.
+    fn points(&self, word: &str) -> Vec<usize> {
+	let mut points = self.0.hyphenation_points(word);
+	points.retain(|point| *point > 0 && *point < word.len() && word.is_char_boundary(*point));
+	points.sort_unstable();
+	points
+    }
+}
+
+impl Debug for Hyphenation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+	write!(f, "Hyphenation")
+    }
+}
+
+impl PartialEq for Hyphenation {
+    fn eq(&self, other: &Self) -> bool {
+	Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// The options controlling how a TextLayout places the glyphs.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct LayoutOptions {
+    /// The width lines are wrapped at, in pixels.
+    pub max_width: Option<u32>,
+    /// The space added after each character, in pixels.
+    pub letter_spacing: i32,
+    /// The space added after each space character on top of the letter spacing, in pixels.
+    pub word_spacing: i32,
+    pub line_height: LineHeight,
+    /// The space added after each line break, in pixels.
+    pub paragraph_spacing: i32,
+    pub tab_stops: TabStops,
+    /// Whether the lines ended by a wrap are stretched to the maximum width by widening their word gaps.
+    pub justify: bool,
+    /// Breaks the words overflowing a line with a hyphen, at the last point leaving room for it.
+    pub hyphenation: Option<Hyphenation>,
+    /// The indent of the first line of each paragraph from the left margin, in pixels. Negative values outdent it.
+    pub first_line_indent: i32,
+    /// The indent of the lines of each paragraph following the first from the left margin, in pixels.
+    pub hanging_indent: i32,
+    /// The space left before every line, in pixels.
+    pub left_margin: i32,
+    /// The space left after every line within the maximum width, in pixels.
+    pub right_margin: i32,
+    /// The number of lines, at least one, after which the layout stops, the rest of the text being left out.
+    pub max_lines: Option<usize>,
+}
+
+impl LayoutOptions {
+    /// Creates options without wrapping nor extra spacing.
+    pub fn new() -> Self {
+	Self::default()
+    }
+
+    /// Wraps lines longer than `max_width` pixels.
+    pub fn with_max_width(mut self, max_width: u32) -> Self {
+	self.max_width = Some(max_width);
+	self
+    }
+
+    /// Sets the space added after each character.
+    pub fn with_letter_spacing(mut self, letter_spacing: i32) -> Self {
+	self.letter_spacing = letter_spacing;
+	self
+    }
+
+    /// Sets the space added after each space character.
+    pub fn with_word_spacing(mut self, word_spacing: i32) -> Self {
+	self.word_spacing = word_spacing;
+	self
+    }
+
+    /// Overrides the line height of the font.
+    pub fn with_line_height(mut self, line_height: LineHeight) -> Self {
+	self.line_height = line_height;
+	self
+    }
+
+    /// Sets the space added after each line break.
+    pub fn with_paragraph_spacing(mut self, paragraph_spacing: i32) -> Self {
+	self.paragraph_spacing = paragraph_spacing;
+	self
+    }
+
+    /// Sets the positions tabs advance to.
+    pub fn with_tab_stops(mut self, tab_stops: TabStops) -> Self {
+	self.tab_stops = tab_stops;
+	self
+    }
+
+    /// Sets whether the lines ended by a wrap are justified. Only applies with a maximum width.
+    pub fn with_justify(mut self, justify: bool) -> Self {
+	self.justify = justify;
+	self
+    }
+
+    /// Breaks the words overflowing a line at the hyphenation points found by `hyphenator`. Only applies with a maximum width.
+    pub fn with_hyphenator<H: Hyphenator + Send + Sync + 'static>(mut self, hyphenator: H) -> Self {
+	self.hyphenation = Some(Hyphenation::new(hyphenator));
+	self
+    }
+
+    /// Sets the indent of the first line of each paragraph.
+    pub fn with_first_line_indent(mut self, first_line_indent: i32) -> Self {
+	self.first_line_indent = first_line_indent;
+	self
+    }
+
+    /// Sets the indent of the lines of each paragraph following the first.
+    pub fn with_hanging_indent(mut self, hanging_indent: i32) -> Self {
+	self.hanging_indent = hanging_indent;
+	self
+    }
+
+    /// Sets the spaces left before and after every line.
+    pub fn with_margins(mut self, left_margin: i32, right_margin: i32) -> Self {
+	self.left_margin = left_margin;
+	self.right_margin = right_margin;
+	self
+    }
+
+    /// Stops the layout after `max_lines` lines.
+    pub fn with_max_lines(mut self, max_lines: usize) -> Self {
+	self.max_lines = Some(max_lines);
+	self
+    }
+
+    /// Returns the advance of `c` of the font `font` at `pen_x` with the extra spacing. Line breaks don't advance and tabs advance to the next stop.
+    fn advance<A: GlyphAtlas>(&self, atlas: &A, font: FontId, c: char, pen_x: i32) -> i32 {
+	match c {
+	    '\n' => 0,
+	    '\t' => self.tab_stops.next_stop(atlas, font, pen_x) - pen_x,
+	    SOFT_HYPHEN => 0,
+	    ' ' | NO_BREAK_SPACE => atlas.glyph_advance(font, c) + self.letter_spacing + self.word_spacing,
+	    c => atlas.glyph_advance(font, c) + self.letter_spacing,
+	}
+    }
+
+    /// Returns the advance of `c`, at byte `index` of the grapheme cluster `grapheme`, at `pen_x`. A cluster the atlas draws as
+    /// a single glyph, like an emoji ZWJ sequence, advances by it. Otherwise only its first character advances, the others
+    /// being overlaid on it, except for emoji joined by a ZWJ which are drawn side by side.
+    fn cluster_advance<A: GlyphAtlas>(&self, atlas: &A, font: FontId, grapheme: &str, index: usize, c: char, pen_x: i32) -> i32 {
+	let sequence = || (grapheme.len() > c.len_utf8()).then(|| atlas.sequence_glyph(font, grapheme)).flatten();
+
+	if index == 0 {
+	    return match sequence() {
+		Some(entry) => entry.metrics().advance + self.letter_spacing,
+		None => self.advance(atlas, font, c, pen_x),
+	    };
+	}
+
+	let joined = grapheme[..index].ends_with(ZERO_WIDTH_JOINER) && Script::of(c) == Script::Emoji;
+	match joined && sequence().is_none() {
+	    true => self.advance(atlas, font, c, pen_x),
+	    false => 0,
+	}
+    }
+}
+
+/// A range of text and the area it covers once laid out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextBoundary {
+    pub range: Range<usize>,
+    pub line: usize,
+    pub rectangle: LayoutRect,
+}
+
+impl TextBoundary {
+    /// Creates a boundary from its byte range, line index and rectangle.
+    pub fn new(range: Range<usize>, line: usize, rectangle: LayoutRect) -> Self {
+	Self { range, line, rectangle }
+    }
+}
+
+/// A piece of text between two break opportunities, a mandatory break being kept as trailing whitespace.
+struct Segment {
+    /// Byte range of the word, without its trailing whitespace.
+    word: Range<usize>,
+    /// Byte range of the whole segment, trailing whitespace included.
+    range: Range<usize>,
+}
+
+/// Splits the text at each break opportunity, after whitespace other than no-break spaces and after soft hyphens.
+/// These are the only ones: the Unicode line breaking rules (UAX #14) aren't applied, so text without spaces, like Chinese or
+/// Thai, is never broken and punctuation stays with the word it touches. The last segment is always present, even if empty.
+fn segments(text: &str) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut start = 0;
+    let mut word_end = None;
+
+    for (i, c) in text.char_indices() {
+	if c == '\n' {
+	    segments.push(Segment {
+		word: start..word_end.unwrap_or(i),
+		range: start..i + 1,
+	    });
+	    start = i + 1;
+	    word_end = None;
+	} else if is_breaking_space(c) {
+	    word_end.get_or_insert(i);
+	} else {
+	    if let Some(end) = word_end {
+		segments.push(Segment {
+		    word: start..end,
+		    range: start..i,
+		});
+		start = i;
+		word_end = None;
+	    }
+
+	    if c == SOFT_HYPHEN {
+		let end = i + c.len_utf8();
+		segments.push(Segment {
+		    word: start..end,
+		    range: start..end,
+		});
+		start = end;
+	    }
+	}
+    }
+
+    segments.push(Segment {
+	word: start..word_end.unwrap_or(text.len()),
+	range: start..text.len(),
+    });
+
+    segments
+}
+
+/// Stretches the lines ended by a wrap to `right_edge` by widening their gaps between words evenly, the last line of each
+/// paragraph keeping its width. Spaces and no-break spaces before the trailing whitespace of a line are gaps.
+fn justify(glyphs: &mut [GlyphPlacement], lines: &mut [TextBoundary], words: &mut [TextBoundary], right_edge: i32) {
+    for line in 0..lines.len().saturating_sub(1) {
+	let line_end = (lines[line].rectangle.x + lines[line].rectangle.width) as i32;
+	let is_gap = |glyph: &GlyphPlacement| glyph.line == line && matches!(glyph.c, ' ' | NO_BREAK_SPACE) && glyph.x < line_end;
+	let gaps = glyphs.iter().filter(|glyph| is_gap(glyph)).count() as i32;
+	let extra = right_edge - line_end;
+
+	// A line ending where the next one starts was wrapped
+	if lines[line].range.end != lines[line + 1].range.start || gaps == 0 || extra <= 0 {
+	    continue;
+	}
+
+	let mut gap = 0;
+	let mut shift = 0;
+	for glyph in glyphs.iter_mut().filter(|glyph| glyph.line == line) {
+	    let widened = is_gap(glyph);
+	    glyph.x += shift;
+
+	    if widened {
+		gap += 1;
+		let stretch = extra * gap / gaps - extra * (gap - 1) / gaps;
+		glyph.advance += stretch;
+		shift += stretch;
+	    }
+	}
+
+	for word in words.iter_mut().filter(|word| word.line == line) {
+	    if let Some(glyph) = glyphs.iter().find(|glyph| glyph.index == word.range.start) {
+		word.rectangle.x = glyph.x as f32;
+	    }
+	}
+	lines[line].rectangle.width = right_edge as f32 - lines[line].rectangle.x;
+    }
+}
+
+/// Returns true if `c` is a letter of a script written right to left, like Hebrew or Arabic.
+fn is_right_to_left(c: char) -> bool {
+    matches!(c as u32, 0x5be | 0x5c0 | 0x5c3 | 0x5c6 | 0x5d0..=0x5ff | 0x608 | 0x60b | 0x60d | 0x61b..=0x64a | 0x66d..=0x66f | 0x671..=0x6d5 | 0x6e5 | 0x6e6)
+	|| matches!(c as u32, 0x6ee | 0x6ef | 0x6fa..=0x8ff | 0xfb1d..=0xfdff | 0xfe70..=0xfefe | 0x10800..=0x10fff | 0x1e800..=0x1efff)
+}
+
+/// Returns true if `c` is a digit, which keeps numbers left to right inside right to left runs.
+fn is_digit(c: char) -> bool {
+    c.is_ascii_digit() || matches!(c as u32, 0x660..=0x669 | 0x6f0..=0x6f9)
+}
+
+/// Returns the left and right edges of the advances of `glyphs`.
+fn glyph_extent<'a, I: IntoIterator<Item = &'a GlyphPlacement>>(glyphs: I) -> (i32, i32) {
+    glyphs.into_iter().fold((i32::MAX, i32::MIN), |(left, right), glyph| (left.min(glyph.x), right.max(glyph.x + glyph.advance)))
+}
+
+/// Mirrors the grapheme clusters of `glyphs` starting at each of `bounds` but the last, where the last one ends, within the
+/// extent they cover together.
+fn mirror_clusters(glyphs: &mut [GlyphPlacement], bounds: &[usize]) {
+    let (left, right) = glyph_extent(&glyphs[bounds[0]..bounds[bounds.len() - 1]]);
+
+    for cluster in bounds.windows(2) {
+	let cluster_glyphs = &mut glyphs[cluster[0]..cluster[1]];
+	let (cluster_left, cluster_right) = glyph_extent(cluster_glyphs.iter());
+	let shift = left + right - cluster_right - cluster_left;
+	for glyph in cluster_glyphs.iter_mut() {
+	    glyph.x += shift;
+	}
+    }
+}
+
+/// Places the runs of right to left characters of each line from right to left, within the extent they were laid out in, and
+/// moves their words along. Whitespace and digits between two right to left characters of a line belong to their run, the
+/// digits of a number staying left to right. This is only a part of the Unicode bidirectional algorithm: paragraphs are left
+/// to right, and neither embeddings nor mirrored brackets are handled.
+fn place_right_to_left_runs(glyphs: &mut [GlyphPlacement], words: &mut [TextBoundary]) {
+    // The first glyph of each grapheme cluster, then the end of the glyphs
+    let mut bounds: Vec<usize> = (0..glyphs.len())
+	.filter(|&i| i == 0 || (glyphs[i].cluster, glyphs[i].line) != (glyphs[i - 1].cluster, glyphs[i - 1].line))
+	.collect();
+    bounds.push(glyphs.len());
+
+    let mut first = 0;
+    while first + 1 < bounds.len() {
+	let line = glyphs[bounds[first]].line;
+	if !is_right_to_left(glyphs[bounds[first]].c) {
+	    first += 1;
+	    continue;
+	}
+
+	// The run ends with its last right to left cluster before a left to right character or the end of the line
+	let mut last = first;
+	for (next, start) in bounds[..bounds.len() - 1].iter().enumerate().skip(first + 1) {
+	    let glyph = &glyphs[*start];
+	    if glyph.line != line || !(is_right_to_left(glyph.c) || is_digit(glyph.c) || glyph.c.is_whitespace()) {
+		break;
+	    }
+	    if is_right_to_left(glyph.c) {
+		last = next;
+	    }
+	}
+
+	mirror_clusters(glyphs, &bounds[first..=last + 1]);
+	let mut cluster = first;
+	while cluster <= last {
+	    let digits = bounds[cluster..=last].iter().take_while(|start| is_digit(glyphs[**start].c)).count();
+	    if digits > 1 {
+		mirror_clusters(glyphs, &bounds[cluster..=cluster + digits]);
+	    }
+	    for glyph in glyphs[bounds[cluster]..bounds[cluster + digits.max(1)]].iter_mut() {
+		glyph.right_to_left = digits == 0;
+	    }
+	    cluster += digits.max(1);
+	}
+
+	let range = glyphs[bounds[first]].cluster..glyphs[bounds[last + 1] - 1].index + 1;
+	for word in words.iter_mut().filter(|word| word.line == line && word.range.start < range.end && range.start < word.range.end) {
+	    let (left, right) = glyph_extent(glyphs.iter().filter(|glyph| glyph.line == line && word.range.contains(&glyph.cluster)));
+	    if left <= right {
+		word.rectangle.x = left as f32;
+		word.rectangle.width = (right - left) as f32;
+	    }
+	}
+
+	first = last + 1;
+    }
+}
+
+/// A character placed by the layout, in pixels of layout space.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct GlyphPlacement {
+    /// The index of the glyph in its face, 0 if the atlas has no glyph for the character.
+    pub glyph_id: u32,
+    pub c: char,
+    /// Byte index of the character in the text.
+    pub index: usize,
+    /// Byte index of the first character of the grapheme cluster containing this one.
+    pub cluster: usize,
+    pub font: FontId,
+    /// The pen position on the baseline the glyph is drawn at.
+    pub x: i32,
+    pub y: i32,
+    pub advance: i32,
+    pub line: usize,
+    /// True if the glyph belongs to a run of right to left text, placed from right to left. The caret before its cluster is
+    /// on its right edge.
+    pub right_to_left: bool,
+}
+
+/// Consecutive glyphs of a line sharing font, color and decoration.
+struct DecoratedRun {
+    line: usize,
+    start: i32,
+    end: i32,
+    font: FontId,
+    color: Color,
+    decoration: Decoration,
+}
+
+impl DecoratedRun {
+    /// Returns true if `other` can extend this run, ignoring their extents.
+    fn continues_with(&self, other: &DecoratedRun) -> bool {
+	self.line == other.line && self.font == other.font && self.color == other.color && self.decoration == other.decoration
+    }
+}
+
+/// A paragraph of text laid out with the metrics of a font atlas, wrapped at word boundaries.
+pub struct TextLayout {
+    glyphs: Vec<GlyphPlacement>,
+    lines: Vec<TextBoundary>,
+    words: Vec<TextBoundary>,
+    /// Distance from the top of each line to its baseline.
+    baselines: Vec<i32>,
+    /// Colors of the spans of a rich text, used when none are given to the generators.
+    colors: Vec<(Range<usize>, Color)>,
+    /// Decorations of the spans of a rich text.
+    decorations: Vec<(Range<usize>, Decoration)>,
+    /// The grapheme clusters drawn as a single glyph of the atlas, by byte index.
+    sequences: HashMap<usize, String>,
+    /// The length of the start of the text laid out, and whether text was left out after it.
+    consumed: usize,
+    clamped: bool,
+    config: LayoutConfig,
+}
+
+impl TextLayout {
+    /// Lays out `text` using the glyphs of `atlas`, wrapping lines longer than `max_width` pixels.
+    pub fn new<A: GlyphAtlas>(text: &str, atlas: &A, max_width: Option<u32>) -> Self {
+	Self::with_options(text, atlas, &LayoutOptions { max_width, ..LayoutOptions::default() })
+    }
+
+    /// Lays out `text` using the glyphs of `atlas` and the given options.
+    pub fn with_options<A: GlyphAtlas>(text: &str, atlas: &A, options: &LayoutOptions) -> Self {
+	Self::with_fonts(text, atlas, options, &[])
+    }
+
+    /// Lays out `text` using the glyphs of `atlas`, each script run with the first font of the fallback chain `fonts` supporting it.
+    pub fn with_font_chain<A: GlyphAtlas>(text: &str, atlas: &A, options: &LayoutOptions, fonts: &[FontId]) -> Self {
+	Self::with_fonts(text, atlas, options, &select_fonts(text, atlas, fonts))
+    }
+
+    /// Lays out the spans of `rich_text` as one paragraph, each with the font and color of its style resolved by `theme`.
+    /// The size of a span is the size its font was rendered at in `atlas`.
+    pub fn from_rich_text<A: GlyphAtlas>(rich_text: &RichText, theme: &dyn Theme, atlas: &A, options: &LayoutOptions) -> Self {
+	let styles = rich_text.resolve(theme);
+	let fonts: Vec<(Range<usize>, FontId)> = styles.iter().map(|(range, style)| (range.clone(), style.font)).collect();
+
+	let mut layout = Self::with_fonts(&rich_text.text(), atlas, options, &fonts);
+	layout.colors = styles.iter().map(|(range, style)| (range.clone(), style.color)).collect();
+	layout.decorations = styles.into_iter().map(|(range, style)| (range, style.decoration)).collect();
+	layout
+    }
+
+    /// Lays out `text`, the characters in each byte range of `fonts` using its font and the others the first font of `atlas`.
+    fn with_fonts<A: GlyphAtlas>(text: &str, atlas: &A, options: &LayoutOptions, fonts: &[(Range<usize>, FontId)]) -> Self {
+	// Past the end of a span, the font is still the one of that span, for the empty line ending the text
+	let font_at = |index: usize| {
+	    fonts.iter()
+		.rfind(|(range, _)| range.start <= index && !range.is_empty())
+		.map_or(FontId(0), |(_, font)| *font)
+	};
+	let max_width = options.max_width;
+	let line_rect = |x: i32, width: i32| LayoutRect::new(x as f32, 0.0, width as f32, 0.0);
+	let text_width = |range: Range<usize>| -> i32 {
+	    text[range.clone()].grapheme_indices(true).fold(0, |width, (i, grapheme)| {
+		let font = font_at(range.start + i);
+		grapheme.char_indices().fold(width, |width, (j, c)| width + options.cluster_advance(atlas, font, grapheme, j, c, width))
+	    })
+	};
+
+	let mut glyphs: Vec<GlyphPlacement> = Vec::with_capacity(text.len());
+	let mut sequences = HashMap::new();
+	let mut lines = Vec::new();
+	let mut words = Vec::new();
+
+	// Lines start after the left margin and the indent of their place in the paragraph, and wrap before the right margin
+	let indent = |first_line: bool| options.left_margin + if first_line { options.first_line_indent } else { options.hanging_indent };
+	let right_edge = max_width.map(|max_width| max_width as i32 - options.right_margin);
+
+	let mut line_start = 0;
+	let mut line_x = indent(true);
+	let mut line_end = line_x;
+	let mut pen_x = line_x;
+
+	let mut pending: VecDeque<Segment> = segments(text).into();
+	let mut continued = None;
+	while let Some(mut segment) = pending.pop_front() {
+	    // The lines past the maximum are dropped below, so the text following them isn't laid out
+	    if options.max_lines.is_some_and(|max_lines| lines.len() > max_lines) {
+		break;
+	    }
+
+	    let mut word_width = text_width(segment.word.clone());
+	    let mut hyphenated = false;
+	    // The start of the word the segment was split from by hyphenation, hyphenators being given whole words
+	    let word_start = continued.take().unwrap_or(segment.word.start);
+
+	    // The word overflows, so it is hyphenated to fill the line, or goes on the next line unless it is alone on its line
+	    if let Some(right_edge) = right_edge.filter(|right_edge| pen_x + word_width > *right_edge) {
+		let split = options.hyphenation.as_ref().and_then(|hyphenation| {
+		    hyphenation.points(&text[word_start..segment.word.end]).into_iter().rev().find(|point| {
+			let end = word_start + point;
+			if end <= segment.word.start {
+			    return false;
+			}
+
+			let hyphen_advance = options.advance(atlas, font_at(end - 1), '-', 0);
+			pen_x + text_width(segment.word.start..end) + hyphen_advance <= right_edge
+		    })
+		});
+
+		if let Some(point) = split {
+		    let end = word_start + point;
+		    continued = Some(word_start);
+		    pending.push_front(Segment { word: end..segment.word.end, range: end..segment.range.end });
+		    segment = Segment { word: segment.word.start..end, range: segment.word.start..end };
+		    word_width = text_width(segment.word.clone());
+		    hyphenated = true;
+		} else if pen_x > line_x {
+		    // A line broken at a soft hyphen ends with a hyphen
+		    if let Some(hyphen) = glyphs.last_mut().filter(|glyph| glyph.c == SOFT_HYPHEN && glyph.line == lines.len()) {
+			hyphen.advance = options.advance(atlas, hyphen.font, '-', hyphen.x);
+			hyphen.glyph_id = atlas.glyph(hyphen.font, '-').map_or(0, |entry| entry.glyph_id());
+			line_end = line_end.max(hyphen.x + hyphen.advance);
+		    }
+
+		    lines.push(TextBoundary::new(line_start..segment.range.start, lines.len(), line_rect(line_x, line_end - line_x)));
+		    line_start = segment.range.start;
+		    line_x = indent(false);
+		    line_end = line_x;
+		    pen_x = line_x;
+
+		    // The word may still be hyphenated on its new line
+		    if options.hyphenation.is_some() {
+			continued = Some(word_start);
+			pending.push_front(segment);
+			continue;
+		    }
+		}
+	    }
+
+	    if !segment.word.is_empty() {
+		let word_x = pen_x + text_width(segment.range.start..segment.word.start);
+		words.push(TextBoundary::new(segment.word.clone(), lines.len(), line_rect(word_x, word_width)));
+		line_end = word_x + word_width;
+	    }
+
+	    for (i, grapheme) in text[segment.range.clone()].grapheme_indices(true) {
+		let cluster = segment.range.start + i;
+		let font = font_at(cluster);
+		let sequence = (grapheme.chars().nth(1).is_some()).then(|| atlas.sequence_glyph(font, grapheme)).flatten();
+		if sequence.is_some() {
+		    sequences.insert(cluster, String::from(grapheme));
+		}
+
+		for (j, c) in grapheme.char_indices() {
+		    if c == '\n' {
+			let break_index = cluster + j;
+			lines.push(TextBoundary::new(line_start..break_index, lines.len(), line_rect(line_x, line_end - line_x)));
+			line_start = segment.range.end;
+			line_x = indent(true);
+			line_end = line_x;
+			pen_x = line_x;
+			continue;
+		    }
+
+		    let advance = options.cluster_advance(atlas, font, grapheme, j, c, pen_x);
+		    let glyph_id = match sequence {
+			Some(entry) if j == 0 => entry.glyph_id(),
+			_ => atlas.glyph(font, c).map_or(0, |entry| entry.glyph_id()),
+		    };
+		    glyphs.push(GlyphPlacement { glyph_id, c, index: cluster + j, cluster, font, x: pen_x, y: 0, advance, line: lines.len(), right_to_left: false });
+		    pen_x += advance;
+		}
+	    }
+
+	    // A hyphenated word ends its line with a hyphen, placed like a soft hyphen at a break. It belongs to the last character
+	    // before the break without starting a cluster, so that carets and hit tests ignore it.
+	    if hyphenated {
+		let last = glyphs.last().unwrap();
+		let (index, font) = (last.index, last.font);
+		let advance = options.advance(atlas, font, '-', pen_x);
+		let glyph_id = atlas.glyph(font, '-').map_or(0, |entry| entry.glyph_id());
+		glyphs.push(GlyphPlacement { glyph_id, c: SOFT_HYPHEN, index, cluster: segment.range.end, font, x: pen_x, y: 0, advance, line: lines.len(), right_to_left: false });
+
+		lines.push(TextBoundary::new(line_start..segment.range.end, lines.len(), line_rect(line_x, line_end.max(pen_x + advance) - line_x)));
+		line_start = segment.range.end;
+		line_x = indent(false);
+		line_end = line_x;
+		pen_x = line_x;
+	    }
+	}
+
+	lines.push(TextBoundary::new(line_start..text.len(), lines.len(), line_rect(line_x, line_end - line_x)));
+
+	// The text is clamped at the start of the first line past the maximum
+	let max_lines = options.max_lines.unwrap_or(usize::MAX).max(1);
+	let consumed = lines.get(max_lines).map_or(text.len(), |line| line.range.start);
+	lines.truncate(max_lines);
+	glyphs.retain(|glyph| glyph.line < max_lines);
+	words.retain(|word| word.line < max_lines);
+
+	if let Some(right_edge) = right_edge.filter(|_| options.justify) {
+	    justify(&mut glyphs, &mut lines, &mut words, right_edge);
+	}
+	place_right_to_left_runs(&mut glyphs, &mut words);
+
+	// A line is as tall as the tallest font it contains, and all its glyphs share its baseline
+	let mut line_metrics: Vec<LineMetrics> = lines.iter().map(|line| atlas.font_metrics(font_at(line.range.start))).collect();
+	for glyph in glyphs.iter() {
+	    let font_metrics = atlas.font_metrics(glyph.font);
+	    let metrics = &mut line_metrics[glyph.line];
+	    metrics.ascender = metrics.ascender.max(font_metrics.ascender);
+	    metrics.line_height = metrics.line_height.max(font_metrics.line_height);
+	}
+
+	let mut baselines = Vec::with_capacity(lines.len());
+	let mut line_top = 0;
+	for (index, metrics) in line_metrics.iter().enumerate() {
+	    let line_height = options.line_height.resolve(metrics.line_height);
+
+	    // The difference with the font line height is split above and below the glyphs
+	    baselines.push(metrics.ascender + (line_height - metrics.line_height) / 2);
+	    lines[index].rectangle.y = line_top as f32;
+	    lines[index].rectangle.height = line_height as f32;
+	    line_top += line_height;
+
+	    // A line ending before the next one starts was ended by a line break
+	    if lines.get(index + 1).is_some_and(|next| lines[index].range.end < next.range.start) {
+		line_top += options.paragraph_spacing;
+	    }
+	}
+
+	for glyph in glyphs.iter_mut() {
+	    glyph.y = lines[glyph.line].rectangle.y as i32 + baselines[glyph.line];
+	}
+
+	for word in words.iter_mut() {
+	    word.rectangle.y = lines[word.line].rectangle.y;
+	    word.rectangle.height = lines[word.line].rectangle.height;
+	}
+
+	Self {
+	    glyphs,
+	    lines,
+	    words,
+	    baselines,
+	    colors: Vec::new(),
+	    decorations: Vec::new(),
+	    sequences,
+	    consumed,
+	    clamped: consumed < text.len(),
+	    config: LayoutConfig::default(),
+	}
+    }
+
+    /// Returns the length in bytes of the start of the text that was laid out, the whole text unless the layout was clamped
+    /// by a maximum number of lines.
+    pub fn consumed(&self) -> usize {
+	self.consumed
+    }
+
+    /// Returns true if lines past the maximum number of lines were left out, to show that there is more text.
+    pub fn is_clamped(&self) -> bool {
+	self.clamped
+    }
+
+    /// Sets the coordinate conventions of the generated vertices and instances.
+    pub fn with_config(mut self, config: LayoutConfig) -> Self {
+	self.config = config;
+	self
+    }
+
+    /// Returns the placement of each character of the text except line breaks, in text order.
+    /// The characters following the first one of a grapheme cluster are placed over it with no advance.
+    /// The position of a placement in the slice is its glyph index, used by `prefix_vertices`, and `index` its byte offset.
+    pub fn glyphs(&self) -> &[GlyphPlacement] {
+	&self.glyphs
+    }
+
+    /// Returns the number of lines of the paragraph.
+    pub fn line_count(&self) -> usize {
+	self.lines.len()
+    }
+
+    /// Returns the byte range and area of each line, trailing whitespace excluded from the area.
+    pub fn line_boundaries(&self) -> &[TextBoundary] {
+	&self.lines
+    }
+
+    /// Returns the byte range and area of each word, in text order. Words are split at whitespace and soft hyphens only, like
+    /// lines are wrapped, so a run of text without spaces is a single word.
+    pub fn word_boundaries(&self) -> &[TextBoundary] {
+	&self.words
+    }
+
+    /// Returns the word containing the byte at `byte_index`, if any.
+    pub fn word_at(&self, byte_index: usize) -> Option<&TextBoundary> {
+	self.words.iter().find(|word| word.range.contains(&byte_index))
+    }
+
+    /// Returns the caret placed before the grapheme cluster containing the byte at `byte_index`, as a zero width rectangle of the line height.
+    /// An index past the end of the text places the caret at the end of the last line.
+    pub fn caret_position(&self, byte_index: usize) -> LayoutRect {
+	let text_len = self.lines.last().map_or(0, |line| line.range.end);
+	let byte_index = self.glyphs.iter()
+	    .find(|glyph| glyph.index <= byte_index && byte_index < glyph.index + glyph.c.len_utf8())
+	    .map_or(byte_index.min(text_len), |glyph| glyph.cluster);
+
+	let (line, x) = match self.glyphs.iter().find(|glyph| glyph.index == byte_index) {
+	    Some(glyph) if glyph.right_to_left => (glyph.line, glyph.x + glyph.advance),
+	    Some(glyph) => (glyph.line, glyph.x),
+	    // The index is a line break or the end of the text, so the caret goes after the previous cluster of the same line
+	    None => match self.glyphs.iter().rev().find(|glyph| glyph.index < byte_index) {
+		Some(glyph) if byte_index <= self.lines[glyph.line].range.end && glyph.right_to_left => {
+		    let (left, _) = glyph_extent(self.glyphs.iter().filter(|other| other.cluster == glyph.cluster && other.line == glyph.line));
+		    (glyph.line, left)
+		},
+		Some(glyph) if byte_index <= self.lines[glyph.line].range.end => (glyph.line, glyph.x + glyph.advance),
+		_ => {
+		    let line = self.lines.iter()
+			.position(|line| line.range.start <= byte_index && byte_index <= line.range.end)
+			.unwrap_or(self.lines.len() - 1);
+		    (line, self.lines[line].rectangle.x as i32)
+		}
+	    },
+	};
+
+	let line_rectangle = &self.lines[line].rectangle;
+	LayoutRect::new(x as f32, line_rectangle.y, 0.0, line_rectangle.height)
+    }
+
+    /// Returns the byte index of the caret position closest to the point (`x`, `y`) of layout space, always at a grapheme cluster boundary.
+    /// Points above or below the paragraph hit the first or last line.
+    pub fn hit_test(&self, x: f32, y: f32) -> usize {
+	// Points in the spacing between paragraphs hit the line above
+	let line = self.lines.iter().rposition(|line| line.rectangle.y <= y).unwrap_or(0);
+
+	let line_glyphs: Vec<&GlyphPlacement> = self.glyphs.iter().filter(|glyph| glyph.line == line && glyph.index == glyph.cluster).collect();
+	let mut visual_order = line_glyphs.clone();
+	visual_order.sort_by_key(|glyph| glyph.x);
+
+	// A wrapped line ends with the whitespace it was broken at, and the caret stays before it to remain on this line
+	let wrapped = line + 1 < self.lines.len() && self.lines[line].range.end == self.lines[line + 1].range.start;
+	let after = |index: usize| match line_glyphs.iter().position(|glyph| glyph.index == index) {
+	    Some(position) if position + 1 < line_glyphs.len() => line_glyphs[position + 1].index,
+	    _ if wrapped => index,
+	    _ => self.lines[line].range.end,
+	};
+
+	// The caret before a right to left cluster is on its right edge, so its left half hits the caret after it
+	for glyph in visual_order.iter() {
+	    if x < glyph.x as f32 + glyph.advance as f32 / 2.0 {
+		return match glyph.right_to_left {
+		    true => after(glyph.index),
+		    false => glyph.index,
+		};
+	    }
+	    if x < (glyph.x + glyph.advance) as f32 {
+		return match glyph.right_to_left {
+		    true => glyph.index,
+		    false => after(glyph.index),
+		};
+	    }
+	}
+
+	match visual_order.last() {
+	    Some(glyph) if glyph.right_to_left => glyph.index,
+	    Some(glyph) => after(glyph.index),
+	    None => self.lines[line].range.end,
+	}
+    }
+
+    /// Returns the rectangles of layout space covering the glyphs of the byte `range`, one per line and visually contiguous run,
+    /// so that a range crossing the edge of a run placed right to left is covered by several rectangles.
+    pub fn selection_rects(&self, range: Range<usize>) -> Vec<LayoutRect> {
+	let mut rects = Vec::new();
+
+	for line_glyphs in self.glyphs.chunk_by(|a, b| a.line == b.line) {
+	    let line = line_glyphs[0].line;
+	    let line_rectangle = &self.lines[line].rectangle;
+	    let run_rect = |start: i32, end: i32| {
+		LayoutRect::new(start as f32, line_rectangle.y, (end - start) as f32, line_rectangle.height)
+	    };
+	    let mut visual_order: Vec<&GlyphPlacement> = line_glyphs.iter().filter(|glyph| glyph.index == glyph.cluster).collect();
+	    visual_order.sort_by_key(|glyph| glyph.x);
+
+	    let mut run: Option<(i32, i32)> = None;
+	    for glyph in visual_order {
+		if range.contains(&glyph.cluster) {
+		    let start = run.map_or(glyph.x, |(start, _)| start);
+		    run = Some((start, glyph.x + glyph.advance));
+		} else if let Some((start, end)) = run.take() {
+		    rects.push(run_rect(start, end));
+		}
+	    }
+
+	    if let Some((start, end)) = run {
+		rects.push(run_rect(start, end));
+	    }
+	}
+
+	rects
+    }
+
+    /// Generates the vertices of the paragraph, (`x`, `y`) being its anchor, the top-left corner by default.
+    /// The glyphs of a rich text get the color of their span, the others are white.
+    pub fn generate_buffers<A: GlyphAtlas>(&self, atlas: &A, x: i32, y: i32) -> Vec<TextVertex> {
+	self.generate_colored_buffers(atlas, x, y, &self.colors)
+    }
+
+    /// Same as `generate_buffers`, the vertices of the glyphs in each byte range of `colors` getting its color.
+    /// Glyphs outside of all ranges are white.
+    pub fn generate_colored_buffers<A: GlyphAtlas>(&self, atlas: &A, x: i32, y: i32, colors: &[(Range<usize>, Color)]) -> Vec<TextVertex> {
+	let mut vertex_buffer = Vec::<TextVertex>::with_capacity(self.glyphs.len() * 6);
+
+	self.for_each_glyph(atlas, x, y, |index, entry, x, y| {
+	    push_glyph_quad(&mut vertex_buffer, entry, atlas.page_size(), x, y, color_at(colors, index), &self.config)
+	});
+
+	vertex_buffer
+    }
+
+    /// Same as `generate_buffers` for the first `glyph_count` placements of `glyphs` only, for typewriter animations revealing
+    /// the text glyph by glyph without laying it out again.
+    pub fn prefix_vertices<A: GlyphAtlas>(&self, atlas: &A, x: i32, y: i32, glyph_count: usize) -> Vec<TextVertex> {
+	let mut vertex_buffer = Vec::<TextVertex>::with_capacity(glyph_count.min(self.glyphs.len()) * 6);
+
+	self.for_each_glyph_of(glyph_count, atlas, x, y, |index, entry, x, y| {
+	    push_glyph_quad(&mut vertex_buffer, entry, atlas.page_size(), x, y, color_at(&self.colors, index), &self.config)
+	});
+
+	vertex_buffer
+    }
+
+    /// Returns the number of vertices `generate_buffers` emits for the first `glyph_count` placements of `glyphs`, also the
+    /// number of indices `generate_indexed_buffers` emits for them, so that a prefix of the buffers generated once is drawn.
+    pub fn prefix_vertex_count<A: GlyphAtlas>(&self, atlas: &A, glyph_count: usize) -> usize {
+	let mut quads = 0;
+	self.for_each_glyph_of(glyph_count, atlas, 0, 0, |_, entry, _, _| quads += usize::from(!entry.is_blank()));
+	quads * 6
+    }
+
+    /// Same as `generate_buffers`, but with 4 unique vertices per glyph and an index buffer made of 2 triangles per glyph.
+    pub fn generate_indexed_buffers<A: GlyphAtlas>(&self, atlas: &A, x: i32, y: i32) -> (Vec<TextVertex>, Vec<u32>) {
+	self.generate_colored_indexed_buffers(atlas, x, y, &self.colors)
+    }
+
+    /// Same as `generate_indexed_buffers`, the vertices of the glyphs in each byte range of `colors` getting its color.
+    /// Glyphs outside of all ranges are white.
+    pub fn generate_colored_indexed_buffers<A: GlyphAtlas>(&self, atlas: &A, x: i32, y: i32, colors: &[(Range<usize>, Color)]) -> (Vec<TextVertex>, Vec<u32>) {
+	let mut vertex_buffer = Vec::<TextVertex>::with_capacity(self.glyphs.len() * 4);
+
+	self.for_each_glyph(atlas, x, y, |index, entry, x, y| {
+	    push_indexed_glyph_quad(&mut vertex_buffer, entry, atlas.page_size(), x, y, color_at(colors, index), &self.config)
+	});
+
+	let index_buffer = quad_indices(vertex_buffer.len() / 4);
+	(vertex_buffer, index_buffer)
+    }
+
+    /// Hands the quad of each visible glyph of the paragraph to `emitter`, (`x`, `y`) being its anchor, the top-left corner by default.
+    /// The glyphs of a rich text get the color of their span, the others are white.
+    pub fn emit_vertices<A: GlyphAtlas, E: VertexEmitter>(&self, atlas: &A, x: i32, y: i32, emitter: &mut E) {
+	self.emit_colored_vertices(atlas, x, y, &self.colors, emitter)
+    }
+
+    /// Same as `emit_vertices`, the glyphs in each byte range of `colors` getting its color.
+    /// Glyphs outside of all ranges are white.
+    pub fn emit_colored_vertices<A: GlyphAtlas, E: VertexEmitter>(&self, atlas: &A, x: i32, y: i32, colors: &[(Range<usize>, Color)], emitter: &mut E) {
+	self.for_each_glyph(atlas, x, y, |index, entry, x, y| {
+	    if !entry.is_blank() {
+		let corners = entry.quad(atlas.page_size(), x, y, color_at(colors, index), &self.config);
+		emitter.emit_quad(&GlyphQuad::new(index, &corners));
+	    }
+	});
+    }
+
+    /// Hands the quad of each visible glyph of the paragraph to `emitter`, the first baseline laid along `path` from `offset` along it.
+    /// Each glyph is rotated to the tangent of the path at the middle of its advance, and the following lines keep their distance to
+    /// the first baseline. Glyphs whose middle falls outside of the path are left out.
+    pub fn emit_vertices_on_path<A: GlyphAtlas, E: VertexEmitter>(&self, atlas: &A, path: &TextPath, offset: f32, emitter: &mut E) {
+	let first_baseline = self.glyphs.first().map_or(0, |glyph| glyph.y);
+
+	for glyph in self.glyphs.iter() {
+	    let entry = match self.glyph_entry(atlas, glyph) {
+		Some(entry) if !entry.is_blank() => entry,
+		_ => continue,
+	    };
+	    let middle = glyph.advance as f32 / 2.0;
+	    let (origin, [tangent_x, tangent_y]) = match path.sample(offset + glyph.x as f32 + middle) {
+		Some(sample) => sample,
+		None => continue,
+	    };
+
+	    // The quad is computed around the middle of the glyph on the first baseline, then rotated along the tangent
+	    let shift = glyph.y - first_baseline;
+	    let y = match self.config.y_axis {
+		YAxis::Up => -shift,
+		YAxis::Down => shift,
+	    };
+	    let corners = entry.quad(atlas.page_size(), 0, y, color_at(&self.colors, glyph.index), &self.config);
+	    let mut quad = GlyphQuad::new(glyph.index, &corners);
+	    for position in quad.positions.iter_mut() {
+		let [x, y] = [position[0] - middle, position[1]];
+		*position = [origin[0] + x * tangent_x - y * tangent_y, origin[1] + x * tangent_y + y * tangent_x];
+	    }
+	    if self.config.pixel_snap {
+		quad = quad.snapped(1.0);
+	    }
+
+	    emitter.emit_quad(&quad);
+	}
+    }
+
+    /// Same as `emit_vertices_on_path`, with 4 unique vertices per glyph and an index buffer made of 2 triangles per glyph.
+    pub fn generate_indexed_buffers_on_path<A: GlyphAtlas>(&self, atlas: &A, path: &TextPath, offset: f32) -> (Vec<TextVertex>, Vec<u32>) {
+	let mut vertex_buffer = Vec::<TextVertex>::with_capacity(self.glyphs.len() * 4);
+	self.emit_vertices_on_path(atlas, path, offset, &mut |quad: &GlyphQuad| vertex_buffer.extend_from_slice(&quad.vertices()));
+
+	let index_buffer = quad_indices(vertex_buffer.len() / 4);
+	(vertex_buffer, index_buffer)
+    }
+
+    /// Generates one instance per visible glyph of the paragraph, drawn in `color`, (`x`, `y`) being its anchor, the top-left corner by default.
+    pub fn generate_instances<A: GlyphAtlas>(&self, atlas: &A, x: i32, y: i32, color: Color) -> Vec<GlyphInstance> {
+	self.generate_colored_instances(atlas, x, y, &[(0..usize::MAX, color)])
+    }
+
+    /// Same as `generate_instances`, the glyphs in each byte range of `colors` getting its color.
+    /// Glyphs outside of all ranges are white.
+    pub fn generate_colored_instances<A: GlyphAtlas>(&self, atlas: &A, x: i32, y: i32, colors: &[(Range<usize>, Color)]) -> Vec<GlyphInstance> {
+	let mut instances = Vec::<GlyphInstance>::with_capacity(self.glyphs.len());
+
+	self.for_each_glyph(atlas, x, y, |index, entry, x, y| {
+	    push_glyph_instance(&mut instances, entry, atlas.page_size(), x, y, color_at(colors, index), &self.config)
+	});
+
+	instances
+    }
+
+    /// Generates the underlines and strikeouts of the spans of a rich text, 6 vertices per line drawn, (`x`, `y`) being the anchor of the paragraph.
+    /// A line is drawn under or across each run of glyphs sharing font, color and decoration, with the metrics of that font.
+    /// The quads don't sample the atlas, their uvs are all zero and they are meant to be filled with the color of their vertices.
+    pub fn generate_decoration_buffers<A: GlyphAtlas>(&self, atlas: &A, x: i32, y: i32) -> Vec<TextVertex> {
+	let mut vertex_buffer = Vec::<TextVertex>::new();
+	let (x, y) = self.top_left(x, y);
+
+	for run in self.decorated_runs() {
+	    let metrics = atlas.font_metrics(run.font);
+	    let baseline = self.lines[run.line].rectangle.y as i32 + self.baselines[run.line];
+
+	    let mut push_stroke = |position: i32, thickness: i32| {
+		// Top of the stroke in layout space, where y points down
+		let top = baseline - position - thickness / 2;
+		let (bottom, top) = match self.config.y_axis {
+		    YAxis::Up => (y - top - thickness, y - top),
+		    YAxis::Down => (y + top + thickness, y + top),
+		};
+		push_solid_quad(&mut vertex_buffer, (x + run.start) as f32, bottom as f32, (x + run.end) as f32, top as f32, run.color);
+	    };
+
+	    if run.decoration.underline {
+		push_stroke(metrics.underline_position, metrics.underline_thickness);
+	    }
+	    if run.decoration.strikethrough {
+		push_stroke(metrics.strikeout_position, metrics.strikeout_thickness);
+	    }
+	}
+
+	vertex_buffer
+    }
+
+    /// Returns the runs of decorated glyphs, trailing whitespace excluded.
+    fn decorated_runs(&self) -> Vec<DecoratedRun> {
+	let mut runs: Vec<DecoratedRun> = Vec::new();
+
+	for glyph in self.glyphs.iter().filter(|glyph| glyph.index == glyph.cluster) {
+	    let decoration = self.decorations.iter()
+		.find(|(range, _)| range.contains(&glyph.index))
+		.map_or_else(Decoration::default, |(_, decoration)| *decoration);
+	    if decoration == Decoration::default() {
+		continue;
+	    }
+
+	    let color = color_at(&self.colors, glyph.index);
+	    let line_rectangle = &self.lines[glyph.line].rectangle;
+	    let end = (glyph.x + glyph.advance).min((line_rectangle.x + line_rectangle.width) as i32);
+
+	    let run = DecoratedRun { line: glyph.line, start: glyph.x, end, font: glyph.font, color, decoration };
+	    match runs.last_mut() {
+		Some(last) if last.end == run.start && last.continues_with(&run) => last.end = run.end,
+		_ if run.start < run.end => runs.push(run),
+		_ => {},
+	    }
+	}
+
+	runs
+    }
+
+    /// Returns the entry of `atlas` drawing `glyph`, one of the placements of the layout, or None if nothing is drawn for it.
+    /// A soft hyphen is only drawn, as a hyphen, at the end of a line broken at it, and a cluster drawn as a single glyph,
+    /// like an emoji ZWJ sequence, by its first character.
+    pub fn glyph_entry<'a, A: GlyphAtlas>(&self, atlas: &'a A, glyph: &GlyphPlacement) -> Option<&'a FontAtlasEntry> {
+	if let Some(sequence) = self.sequences.get(&glyph.cluster) {
+	    return (glyph.index == glyph.cluster).then(|| atlas.sequence_glyph(glyph.font, sequence)).flatten();
+	}
+
+	match glyph.c {
+	    SOFT_HYPHEN if glyph.advance == 0 => None,
+	    SOFT_HYPHEN => atlas.glyph(glyph.font, '-'),
+	    c => atlas.glyph(glyph.font, c),
+	}
+    }
+
+    /// Returns the size of the paragraph: the right end of its widest line and the bottom of its last line.
+    pub fn size(&self) -> (i32, i32) {
+	let width = self.lines.iter().fold(0.0f32, |width, line| width.max(line.rectangle.x + line.rectangle.width));
+	let height = self.lines.last().map_or(0.0, |line| line.rectangle.y + line.rectangle.height);
+
+	(width as i32, height as i32)
+    }
+
+    /// Returns the top-left corner of the paragraph whose anchor is at (`x`, `y`).
+    fn top_left(&self, x: i32, y: i32) -> (i32, i32) {
+	let (width, height) = self.size();
+	let baseline = self.baselines.first().copied().unwrap_or(0);
+	let (anchor_x, anchor_y) = self.config.anchor.position(width, height, baseline);
+
+	match self.config.y_axis {
+	    YAxis::Up => (x - anchor_x, y + anchor_y),
+	    YAxis::Down => (x - anchor_x, y - anchor_y),
+	}
+    }
+
+    /// Calls `f` with the byte index of each glyph, its atlas entry and the pen position it is drawn at, the paragraph anchored at (`x`, `y`).
+    fn for_each_glyph<A: GlyphAtlas, F>(&self, atlas: &A, x: i32, y: i32, f: F) where F: FnMut(usize, &FontAtlasEntry, i32, i32) {
+	self.for_each_glyph_of(self.glyphs.len(), atlas, x, y, f)
+    }
+
+    /// Same as `for_each_glyph` for the first `glyph_count` placements only.
+    fn for_each_glyph_of<A: GlyphAtlas, F>(&self, glyph_count: usize, atlas: &A, x: i32, y: i32, mut f: F) where F: FnMut(usize, &FontAtlasEntry, i32, i32) {
+	let (x, y) = self.top_left(x, y);
+
+	for glyph in self.glyphs.iter().take(glyph_count) {
+	    let entry = match self.glyph_entry(atlas, glyph) {
+		Some(entry) => entry,
+		None => continue,
+	    };
+
+	    let baseline = match self.config.y_axis {
+		YAxis::Up => y - glyph.y,
+		YAxis::Down => y + glyph.y,
+	    };
+	    f(glyph.index, entry, x + glyph.x, baseline);
+	}
+    }
+}
@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+
+use super::atlas::GlyphAtlas;
+use super::layout::{LayoutOptions, TextLayout};
+use super::multi_atlas::FontId;
+
+/// The style of the texts laid out by a LayoutCache: their font, the size of the atlas and the width they are wrapped at.
+type StyleKey = (FontId, u32, Option<u32>);
+
+/// A layout kept by a LayoutCache, with whether it was requested since the last eviction.
+struct CachedLayout {
+    layout: TextLayout,
+    used: bool,
+}
+
+/// Keeps the layouts of texts drawn again and again, like static labels, so that they aren't laid out every frame.
+/// Layouts are keyed by text, font, size and maximum width, the other options being those of the cache. The layouts made with
+/// an atlas are dropped once the generation of the atlas of their size changes, when glyphs are added, the atlas is repacked
+/// or another atlas is given, generations being unique across atlases.
+pub struct LayoutCache {
+    options: LayoutOptions,
+    layouts: HashMap<StyleKey, HashMap<String, CachedLayout>>,
+    /// The generation of the atlas of each size the cached layouts were made with.
+    generations: HashMap<u32, u64>,
+}
+
+impl LayoutCache {
+    /// Creates an empty cache laying out texts with `options`, their maximum width being given with each text.
+    pub fn new(options: LayoutOptions) -> Self {
+	Self {
+	    options,
+	    layouts: HashMap::new(),
+	    generations: HashMap::new(),
+	}
+    }
+
+    /// Returns the layout of `text` with the font `font` of `atlas`, the atlas of the glyphs at `size`, wrapped at
+    /// `max_width`. The text is only laid out if it wasn't with the same style and generation of the atlas.
+    pub fn layout<A: GlyphAtlas>(&mut self, atlas: &A, text: &str, font: FontId, size: u32, max_width: Option<u32>) -> &TextLayout {
+	let generation = atlas.generation();
+	if self.generations.insert(size, generation).is_some_and(|cached| cached != generation) {
+	    self.layouts.retain(|(_, layout_size, _), _| *layout_size != size);
+	}
+
+	let texts = self.layouts.entry((font, size, max_width)).or_default();
+	if !texts.contains_key(text) {
+	    let options = LayoutOptions { max_width, ..self.options.clone() };
+	    let layout = TextLayout::with_font_chain(text, atlas, &options, &[font]);
+	    texts.insert(String::from(text), CachedLayout { layout, used: false });
+	}
+
+	let cached = texts.get_mut(text).unwrap();
+	cached.used = true;
+	&cached.layout
+    }
+
+    /// Drops the layouts that weren't requested since the last call, to be called once per frame for instance.
+    pub fn evict_unused(&mut self) {
+	for texts in self.layouts.values_mut() {
+	    texts.retain(|_, cached| std::mem::take(&mut cached.used));
+	}
+	self.layouts.retain(|_, texts| !texts.is_empty());
+    }
+
+    /// Returns the number of layouts kept.
+    pub fn len(&self) -> usize {
+	self.layouts.values().map(HashMap::len).sum()
+    }
+
+    /// Returns true if no layout is kept.
+    pub fn is_empty(&self) -> bool {
+	self.len() == 0
+    }
+
+    /// Drops all the layouts.
+    pub fn clear(&mut self) {
+	self.layouts.clear();
+	self.generations.clear();
+    }
+}
@@ -1,11 +1,46 @@
-use std::boxed::Box;
 use std::fmt::{Debug, Display};
-use image::{ImageBuffer, Rgb};
 
+use pixels::Pixels;
+
+#[cfg(feature = "ab-glyph")]
+pub mod ab_font;
 pub mod atlas;
+#[cfg(feature = "bevy")]
+pub mod bevy;
+pub mod brush;
+pub mod charset;
+pub mod compress;
+#[cfg(feature = "egui")]
+pub mod egui;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "system-fonts")]
+pub mod font_db;
+#[cfg(feature = "glow")]
+pub mod gl;
+#[cfg(feature = "wgpu")]
+pub mod gpu;
+pub mod layout;
+pub mod layout_cache;
+#[cfg(feature = "freetype")]
+pub mod loader;
+#[cfg(feature = "freetype")]
+pub mod measure;
+pub mod multi_atlas;
+pub mod path;
+pub mod pixels;
+pub mod preview;
+pub mod rasterizer;
+pub mod rich_text;
+pub mod scaled_atlas;
+pub mod script;
+#[cfg(feature = "freetype")]
+pub mod sync;
+#[cfg(feature = "watch")]
+pub mod watch;
 
 /// A rectangle constrained by corner position and sizes
-#[derive(Default, Debug, Copy, Clone)]
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct Rectangle {
     pub top: u32,
     pub left: u32,
@@ -26,98 +61,196 @@ impl Rectangle {
 
     /// Returns true if the two rectangles hase the same sizes.
     pub fn same_size(&self, other: &Rectangle) -> bool {
-	return self.width == other.width && self.height == other.height
+	self.width == other.width && self.height == other.height
+    }
+
+    /// Returns the column right after the rectangle.
+    pub fn right(&self) -> u32 {
+	self.left + self.width
+    }
+
+    /// Returns the row right below the rectangle.
+    pub fn bottom(&self) -> u32 {
+	self.top + self.height
+    }
+
+    /// Returns the number of pixels covered by the rectangle.
+    pub fn area(&self) -> u64 {
+	self.width as u64 * self.height as u64
+    }
+
+    /// Returns true if the rectangle covers no pixel.
+    pub fn is_empty(&self) -> bool {
+	self.width == 0 || self.height == 0
+    }
+
+    /// Returns true if every pixel of the `other` rectangle is covered by the current one, which is always the case of an empty one.
+    pub fn contains(&self, other: &Rectangle) -> bool {
+	other.is_empty()
+	    || (self.left <= other.left && self.top <= other.top && other.right() <= self.right() && other.bottom() <= self.bottom())
+    }
+
+    /// Returns true if the two rectangles cover at least one common pixel.
+    pub fn intersects(&self, other: &Rectangle) -> bool {
+	self.intersection(other).is_some()
+    }
+
+    /// Returns the pixels covered by both rectangles, or None if they have none in common.
+    pub fn intersection(&self, other: &Rectangle) -> Option<Rectangle> {
+	let left = self.left.max(other.left);
+	let top = self.top.max(other.top);
+	let right = self.right().min(other.right());
+	let bottom = self.bottom().min(other.bottom());
+
+	(left < right && top < bottom).then(|| Rectangle::new(top, left, right - left, bottom - top))
+    }
+
+    /// Returns the smallest rectangle covering both rectangles, empty ones being ignored.
+    pub fn union(&self, other: &Rectangle) -> Rectangle {
+	if other.is_empty() {
+	    return *self;
+	}
+	if self.is_empty() {
+	    return *other;
+	}
+
+	let left = self.left.min(other.left);
+	let top = self.top.min(other.top);
+	Rectangle::new(top, left, self.right().max(other.right()) - left, self.bottom().max(other.bottom()) - top)
     }
 }
 
-/// A binary tree node containing rectangles.
+/// A binary tree of rectangles packing the glyphs, its nodes being stored in an arena rather than boxed one by one.
 #[derive(Debug)]
 pub struct Node {
     pub rectangle: Rectangle,
-    pub children: [Option<Box<Node>>; 2],
-    pub occupied: bool
+    slots: Vec<Slot>,
+}
+
+/// A node of the packing tree, pointing at its two children in the arena once split.
+#[derive(Debug)]
+struct Slot {
+    rectangle: Rectangle,
+    children: Option<[usize; 2]>,
+    occupied: bool,
+}
+
+impl Slot {
+    fn new(rectangle: Rectangle) -> Self {
+	Self {
+	    rectangle,
+	    children: None,
+	    occupied: false,
+	}
+    }
 }
 
 impl Node {
-    /// Creates a node containing the given node.
+    /// Creates a tree covering the given rectangle.
     pub fn new(rectangle: Rectangle) -> Self {
 	Self {
 	    rectangle,
-	    children: [None, None],
-	    occupied: false
+	    slots: vec![Slot::new(rectangle)],
 	}
-   }
+    }
 
-    /// Returns true if the given node is a leaf,
+    /// Returns true if the root of the tree wasn't split yet.
     pub fn is_leaf(&self) -> bool {
-	self.children[0].is_none() && self.children[1].is_none()
+	self.slots[0].children.is_none()
+    }
+
+    /// Returns true if a rectangle was inserted exactly in the root of the tree.
+    pub fn occupied(&self) -> bool {
+	self.slots[0].occupied
+    }
+
+    /// Returns the two children of the root once it is split, to walk the tree.
+    pub fn children(&self) -> [Option<NodeRef<'_>>; 2] {
+	NodeRef { slots: &self.slots, index: 0 }.children()
     }
 
     /// Returns a result indicating if the given rectangle were sucessfully inserted in the tree.
+    /// The tree is walked depth first without recursion, trying the first child of each node before the second one.
     pub fn insert(&mut self, rectangle: &Rectangle) -> Result<Rectangle, NodeInsertError> {
-	// If we are in a leaf
-	if self.is_leaf() {
-	    // If the node is already occupied, we can't insert the new rectangle
-	    if self.occupied {
-		return Err(NodeInsertError(rectangle.clone()));
+	let mut pending = vec![0];
+
+	while let Some(index) = pending.pop() {
+	    // Nodes that aren't leaves hand the rectangle to their children
+	    if let Some([first, second]) = self.slots[index].children {
+		pending.push(second);
+		pending.push(first);
+		continue;
 	    }
 
-	    // If the rectangle fit
-	    if rectangle.fit_in(&self.rectangle) {
-		// If it fits perfectly
-		if rectangle.same_size(&self.rectangle) {
-		    self.occupied = true;
-		    return Ok(self.rectangle.clone());
-		}
-		// Otherwise
-		let delta_width = self.rectangle.width - rectangle.width;
-		let delta_height = self.rectangle.height - rectangle.height;
-
-		if delta_width > delta_height {
-		    self.children[0] = Some(
-			Box::new(
-			    Node::new(
-				Rectangle::new(
-				    self.rectangle.top, self.rectangle.left,
-				    rectangle.width, self.rectangle.height)
-			    )));
-		    self.children[1] = Some(
-			Box::new(
-			    Node::new(
-				Rectangle::new(
-				    self.rectangle.top, self.rectangle.left + rectangle.width,
-				    self.rectangle.width - rectangle.width, self.rectangle.height)
-			    )));
-		} else {
-		    self.children[0] = Some(
-			Box::new(
-			    Node::new(
-				Rectangle::new(
-				    self.rectangle.top, self.rectangle.left,
-				    self.rectangle.width, rectangle.height)
-			    )));
-		    self.children[1] = Some(
-			Box::new(
-			    Node::new(
-				Rectangle::new(
-				    self.rectangle.top + rectangle.height, self.rectangle.left,
-				    self.rectangle.width, self.rectangle.height - rectangle.height)
-			    )));
-		}
-
-		return self.children[0].as_mut().unwrap().insert(rectangle);
+	    let slot = &mut self.slots[index];
+	    if slot.occupied || !rectangle.fit_in(&slot.rectangle) {
+		continue;
 	    }
 
-	    // The rectangle does not fit
-	    return Err(NodeInsertError(rectangle.clone()));
-	} else {    // We are not in a leaf
-	    // We try to insert it in the first children
-	    match self.children[0].as_mut().unwrap().insert(rectangle) {
-		Ok(rect) => Ok(rect),
-		Err(_) => {
-		    self.children[1].as_mut().unwrap().insert(rectangle)
-		}
+	    // If it fits perfectly
+	    if rectangle.same_size(&slot.rectangle) {
+		slot.occupied = true;
+		return Ok(slot.rectangle);
 	    }
+
+	    // Otherwise the leaf is split along its largest leftover, the first child being given the size of the rectangle on that axis
+	    let node = slot.rectangle;
+	    let delta_width = node.width - rectangle.width;
+	    let delta_height = node.height - rectangle.height;
+
+	    let children = if delta_width > delta_height {
+		[
+		    Rectangle::new(node.top, node.left, rectangle.width, node.height),
+		    Rectangle::new(node.top, node.left + rectangle.width, node.width - rectangle.width, node.height),
+		]
+	    } else {
+		[
+		    Rectangle::new(node.top, node.left, node.width, rectangle.height),
+		    Rectangle::new(node.top + rectangle.height, node.left, node.width, node.height - rectangle.height),
+		]
+	    };
+
+	    let first = self.slots.len();
+	    self.slots[index].children = Some([first, first + 1]);
+	    self.slots.extend(children.iter().map(|child| Slot::new(*child)));
+
+	    // The rectangle fits in the first child, which is split again unless it fits perfectly
+	    pending.push(first);
+	}
+
+	// The rectangle does not fit
+	Err(NodeInsertError(*rectangle))
+    }
+}
+
+/// A node of a packing tree below its root, borrowed from the arena of the tree.
+#[derive(Debug, Copy, Clone)]
+pub struct NodeRef<'a> {
+    slots: &'a [Slot],
+    index: usize,
+}
+
+impl<'a> NodeRef<'a> {
+    /// Returns the rectangle covered by the node.
+    pub fn rectangle(&self) -> Rectangle {
+	self.slots[self.index].rectangle
+    }
+
+    /// Returns true if the node wasn't split yet.
+    pub fn is_leaf(&self) -> bool {
+	self.slots[self.index].children.is_none()
+    }
+
+    /// Returns true if a rectangle was inserted exactly in the node.
+    pub fn occupied(&self) -> bool {
+	self.slots[self.index].occupied
+    }
+
+    /// Returns the two children of the node once it is split.
+    pub fn children(&self) -> [Option<NodeRef<'a>>; 2] {
+	match self.slots[self.index].children {
+	    Some(children) => children.map(|index| Some(NodeRef { slots: self.slots, index })),
+	    None => [None, None],
 	}
     }
 }
@@ -125,31 +258,64 @@ impl Node {
 #[derive(Debug)]
 pub struct NodeInsertError(Rectangle);
 
+impl NodeInsertError {
+    /// Returns the rectangle that couldn't be inserted.
+    pub fn rectangle(&self) -> &Rectangle {
+	&self.0
+    }
+}
+
 impl Display for NodeInsertError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
 	write!(f, "Can't insert rectangle of size ({},{})", self.0.width, self.0.height)
     }
 }
 
+impl std::error::Error for NodeInsertError {}
+
 /// A struct representing a glyph in the font atlas.
-#[derive(Debug)]
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
 pub struct Glyph {
     pub metrics: GlyphMetrics,
-    pub bitmap: ImageBuffer<Rgb<u8>, Vec<u8>>
+    pub bitmap: Pixels,
+    /// True for the glyphs of color fonts, whose RGBA bitmap holds their colors premultiplied by their alpha rather than coverage.
+    pub color: bool,
 }
 
 impl Glyph {
     /// Creates a glyph from its metrics and its associated bitmap.
-    pub fn new(metrics: GlyphMetrics, bitmap: ImageBuffer<Rgb<u8>, Vec<u8>>) -> Self {
+    pub fn new(metrics: GlyphMetrics, bitmap: Pixels) -> Self {
 	Self {
 	    metrics,
 	    bitmap,
+	    color: false,
 	}
     }
+
+    /// Sets whether the bitmap holds the colors of a color glyph rather than coverage.
+    pub fn with_color(mut self, color: bool) -> Self {
+	self.color = color;
+	self
+    }
+
+    /// Returns the glyph with its bitmap cropped to the pixels it inks, the bearings moved to match.
+    /// A blank glyph gets an empty bitmap, like a space.
+    pub fn cropped(&self) -> Glyph {
+	let bounds = self.bitmap.ink_bounds().unwrap_or_default();
+	let metrics = GlyphMetrics::new(
+	    bounds.width,
+	    bounds.height,
+	    self.metrics.bearing_x + bounds.left as i32,
+	    self.metrics.bearing_y - bounds.top as i32,
+	    self.metrics.advance
+	);
+
+	Glyph::new(metrics, self.bitmap.crop(&bounds)).with_color(self.color)
+    }
 }
 
 /// A struct representing various metrics about a glyph.
-#[derive(Debug)]
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct GlyphMetrics {
     pub width: u32,
     pub height: u32,
@@ -171,3 +337,64 @@ impl GlyphMetrics {
 	}
     }
 }
+
+/// A 2x2 matrix applied to the glyph outlines before they are rasterized.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Transform {
+    pub xx: f32,
+    pub xy: f32,
+    pub yx: f32,
+    pub yy: f32,
+}
+
+impl Transform {
+    /// Creates a transform from its matrix coefficients.
+    pub fn new(xx: f32, xy: f32, yx: f32, yy: f32) -> Self {
+	Self { xx, xy, yx, yy }
+    }
+
+    /// Creates a transform leaving the glyphs untouched.
+    pub fn identity() -> Self {
+	Self::new(1.0, 0.0, 0.0, 1.0)
+    }
+
+    /// Creates a counter-clockwise rotation of `angle` radians.
+    pub fn rotation(angle: f32) -> Self {
+	let (sin, cos) = angle.sin_cos();
+	Self::new(cos, -sin, sin, cos)
+    }
+
+    /// Creates an horizontal shear, `factor` being the x offset per unit of height.
+    pub fn skew(factor: f32) -> Self {
+	Self::new(1.0, factor, 0.0, 1.0)
+    }
+
+    /// Returns the transform applying `self` then `other`.
+    pub fn then(&self, other: &Transform) -> Self {
+	Self::new(
+	    other.xx * self.xx + other.xy * self.yx,
+	    other.xx * self.xy + other.xy * self.yy,
+	    other.yx * self.xx + other.yy * self.yx,
+	    other.yx * self.xy + other.yy * self.yy
+	)
+    }
+
+    /// Converts the transform to a FreeType 16.16 fixed point matrix.
+    #[cfg(feature = "freetype")]
+    pub fn to_ft_matrix(&self) -> freetype::Matrix {
+	let to_fixed = |v: f32| (v * 65536.0).round() as freetype::ffi::FT_Fixed;
+
+	freetype::Matrix {
+	    xx: to_fixed(self.xx),
+	    xy: to_fixed(self.xy),
+	    yx: to_fixed(self.yx),
+	    yy: to_fixed(self.yy),
+	}
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+	Self::identity()
+    }
+}
@@ -0,0 +1,912 @@
+use std::cell::{Cell, OnceCell, RefCell};
+use std::collections::{HashMap, VecDeque};
+use std::ffi::{c_char, c_void, CString};
+use std::path::{Path, PathBuf};
+use std::fmt::{Debug, Display};
+use std::sync::Arc;
+
+use freetype::face::{Face, LoadFlag, StyleFlag};
+use freetype::tt_os2::TrueTypeOS2Table;
+use freetype::bitmap::PixelMode;
+use freetype::{Bitmap, Library, LcdFilter};
+use freetype::freetype_sys as ffi;
+
+use super::{Glyph, GlyphMetrics, Rectangle, Transform};
+use super::atlas::{AtlasGeneratorError, AtlasLoadMode, LineMetrics, PIXEL_DPI};
+use super::pixels::{expand_gray, to_rgba, PixelFormat, Pixels};
+use super::rasterizer::{hash_font_data, synthesize_box, DriverProperty, Rasterizer};
+use super::script::sequence_glyph_name;
+#[cfg(feature = "system-fonts")]
+use super::font_db::{FontDatabase, FontFace, FontQuery, Style, Weight};
+
+extern "C" {
+    // Declared by ftadvanc.h, which freetype-sys doesn't bind
+    fn FT_Get_Advance(face: ffi::FT_Face, glyph_index: ffi::FT_UInt, load_flags: ffi::FT_Int32, advance: *mut ffi::FT_Fixed) -> ffi::FT_Error;
+    // Declared by ftdriver.h, which freetype-sys doesn't bind
+    fn FT_Property_Set(library: ffi::FT_Library, module_name: *const c_char, property_name: *const c_char, value: *const c_void) -> ffi::FT_Error;
+    // Declared by freetype.h since FreeType 2.8, which freetype-sys doesn't bind
+    fn FT_Face_Properties(face: ffi::FT_Face, num_properties: ffi::FT_UInt, properties: *mut ffi::FT_Parameter) -> ffi::FT_Error;
+}
+
+/// The tag of the face property darkening the stems, FT_PARAM_TAG_STEM_DARKENING.
+const STEM_DARKENING_TAG: ffi::FT_ULong = ((b'd' as ffi::FT_ULong) << 24) | ((b'a' as ffi::FT_ULong) << 16) | ((b'r' as ffi::FT_ULong) << 8) | b'k' as ffi::FT_ULong;
+
+/// A FreeType library instance shared by all the fonts loaded through it.
+pub struct FontSystem {
+    library: Library,
+    #[cfg(feature = "system-fonts")]
+    font_database: OnceCell<FontDatabase>,
+}
+
+impl FontSystem {
+    /// Initializes the FreeType library.
+    pub fn new() -> Result<Self, FontLoaderError> {
+	let library = Library::init().map_err(FontLoaderError::LibraryError)?;
+
+	// FreeType builds without ClearType filtering report this as unimplemented but still render LCD bitmaps
+	match library.set_lcd_filter(LcdFilter::LcdFilterDefault) {
+	    Ok(()) | Err(freetype::Error::UnimplementedFeature) => {},
+	    Err(error) => return Err(FontLoaderError::LibraryError(error)),
+	}
+
+	Ok(Self {
+	    library,
+	    #[cfg(feature = "system-fonts")]
+	    font_database: OnceCell::new(),
+	})
+    }
+
+    /// Returns the database of the fonts installed on the system, scanned on first use.
+    #[cfg(feature = "system-fonts")]
+    pub fn font_database(&self) -> &FontDatabase {
+	self.font_database.get_or_init(FontDatabase::system)
+    }
+
+    /// Loads the face at `face_index` in the font file loaded in `data`.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(bytes = data.len(), face_index), err(Display)))]
+    pub fn new_memory_face(&self, data: Vec<u8>, face_index: isize) -> Result<Face, FontLoaderError> {
+	self.library.new_memory_face(data, face_index).map_err(FontLoaderError::FaceError)
+    }
+
+    /// Loads the face at `face_index` in the given font file.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(path = %font_filepath.as_ref().display(), face_index), err(Display)))]
+    pub fn new_face<P>(&self, font_filepath: P, face_index: isize) -> Result<Face, FontLoaderError> where P: AsRef<Path> {
+	self.library.new_face(font_filepath.as_ref(), face_index).map_err(FontLoaderError::FaceError)
+    }
+}
+
+/// Panics if the FreeType library can't be initialized, which `FontSystem::new` reports instead.
+impl Default for FontSystem {
+    fn default() -> Self {
+	Self::new().expect("Failed to init freetype library")
+    }
+}
+
+/// The key of a rendered glyph in the cache of a FontLoader: character, size, dpi and load flags.
+type GlyphKey = (char, u32, u32, i32);
+
+/// The glyphs already rendered by a FontLoader, evicted in insertion order once `capacity` is reached.
+#[derive(Default)]
+struct GlyphCache {
+    glyphs: HashMap<GlyphKey, Arc<Glyph>>,
+    order: VecDeque<GlyphKey>,
+    capacity: Option<usize>,
+}
+
+impl GlyphCache {
+    fn insert(&mut self, key: GlyphKey, glyph: Arc<Glyph>) {
+	if self.capacity == Some(0) {
+	    return;
+	}
+
+	if let Some(capacity) = self.capacity {
+	    while self.order.len() >= capacity {
+		if let Some(oldest) = self.order.pop_front() {
+		    self.glyphs.remove(&oldest);
+		}
+	    }
+	}
+
+	self.order.push_back(key);
+	self.glyphs.insert(key, glyph);
+    }
+
+    fn clear(&mut self) {
+	self.glyphs.clear();
+	self.order.clear();
+    }
+}
+
+/// The names and properties of a font face, given by `FontLoader::info`, for font pickers and validation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FaceInfo {
+    pub family_name: Option<String>,
+    pub style_name: Option<String>,
+    pub postscript_name: Option<String>,
+    /// The size of the em square in font units, 0 for bitmap fonts.
+    pub units_per_em: u16,
+    pub glyph_count: u32,
+    pub bold: bool,
+    pub italic: bool,
+    /// True for outline fonts, which render at any size, false for bitmap fonts with fixed sizes.
+    pub scalable: bool,
+}
+
+/// The encoding of the character codes of a charmap.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Encoding {
+    Unicode,
+    /// The Microsoft symbol encoding of fonts like Wingdings, whose characters FreeType maps from U+F020 to U+F0FF.
+    MsSymbol,
+    Sjis,
+    Gb2312,
+    Big5,
+    Wansung,
+    Johab,
+    AdobeStandard,
+    AdobeExpert,
+    AdobeCustom,
+    AdobeLatin1,
+    OldLatin2,
+    AppleRoman,
+    /// An encoding FreeType doesn't know, given by its tag.
+    Other(u32),
+}
+
+impl Encoding {
+    fn from_ft_encoding(encoding: ffi::FT_Encoding) -> Self {
+	match encoding {
+	    ffi::FT_ENCODING_UNICODE => Encoding::Unicode,
+	    ffi::FT_ENCODING_MS_SYMBOL => Encoding::MsSymbol,
+	    ffi::FT_ENCODING_SJIS => Encoding::Sjis,
+	    ffi::FT_ENCODING_GB2312 => Encoding::Gb2312,
+	    ffi::FT_ENCODING_BIG5 => Encoding::Big5,
+	    ffi::FT_ENCODING_WANSUNG => Encoding::Wansung,
+	    ffi::FT_ENCODING_JOHAB => Encoding::Johab,
+	    ffi::FT_ENCODING_ADOBE_STANDARD => Encoding::AdobeStandard,
+	    ffi::FT_ENCODING_ADOBE_EXPERT => Encoding::AdobeExpert,
+	    ffi::FT_ENCODING_ADOBE_CUSTOM => Encoding::AdobeCustom,
+	    ffi::FT_ENCODING_ADOBE_LATIN_1 => Encoding::AdobeLatin1,
+	    ffi::FT_ENCODING_OLD_LATIN_2 => Encoding::OldLatin2,
+	    ffi::FT_ENCODING_APPLE_ROMAN => Encoding::AppleRoman,
+	    tag => Encoding::Other(tag),
+	}
+    }
+}
+
+/// A character map of a face, turning the character codes of an encoding into glyph indices.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct CharMap {
+    /// The index of the charmap in the face, given to `FontLoader::select_charmap`.
+    pub index: usize,
+    pub encoding: Encoding,
+    /// The platform and encoding ids of the cmap subtable in TrueType and OpenType fonts.
+    pub platform_id: u16,
+    pub encoding_id: u16,
+}
+
+/// A struct loading and rendering the glyphs of a font face.
+pub struct FontLoader {
+    ft_font_face: Face,
+    transform: Cell<Option<Transform>>,
+    stem_darkening: Cell<bool>,
+    char_size: Cell<(u32, u32)>,
+    cache: RefCell<GlyphCache>,
+    /// The font file and face index the face was loaded from, to hash its data on demand.
+    source: Option<(PathBuf, u32)>,
+    font_hash: OnceCell<Option<u64>>,
+}
+
+impl FontLoader {
+    /// Creates a loader from the given font filepath, using the library of `font_system`.
+    pub fn new<P>(font_system: &FontSystem, font_filepath: P) -> Result<FontLoader, FontLoaderError> where P: AsRef<Path> {
+	Self::with_face_index(font_system, font_filepath, 0)
+    }
+
+    /// Creates a loader from the face at `face_index` in the given font file, for font collections.
+    pub fn with_face_index<P>(font_system: &FontSystem, font_filepath: P, face_index: u32) -> Result<FontLoader, FontLoaderError> where P: AsRef<Path> {
+	let face = font_system.new_face(font_filepath.as_ref(), face_index as isize)?;
+
+	Ok(FontLoader {
+	    source: Some((font_filepath.as_ref().to_path_buf(), face_index)),
+	    ..Self::from_face(face)
+	})
+    }
+
+    /// Creates a loader from the face at `face_index` in the font file loaded in `data`.
+    pub fn from_vec(font_system: &FontSystem, data: Vec<u8>, face_index: u32) -> Result<FontLoader, FontLoaderError> {
+	let font_hash = hash_font_data(&data, face_index);
+	let face = font_system.new_memory_face(data, face_index as isize)?;
+
+	Ok(FontLoader {
+	    font_hash: OnceCell::from(Some(font_hash)),
+	    ..Self::from_face(face)
+	})
+    }
+
+    /// Creates a loader from the face at `face_index` in the font file content `data`, which is copied.
+    pub fn from_bytes(font_system: &FontSystem, data: &[u8], face_index: u32) -> Result<FontLoader, FontLoaderError> {
+	Self::from_vec(font_system, data.to_vec(), face_index)
+    }
+
+    /// Creates a loader from a face found in a font database.
+    #[cfg(feature = "system-fonts")]
+    pub fn from_font_face(font_system: &FontSystem, font_face: &FontFace) -> Result<FontLoader, FontLoaderError> {
+	Self::with_face_index(font_system, &font_face.path, font_face.face_index)
+    }
+
+    /// Creates a loader from the installed face of the given family best matching `style` and `weight`.
+    #[cfg(feature = "system-fonts")]
+    pub fn from_family(font_system: &FontSystem, family: &str, style: Style, weight: Weight) -> Result<FontLoader, FontLoaderError> {
+	let query = FontQuery::new(&[family]).with_style(style).with_weight(weight);
+
+	match font_system.font_database().query(&query) {
+	    Some(font_face) => Self::from_font_face(font_system, &font_face),
+	    None => Err(FontLoaderError::FamilyNotFound(String::from(family))),
+	}
+    }
+
+    /// Creates a loader from an already loaded face.
+    pub fn from_face(face: Face) -> FontLoader {
+	FontLoader {
+	    ft_font_face: face,
+	    transform: Cell::new(None),
+	    stem_darkening: Cell::new(false),
+	    char_size: Cell::new((0, 0)),
+	    cache: RefCell::new(GlyphCache::default()),
+	    source: None,
+	    font_hash: OnceCell::new(),
+	}
+    }
+
+    /// Returns the underlying FreeType face.
+    pub fn face(&self) -> &Face {
+	&self.ft_font_face
+    }
+
+    /// Returns the names and properties of the face.
+    pub fn info(&self) -> FaceInfo {
+	let face = &self.ft_font_face;
+	let style_flags = face.style_flags();
+
+	FaceInfo {
+	    family_name: face.family_name(),
+	    style_name: face.style_name(),
+	    postscript_name: face.postscript_name(),
+	    units_per_em: face.em_size() as u16,
+	    glyph_count: face.raw().num_glyphs as u32,
+	    bold: style_flags.contains(StyleFlag::BOLD),
+	    italic: style_flags.contains(StyleFlag::ITALIC),
+	    scalable: face.is_scalable(),
+	}
+    }
+
+    /// Returns the charmaps of the face, in the order of the font file.
+    pub fn charmaps(&self) -> Vec<CharMap> {
+	let face = self.ft_font_face.raw();
+	if face.charmaps.is_null() {
+	    return Vec::new();
+	}
+
+	// The charmaps are owned by the face, which outlives the loop
+	let charmaps = unsafe { std::slice::from_raw_parts(face.charmaps, face.num_charmaps as usize) };
+	charmaps.iter()
+	    .enumerate()
+	    .map(|(index, charmap)| {
+		let charmap = unsafe { &**charmap };
+		CharMap {
+		    index,
+		    encoding: Encoding::from_ft_encoding(charmap.encoding),
+		    platform_id: charmap.platform_id,
+		    encoding_id: charmap.encoding_id,
+		}
+	    })
+	    .collect()
+    }
+
+    /// Returns the charmap characters are looked up in, chosen by FreeType when the face is loaded, Unicode if there is one.
+    pub fn charmap(&self) -> Option<CharMap> {
+	let current = self.ft_font_face.raw().charmap;
+	if current.is_null() {
+	    return None;
+	}
+
+	let index = unsafe { ffi::FT_Get_Charmap_Index(current) };
+	match index {
+	    index if index >= 0 => self.charmaps().get(index as usize).copied(),
+	    _ => None,
+	}
+    }
+
+    /// Selects the charmap at `index` in `charmaps` to look characters up, like the symbol charmap of a font without a Unicode one.
+    /// The cached glyphs are dropped, as characters may now give other glyphs.
+    pub fn select_charmap(&self, index: usize) -> Result<(), FontLoaderError> {
+	let face = self.ft_font_face.raw();
+	if index >= face.num_charmaps.max(0) as usize {
+	    return Err(FontLoaderError::CharMapError(index, freetype::Error::InvalidCharMapHandle));
+	}
+
+	// FreeType only switches the current charmap of the face, shared by its clones
+	let mut face = self.ft_font_face.clone();
+	let error = unsafe {
+	    let charmap = *face.raw().charmaps.add(index);
+	    ffi::FT_Set_Charmap(face.raw_mut(), charmap)
+	};
+	if error != 0 {
+	    return Err(FontLoaderError::CharMapError(index, error.into()));
+	}
+
+	self.clear_cache();
+	Ok(())
+    }
+
+    /// Sets the size of the rendered glyphs, in 26.6 points at the given `dpi`.
+    pub fn set_char_size(&self, size: u32, dpi: u32) -> Result<(), FontLoaderError> {
+	self.ft_font_face.set_char_size(0, size as isize, 0, dpi).map_err(FontLoaderError::SizeError)?;
+	self.char_size.set((size, dpi));
+	Ok(())
+    }
+
+    /// Sets the size of the rendered glyphs so that the em is `pixel_height` pixels high.
+    pub fn set_pixel_sizes(&self, pixel_height: u32) -> Result<(), FontLoaderError> {
+	self.ft_font_face.set_pixel_sizes(0, pixel_height).map_err(FontLoaderError::SizeError)?;
+	// The same size as 26.6 points at 72 DPI, so glyphs are cached once for both
+	self.char_size.set((pixel_height * 64, PIXEL_DPI));
+	Ok(())
+    }
+
+    /// Returns true if the face has a glyph for the character `c`.
+    pub fn has_glyph(&self, c: char) -> bool {
+	self.glyph_index(c) != 0
+    }
+
+    /// Returns the index of the glyph of the character `c` in the face, 0 being the .notdef glyph.
+    pub fn glyph_index(&self, c: char) -> u32 {
+	self.ft_font_face.get_char_index(c as usize)
+    }
+
+    /// Returns the horizontal advance in pixels of each character of `text` at the current size, read from the font metrics
+    /// without rendering the glyphs, for measuring text. Characters missing from the face advance like the .notdef glyph.
+    pub fn advances(&self, text: &str) -> Result<Vec<f32>, FontLoaderError> {
+	let mut face = self.ft_font_face.clone();
+
+	text.chars()
+	    .map(|c| {
+		let mut advance: ffi::FT_Fixed = 0;
+		let error = unsafe { FT_Get_Advance(face.raw_mut(), self.glyph_index(c), LoadFlag::DEFAULT.bits(), &mut advance) };
+
+		// The advance is given in 16.16 pixels
+		match error {
+		    0 => Ok(advance as f32 / 65536.0),
+		    error => Err(FontLoaderError::LoadError(c, error.into())),
+		}
+	    })
+	    .collect()
+    }
+
+    /// Returns the index of the glyph given `name` by the PostScript names of the face, like `uni00E9` or `eacute`, for icon
+    /// fonts addressing their glyphs by name. None if no glyph has it, or for the .notdef glyph.
+    pub fn glyph_index_by_name(&self, name: &str) -> Option<u32> {
+	if !self.ft_font_face.has_glyph_names() {
+	    return None;
+	}
+
+	let name = CString::new(name).ok()?;
+	let mut face = self.ft_font_face.clone();
+	let glyph_index = unsafe { ffi::FT_Get_Name_Index(face.raw_mut(), name.as_ptr() as *mut _) };
+
+	(glyph_index != 0).then_some(glyph_index)
+    }
+
+    /// Returns the vertical metrics of the face at the current size.
+    pub fn line_metrics(&self) -> LineMetrics {
+	let size_metrics = match self.ft_font_face.size_metrics() {
+	    Some(size_metrics) => size_metrics,
+	    None => return LineMetrics::default(),
+	};
+
+	// Font units are scaled to 26.6 pixels by the 16.16 y scale of the size
+	let scale = size_metrics.y_scale as f64 / 65536.0 / 64.0;
+	let to_pixels = |units: i32| (units as f64 * scale).round() as i32;
+	let ascender = size_metrics.ascender as i32 / 64;
+
+	let underline_position = to_pixels(self.ft_font_face.underline_position() as i32);
+	let underline_thickness = to_pixels(self.ft_font_face.underline_thickness() as i32).max(1);
+
+	// The strikeout is only described by the OS/2 table, whose position is the top of the stroke
+	let mut face = self.ft_font_face.clone();
+	let (strikeout_position, strikeout_thickness) = match TrueTypeOS2Table::from_face(&mut face) {
+	    Some(os2) => {
+		let size = os2.y_strikeout_size() as i32;
+		(to_pixels(os2.y_strikeout_position() as i32 - size / 2), to_pixels(size).max(1))
+	    },
+	    None => (ascender / 3, underline_thickness),
+	};
+
+	LineMetrics::new(ascender, size_metrics.descender as i32 / 64, size_metrics.height as i32 / 64)
+	    .with_underline(underline_position, underline_thickness)
+	    .with_strikeout(strikeout_position, strikeout_thickness)
+    }
+
+    /// Sets the transform applied to every glyph before rasterization.
+    pub fn set_transform(&self, transform: Option<Transform>) {
+	let mut matrix = transform.unwrap_or_default().to_ft_matrix();
+	let mut delta = freetype::Vector { x: 0, y: 0 };
+	self.ft_font_face.set_transform(&mut matrix, &mut delta);
+
+	// Cached glyphs were rendered with the previous transform, which isn't part of their key
+	if self.transform.replace(transform) != transform {
+	    self.clear_cache();
+	}
+    }
+
+    /// Sets whether the stems of the glyphs are darkened, thickening thin fonts at small sizes. FreeType only darkens CFF fonts
+    /// and the glyphs hinted by its autohinter, TrueType fonts hinted by their own instructions being left as they are.
+    pub fn set_stem_darkening(&self, stem_darkening: bool) -> Result<(), FontLoaderError> {
+	if self.stem_darkening.get() == stem_darkening {
+	    return Ok(());
+	}
+
+	let mut value: ffi::FT_Bool = stem_darkening as ffi::FT_Bool;
+	let mut property = ffi::FT_Parameter { tag: STEM_DARKENING_TAG, data: &mut value as *mut ffi::FT_Bool as ffi::FT_Pointer };
+	let mut face = self.ft_font_face.clone();
+	match unsafe { FT_Face_Properties(face.raw_mut(), 1, &mut property) } {
+	    0 => {},
+	    error => return Err(FontLoaderError::PropertyError(error.into())),
+	}
+
+	// Cached glyphs were rendered with the previous setting, which isn't part of their key
+	self.stem_darkening.set(stem_darkening);
+	self.clear_cache();
+
+	Ok(())
+    }
+
+    /// Sets `property` on its driver, like the `interpreter-version` of the `truetype` driver. Driver properties belong to
+    /// the FreeType library, so they change the rendering of every face of its FontSystem, but only the glyphs cached by this
+    /// loader are rendered again. They are set each time, as another loader of the library may have changed them since.
+    pub fn set_driver_property(&self, property: &DriverProperty) -> Result<(), FontLoaderError> {
+	let (module_name, property_name) = match (CString::new(property.module().name()), CString::new(property.name())) {
+	    (Ok(module_name), Ok(property_name)) => (module_name, property_name),
+	    _ => return Err(FontLoaderError::PropertyError(freetype::Error::InvalidArgument)),
+	};
+
+	// The driver reads the value through a pointer to the type of the property, which each variant fixes
+	let (number, flag) = match property {
+	    DriverProperty::InterpreterVersion(number) | DriverProperty::HintingEngine(_, number) => (*number as ffi::FT_UInt, 0),
+	    DriverProperty::NoStemDarkening(_, flag) => (0, *flag as ffi::FT_Bool),
+	    DriverProperty::DarkeningParameters(..) => (0, 0),
+	};
+	let pointer: *const c_void = match property {
+	    DriverProperty::InterpreterVersion(_) | DriverProperty::HintingEngine(..) => &number as *const ffi::FT_UInt as *const c_void,
+	    DriverProperty::NoStemDarkening(..) => &flag as *const ffi::FT_Bool as *const c_void,
+	    DriverProperty::DarkeningParameters(_, parameters) => parameters.as_ptr() as *const ffi::FT_Int as *const c_void,
+	};
+
+	// Every glyph slot points back to the library of its face
+	let library = unsafe { (*self.ft_font_face.raw().glyph).library };
+	match unsafe { FT_Property_Set(library, module_name.as_ptr(), property_name.as_ptr(), pointer) } {
+	    0 => {},
+	    error => return Err(FontLoaderError::PropertyError(error.into())),
+	}
+
+	self.clear_cache();
+
+	Ok(())
+    }
+
+    /// Removes all the glyphs rendered so far from the cache.
+    pub fn clear_cache(&self) {
+	self.cache.borrow_mut().clear();
+    }
+
+    /// Limits the number of cached glyphs, evicting the oldest ones first. None lifts the limit.
+    pub fn set_cache_capacity(&self, capacity: Option<usize>) {
+	let mut cache = self.cache.borrow_mut();
+	cache.capacity = capacity;
+
+	if let Some(capacity) = capacity {
+	    while cache.order.len() > capacity {
+		if let Some(oldest) = cache.order.pop_front() {
+		    cache.glyphs.remove(&oldest);
+		}
+	    }
+	}
+    }
+
+    /// Returns the number of glyphs in the cache.
+    pub fn cached_glyph_count(&self) -> usize {
+	self.cache.borrow().glyphs.len()
+    }
+
+    /// Converts a FreeType bitmap to an RGB image, gray pixels being written to the 3 channels. Color glyphs give an RGBA
+    /// image of their colors, which FreeType premultiplies by their alpha.
+    fn convert_bitmap(&self, bitmap: &Bitmap) -> Result<Pixels, FontLoaderError> {
+	let (width, height) = bitmap_size(bitmap);
+
+	if matches!(bitmap.pixel_mode(), Ok(PixelMode::Bgra)) {
+	    let (source, pitch) = (bitmap_buffer(bitmap), bitmap.pitch().unsigned_abs() as usize);
+	    if height > 0 && source.len() < (height as usize - 1) * pitch + width as usize * 4 {
+		return Err(FontLoaderError::BitmapError);
+	    }
+
+	    return Ok(Pixels::from_fn(width, height, PixelFormat::Rgba8, |x, y| {
+		let start = y as usize * pitch + x as usize * 4;
+		[source[start + 2], source[start + 1], source[start], source[start + 3]]
+	    }));
+	}
+	let channels = if matches!(bitmap.pixel_mode(), Ok(PixelMode::Lcd)) { 3 } else { 1 };
+
+	let mut image = Pixels::new(width, height, PixelFormat::Rgb8);
+	write_rows(bitmap_buffer(bitmap), bitmap.pitch() as usize, channels, (width, height), &mut image, (Rectangle::new(0, 0, width, height), false))?;
+
+	Ok(image)
+    }
+
+    /// Loads a glyph from the associated font file, rendering it only the first time at a given size and dpi.
+    pub fn load_glyph(&self, c: char, load_flags: LoadFlag) -> Result<Arc<Glyph>, FontLoaderError> {
+	let (size, dpi) = self.char_size.get();
+	let key = (c, size, dpi, load_flags.bits());
+
+	if let Some(glyph) = self.cache.borrow().glyphs.get(&key) {
+	    #[cfg(feature = "tracing")]
+	    tracing::trace!(?c, "glyph found in the cache");
+	    return Ok(Arc::clone(glyph));
+	}
+
+	#[cfg(feature = "tracing")]
+	let _span = tracing::trace_span!("rasterize", ?c, size, dpi).entered();
+
+	if let Err(error) = self.ft_font_face.load_char(c as usize, load_flags) {
+	    return Err(FontLoaderError::LoadError(c, error));
+	}
+
+	let glyph = Arc::new(self.loaded_glyph()?);
+	self.cache.borrow_mut().insert(key, Arc::clone(&glyph));
+
+	Ok(glyph)
+    }
+
+    /// Renders `c` and writes its bitmap in `buffer` at the position returned by `place` from its width and height, along with
+    /// whether it is stored rotated 90° clockwise, without going through an intermediate image. Glyphs found in the cache are
+    /// copied from it, and the glyphs rendered aren't added to it. Returns the metrics of the glyph and the result of `place`.
+    pub fn blit_glyph<F, E>(&self, c: char, load_flags: LoadFlag, buffer: &mut Pixels, place: F) -> Result<(GlyphMetrics, (Rectangle, bool)), E>
+    where F: FnOnce(u32, u32) -> Result<(Rectangle, bool), E>, E: From<FontLoaderError> {
+	let (size, dpi) = self.char_size.get();
+	let key = (c, size, dpi, load_flags.bits());
+
+	let cached = self.cache.borrow().glyphs.get(&key).cloned();
+	if let Some(glyph) = cached {
+	    #[cfg(feature = "tracing")]
+	    tracing::trace!(?c, "glyph found in the cache");
+	    let (width, height) = glyph.bitmap.dimensions();
+	    let placement = place(width, height)?;
+	    write_rows(&glyph.bitmap, glyph.bitmap.stride(), glyph.bitmap.format.channels(), (width, height), buffer, placement)?;
+
+	    return Ok((glyph.metrics, placement));
+	}
+
+	#[cfg(feature = "tracing")]
+	let _span = tracing::trace_span!("rasterize", ?c, size, dpi).entered();
+
+	if let Err(error) = self.ft_font_face.load_char(c as usize, load_flags) {
+	    return Err(FontLoaderError::LoadError(c, error).into());
+	}
+
+	let (width, height) = bitmap_size(&self.ft_font_face.glyph().bitmap());
+	let placement = place(width, height)?;
+
+	// The bitmap is looked up again in case `place` loaded another glyph in the slot
+	let bitmap = self.ft_font_face.glyph().bitmap();
+	if bitmap_size(&bitmap) != (width, height) {
+	    return Err(FontLoaderError::BitmapError.into());
+	}
+
+	// Color bitmaps are converted first, FreeType storing them in BGRA order
+	match bitmap.pixel_mode() {
+	    Ok(PixelMode::Bgra) => write_rows(&self.convert_bitmap(&bitmap)?, width as usize * 4, 4, (width, height), buffer, placement)?,
+	    Ok(PixelMode::Lcd) => write_rows(bitmap_buffer(&bitmap), bitmap.pitch() as usize, 3, (width, height), buffer, placement)?,
+	    _ => write_rows(bitmap_buffer(&bitmap), bitmap.pitch() as usize, 1, (width, height), buffer, placement)?,
+	}
+
+	Ok((self.loaded_metrics(width, height), placement))
+    }
+
+    /// Returns the metrics of the glyph of `c` without rendering it, unless a transform is set since FreeType metrics ignore it.
+    /// Glyphs found in the cache give their metrics.
+    pub fn measure_glyph(&self, c: char, load_flags: LoadFlag) -> Result<GlyphMetrics, FontLoaderError> {
+	let (size, dpi) = self.char_size.get();
+	if let Some(glyph) = self.cache.borrow().glyphs.get(&(c, size, dpi, load_flags.bits())) {
+	    return Ok(glyph.metrics);
+	}
+
+	if self.transform.get().is_some() {
+	    return Ok(self.load_glyph(c, load_flags)?.metrics);
+	}
+
+	if let Err(error) = self.ft_font_face.load_char(c as usize, load_flags & !LoadFlag::RENDER) {
+	    return Err(FontLoaderError::LoadError(c, error));
+	}
+
+	Ok(self.loaded_metrics(0, 0))
+    }
+
+    /// Loads the glyph at `glyph_index` in the face.
+    pub fn load_glyph_index(&self, glyph_index: u32, load_flags: LoadFlag) -> Result<Glyph, FontLoaderError> {
+	if let Err(error) = self.ft_font_face.load_glyph(glyph_index, load_flags) {
+	    return Err(FontLoaderError::GlyphIndexError(glyph_index, error));
+	}
+
+	self.loaded_glyph()
+    }
+
+    /// Loads the glyph given `name` by the PostScript names of the face, see `glyph_index_by_name`.
+    pub fn load_glyph_by_name(&self, name: &str, load_flags: LoadFlag) -> Result<Glyph, FontLoaderError> {
+	match self.glyph_index_by_name(name) {
+	    Some(glyph_index) => self.load_glyph_index(glyph_index, load_flags),
+	    None => Err(FontLoaderError::GlyphNameNotFound(String::from(name))),
+	}
+    }
+
+    /// Loads the .notdef glyph of the face, or synthesizes a box if the font leaves it empty.
+    pub fn load_notdef(&self, load_flags: LoadFlag) -> Result<Glyph, FontLoaderError> {
+	let glyph = self.load_glyph_index(0, load_flags)?;
+
+	if glyph.bitmap.width > 0 && glyph.bitmap.height > 0 {
+	    return Ok(glyph);
+	}
+
+	Ok(synthesize_box(&self.line_metrics()))
+    }
+
+    /// Converts the glyph currently loaded in the face slot.
+    fn loaded_glyph(&self) -> Result<Glyph, FontLoaderError> {
+	let ft_glyph = self.ft_font_face.glyph();
+	let raw_bitmap = ft_glyph.bitmap();
+
+	let bitmap = self.convert_bitmap(&raw_bitmap)?;
+	let metrics = self.loaded_metrics(bitmap.width, bitmap.height);
+
+	Ok(Glyph::new(metrics, bitmap).with_color(matches!(raw_bitmap.pixel_mode(), Ok(PixelMode::Bgra))))
+    }
+
+    /// Returns the metrics of the glyph currently loaded in the face slot, whose bitmap is `width` by `height` pixels.
+    fn loaded_metrics(&self, width: u32, height: u32) -> GlyphMetrics {
+	let ft_glyph = self.ft_font_face.glyph();
+
+	// FreeType glyph metrics ignore the face transform, so we use the rendered bitmap instead
+	if self.transform.get().is_some() {
+	    GlyphMetrics::new(
+		width,
+		height,
+		ft_glyph.bitmap_left(),
+		ft_glyph.bitmap_top(),
+		ft_glyph.advance().x as i32 / 64
+	    )
+	} else {
+	    GlyphMetrics::new(
+		ft_glyph.metrics().width as u32 / 64,
+		ft_glyph.metrics().height as u32 / 64,
+		ft_glyph.metrics().horiBearingX as i32 / 64,
+		ft_glyph.metrics().horiBearingY as i32 / 64,
+		ft_glyph.metrics().horiAdvance as i32 / 64
+	    )
+	}
+    }
+}
+
+impl Rasterizer for FontLoader {
+    fn set_char_size(&self, size: u32, dpi: u32) -> Result<(), AtlasGeneratorError> {
+	Ok(FontLoader::set_char_size(self, size, dpi)?)
+    }
+
+    fn set_pixel_sizes(&self, pixel_height: u32) -> Result<(), AtlasGeneratorError> {
+	Ok(FontLoader::set_pixel_sizes(self, pixel_height)?)
+    }
+
+    fn set_transform(&self, transform: Option<Transform>) {
+	FontLoader::set_transform(self, transform)
+    }
+
+    fn glyph_index(&self, c: char) -> u32 {
+	FontLoader::glyph_index(self, c)
+    }
+
+    fn line_metrics(&self) -> LineMetrics {
+	FontLoader::line_metrics(self)
+    }
+
+    /// Font files are read again the first time, and faces given to `from_face` have no hash.
+    fn font_hash(&self) -> Option<u64> {
+	*self.font_hash.get_or_init(|| {
+	    let (path, face_index) = self.source.as_ref()?;
+	    std::fs::read(path).ok().map(|data| hash_font_data(&data, *face_index))
+	})
+    }
+
+    fn set_stem_darkening(&self, stem_darkening: bool) -> Result<(), AtlasGeneratorError> {
+	Ok(FontLoader::set_stem_darkening(self, stem_darkening)?)
+    }
+
+    fn set_driver_property(&self, property: &DriverProperty) -> Result<(), AtlasGeneratorError> {
+	Ok(FontLoader::set_driver_property(self, property)?)
+    }
+
+    fn rasterize(&self, c: char, load_mode: AtlasLoadMode) -> Result<Arc<Glyph>, AtlasGeneratorError> {
+	Ok(self.load_glyph(c, load_mode.load_flags())?)
+    }
+
+    fn measure(&self, c: char, load_mode: AtlasLoadMode) -> Result<GlyphMetrics, AtlasGeneratorError> {
+	Ok(self.measure_glyph(c, load_mode.load_flags())?)
+    }
+
+    fn rasterize_notdef(&self, load_mode: AtlasLoadMode) -> Result<Glyph, AtlasGeneratorError> {
+	Ok(self.load_notdef(load_mode.load_flags())?)
+    }
+
+    /// Sequences are found by the name emoji fonts give their glyphs, see `sequence_glyph_name`, rather than shaped with the
+    /// ligatures of the font. Fonts without glyph names, like CFF2 ones and TrueType ones with a version 3 `post` table, or
+    /// naming their glyphs otherwise, have no sequence glyph.
+    fn rasterize_sequence(&self, sequence: &str, load_mode: AtlasLoadMode) -> Result<Option<(u32, Glyph)>, AtlasGeneratorError> {
+	match self.glyph_index_by_name(&sequence_glyph_name(sequence)) {
+	    Some(glyph_index) => Ok(Some((glyph_index, self.load_glyph_index(glyph_index, load_mode.load_flags())?))),
+	    None => Ok(None),
+	}
+    }
+
+    fn blit(&self, c: char, load_mode: AtlasLoadMode, buffer: &mut Pixels, place: &mut dyn FnMut(u32, u32) -> Result<(Rectangle, bool), AtlasGeneratorError>) -> Result<(GlyphMetrics, (Rectangle, bool)), AtlasGeneratorError> {
+	self.blit_glyph(c, load_mode.load_flags(), buffer, place)
+    }
+}
+
+/// Returns the size in pixels of a FreeType bitmap, LCD bitmaps having 3 bytes per pixel.
+fn bitmap_size(bitmap: &Bitmap) -> (u32, u32) {
+    match bitmap.pixel_mode() {
+	Ok(PixelMode::Lcd) => (bitmap.width() as u32 / 3, bitmap.rows() as u32),
+	_ => (bitmap.width() as u32, bitmap.rows() as u32),
+    }
+}
+
+/// Returns the bytes of a FreeType bitmap, blank glyphs having no buffer at all.
+fn bitmap_buffer(bitmap: &Bitmap) -> &[u8] {
+    match bitmap.width() == 0 || bitmap.rows() == 0 {
+	true => &[],
+	false => bitmap.buffer(),
+    }
+}
+
+/// Writes the `size` pixels of the rows of `source`, `pitch` bytes apart with `channels` bytes per pixel, in `buffer` at `placement`.
+/// Pixels are converted to the format of `buffer` like `Pixels::to_format` does, gray ones being written to every channel, and
+/// a rotated placement receives the bitmap rotated 90° clockwise.
+fn write_rows(source: &[u8], pitch: usize, channels: usize, size: (u32, u32), buffer: &mut Pixels, placement: (Rectangle, bool)) -> Result<(), FontLoaderError> {
+    let (width, height) = size;
+    let (position, rotated) = placement;
+    let placed_size = if rotated { (height, width) } else { (width, height) };
+
+    if width == 0 || height == 0 {
+	return Ok(());
+    }
+    if placed_size != (position.width, position.height)
+	|| position.left + position.width > buffer.width || position.top + position.height > buffer.height
+	|| source.len() < (height as usize - 1) * pitch + width as usize * channels {
+	return Err(FontLoaderError::BitmapError);
+    }
+
+    let (buffer_width, pixel_size) = (buffer.width as usize, buffer.format.channels());
+    let destination: &mut [u8] = buffer;
+    let rows = (0..height as usize).map(|y| &source[y * pitch..][..width as usize * channels]);
+    let row_start = |y: usize| ((position.top as usize + y) * buffer_width + position.left as usize) * pixel_size;
+
+    // The layout is checked once rather than per pixel, so that the common upright copies are plain slice copies
+    match (rotated, channels, pixel_size) {
+	(false, ..) if channels == pixel_size => {
+	    for (y, row) in rows.enumerate() {
+		let start = row_start(y);
+		destination[start..start + row.len()].copy_from_slice(row);
+	    }
+	},
+	(false, 1, 3) => {
+	    for (y, row) in rows.enumerate() {
+		let start = row_start(y);
+		expand_gray(row, &mut destination[start..start + row.len() * 3]);
+	    }
+	},
+	_ => {
+	    for (y, row) in rows.enumerate() {
+		for (x, pixel) in row.chunks_exact(channels).enumerate() {
+		    // Row y of the bitmap becomes column height - 1 - y of a rotated placement
+		    let (column, line) = match rotated {
+			true => (position.left as usize + height as usize - 1 - y, position.top as usize + x),
+			false => (position.left as usize + x, position.top as usize + y),
+		    };
+		    let start = (line * buffer_width + column) * pixel_size;
+		    destination[start..start + pixel_size].copy_from_slice(&to_rgba(pixel)[..pixel_size]);
+		}
+	    }
+	},
+    }
+
+    Ok(())
+}
+
+/// An enum representing all the error that could happen using the loader.
+pub enum FontLoaderError {
+    LibraryError(freetype::Error),
+    FaceError(freetype::Error),
+    SizeError(freetype::Error),
+    BitmapError,
+    LoadError(char, freetype::Error),
+    GlyphIndexError(u32, freetype::Error),
+    FamilyNotFound(String),
+    CharMapError(usize, freetype::Error),
+    GlyphNameNotFound(String),
+    PropertyError(freetype::Error),
+    Cancelled,
+}
+
+impl FontLoaderError {
+    /// Returns the FreeType error that caused this error, if any.
+    pub fn freetype_error(&self) -> Option<freetype::Error> {
+	match self {
+	    FontLoaderError::LibraryError(error) => Some(*error),
+	    FontLoaderError::FaceError(error) => Some(*error),
+	    FontLoaderError::SizeError(error) => Some(*error),
+	    FontLoaderError::LoadError(_, error) => Some(*error),
+	    FontLoaderError::GlyphIndexError(_, error) => Some(*error),
+	    FontLoaderError::CharMapError(_, error) => Some(*error),
+	    FontLoaderError::PropertyError(error) => Some(*error),
+	    FontLoaderError::BitmapError | FontLoaderError::FamilyNotFound(_) | FontLoaderError::GlyphNameNotFound(_) | FontLoaderError::Cancelled => None,
+	}
+    }
+}
+
+impl Display for FontLoaderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+	match self {
+	    FontLoaderError::LibraryError(_) => write!(f, "Can't initialize the FreeType library"),
+	    FontLoaderError::FaceError(_) => write!(f, "Can't load font face"),
+	    FontLoaderError::SizeError(_) => write!(f, "Can't set the font size"),
+	    FontLoaderError::BitmapError => write!(f, "Can't convert the glyph bitmap"),
+	    FontLoaderError::LoadError(c, _) => write!(f, "Can't load character {}", c),
+	    FontLoaderError::GlyphIndexError(index, _) => write!(f, "Can't load glyph {}", index),
+	    FontLoaderError::FamilyNotFound(family) => write!(f, "Can't find font family {}", family),
+	    FontLoaderError::CharMapError(index, _) => write!(f, "Can't select charmap {}", index),
+	    FontLoaderError::GlyphNameNotFound(name) => write!(f, "Can't find glyph named {}", name),
+	    FontLoaderError::PropertyError(_) => write!(f, "Can't set the face or driver property"),
+	    FontLoaderError::Cancelled => write!(f, "The font task was cancelled before it completed"),
+	}
+    }
+}
+
+impl Debug for FontLoaderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+	match self {
+	    FontLoaderError::LibraryError(_) => write!(f, "Can't initialize the FreeType library"),
+	    FontLoaderError::FaceError(_) => write!(f, "Can't load font face"),
+	    FontLoaderError::SizeError(_) => write!(f, "Can't set the font size"),
+	    FontLoaderError::BitmapError => write!(f, "Can't convert the glyph bitmap"),
+	    FontLoaderError::LoadError(c, _) => write!(f, "Can't load character {}", c),
+	    FontLoaderError::GlyphIndexError(index, _) => write!(f, "Can't load glyph {}", index),
+	    FontLoaderError::FamilyNotFound(family) => write!(f, "Can't find font family {}", family),
+	    FontLoaderError::CharMapError(index, _) => write!(f, "Can't select charmap {}", index),
+	    FontLoaderError::GlyphNameNotFound(name) => write!(f, "Can't find glyph named {}", name),
+	    FontLoaderError::PropertyError(_) => write!(f, "Can't set the face or driver property"),
+	    FontLoaderError::Cancelled => write!(f, "The font task was cancelled before it completed"),
+	}
+    }
+}
+
+impl std::error::Error for FontLoaderError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+	match self {
+	    FontLoaderError::LibraryError(error) => Some(error),
+	    FontLoaderError::FaceError(error) => Some(error),
+	    FontLoaderError::SizeError(error) => Some(error),
+	    FontLoaderError::LoadError(_, error) => Some(error),
+	    FontLoaderError::GlyphIndexError(_, error) => Some(error),
+	    FontLoaderError::CharMapError(_, error) => Some(error),
+	    FontLoaderError::PropertyError(error) => Some(error),
+	    FontLoaderError::BitmapError | FontLoaderError::FamilyNotFound(_) | FontLoaderError::GlyphNameNotFound(_) | FontLoaderError::Cancelled => None,
+	}
+    }
+}
@@ -1,51 +1,348 @@
-use std::ffi::OsStr;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-use freetype::{face::LoadFlag};
+use clap::{Parser, ValueEnum};
+use image::{GrayImage, Luma};
+use serde::Deserialize;
 
-use font::{atlas::{AtlasGenerator, AtlasGeneratorOption, Padding, AtlasLoadMode}};
+use font::atlas::{AtlasGenerator, AtlasGeneratorError, AtlasLoadMode, FontAtlas, Padding, GLYPHS};
+use font::charset::Charset;
+use font::preview::generate_html_preview;
+use font::loader::FontLoaderError;
 
-const FONT_DIRECTORY: &str = "/home/corendos/dev/rust/font/resources/fonts";
-const FONT_SIZE: u32 = 30 * 64;
+/// Bakes the glyphs of a font into a texture atlas and its metadata.
+#[derive(Parser)]
+#[command(name = "font-bake", version)]
+struct Args {
+    /// Path of a TOML or JSON file listing several atlases to bake in parallel, instead of a single one.
+    #[arg(long, conflicts_with_all = ["font", "size", "charset", "charset_file", "mode", "width", "height", "padding", "rotate", "out", "meta", "preview"])]
+    config: Option<PathBuf>,
+    /// Path of the font file.
+    #[arg(long, required_unless_present = "config")]
+    font: Option<PathBuf>,
+    /// Height of the glyphs, in pixels.
+    #[arg(long, default_value_t = default_size())]
+    size: u32,
+    /// Characters to bake: `ascii`, `latin1`, `default`, or the characters themselves.
+    #[arg(long, default_value_t = default_charset())]
+    charset: String,
+    /// Files whose characters are added to the charset, only string values being read from JSON files. Can be repeated.
+    #[arg(long)]
+    charset_file: Vec<PathBuf>,
+    /// How glyphs are rendered.
+    #[arg(long, value_enum, default_value_t = Mode::Gray)]
+    mode: Mode,
+    /// Width of the atlas, in pixels.
+    #[arg(long, default_value_t = default_atlas_size())]
+    width: u32,
+    /// Height of the atlas, in pixels.
+    #[arg(long, default_value_t = default_atlas_size())]
+    height: u32,
+    /// Empty pixels around each glyph.
+    #[arg(long, default_value_t = default_padding())]
+    padding: u32,
+    /// Packs the glyphs that don't fit upright rotated 90° clockwise.
+    #[arg(long)]
+    rotate: bool,
+    /// Path of the image of the atlas, whose format follows its extension.
+    #[arg(long, required_unless_present = "config")]
+    out: Option<PathBuf>,
+    /// Path of the JSON file describing the glyphs of the atlas.
+    #[arg(long)]
+    meta: Option<PathBuf>,
+    /// Path of an HTML page showing each glyph, its metrics and its place in the atlas.
+    #[arg(long)]
+    preview: Option<PathBuf>,
+}
+
+/// An atlas to bake, from the command line or from a config file where only `font` and `out` are required.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Job {
+    font: PathBuf,
+    #[serde(default = "default_size")]
+    size: u32,
+    #[serde(default = "default_charset")]
+    charset: String,
+    #[serde(default)]
+    charset_files: Vec<PathBuf>,
+    #[serde(default)]
+    mode: Mode,
+    #[serde(default = "default_atlas_size")]
+    width: u32,
+    #[serde(default = "default_atlas_size")]
+    height: u32,
+    #[serde(default = "default_padding")]
+    padding: u32,
+    #[serde(default)]
+    rotate: bool,
+    out: PathBuf,
+    meta: Option<PathBuf>,
+    preview: Option<PathBuf>,
+}
 
-fn get_fonts() -> Vec<String> {
+fn default_size() -> u32 {
+    32
+}
 
-    let mut font_paths = Vec::<String>::new();
+fn default_charset() -> String {
+    String::from("default")
+}
 
-    std::fs::read_dir(FONT_DIRECTORY).unwrap().for_each(|dir| {
-	if let Ok(dir_entry) = dir {
-	    if dir_entry.path().as_path().extension() == Some(OsStr::new("ttf")) {
-		font_paths.push(String::from(dir_entry.path().to_str().unwrap()));
-	    }
+fn default_atlas_size() -> u32 {
+    512
+}
+
+fn default_padding() -> u32 {
+    1
+}
+
+/// The jobs of a config file, as `[[jobs]]` tables in TOML or a `jobs` array in JSON.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    jobs: Vec<Job>,
+}
+
+#[derive(Default, Copy, Clone, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Mode {
+    #[default]
+    Gray,
+    Lcd,
+}
+
+impl From<Mode> for AtlasLoadMode {
+    fn from(mode: Mode) -> Self {
+	match mode {
+	    Mode::Gray => AtlasLoadMode::Gray,
+	    Mode::Lcd => AtlasLoadMode::LCD,
 	}
-    });
+    }
+}
+
+/// The ways baking can fail, each exiting with its own code.
+enum BakeError {
+    Generator(AtlasGeneratorError),
+    Write(PathBuf, std::io::Error),
+    Config(PathBuf, String),
+    Read(PathBuf, std::io::Error),
+}
+
+impl BakeError {
+    /// Returns the exit code of the error, 2 being used by clap for invalid arguments.
+    fn exit_code(&self) -> u8 {
+	match self {
+	    BakeError::Generator(AtlasGeneratorError::NoFont) => 3,
+	    BakeError::Generator(AtlasGeneratorError::LoadError(FontLoaderError::LibraryError(_))) => 3,
+	    BakeError::Generator(AtlasGeneratorError::LoadError(FontLoaderError::FaceError(_))) => 3,
+	    #[cfg(feature = "ab-glyph")]
+	    BakeError::Generator(AtlasGeneratorError::FontError(_)) => 3,
+	    BakeError::Generator(AtlasGeneratorError::LoadError(_) | AtlasGeneratorError::Cancelled | AtlasGeneratorError::HookError(_)) => 4,
+	    BakeError::Generator(AtlasGeneratorError::InsertError(_)) => 5,
+	    BakeError::Write(..) => 6,
+	    BakeError::Config(..) => 7,
+	    BakeError::Read(..) => 8,
+	}
+    }
+}
+
+impl From<AtlasGeneratorError> for BakeError {
+    fn from(e: AtlasGeneratorError) -> Self {
+	BakeError::Generator(e)
+    }
+}
+
+impl Display for BakeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+	match self {
+	    BakeError::Generator(AtlasGeneratorError::InsertError(e)) => write!(f, "The atlas is too small for the charset ({}), try a larger atlas width and height", e),
+	    BakeError::Generator(e) => write!(f, "{}", e),
+	    BakeError::Write(path, e) => write!(f, "Can't write {}: {}", path.display(), e),
+	    BakeError::Config(path, e) => write!(f, "Invalid config {}: {}", path.display(), e),
+	    BakeError::Read(path, e) => write!(f, "Can't read {}: {}", path.display(), e),
+	}
+    }
+}
+
+/// Returns the characters of a named charset, or `charset` itself if it isn't a known name.
+fn resolve_charset(charset: &str) -> String {
+    match charset {
+	"ascii" => (' '..='~').collect(),
+	"latin1" => (' '..='~').chain('\u{a0}'..='\u{ff}').collect(),
+	"default" => String::from(GLYPHS),
+	charset => String::from(charset),
+    }
+}
+
+/// Returns the JSON description of the glyphs of `atlas`, sorted by character.
+fn metadata(atlas: &FontAtlas, job: &Job) -> serde_json::Value {
+    let mut entries: Vec<_> = atlas.map.iter().collect();
+    entries.sort_by_key(|(c, _)| **c);
+
+    let glyphs: Vec<serde_json::Value> = entries.into_iter().map(|(c, entry)| {
+	let (position, metrics) = (entry.position(), entry.metrics());
+	serde_json::json!({
+	    "char": c.to_string(),
+	    "codepoint": *c as u32,
+	    "x": position.left,
+	    "y": position.top,
+	    "width": position.width,
+	    "height": position.height,
+	    "bearing_x": metrics.bearing_x,
+	    "bearing_y": metrics.bearing_y,
+	    "advance": metrics.advance,
+	    "rotated": entry.is_rotated(),
+	})
+    }).collect();
+
+    serde_json::json!({
+	"font": job.font.display().to_string(),
+	"size": job.size,
+	"mode": match job.mode { Mode::Gray => "gray", Mode::Lcd => "lcd" },
+	"width": atlas.width,
+	"height": atlas.height,
+	"ascender": atlas.line_metrics.ascender,
+	"descender": atlas.line_metrics.descender,
+	"line_height": atlas.line_metrics.line_height,
+	"hash": format!("{:016x}", atlas.content_hash()),
+	"fingerprint": atlas.fingerprint().map(|fingerprint| format!("{:016x}", fingerprint)),
+	"glyphs": glyphs,
+    })
+}
+
+/// Reads the jobs of the config file at `path`, JSON if its extension is `json` and TOML otherwise.
+/// Relative paths of the jobs are relative to the directory of the file.
+fn read_config(path: &Path) -> Result<Vec<Job>, BakeError> {
+    let config_error = |e: String| BakeError::Config(path.to_path_buf(), e);
+    let content = std::fs::read_to_string(path).map_err(|e| config_error(e.to_string()))?;
+
+    let config: Config = match path.extension().and_then(|extension| extension.to_str()) {
+	Some("json") => serde_json::from_str(&content).map_err(|e| config_error(e.to_string()))?,
+	_ => toml::from_str(&content).map_err(|e| config_error(e.to_string()))?,
+    };
+
+    let directory = path.parent().unwrap_or(Path::new(""));
+    Ok(config.jobs.into_iter().map(|mut job| {
+	job.font = directory.join(&job.font);
+	job.out = directory.join(&job.out);
+	job.charset_files = job.charset_files.iter().map(|file| directory.join(file)).collect();
+	job.meta = job.meta.map(|meta| directory.join(meta));
+	job.preview = job.preview.map(|preview| directory.join(preview));
+	job
+    }).collect())
+}
 
-    font_paths
+fn bake(job: &Job) -> Result<(), BakeError> {
+    let mut charset = Charset::from_text(&resolve_charset(&job.charset));
+    for file in job.charset_files.iter() {
+	charset.add_file(file).map_err(|e| BakeError::Read(file.clone(), e))?;
+    }
+
+    let mut builder = AtlasGenerator::builder()
+	.font(&job.font)
+	.size(job.width, job.height)
+	.dpi(72)
+	.padding(Padding::uniform(job.padding))
+	.mode(job.mode.into())
+	.charset(&charset.to_string())
+	.deterministic();
+    if job.rotate {
+	builder = builder.rotate();
+    }
+    let generator = builder.build()?;
+
+    // At 72 dpi a point is a pixel, and sizes are given in 26.6 fixed point
+    let atlas = generator.generate(job.size * 64)?;
+
+    let image = match job.mode {
+	// The channels of gray coverage are equal, and a luma conversion would round their weighted sum down
+	Mode::Gray => GrayImage::from_fn(atlas.width, atlas.height, |x, y| Luma([atlas.buffer.get_pixel(x, y)[0]])).save(&job.out),
+	Mode::Lcd => atlas.buffer.save(&job.out),
+    };
+    image.map_err(|e| BakeError::Write(job.out.clone(), e))?;
+
+    if let Some(meta) = &job.meta {
+	let json = serde_json::to_string_pretty(&metadata(&atlas, job)).expect("metadata is valid JSON");
+	std::fs::write(meta, json).map_err(|e| BakeError::Write(meta.clone(), e))?;
+    }
+
+    if let Some(preview) = &job.preview {
+	// The image is referenced by name next to the page, and by absolute path elsewhere
+	let image_src = match job.out.file_name() {
+	    Some(name) if job.out.parent() == preview.parent() => PathBuf::from(name),
+	    _ => job.out.canonicalize().map_err(|e| BakeError::Write(job.out.clone(), e))?,
+	};
+	generate_html_preview(&atlas, &image_src.to_string_lossy(), preview).map_err(|e| BakeError::Write(preview.clone(), e))?;
+    }
+
+    Ok(())
 }
 
-fn main() {
-    let fonts = get_fonts();
+/// Bakes all the jobs on as many threads as there are cores, returning the result of each job in order.
+fn bake_all(jobs: &[Job]) -> Vec<Result<(), BakeError>> {
+    let thread_count = std::thread::available_parallelism().map_or(1, |count| count.get()).min(jobs.len());
+    let next_job = AtomicUsize::new(0);
 
-    let generator = AtlasGenerator::new(
-	&fonts[1],
-	AtlasGeneratorOption::new(256, 256, 72, Padding::new(1, 1, 1, 1)),
-	AtlasLoadMode::LCD
-    );
+    // Fonts can't be shared between threads, so each job loads its own
+    let mut results: Vec<(usize, Result<(), BakeError>)> = std::thread::scope(|scope| {
+	let workers: Vec<_> = (0..thread_count).map(|_| scope.spawn(|| {
+	    let mut results = Vec::new();
+	    loop {
+		let index = next_job.fetch_add(1, Ordering::Relaxed);
+		match jobs.get(index) {
+		    Some(job) => results.push((index, bake(job))),
+		    None => return results,
+		}
+	    }
+	})).collect();
 
-    let font_atlas = generator.generate(FONT_SIZE).unwrap();
+	workers.into_iter().flat_map(|worker| worker.join().expect("bake jobs don't panic")).collect()
+    });
 
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
 
-    let start = std::time::Instant::now();
-    generator.load_glyph('é', LoadFlag::RENDER).unwrap();
-    let end = std::time::Instant::now();
+fn main() -> ExitCode {
+    let args = Args::parse();
 
-    println!("Took {} ns", (end - start).as_nanos());
-    font_atlas.buffer.save("output/glyphs.png").unwrap();
+    let jobs = match &args.config {
+	Some(config) => match read_config(config) {
+	    Ok(jobs) => jobs,
+	    Err(e) => {
+		eprintln!("font-bake: {}", e);
+		return ExitCode::from(e.exit_code());
+	    }
+	},
+	None => vec![Job {
+	    font: args.font.expect("clap requires --font without --config"),
+	    size: args.size,
+	    charset: args.charset,
+	    charset_files: args.charset_file,
+	    mode: args.mode,
+	    width: args.width,
+	    height: args.height,
+	    padding: args.padding,
+	    rotate: args.rotate,
+	    out: args.out.expect("clap requires --out without --config"),
+	    meta: args.meta,
+	    preview: args.preview,
+	}],
+    };
 
-    /*
-    let text = "Hello dlrow !";
+    // Every failed job is reported, the exit code being the one of the first failure
+    let mut exit_code = ExitCode::SUCCESS;
+    for (job, result) in jobs.iter().zip(bake_all(&jobs)) {
+	if let Err(e) = result {
+	    eprintln!("font-bake: {}: {}", job.out.display(), e);
+	    if exit_code == ExitCode::SUCCESS {
+		exit_code = ExitCode::from(e.exit_code());
+	    }
+	}
+    }
 
-    let buffer = generate_buffers_from_text(&text, &font_atlas, 0, 0);
-    generate_text_img(text, &font_atlas, "output/text.png");
-    println!("{:#?}", buffer);
-     */
+    exit_code
 }
@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+
+use freetype::face::KerningMode;
+
+use super::Rectangle;
+use super::atlas::{next_generation, AtlasLoadMode, FontAtlasEntry, GlyphAtlas, LineMetrics, PIXEL_DPI};
+use super::layout::{LayoutOptions, TextLayout, NO_BREAK_SPACE, SOFT_HYPHEN};
+use super::loader::{FontLoader, FontLoaderError};
+use super::multi_atlas::FontId;
+use super::pixels::Pixels;
+use super::script::is_default_ignorable;
+
+/// Measures text with the metrics of a font, without rendering any glyph nor packing an atlas, for headless services wrapping
+/// text like a client renderer does. The glyphs are measured the first time they are met, at the size an AtlasGenerator would
+/// set, so the layouts match those made with an atlas of the same font, size and load mode. Characters missing from the font
+/// measure like its .notdef glyph, as in atlases generated with one.
+pub struct TextMeasurer {
+    font_loader: FontLoader,
+    load_mode: AtlasLoadMode,
+    line_metrics: LineMetrics,
+    entries: HashMap<char, FontAtlasEntry>,
+    /// Renewed when characters are measured, see `GlyphAtlas::generation`.
+    generation: u64,
+}
+
+impl TextMeasurer {
+    /// Creates a measurer of the glyphs of `font_loader` at `size`, in 26.6 points at the given `dpi`, rendered in gray.
+    pub fn new(font_loader: FontLoader, size: u32, dpi: u32) -> Result<Self, FontLoaderError> {
+	// Like generators, whole sizes at the pixel DPI are set in pixels
+	match dpi == PIXEL_DPI && size % 64 == 0 {
+	    true => font_loader.set_pixel_sizes(size / 64)?,
+	    false => font_loader.set_char_size(size, dpi)?,
+	}
+
+	Ok(Self {
+	    line_metrics: font_loader.line_metrics(),
+	    font_loader,
+	    load_mode: AtlasLoadMode::Gray,
+	    entries: HashMap::new(),
+	    generation: next_generation(),
+	})
+    }
+
+    /// Measures the glyphs as rendered in `load_mode`, whose hinting can change their advances.
+    pub fn with_load_mode(mut self, load_mode: AtlasLoadMode) -> Self {
+	self.load_mode = load_mode;
+	self.entries.clear();
+	self.generation = next_generation();
+	self
+    }
+
+    /// Returns the loader of the measured font.
+    pub fn font_loader(&self) -> &FontLoader {
+	&self.font_loader
+    }
+
+    /// Returns the vertical metrics of the font at the measured size.
+    pub fn line_metrics(&self) -> LineMetrics {
+	self.line_metrics
+    }
+
+    /// Measures the characters of `text` that weren't yet, along with the space and hyphen layouts may draw.
+    pub fn prepare(&mut self, text: &str) -> Result<(), FontLoaderError> {
+	for c in text.chars().chain([' ', '-']) {
+	    if self.entries.contains_key(&c) || c.is_control() || is_default_ignorable(c) || c == SOFT_HYPHEN {
+		continue;
+	    }
+
+	    let metrics = self.font_loader.measure_glyph(c, self.load_mode.load_flags())?;
+	    let entry = FontAtlasEntry::new(Rectangle::default(), metrics).with_glyph_id(self.font_loader.glyph_index(c));
+	    self.entries.insert(c, entry);
+	    self.generation = next_generation();
+	}
+
+	Ok(())
+    }
+
+    /// Lays out `text` with `options`, measuring its characters first. The layout has no pixels to draw but its glyphs, lines
+    /// and extents are those of a layout made with an atlas.
+    pub fn layout(&mut self, text: &str, options: &LayoutOptions) -> Result<TextLayout, FontLoaderError> {
+	self.prepare(text)?;
+
+	Ok(TextLayout::with_options(text, self, options))
+    }
+
+    /// Returns the kerning between the characters `left` and `right` in pixels, grid-fitted like the advances. Layouts don't
+    /// apply it, so it is only for callers placing glyphs themselves.
+    pub fn kerning(&self, left: char, right: char) -> Result<i32, FontLoaderError> {
+	let face = self.font_loader.face();
+	match face.get_kerning(self.font_loader.glyph_index(left), self.font_loader.glyph_index(right), KerningMode::KerningDefault) {
+	    Ok(kerning) => Ok(kerning.x as i32 / 64),
+	    Err(error) => Err(FontLoaderError::LoadError(left, error)),
+	}
+    }
+
+    /// Returns the width in pixels of `text` on a single line, the sum of the advances of its characters and of the kerning
+    /// between them.
+    pub fn kerned_width(&mut self, text: &str) -> Result<i32, FontLoaderError> {
+	self.prepare(text)?;
+
+	let mut width = 0;
+	let mut previous = None;
+	for c in text.chars() {
+	    width += self.glyph_advance(FontId(0), c);
+	    if let Some(previous) = previous {
+		width += self.kerning(previous, c)?;
+	    }
+	    previous = Some(c);
+	}
+
+	Ok(width)
+    }
+}
+
+/// The measurer holds a single font, so the font is ignored, and has no pages.
+impl GlyphAtlas for TextMeasurer {
+    fn page_size(&self) -> (u32, u32) {
+	(0, 0)
+    }
+
+    fn glyph(&self, _font: FontId, c: char) -> Option<&FontAtlasEntry> {
+	match c {
+	    c if c.is_control() || is_default_ignorable(c) || c == SOFT_HYPHEN => None,
+	    NO_BREAK_SPACE if !self.entries.contains_key(&c) => self.entries.get(&' '),
+	    c => self.entries.get(&c).or_else(|| self.entries.get(&' ')),
+	}
+    }
+
+    fn font_metrics(&self, _font: FontId) -> LineMetrics {
+	self.line_metrics
+    }
+
+    fn page_buffer(&self, _page: usize) -> Option<&Pixels> {
+	None
+    }
+
+    fn generation(&self) -> u64 {
+	self.generation
+    }
+}
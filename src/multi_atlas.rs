@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use super::{Node, Rectangle};
+use super::atlas::{apply_gamma, next_generation, pack_glyph, AtlasGeneratorError, AtlasGeneratorOption, AtlasLoadMode, FontAtlasEntry, GlyphAtlas, LineMetrics};
+use super::pixels::{Background, PixelFormat, Pixels};
+use super::rasterizer::Rasterizer;
+use super::script::is_default_ignorable;
+
+/// An identifier of a font in a MultiFontAtlas, given in the order the fonts were added.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct FontId(pub usize);
+
+/// A page of a MultiFontAtlas, with the packing tree used to fill it.
+pub struct AtlasPage {
+    pub buffer: Pixels,
+    node: Node,
+    dirty_regions: Vec<Rectangle>,
+}
+
+impl AtlasPage {
+    /// Creates an empty page of given `size`.
+    pub fn new(size: (u32, u32)) -> Self {
+	Self {
+	    buffer: Pixels::new(size.0, size.1, PixelFormat::Rgb8),
+	    node: Node::new(Rectangle::new(0, 0, size.0, size.1)),
+	    dirty_regions: Vec::new(),
+	}
+    }
+
+    /// Creates an empty page of given `size` cleared to `background`.
+    pub fn with_background(size: (u32, u32), background: &Background) -> Self {
+	Self::with_format(size, PixelFormat::Rgb8, background)
+    }
+
+    /// Creates an empty page of given `size` and `format` cleared to `background`, RGBA pages keeping the alpha of color glyphs.
+    pub fn with_format(size: (u32, u32), format: PixelFormat, background: &Background) -> Self {
+	Self {
+	    buffer: Pixels::from_background(size.0, size.1, format, background),
+	    node: Node::new(Rectangle::new(0, 0, size.0, size.1)),
+	    dirty_regions: Vec::new(),
+	}
+    }
+
+    /// Records that `region` of the page changed and must be uploaded again.
+    pub fn mark_dirty(&mut self, region: Rectangle) {
+	if !region.is_empty() {
+	    self.dirty_regions.push(region);
+	}
+    }
+
+    /// Returns the regions of the page changed since the last call, leaving none dirty.
+    pub fn take_dirty_regions(&mut self) -> Vec<Rectangle> {
+	std::mem::take(&mut self.dirty_regions)
+    }
+}
+
+/// The pages of a MultiFontAtlas laid out as the layers of a texture array, to be uploaded at once.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct TextureArray {
+    /// The pixels of every layer, one after the other, each row after row without padding.
+    pub data: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+    pub layers: u32,
+    pub format: PixelFormat,
+    /// The regions of each layer changed since the last export, by layer.
+    pub dirty_regions: Vec<Vec<Rectangle>>,
+}
+
+impl TextureArray {
+    /// Returns the number of bytes of a layer.
+    pub fn layer_size(&self) -> usize {
+	self.width as usize * self.height as usize * self.format.channels()
+    }
+
+    /// Returns the pixels of the layer `layer`, or None past the last one.
+    pub fn layer(&self, layer: usize) -> Option<&[u8]> {
+	self.data.chunks_exact(self.layer_size().max(1)).nth(layer)
+    }
+}
+
+/// An atlas containing the glyphs of several fonts, spread across pages of the same size.
+pub struct MultiFontAtlas {
+    pub map: HashMap<(FontId, char), FontAtlasEntry>,
+    pub pages: Vec<AtlasPage>,
+    pub line_metrics: Vec<LineMetrics>,
+    pub width: u32,
+    pub height: u32,
+    /// Stamped at creation, as the entries are only changed while generating, see `GlyphAtlas::generation`.
+    generation: u64,
+}
+
+impl MultiFontAtlas {
+    /// Creates an empty atlas whose pages have the given `atlas_size`.
+    pub fn new(atlas_size: (u32, u32)) -> Self {
+	Self {
+	    map: HashMap::new(),
+	    pages: Vec::new(),
+	    line_metrics: Vec::new(),
+	    width: atlas_size.0,
+	    height: atlas_size.1,
+	    generation: next_generation(),
+	}
+    }
+
+    /// Returns the entry of the character `c` of the font `font_id`.
+    pub fn get(&self, font_id: FontId, c: char) -> Option<&FontAtlasEntry> {
+	self.map.get(&(font_id, c))
+    }
+
+    /// Returns the entry of the character `c` from the first font of `fonts` containing it.
+    pub fn get_with_fallback(&self, fonts: &[FontId], c: char) -> Option<(FontId, &FontAtlasEntry)> {
+	fonts.iter().find_map(|font_id| self.get(*font_id, c).map(|entry| (*font_id, entry)))
+    }
+
+    /// Returns the vertical metrics of the font `font_id`.
+    pub fn font_line_metrics(&self, font_id: FontId) -> Option<&LineMetrics> {
+	self.line_metrics.get(font_id.0)
+    }
+
+    /// Lays out the pages as the layers of a texture array, the layer of an entry being its page, with the regions of each
+    /// page changed since the last export, which leaves none dirty.
+    pub fn texture_array(&mut self) -> TextureArray {
+	let format = self.pages.first().map_or(PixelFormat::Rgb8, |page| page.buffer.format);
+	let mut data = Vec::with_capacity(self.width as usize * self.height as usize * format.channels() * self.pages.len());
+	for page in &self.pages {
+	    data.extend_from_slice(&page.buffer);
+	}
+
+	TextureArray {
+	    data,
+	    width: self.width,
+	    height: self.height,
+	    layers: self.pages.len() as u32,
+	    format,
+	    dirty_regions: self.pages.iter_mut().map(AtlasPage::take_dirty_regions).collect(),
+	}
+    }
+}
+
+/// Characters missing from a font are taken from the first other font containing them.
+impl GlyphAtlas for MultiFontAtlas {
+    fn page_size(&self) -> (u32, u32) {
+	(self.width, self.height)
+    }
+
+    fn glyph(&self, font: FontId, c: char) -> Option<&FontAtlasEntry> {
+	if c.is_control() || is_default_ignorable(c) {
+	    return None;
+	}
+
+	self.get(font, c).or_else(|| (0..self.line_metrics.len()).find_map(|index| self.get(FontId(index), c)))
+    }
+
+    fn has_glyph(&self, font: FontId, c: char) -> bool {
+	self.get(font, c).is_some()
+    }
+
+    fn font_metrics(&self, font: FontId) -> LineMetrics {
+	self.font_line_metrics(font).copied().unwrap_or_default()
+    }
+
+    fn page_buffer(&self, page: usize) -> Option<&Pixels> {
+	self.pages.get(page).map(|page| &page.buffer)
+    }
+
+    fn generation(&self) -> u64 {
+	self.generation
+    }
+}
+
+/// A struct generating a MultiFontAtlas from several fonts, each with its own charset and optionally its own size.
+/// A face is added once per size it is needed at, each getting its own FontId.
+pub struct MultiFontAtlasGenerator {
+    fonts: Vec<(Box<dyn Rasterizer>, String, Option<u32>)>,
+    load_mode: AtlasLoadMode,
+    options: AtlasGeneratorOption,
+}
+
+impl MultiFontAtlasGenerator {
+    /// Creates a generator without any font from the given options and load mode.
+    pub fn new(options: AtlasGeneratorOption, load_mode: AtlasLoadMode) -> Self {
+	Self {
+	    fonts: Vec::new(),
+	    load_mode,
+	    options,
+	}
+    }
+
+    /// Adds a font whose `charset` characters will be packed in the atlas.
+    pub fn add_font<R>(&mut self, rasterizer: R, charset: &str) -> FontId where R: Rasterizer + 'static {
+	self.fonts.push((Box::new(rasterizer), String::from(charset), None));
+	FontId(self.fonts.len() - 1)
+    }
+
+    /// Adds a font whose `charset` characters will be packed in the atlas at `size`, whatever the size given to `generate`.
+    pub fn add_font_sized<R>(&mut self, rasterizer: R, charset: &str, size: u32) -> FontId where R: Rasterizer + 'static {
+	self.fonts.push((Box::new(rasterizer), String::from(charset), Some(size)));
+	FontId(self.fonts.len() - 1)
+    }
+
+    /// Generate an atlas with the fonts added without size at size `size`, opening new pages when the current ones are full.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "info", skip(self), fields(fonts = self.fonts.len()), err(Display)))]
+    pub fn generate(&self, size: u32) -> Result<MultiFontAtlas, AtlasGeneratorError> {
+	let mut atlas = MultiFontAtlas::new(self.options.size);
+
+	for (index, (rasterizer, charset, font_size)) in self.fonts.iter().enumerate() {
+	    rasterizer.set_char_size(font_size.unwrap_or(size), self.options.dpi)?;
+	    rasterizer.set_transform(self.options.transform);
+	    rasterizer.set_stem_darkening(self.options.stem_darkening)?;
+	    for property in self.options.driver_properties.iter() {
+		rasterizer.set_driver_property(property)?;
+	    }
+
+	    atlas.line_metrics.push(rasterizer.line_metrics());
+
+	    for c in charset.chars() {
+		// Characters missing from this font are left to the other fonts of a fallback chain
+		if !rasterizer.has_glyph(c) {
+		    continue;
+		}
+
+		let mut glyph = rasterizer.rasterize(c, self.load_mode)?;
+		if self.options.crop {
+		    glyph = Arc::new(glyph.cropped());
+		}
+		if let Some(hook) = &self.options.glyph_hook {
+		    glyph = Arc::new(hook.apply(&glyph, c)?);
+		}
+
+		let mut packed = None;
+		for (page_index, page) in atlas.pages.iter_mut().enumerate() {
+		    if let Ok(position) = pack_glyph(&mut page.node, &mut page.buffer, &glyph.bitmap, self.options.padding_for(c), self.options.rotate) {
+			packed = Some((page_index, position));
+			break;
+		    }
+		}
+
+		// No page has room left, so the glyph goes on a new one
+		let (page_index, (position, rotated)) = match packed {
+		    Some(packed) => packed,
+		    None => {
+			#[cfg(feature = "tracing")]
+			tracing::debug!(page = atlas.pages.len(), ?c, "page opened");
+			let mut page = AtlasPage::with_format(self.options.size, self.load_mode.pixel_format(), &self.options.background);
+			let position = pack_glyph(&mut page.node, &mut page.buffer, &glyph.bitmap, self.options.padding_for(c), self.options.rotate)?;
+			atlas.pages.push(page);
+			(atlas.pages.len() - 1, position)
+		    }
+		};
+
+		if !glyph.color {
+		    apply_gamma(&mut atlas.pages[page_index].buffer, &position, self.options.gamma);
+		}
+		atlas.pages[page_index].mark_dirty(position);
+		let entry = FontAtlasEntry::new(position, glyph.metrics)
+		    .with_page(page_index)
+		    .with_font(FontId(index))
+		    .with_glyph_id(rasterizer.glyph_index(c))
+		    .with_rotation(rotated)
+		    .with_color(glyph.color);
+		atlas.map.insert((FontId(index), c), entry);
+
+		#[cfg(feature = "tracing")]
+		tracing::trace!(font = index, ?c, page = page_index, left = position.left, top = position.top, rotated, "glyph packed");
+	    }
+	}
+
+	Ok(atlas)
+    }
+}
@@ -0,0 +1,98 @@
+/// The number of segments a Bézier curve is flattened to.
+const CURVE_SEGMENTS: usize = 16;
+
+/// A path text is laid along, made of straight segments, curves being flattened when added.
+/// Its points are in the space of the vertices generated from layouts, so y goes up unless the layout config says otherwise.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextPath {
+    points: Vec<[f32; 2]>,
+    /// The distance along the path of each point.
+    distances: Vec<f32>,
+}
+
+impl TextPath {
+    /// Creates a path starting at `start`.
+    pub fn new(start: [f32; 2]) -> Self {
+	Self {
+	    points: vec![start],
+	    distances: vec![0.0],
+	}
+    }
+
+    /// Creates a path going through `points`, in order.
+    pub fn from_points(points: &[[f32; 2]]) -> Self {
+	let mut path = Self::new(points.first().copied().unwrap_or_default());
+	for point in points.iter().skip(1) {
+	    path = path.line_to(*point);
+	}
+	path
+    }
+
+    /// Adds a straight segment from the last point to `point`. A segment of zero length is ignored.
+    pub fn line_to(mut self, point: [f32; 2]) -> Self {
+	let last = *self.points.last().unwrap();
+	let length = (point[0] - last[0]).hypot(point[1] - last[1]);
+	if length > 0.0 {
+	    self.points.push(point);
+	    self.distances.push(self.length() + length);
+	}
+	self
+    }
+
+    /// Adds a quadratic Bézier curve from the last point to `point`, with the control point `control`.
+    pub fn quadratic_to(self, control: [f32; 2], point: [f32; 2]) -> Self {
+	let start = *self.points.last().unwrap();
+	self.flatten(|t| {
+	    let u = 1.0 - t;
+	    let at = |axis: usize| u * u * start[axis] + 2.0 * u * t * control[axis] + t * t * point[axis];
+	    [at(0), at(1)]
+	})
+    }
+
+    /// Adds a cubic Bézier curve from the last point to `point`, with the control points `first` and `second`.
+    pub fn cubic_to(self, first: [f32; 2], second: [f32; 2], point: [f32; 2]) -> Self {
+	let start = *self.points.last().unwrap();
+	self.flatten(|t| {
+	    let u = 1.0 - t;
+	    let at = |axis: usize| u * u * u * start[axis] + 3.0 * u * u * t * first[axis] + 3.0 * u * t * t * second[axis] + t * t * t * point[axis];
+	    [at(0), at(1)]
+	})
+    }
+
+    /// Adds the segments joining the points of `curve` from t = 0 to 1.
+    fn flatten<F>(mut self, curve: F) -> Self where F: Fn(f32) -> [f32; 2] {
+	for segment in 1..=CURVE_SEGMENTS {
+	    self = self.line_to(curve(segment as f32 / CURVE_SEGMENTS as f32));
+	}
+	self
+    }
+
+    /// Returns the points of the path, curves flattened.
+    pub fn points(&self) -> &[[f32; 2]] {
+	&self.points
+    }
+
+    /// Returns the length of the path.
+    pub fn length(&self) -> f32 {
+	*self.distances.last().unwrap()
+    }
+
+    /// Returns the point at `distance` along the path and the unit tangent there, or None if the distance is outside of the path.
+    pub fn sample(&self, distance: f32) -> Option<([f32; 2], [f32; 2])> {
+	if self.points.len() < 2 || !(0.0..=self.length()).contains(&distance) {
+	    return None;
+	}
+
+	// The segment ending at or after the distance
+	let end = self.distances.iter().position(|end| *end >= distance).unwrap_or(self.distances.len() - 1).max(1);
+	let (start, end) = (end - 1, end);
+	let length = self.distances[end] - self.distances[start];
+	let t = (distance - self.distances[start]) / length;
+
+	let [x0, y0] = self.points[start];
+	let [x1, y1] = self.points[end];
+	let tangent = [(x1 - x0) / length, (y1 - y0) / length];
+
+	Some(([x0 + (x1 - x0) * t, y0 + (y1 - y0) * t], tangent))
+    }
+}
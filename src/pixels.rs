@@ -0,0 +1,312 @@
+use std::fmt::Display;
+use std::ops::{Deref, DerefMut};
+#[cfg(all(feature = "image", not(target_arch = "wasm32")))]
+use std::path::Path;
+
+use super::Rectangle;
+
+/// The layout of the bytes of a pixel.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum PixelFormat {
+    #[default]
+    Rgb8,
+    Rgba8,
+}
+
+impl PixelFormat {
+    /// Returns the number of bytes of a pixel.
+    pub fn channels(&self) -> usize {
+	match self {
+	    PixelFormat::Rgb8 => 3,
+	    PixelFormat::Rgba8 => 4,
+	}
+    }
+}
+
+/// An error telling that pixels don't have the format an operation needs.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PixelFormatError {
+    pub expected: PixelFormat,
+    pub found: PixelFormat,
+}
+
+impl PixelFormatError {
+    /// Creates an error for pixels in the `found` format where the `expected` one was needed.
+    pub fn new(expected: PixelFormat, found: PixelFormat) -> Self {
+	Self { expected, found }
+    }
+}
+
+impl Display for PixelFormatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+	write!(f, "Expected {:?} pixels, found {:?} ones", self.expected, self.found)
+    }
+}
+
+impl std::error::Error for PixelFormatError {}
+
+/// The value the pixels of an atlas are cleared to where nothing is drawn, other than zero to spot during development the
+/// padding and the texels that bleed in when glyphs are sampled.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Background {
+    /// Zero coverage, black, the only background drawing nothing around the glyphs.
+    #[default]
+    Transparent,
+    Color([u8; 3]),
+    /// Squares of `size` pixels alternating between the two colors, starting with the first one at the top left.
+    Checkerboard { size: u32, colors: [[u8; 3]; 2] },
+}
+
+impl Background {
+    /// A magenta background, which no glyph has.
+    pub const MAGENTA: Background = Background::Color([255, 0, 255]);
+
+    /// Returns a checkerboard of magenta and black squares of `size` pixels.
+    pub fn checkerboard(size: u32) -> Self {
+	Background::Checkerboard { size, colors: [[255, 0, 255], [0, 0, 0]] }
+    }
+
+    /// Returns the opaque pixel of the background at (`x`, `y`).
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+	let [r, g, b] = match self {
+	    Background::Transparent => [0, 0, 0],
+	    Background::Color(color) => *color,
+	    Background::Checkerboard { size, colors } => colors[((x / (*size).max(1) + y / (*size).max(1)) % 2) as usize],
+	};
+
+	[r, g, b, 255]
+    }
+}
+
+/// A bitmap stored row after row without padding, dereferencing to its bytes.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Pixels {
+    pub data: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+    pub format: PixelFormat,
+}
+
+impl Pixels {
+    /// Creates a `width` by `height` bitmap of black, and transparent if it has alpha, pixels.
+    pub fn new(width: u32, height: u32, format: PixelFormat) -> Self {
+	Self::from_pixel(width, height, format, &vec![0; format.channels()])
+    }
+
+    /// Creates a `width` by `height` bitmap filled with `pixel`, whose length is the number of channels of `format`.
+    pub fn from_pixel(width: u32, height: u32, format: PixelFormat, pixel: &[u8]) -> Self {
+	let data = pixel.iter().copied().cycle().take(width as usize * height as usize * format.channels()).collect();
+
+	Self { data, width, height, format }
+    }
+
+    /// Creates a `width` by `height` bitmap filled with `background`.
+    pub fn from_background(width: u32, height: u32, format: PixelFormat, background: &Background) -> Self {
+	match background {
+	    Background::Transparent => Self::new(width, height, format),
+	    background => Self::from_fn(width, height, format, |x, y| background.pixel(x, y)),
+	}
+    }
+
+    /// Creates a bitmap from its bytes, or returns None if there aren't as many as its pixels need.
+    pub fn from_raw(width: u32, height: u32, format: PixelFormat, data: Vec<u8>) -> Option<Self> {
+	(data.len() == width as usize * height as usize * format.channels()).then_some(Self { data, width, height, format })
+    }
+
+    /// Creates a bitmap from the pixels returned by `f` for each coordinate, whose length is the number of channels of `format`.
+    pub fn from_fn<F>(width: u32, height: u32, format: PixelFormat, mut f: F) -> Self where F: FnMut(u32, u32) -> [u8; 4] {
+	let channels = format.channels();
+	let mut data = Vec::with_capacity(width as usize * height as usize * channels);
+
+	for y in 0..height {
+	    for x in 0..width {
+		data.extend_from_slice(&f(x, y)[..channels]);
+	    }
+	}
+
+	Self { data, width, height, format }
+    }
+
+    /// Returns the width and height of the bitmap.
+    pub fn dimensions(&self) -> (u32, u32) {
+	(self.width, self.height)
+    }
+
+    /// Returns the number of bytes of a row, rows being stored without padding.
+    pub fn stride(&self) -> usize {
+	self.width as usize * self.format.channels()
+    }
+
+    /// Returns the bytes of the pixel at (`x`, `y`).
+    pub fn get_pixel(&self, x: u32, y: u32) -> &[u8] {
+	let start = self.offset(x, y);
+	&self.data[start..start + self.format.channels()]
+    }
+
+    /// Returns the bytes of the pixel at (`x`, `y`) to modify them.
+    pub fn get_pixel_mut(&mut self, x: u32, y: u32) -> &mut [u8] {
+	let start = self.offset(x, y);
+	&mut self.data[start..start + self.format.channels()]
+    }
+
+    /// Returns the offset of the first byte of the pixel at (`x`, `y`).
+    fn offset(&self, x: u32, y: u32) -> usize {
+	(y as usize * self.width as usize + x as usize) * self.format.channels()
+    }
+
+    /// Returns the smallest region containing every pixel with a non-zero byte, or None if the bitmap is blank.
+    pub fn ink_bounds(&self) -> Option<Rectangle> {
+	let inked = |x: u32, y: u32| self.get_pixel(x, y).iter().any(|byte| *byte != 0);
+	let inked_row = |y: u32| (0..self.width).any(|x| inked(x, y));
+	let inked_column = |x: u32| (0..self.height).any(|y| inked(x, y));
+
+	let top = (0..self.height).find(|y| inked_row(*y))?;
+	let bottom = (top..self.height).rev().find(|y| inked_row(*y))?;
+	let left = (0..self.width).find(|x| inked_column(*x))?;
+	let right = (left..self.width).rev().find(|x| inked_column(*x))?;
+
+	Some(Rectangle::new(top, left, right - left + 1, bottom - top + 1))
+    }
+
+    /// Returns the pixels of `region`, which must be inside the bitmap.
+    pub fn crop(&self, region: &Rectangle) -> Pixels {
+	let row_length = region.width as usize * self.format.channels();
+	let mut data = Vec::with_capacity(row_length * region.height as usize);
+
+	for y in region.top..region.top + region.height {
+	    let start = self.offset(region.left, y);
+	    data.extend_from_slice(&self.data[start..start + row_length]);
+	}
+
+	Self { data, width: region.width, height: region.height, format: self.format }
+    }
+
+    /// Copies `other`, of the same format, with its top-left corner at (`x`, `y`). Returns false without copying anything
+    /// if it doesn't fit.
+    pub fn copy_from(&mut self, other: &Pixels, x: u32, y: u32) -> bool {
+	if other.format != self.format || x as u64 + other.width as u64 > self.width as u64 || y as u64 + other.height as u64 > self.height as u64 {
+	    return false;
+	}
+
+	let row_length = other.width as usize * other.format.channels();
+	for (row, source) in other.data.chunks_exact(row_length.max(1)).enumerate().take(other.height as usize) {
+	    let start = self.offset(x, y + row as u32);
+	    self.data[start..start + row_length].copy_from_slice(source);
+	}
+
+	true
+    }
+
+    /// Returns the bitmap rotated 90° clockwise, pixel (x, y) moving to (height - 1 - y, x).
+    pub fn rotate90(&self) -> Pixels {
+	Self::from_fn(self.height, self.width, self.format, |x, y| {
+	    let mut pixel = [0; 4];
+	    let source = self.get_pixel(y, self.height - 1 - x);
+	    pixel[..source.len()].copy_from_slice(source);
+	    pixel
+	})
+    }
+
+    /// Returns the bitmap converted to `format`. Coverage gets the alpha of its brightest channel, gray coverage becoming
+    /// premultiplied white, and dropping the alpha of premultiplied colors leaves them over black.
+    pub fn to_format(&self, format: PixelFormat) -> Pixels {
+	Self::from_fn(self.width, self.height, format, |x, y| to_rgba(self.get_pixel(x, y)))
+    }
+
+    /// Saves the bitmap to `path`, in the format given by its extension.
+    #[cfg(all(feature = "image", not(target_arch = "wasm32")))]
+    pub fn save<P>(&self, path: P) -> std::io::Result<()> where P: AsRef<Path> {
+	let color = match self.format {
+	    PixelFormat::Rgb8 => image::ColorType::RGB(8),
+	    PixelFormat::Rgba8 => image::ColorType::RGBA(8),
+	};
+
+	image::save_buffer(path, &self.data, self.width, self.height, color)
+    }
+}
+
+/// Returns `pixel`, of 1, 3 or 4 bytes, as a premultiplied RGBA pixel, coverage getting the alpha of its brightest channel.
+pub(crate) fn to_rgba(pixel: &[u8]) -> [u8; 4] {
+    match *pixel {
+	[value] => [value; 4],
+	[r, g, b] => [r, g, b, r.max(g).max(b)],
+	[r, g, b, a] => [r, g, b, a],
+	_ => [0; 4],
+    }
+}
+
+/// The number of gray values `expand_gray` expands at once.
+const GRAY_BLOCK: usize = 16;
+
+/// Writes each value of `gray` to the 3 channels of a pixel of `rgb`, which holds 3 bytes per value. The values are expanded by
+/// fixed size blocks, whose shuffles the compiler vectorizes on stable Rust, `std::simd` being nightly only.
+pub fn expand_gray(gray: &[u8], rgb: &mut [u8]) {
+    let rgb = &mut rgb[..gray.len() * 3];
+    let (rgb_blocks, rgb_rest) = rgb.split_at_mut(gray.len() / GRAY_BLOCK * GRAY_BLOCK * 3);
+    let gray_blocks = gray.chunks_exact(GRAY_BLOCK);
+
+    for (pixels, values) in rgb_blocks.chunks_exact_mut(GRAY_BLOCK * 3).zip(gray_blocks.clone()) {
+	let mut block = [0; GRAY_BLOCK * 3];
+	for (pixel, value) in block.chunks_exact_mut(3).zip(values) {
+	    pixel.fill(*value);
+	}
+	pixels.copy_from_slice(&block);
+    }
+
+    for (pixel, value) in rgb_rest.chunks_exact_mut(3).zip(gray_blocks.remainder()) {
+	pixel.fill(*value);
+    }
+}
+
+impl Deref for Pixels {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+	&self.data
+    }
+}
+
+impl DerefMut for Pixels {
+    fn deref_mut(&mut self) -> &mut [u8] {
+	&mut self.data
+    }
+}
+
+#[cfg(feature = "image")]
+impl From<image::RgbImage> for Pixels {
+    fn from(image: image::RgbImage) -> Self {
+	let (width, height) = image.dimensions();
+	Self { data: image.into_raw(), width, height, format: PixelFormat::Rgb8 }
+    }
+}
+
+#[cfg(feature = "image")]
+impl From<image::RgbaImage> for Pixels {
+    fn from(image: image::RgbaImage) -> Self {
+	let (width, height) = image.dimensions();
+	Self { data: image.into_raw(), width, height, format: PixelFormat::Rgba8 }
+    }
+}
+
+#[cfg(feature = "image")]
+impl From<image::DynamicImage> for Pixels {
+    fn from(image: image::DynamicImage) -> Self {
+	match image {
+	    image::DynamicImage::ImageRgb8(image) => image.into(),
+	    image => image.to_rgba().into(),
+	}
+    }
+}
+
+#[cfg(feature = "image")]
+impl From<Pixels> for image::DynamicImage {
+    fn from(pixels: Pixels) -> Self {
+	let (width, height) = pixels.dimensions();
+
+	// The length of the bytes always matches the dimensions, so the conversions can't fail
+	match pixels.format {
+	    PixelFormat::Rgb8 => image::DynamicImage::ImageRgb8(image::RgbImage::from_raw(width, height, pixels.data).unwrap_or_else(|| image::RgbImage::new(width, height))),
+	    PixelFormat::Rgba8 => image::DynamicImage::ImageRgba8(image::RgbaImage::from_raw(width, height, pixels.data).unwrap_or_else(|| image::RgbaImage::new(width, height))),
+	}
+    }
+}
@@ -0,0 +1,79 @@
+use std::fmt::Write as _;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+
+use super::atlas::{FontAtlas, FontAtlasEntry};
+
+/// How much the atlas and the glyphs are magnified in the preview.
+const ZOOM: u32 = 2;
+
+/// Saves an HTML page showing every glyph of `atlas` with its codepoint, metrics and position, and the atlas with the outline of each entry.
+/// `image_src` is the location of the image of the atlas relative to the page, for instance the path given to `buffer.save`.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn generate_html_preview<P>(atlas: &FontAtlas, image_src: &str, save_path: P) -> std::io::Result<()> where P: AsRef<Path> {
+    std::fs::write(save_path, html_preview(atlas, image_src))
+}
+
+/// Returns the HTML page saved by `generate_html_preview`, `image_src` being the URL of the image of the atlas.
+pub fn html_preview(atlas: &FontAtlas, image_src: &str) -> String {
+    let mut entries: Vec<(Option<char>, &FontAtlasEntry)> = atlas.map.iter().map(|(c, entry)| (Some(*c), entry)).collect();
+    entries.sort_by_key(|(c, _)| *c);
+    entries.extend(atlas.notdef.iter().map(|notdef| (None, notdef)));
+
+    // Entities aren't decoded in style elements, so the characters ending the url are percent encoded instead
+    let image_src = image_src.replace('"', "%22").replace('<', "%3C");
+    let mut html = String::new();
+
+    // Writing to a String never fails
+    let _ = write!(html, "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Font atlas preview</title>\n<style>\n\
+	body {{ font-family: sans-serif; background: #222; color: #ddd; }}\n\
+	table {{ border-collapse: collapse; }}\n\
+	td, th {{ border: 1px solid #444; padding: 2px 8px; text-align: right; }}\n\
+	.glyph {{ background-image: url(\"{src}\"); background-size: {width}px {height}px; image-rendering: pixelated; outline: 1px solid #555; margin: 4px auto; }}\n\
+	.atlas {{ position: relative; width: {width}px; height: {height}px; background-image: url(\"{src}\"); background-size: 100%; image-rendering: pixelated; }}\n\
+	.atlas div {{ position: absolute; outline: 1px solid rgba(255, 64, 64, 0.6); }}\n\
+	</style>\n</head>\n<body>\n",
+	src = image_src, width = atlas.width * ZOOM, height = atlas.height * ZOOM);
+
+    let _ = write!(html, "<h1>{} glyphs in a {}x{} atlas</h1>\n<p>Ascender {}, descender {}, line height {}</p>\n",
+	entries.len(), atlas.width, atlas.height, atlas.line_metrics.ascender, atlas.line_metrics.descender, atlas.line_metrics.line_height);
+
+    html.push_str("<table>\n<tr><th>Glyph</th><th>Char</th><th>Codepoint</th><th>Width</th><th>Height</th><th>Bearing x</th><th>Bearing y</th><th>Advance</th><th>Atlas x</th><th>Atlas y</th><th>Page</th><th>Rotated</th></tr>\n");
+    for (c, entry) in entries.iter() {
+	let (metrics, position) = (entry.metrics(), entry.position());
+	let _ = writeln!(html, "<tr><td><div class=\"glyph\" style=\"width: {}px; height: {}px; background-position: -{}px -{}px\"></div></td>\
+	    <td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+	    position.width * ZOOM, position.height * ZOOM, position.left * ZOOM, position.top * ZOOM,
+	    c.map_or(String::new(), |c| escape(&c.to_string())), codepoint(*c), metrics.width, metrics.height, metrics.bearing_x, metrics.bearing_y, metrics.advance,
+	    position.left, position.top, entry.page(), if entry.is_rotated() { "yes" } else { "" });
+    }
+    html.push_str("</table>\n<h2>Atlas</h2>\n<div class=\"atlas\">\n");
+
+    for (c, entry) in entries.iter().filter(|(_, entry)| !entry.is_blank()) {
+	let position = entry.position();
+	let _ = writeln!(html, "<div title=\"{}\" style=\"left: {}px; top: {}px; width: {}px; height: {}px\"></div>",
+	    codepoint(*c), position.left * ZOOM, position.top * ZOOM, position.width * ZOOM, position.height * ZOOM);
+    }
+    html.push_str("</div>\n</body>\n</html>\n");
+
+    html
+}
+
+/// Returns the codepoint of `c` in the U+XXXX notation, or `.notdef` for the glyph of missing characters.
+fn codepoint(c: Option<char>) -> String {
+    c.map_or_else(|| String::from(".notdef"), |c| format!("U+{:04X}", c as u32))
+}
+
+/// Escapes the characters of `text` that have a meaning in HTML.
+fn escape(text: &str) -> String {
+    text.chars().fold(String::with_capacity(text.len()), |mut escaped, c| {
+	match c {
+	    '&' => escaped.push_str("&amp;"),
+	    '<' => escaped.push_str("&lt;"),
+	    '>' => escaped.push_str("&gt;"),
+	    '"' => escaped.push_str("&quot;"),
+	    c => escaped.push(c),
+	}
+	escaped
+    })
+}
@@ -0,0 +1,181 @@
+use std::sync::Arc;
+
+use super::{Glyph, GlyphMetrics, Rectangle, Transform};
+use super::atlas::{AtlasGeneratorError, AtlasLoadMode, LineMetrics, PIXEL_DPI};
+#[cfg(any(feature = "freetype", feature = "ab-glyph"))]
+use super::atlas::ContentHasher;
+use super::pixels::Pixels;
+#[cfg(any(feature = "freetype", feature = "ab-glyph"))]
+use super::pixels::PixelFormat;
+
+/// A FreeType module with properties, see DriverProperty.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum DriverModule {
+    TrueType,
+    Cff,
+    Type1,
+    T1Cid,
+    Autofitter,
+}
+
+impl DriverModule {
+    /// Returns the name FreeType knows the module by.
+    pub fn name(&self) -> &'static str {
+	match self {
+	    DriverModule::TrueType => "truetype",
+	    DriverModule::Cff => "cff",
+	    DriverModule::Type1 => "type1",
+	    DriverModule::T1Cid => "t1cid",
+	    DriverModule::Autofitter => "autofitter",
+	}
+    }
+}
+
+/// A property of a font driver, with its value typed like the driver reads it. Setting one on a module that doesn't have
+/// it is an error. See the FreeType documentation of ftdriver.h for the values.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum DriverProperty {
+    /// The `interpreter-version` of the `truetype` driver, 35, 38 or 40, choosing how TrueType fonts are hinted.
+    InterpreterVersion(u32),
+    /// The `hinting-engine` of the `cff`, `type1` or `t1cid` driver, 0 for FreeType's and 1 for Adobe's.
+    HintingEngine(DriverModule, u32),
+    /// The `no-stem-darkening` of the `cff`, `type1`, `t1cid` or `autofitter` module.
+    NoStemDarkening(DriverModule, bool),
+    /// The `darkening-parameters` of the `cff`, `type1`, `t1cid` or `autofitter` module, 4 pairs of stem width and
+    /// darkening amount.
+    DarkeningParameters(DriverModule, [i32; 8]),
+}
+
+impl DriverProperty {
+    /// Returns the module the property is set on.
+    pub fn module(&self) -> DriverModule {
+	match self {
+	    DriverProperty::InterpreterVersion(_) => DriverModule::TrueType,
+	    DriverProperty::HintingEngine(module, _) | DriverProperty::NoStemDarkening(module, _) | DriverProperty::DarkeningParameters(module, _) => *module,
+	}
+    }
+
+    /// Returns the name FreeType knows the property by.
+    pub fn name(&self) -> &'static str {
+	match self {
+	    DriverProperty::InterpreterVersion(_) => "interpreter-version",
+	    DriverProperty::HintingEngine(..) => "hinting-engine",
+	    DriverProperty::NoStemDarkening(..) => "no-stem-darkening",
+	    DriverProperty::DarkeningParameters(..) => "darkening-parameters",
+	}
+    }
+}
+
+/// A backend turning the characters of a font face into glyph bitmaps, FontLoader being the FreeType one.
+/// The sizes are given in 26.6 points like FreeType does, whatever the backend.
+pub trait Rasterizer {
+    /// Sets the size of the rendered glyphs, in 26.6 points at the given `dpi`.
+    fn set_char_size(&self, size: u32, dpi: u32) -> Result<(), AtlasGeneratorError>;
+
+    /// Sets the size of the rendered glyphs so that the em is `pixel_height` pixels high.
+    fn set_pixel_sizes(&self, pixel_height: u32) -> Result<(), AtlasGeneratorError> {
+	self.set_char_size(pixel_height * 64, PIXEL_DPI)
+    }
+
+    /// Sets the transform applied to every glyph before rasterization.
+    fn set_transform(&self, transform: Option<Transform>);
+
+    /// Sets whether the stems of the glyphs are darkened, thickening thin fonts at small sizes. Backends that can't darken
+    /// them ignore it.
+    fn set_stem_darkening(&self, _stem_darkening: bool) -> Result<(), AtlasGeneratorError> {
+	Ok(())
+    }
+
+    /// Sets a property of a font driver of the backend, changing how the glyphs are rendered. Backends without drivers
+    /// ignore it.
+    fn set_driver_property(&self, _property: &DriverProperty) -> Result<(), AtlasGeneratorError> {
+	Ok(())
+    }
+
+    /// Returns the index of the glyph of the character `c` in the face, 0 being the .notdef glyph.
+    fn glyph_index(&self, c: char) -> u32;
+
+    /// Returns true if the face has a glyph for the character `c`.
+    fn has_glyph(&self, c: char) -> bool {
+	self.glyph_index(c) != 0
+    }
+
+    /// Returns the vertical metrics of the face at the current size.
+    fn line_metrics(&self) -> LineMetrics;
+
+    /// Returns a hash of the font file data and face index, the same across runs and platforms, or None if the backend can't
+    /// tell. Generators can't fingerprint atlases of fonts without one.
+    fn font_hash(&self) -> Option<u64> {
+	None
+    }
+
+    /// Renders the glyph of the character `c` in the given mode, LCD bitmaps having one subpixel per channel.
+    fn rasterize(&self, c: char, load_mode: AtlasLoadMode) -> Result<Arc<Glyph>, AtlasGeneratorError>;
+
+    /// Returns the metrics of the glyph of the character `c` rendered in the given mode, without rendering it if the backend can.
+    fn measure(&self, c: char, load_mode: AtlasLoadMode) -> Result<GlyphMetrics, AtlasGeneratorError> {
+	Ok(self.rasterize(c, load_mode)?.metrics)
+    }
+
+    /// Renders the .notdef glyph of the face, or a box if the font leaves it empty.
+    fn rasterize_notdef(&self, load_mode: AtlasLoadMode) -> Result<Glyph, AtlasGeneratorError>;
+
+    /// Renders the single glyph the face draws for a sequence of characters, like an emoji ZWJ sequence, along with its index.
+    /// None if the face has no glyph for it, which is always the case for backends that can't tell.
+    fn rasterize_sequence(&self, _sequence: &str, _load_mode: AtlasLoadMode) -> Result<Option<(u32, Glyph)>, AtlasGeneratorError> {
+	Ok(None)
+    }
+
+    /// Renders `c` and writes its bitmap in `buffer` at the position returned by `place` from its width and height, along with
+    /// whether it is stored rotated 90° clockwise, converted to the format of `buffer`. Returns the metrics of the glyph and the
+    /// result of `place`.
+    fn blit(&self, c: char, load_mode: AtlasLoadMode, buffer: &mut Pixels, place: &mut dyn FnMut(u32, u32) -> Result<(Rectangle, bool), AtlasGeneratorError>) -> Result<(GlyphMetrics, (Rectangle, bool)), AtlasGeneratorError> {
+	let glyph = self.rasterize(c, load_mode)?;
+	let (position, rotated) = place(glyph.bitmap.width, glyph.bitmap.height)?;
+
+	let converted;
+	let bitmap = match glyph.bitmap.format == buffer.format {
+	    true => &glyph.bitmap,
+	    false => {
+		converted = glyph.bitmap.to_format(buffer.format);
+		&converted
+	    },
+	};
+	if rotated {
+	    buffer.copy_from(&bitmap.rotate90(), position.left, position.top);
+	} else if position.width > 0 && position.height > 0 {
+	    buffer.copy_from(bitmap, position.left, position.top);
+	}
+
+	Ok((glyph.metrics, (position, rotated)))
+    }
+}
+
+/// Returns the hash of the font file content `data` and of the face at `face_index`, for `Rasterizer::font_hash`.
+#[cfg(any(feature = "freetype", feature = "ab-glyph"))]
+pub(crate) fn hash_font_data(data: &[u8], face_index: u32) -> u64 {
+    let mut hasher = ContentHasher::new();
+    hasher.write(data);
+    hasher.write_u32(face_index);
+    hasher.finish()
+}
+
+/// Creates a hollow box glyph sized after the given line metrics, for fonts whose .notdef glyph is empty.
+#[cfg(any(feature = "freetype", feature = "ab-glyph"))]
+pub(crate) fn synthesize_box(line_metrics: &LineMetrics) -> Glyph {
+    let ascender = std::cmp::max(line_metrics.ascender, 4) as u32;
+    let height = ascender * 3 / 4;
+    let width = std::cmp::max(height * 2 / 3, 3);
+
+    let bitmap = Pixels::from_fn(width, height, PixelFormat::Rgb8, |x, y| {
+	if x == 0 || y == 0 || x == width - 1 || y == height - 1 {
+	    [255, 255, 255, 255]
+	} else {
+	    [0, 0, 0, 0]
+	}
+    });
+
+    let metrics = GlyphMetrics::new(width, height, 1, height as i32, width as i32 + 2);
+
+    Glyph::new(metrics, bitmap)
+}
@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use super::multi_atlas::FontId;
+
+/// A color with 8 bits per channel and straight alpha.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Color {
+    /// Creates a color from its four channels.
+    pub fn new(r: u8, g: u8, b: u8, a: u8) -> Self {
+	Self { r, g, b, a }
+    }
+
+    /// Creates an opaque color.
+    pub fn rgb(r: u8, g: u8, b: u8) -> Self {
+	Self::new(r, g, b, 255)
+    }
+
+    /// Returns the color channels as normalized floats.
+    pub fn to_f32(&self) -> [f32; 4] {
+	[self.r as f32 / 255.0, self.g as f32 / 255.0, self.b as f32 / 255.0, self.a as f32 / 255.0]
+    }
+}
+
+impl Default for Color {
+    fn default() -> Self {
+	Self::rgb(255, 255, 255)
+    }
+}
+
+/// A trait turning color descriptions into colors, so themes can use their own color syntax.
+pub trait ColorParser {
+    /// Returns the color described by `s`, or None if it can't be parsed.
+    fn parse_color(&self, s: &str) -> Option<Color>;
+}
+
+/// A parser for `#rgb`, `#rrggbb` and `#rrggbbaa` colors, plus a few basic color names.
+#[derive(Default, Debug, Copy, Clone)]
+pub struct HexColorParser;
+
+impl ColorParser for HexColorParser {
+    fn parse_color(&self, s: &str) -> Option<Color> {
+	let s = s.trim();
+
+	match s.to_ascii_lowercase().as_str() {
+	    "black" => return Some(Color::rgb(0, 0, 0)),
+	    "white" => return Some(Color::rgb(255, 255, 255)),
+	    "red" => return Some(Color::rgb(255, 0, 0)),
+	    "green" => return Some(Color::rgb(0, 255, 0)),
+	    "blue" => return Some(Color::rgb(0, 0, 255)),
+	    "transparent" => return Some(Color::new(0, 0, 0, 0)),
+	    _ => {}
+	}
+
+	let digits = s.strip_prefix('#')?;
+	if !digits.is_ascii() {
+	    return None;
+	}
+	let channel = |i: usize, len: usize| u8::from_str_radix(&digits[i * len..(i + 1) * len], 16).ok();
+
+	match digits.len() {
+	    3 => Some(Color::rgb(channel(0, 1)? * 17, channel(1, 1)? * 17, channel(2, 1)? * 17)),
+	    6 => Some(Color::rgb(channel(0, 2)?, channel(1, 2)?, channel(2, 2)?)),
+	    8 => Some(Color::new(channel(0, 2)?, channel(1, 2)?, channel(2, 2)?, channel(3, 2)?)),
+	    _ => None,
+	}
+    }
+}
+
+/// The lines drawn over or under a span of text.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Decoration {
+    pub underline: bool,
+    pub strikethrough: bool,
+}
+
+impl Decoration {
+    /// Creates a decoration from its components.
+    pub fn new(underline: bool, strikethrough: bool) -> Self {
+	Self { underline, strikethrough }
+    }
+}
+
+/// A fully resolved text style.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct TextStyle {
+    pub color: Color,
+    /// The font in the atlas, which also gives the size of the text.
+    pub font: FontId,
+    pub decoration: Decoration,
+}
+
+impl TextStyle {
+    /// Creates a style from its components.
+    pub fn new(color: Color, font: FontId, decoration: Decoration) -> Self {
+	Self { color, font, decoration }
+    }
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+	Self::new(Color::default(), FontId(0), Decoration::default())
+    }
+}
+
+/// The style of a span, either given directly or by name to be resolved by a Theme at layout time.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum StyleRef {
+    Inline(TextStyle),
+    Named(String),
+}
+
+/// A trait resolving named styles, so text can be re-themed without rebuilding its spans.
+pub trait Theme {
+    /// Returns the style registered under `name`, if any.
+    fn style(&self, name: &str) -> Option<TextStyle>;
+
+    /// Returns the style used for unknown style names.
+    fn default_style(&self) -> TextStyle {
+	TextStyle::default()
+    }
+
+    /// Resolves a style reference to a concrete style.
+    fn resolve(&self, style: &StyleRef) -> TextStyle {
+	match style {
+	    StyleRef::Inline(style) => *style,
+	    StyleRef::Named(name) => self.style(name).unwrap_or_else(|| self.default_style()),
+	}
+    }
+}
+
+/// A theme storing its named styles in a map.
+#[derive(Default, Debug, Clone)]
+pub struct MapTheme {
+    styles: HashMap<String, TextStyle>,
+    default_style: TextStyle,
+}
+
+impl MapTheme {
+    /// Creates an empty theme falling back to `default_style`.
+    pub fn new(default_style: TextStyle) -> Self {
+	Self {
+	    styles: HashMap::new(),
+	    default_style,
+	}
+    }
+
+    /// Registers a style under `name`, replacing the previous one.
+    pub fn set_style(&mut self, name: &str, style: TextStyle) {
+	self.styles.insert(String::from(name), style);
+    }
+
+    /// Registers a style whose color is described by `color`, returning false if `parser` can't parse it.
+    pub fn set_style_parsed(&mut self, name: &str, color: &str, parser: &dyn ColorParser, font: FontId, decoration: Decoration) -> bool {
+	match parser.parse_color(color) {
+	    Some(color) => {
+		self.set_style(name, TextStyle::new(color, font, decoration));
+		true
+	    },
+	    None => false,
+	}
+    }
+}
+
+impl Theme for MapTheme {
+    fn style(&self, name: &str) -> Option<TextStyle> {
+	self.styles.get(name).copied()
+    }
+
+    fn default_style(&self) -> TextStyle {
+	self.default_style
+    }
+}
+
+/// A piece of text sharing a single style.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub text: String,
+    pub style: StyleRef,
+}
+
+impl Span {
+    /// Creates a span from its text and style.
+    pub fn new(text: &str, style: StyleRef) -> Self {
+	Self {
+	    text: String::from(text),
+	    style,
+	}
+    }
+}
+
+/// A text made of styled spans.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RichText {
+    pub spans: Vec<Span>,
+}
+
+impl RichText {
+    /// Creates a rich text without any span.
+    pub fn new() -> Self {
+	Self::default()
+    }
+
+    /// Appends a span with an inline style.
+    pub fn push(&mut self, text: &str, style: TextStyle) -> &mut Self {
+	self.spans.push(Span::new(text, StyleRef::Inline(style)));
+	self
+    }
+
+    /// Appends a span whose style is resolved by name.
+    pub fn push_named(&mut self, text: &str, style_name: &str) -> &mut Self {
+	self.spans.push(Span::new(text, StyleRef::Named(String::from(style_name))));
+	self
+    }
+
+    /// Returns the text of all the spans, concatenated.
+    pub fn text(&self) -> String {
+	self.spans.iter().map(|span| span.text.as_str()).collect()
+    }
+
+    /// Resolves the style of each span with `theme`, returning the byte range they cover in `text()`.
+    pub fn resolve(&self, theme: &dyn Theme) -> Vec<(Range<usize>, TextStyle)> {
+	let mut start = 0;
+
+	self.spans.iter().map(|span| {
+	    let range = start..start + span.text.len();
+	    start = range.end;
+	    (range, theme.resolve(&span.style))
+	}).collect()
+    }
+
+    /// Resolves the color of each span with `theme`, in the form taken by the colored buffer generators.
+    pub fn colors(&self, theme: &dyn Theme) -> Vec<(Range<usize>, Color)> {
+	self.resolve(theme).into_iter().map(|(range, style)| (range, style.color)).collect()
+    }
+}
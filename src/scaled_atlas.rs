@@ -0,0 +1,97 @@
+use super::atlas::{FontAtlas, FontAtlasEntry, LineMetrics};
+
+/// The same charset and options baked at several scale factors, such as 1, 1.5 and 2, for displays of different DPI.
+/// Metrics are in physical pixels in each atlas, and divided by the scale when given in logical pixels.
+pub struct ScaledAtlas {
+    atlases: Vec<(f32, FontAtlas)>,
+}
+
+impl ScaledAtlas {
+    /// Creates a scaled atlas from atlases and the scale they were baked at. Scales that aren't positive are dropped, along with
+    /// the atlases of a scale given twice but the first.
+    pub fn new(atlases: Vec<(f32, FontAtlas)>) -> Self {
+	let mut atlases: Vec<(f32, FontAtlas)> = atlases.into_iter().filter(|(scale, _)| *scale > 0.0 && scale.is_finite()).collect();
+	atlases.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+	atlases.dedup_by(|(a, _), (b, _)| a == b);
+
+	Self { atlases }
+    }
+
+    /// Returns the scales baked, in increasing order.
+    pub fn scales(&self) -> Vec<f32> {
+	self.atlases.iter().map(|(scale, _)| *scale).collect()
+    }
+
+    /// Returns the atlases with their scale, by increasing scale.
+    pub fn atlases(&self) -> &[(f32, FontAtlas)] {
+	&self.atlases
+    }
+
+    /// Returns the atlases with their scale mutably, to upload or update them.
+    pub fn atlases_mut(&mut self) -> &mut [(f32, FontAtlas)] {
+	&mut self.atlases
+    }
+
+    /// Returns the smallest baked scale at least `scale`, so that glyphs are downscaled rather than blurred, or the largest
+    /// one if `scale` is above all of them. None if nothing was baked.
+    pub fn nearest_scale(&self, scale: f32) -> Option<f32> {
+	self.atlases.iter()
+	    .map(|(baked, _)| *baked)
+	    .find(|baked| *baked >= scale)
+	    .or_else(|| self.atlases.last().map(|(baked, _)| *baked))
+    }
+
+    /// Returns the atlas to draw at `scale`, given by `nearest_scale`.
+    pub fn at_scale(&self, scale: f32) -> Option<&FontAtlas> {
+	let scale = self.nearest_scale(scale)?;
+	self.atlases.iter().find(|(baked, _)| *baked == scale).map(|(_, atlas)| atlas)
+    }
+
+    /// Returns the entry of the character `c` in the atlas to draw at `scale`, with the fallbacks of `FontAtlas::lookup`.
+    pub fn lookup(&self, c: char, scale: f32) -> Option<ScaledEntry<'_>> {
+	let scale = self.nearest_scale(scale)?;
+	let (scale, atlas) = self.atlases.iter().find(|(baked, _)| *baked == scale)?;
+
+	atlas.lookup(c).map(|entry| ScaledEntry { atlas, entry, scale: *scale })
+    }
+
+    /// Returns the vertical metrics of the font at `scale`, in logical pixels.
+    pub fn line_metrics(&self, scale: f32) -> Option<[f32; 3]> {
+	let scale = self.nearest_scale(scale)?;
+	let LineMetrics { ascender, descender, line_height, .. } = self.at_scale(scale)?.line_metrics;
+
+	Some([ascender as f32 / scale, descender as f32 / scale, line_height as f32 / scale])
+    }
+}
+
+/// An entry of a ScaledAtlas, with the atlas containing it and the scale it was baked at.
+#[derive(Copy, Clone)]
+pub struct ScaledEntry<'a> {
+    pub atlas: &'a FontAtlas,
+    pub entry: &'a FontAtlasEntry,
+    pub scale: f32,
+}
+
+impl ScaledEntry<'_> {
+    /// Returns the uv of the top-left and bottom-right corners of the glyph, normalized to the size of its atlas.
+    pub fn uv_rect(&self) -> [f32; 4] {
+	self.entry.uv_rect((self.atlas.width, self.atlas.height), false)
+    }
+
+    /// Returns the width and height of the glyph quad, in logical pixels.
+    pub fn size(&self) -> [f32; 2] {
+	let metrics = self.entry.metrics();
+	[metrics.width as f32 / self.scale, metrics.height as f32 / self.scale]
+    }
+
+    /// Returns the horizontal and vertical bearings of the glyph, in logical pixels.
+    pub fn bearing(&self) -> [f32; 2] {
+	let metrics = self.entry.metrics();
+	[metrics.bearing_x as f32 / self.scale, metrics.bearing_y as f32 / self.scale]
+    }
+
+    /// Returns the horizontal advance of the glyph, in logical pixels.
+    pub fn advance(&self) -> f32 {
+	self.entry.metrics().advance as f32 / self.scale
+    }
+}
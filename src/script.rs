@@ -0,0 +1,142 @@
+use std::ops::Range;
+
+use super::atlas::GlyphAtlas;
+use super::multi_atlas::FontId;
+
+/// The zero width joiner, gluing emoji into a sequence drawn as a single glyph, like 👩‍💻.
+pub const ZERO_WIDTH_JOINER: char = '\u{200d}';
+
+/// The variation selector asking for the text presentation of an emoji.
+pub const TEXT_PRESENTATION: char = '\u{fe0e}';
+
+/// The variation selector asking for the emoji presentation of a character.
+pub const EMOJI_PRESENTATION: char = '\u{fe0f}';
+
+/// Returns true if `c` is invisible and takes no room unless a font draws it as part of a sequence, like joiners, variation
+/// selectors and tags. The soft hyphen, drawn at line breaks, isn't one of them here.
+pub fn is_default_ignorable(c: char) -> bool {
+    matches!(c as u32, 0x34f | 0x180b..=0x180f | 0x200b..=0x200f | 0x202a..=0x202e | 0x2060..=0x2064 | 0xfe00..=0xfe0f | 0xfeff | 0xe0000..=0xe0fff)
+}
+
+/// Returns the name emoji fonts give the glyph of `sequence`, like `u1F469_200D_1F4BB` for 👩‍💻: the codepoints in hexadecimal
+/// joined by underscores, without the emoji presentation selectors.
+pub fn sequence_glyph_name(sequence: &str) -> String {
+    let codepoints: Vec<String> = sequence.chars()
+	.filter(|c| *c != EMOJI_PRESENTATION)
+	.map(|c| format!("{:04X}", c as u32))
+	.collect();
+
+    format!("u{}", codepoints.join("_"))
+}
+
+/// The writing system of a character, for the scripts fonts usually cover separately.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Script {
+    /// Characters shared by several scripts, such as spaces, digits and punctuation.
+    Common,
+    /// Combining marks, taking the script of the character they follow.
+    Inherited,
+    Latin,
+    Greek,
+    Cyrillic,
+    Armenian,
+    Hebrew,
+    Arabic,
+    Devanagari,
+    Bengali,
+    Thai,
+    Georgian,
+    Hangul,
+    Hiragana,
+    Katakana,
+    Han,
+    /// Emoji and pictographs, usually drawn by a color font of their own.
+    Emoji,
+    /// Characters of the scripts not listed above.
+    Unknown,
+}
+
+impl Script {
+    /// Returns the script of the character `c`, from the Unicode block containing it.
+    pub fn of(c: char) -> Script {
+	match c as u32 {
+	    0x41..=0x5a | 0x61..=0x7a | 0xaa | 0xba | 0xc0..=0xd6 | 0xd8..=0xf6 | 0xf8..=0x24f => Script::Latin,
+	    0x250..=0x2af | 0x1d00..=0x1d25 | 0x1e00..=0x1eff | 0x2c60..=0x2c7f | 0xa720..=0xa7ff | 0xab30..=0xab6f => Script::Latin,
+	    0xff21..=0xff3a | 0xff41..=0xff5a => Script::Latin,
+	    0x300..=0x36f | 0x1ab0..=0x1aff | 0x1dc0..=0x1dff | 0x20d0..=0x20ff | 0xfe00..=0xfe0f | 0xfe20..=0xfe2f => Script::Inherited,
+	    0x200c | 0x200d | 0xe0020..=0xe007f | 0xe0100..=0xe01ef => Script::Inherited,
+	    0x370..=0x373 | 0x375..=0x377 | 0x37a..=0x37d | 0x37f..=0x3ff | 0x1f00..=0x1fff => Script::Greek,
+	    0x400..=0x52f | 0x1c80..=0x1c8f | 0x2de0..=0x2dff | 0xa640..=0xa69f => Script::Cyrillic,
+	    0x530..=0x58f | 0xfb13..=0xfb17 => Script::Armenian,
+	    0x591..=0x5ff | 0xfb1d..=0xfb4f => Script::Hebrew,
+	    // The Arabic comma, semicolon, question mark and tatweel are shared with other scripts
+	    0x60c | 0x61b | 0x61f | 0x640 => Script::Common,
+	    0x64b..=0x655 | 0x670 => Script::Inherited,
+	    0x600..=0x6ff | 0x750..=0x77f | 0x8a0..=0x8ff | 0xfb50..=0xfdff | 0xfe70..=0xfeff => Script::Arabic,
+	    0x964 | 0x965 => Script::Common,
+	    0x900..=0x97f | 0xa8e0..=0xa8ff => Script::Devanagari,
+	    0x980..=0x9ff => Script::Bengali,
+	    0xe01..=0xe3a | 0xe40..=0xe5b => Script::Thai,
+	    0x10a0..=0x10ff | 0x2d00..=0x2d2f => Script::Georgian,
+	    0x1100..=0x11ff | 0x3131..=0x318e | 0xa960..=0xa97f | 0xac00..=0xd7ff | 0xffa0..=0xffdc => Script::Hangul,
+	    0x3041..=0x3096 | 0x309d..=0x309f => Script::Hiragana,
+	    // The prolonged sound mark is used in both kana
+	    0x30fc => Script::Common,
+	    0x30a1..=0x30fa | 0x30fd..=0x30ff | 0x31f0..=0x31ff | 0xff66..=0xff6f | 0xff71..=0xff9d => Script::Katakana,
+	    0x2e80..=0x2fdf | 0x3005 | 0x3007 | 0x3021..=0x3029 | 0x3038..=0x303b | 0x3400..=0x4dbf | 0x4e00..=0x9fff => Script::Han,
+	    0xf900..=0xfaff | 0x20000..=0x323af => Script::Han,
+	    0x1f000..=0x1faff | 0x2600..=0x27bf | 0x231a | 0x231b | 0x23e9..=0x23f3 | 0x2b50 | 0x2b55 => Script::Emoji,
+	    _ if c.is_alphabetic() => Script::Unknown,
+	    _ => Script::Common,
+	}
+    }
+
+    /// Returns true if the script has characters of its own, rather than taking the script of its neighbours.
+    pub fn is_real(&self) -> bool {
+	!matches!(self, Script::Common | Script::Inherited)
+    }
+}
+
+/// Splits `text` in runs of a single script, returned in text order with the byte range they span.
+/// Common and inherited characters join the run they are in, or the following one at the start of the text.
+/// A text without any character of a real script is a single common run.
+pub fn itemize(text: &str) -> Vec<(Range<usize>, Script)> {
+    let mut runs: Vec<(Range<usize>, Script)> = Vec::new();
+    let mut start = 0;
+    let mut script = Script::Common;
+
+    for (index, c) in text.char_indices() {
+	let char_script = Script::of(c);
+	if !char_script.is_real() || char_script == script {
+	    continue;
+	}
+
+	if script.is_real() {
+	    runs.push((start..index, script));
+	    start = index;
+	}
+	script = char_script;
+    }
+
+    if !text.is_empty() {
+	runs.push((start..text.len(), script));
+    }
+
+    runs
+}
+
+/// Splits `text` in script runs and gives each the first font of `fonts` having a glyph for all the characters of its script,
+/// or the first font if none has them all. Common and inherited characters take the font of their run.
+pub fn select_fonts<A: GlyphAtlas>(text: &str, atlas: &A, fonts: &[FontId]) -> Vec<(Range<usize>, FontId)> {
+    itemize(text).into_iter()
+	.map(|(range, script)| {
+	    let supports = |font: &FontId| text[range.clone()].chars()
+		.filter(|c| script == Script::Common || Script::of(*c) == script)
+		.filter(|c| !c.is_whitespace() && !c.is_control())
+		.all(|c| atlas.has_glyph(*font, c));
+	    let font = fonts.iter().copied().find(supports).or_else(|| fonts.first().copied()).unwrap_or(FontId(0));
+
+	    (range, font)
+	})
+	.collect()
+}
@@ -0,0 +1,211 @@
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use freetype::face::LoadFlag;
+
+use super::{Glyph, Transform};
+use super::atlas::{AtlasGenerator, AtlasGeneratorError, AtlasGeneratorOption, AtlasLoadMode, FontAtlas, LineMetrics};
+use super::loader::{CharMap, FaceInfo, FontLoader, FontLoaderError, FontSystem};
+use super::multi_atlas::FontId;
+
+/// A value owning its own FreeType library along with every face created from it.
+struct Isolated<T> {
+    // Declared first so the faces are dropped before their library
+    value: T,
+    font_system: FontSystem,
+}
+
+// SAFETY: FreeType objects are only unsafe to use from several threads at once, and faces share their
+// library through a non-atomic reference count. An Isolated value owns its library and all the faces
+// using it, never hands them out, and is only reached through a Mutex, so no two threads can touch them
+// concurrently. Glyphs and atlases handed out own their pixels and don't point back to FreeType. The
+// generators built here are only ever given FontLoaders created from their own library.
+unsafe impl Send for Isolated<FontLoader> {}
+unsafe impl Send for Isolated<AtlasGenerator> {}
+
+/// Locks `mutex`, ignoring poisoning: a panic while rendering doesn't leave FreeType in a broken state.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// A FontLoader that can be shared between threads, serializing the calls behind a mutex.
+pub struct SyncFontLoader {
+    inner: Mutex<Isolated<FontLoader>>,
+}
+
+impl SyncFontLoader {
+    /// Creates a loader from the given font filepath, with a FreeType library of its own.
+    pub fn new<P>(font_filepath: P) -> Result<Self, FontLoaderError> where P: AsRef<Path> {
+	Self::with_face_index(font_filepath, 0)
+    }
+
+    /// Creates a loader from the face at `face_index` in the given font file, with a FreeType library of its own.
+    pub fn with_face_index<P>(font_filepath: P, face_index: u32) -> Result<Self, FontLoaderError> where P: AsRef<Path> {
+	let font_system = FontSystem::new()?;
+	let font_loader = FontLoader::with_face_index(&font_system, font_filepath, face_index)?;
+
+	Ok(Self {
+	    inner: Mutex::new(Isolated { value: font_loader, font_system }),
+	})
+    }
+
+    /// Sets the size of the rendered glyphs, in 26.6 points at the given `dpi`.
+    pub fn set_char_size(&self, size: u32, dpi: u32) -> Result<(), FontLoaderError> {
+	lock(&self.inner).value.set_char_size(size, dpi)
+    }
+
+    /// Sets the size of the rendered glyphs so that the em is `pixel_height` pixels high.
+    pub fn set_pixel_sizes(&self, pixel_height: u32) -> Result<(), FontLoaderError> {
+	lock(&self.inner).value.set_pixel_sizes(pixel_height)
+    }
+
+    /// Sets the transform applied to every glyph before rasterization.
+    pub fn set_transform(&self, transform: Option<Transform>) {
+	lock(&self.inner).value.set_transform(transform)
+    }
+
+    /// Returns true if the face has a glyph for the character `c`.
+    pub fn has_glyph(&self, c: char) -> bool {
+	lock(&self.inner).value.has_glyph(c)
+    }
+
+    /// Returns the vertical metrics of the face at the current size.
+    pub fn line_metrics(&self) -> LineMetrics {
+	lock(&self.inner).value.line_metrics()
+    }
+
+    /// Returns the names and properties of the face.
+    pub fn info(&self) -> FaceInfo {
+	lock(&self.inner).value.info()
+    }
+
+    /// Returns the charmaps of the face, in the order of the font file.
+    pub fn charmaps(&self) -> Vec<CharMap> {
+	lock(&self.inner).value.charmaps()
+    }
+
+    /// Selects the charmap at `index` in `charmaps` to look characters up.
+    pub fn select_charmap(&self, index: usize) -> Result<(), FontLoaderError> {
+	lock(&self.inner).value.select_charmap(index)
+    }
+
+    /// Loads a glyph, rendering it only the first time at a given size and dpi.
+    pub fn load_glyph(&self, c: char, load_flags: LoadFlag) -> Result<Arc<Glyph>, FontLoaderError> {
+	lock(&self.inner).value.load_glyph(c, load_flags)
+    }
+
+    /// Sets the size and loads a glyph in a single lock, so other threads can't change the size in between.
+    pub fn load_glyph_sized(&self, c: char, size: u32, dpi: u32, load_flags: LoadFlag) -> Result<Arc<Glyph>, FontLoaderError> {
+	let inner = lock(&self.inner);
+	inner.value.set_char_size(size, dpi)?;
+	inner.value.load_glyph(c, load_flags)
+    }
+
+    /// Loads the .notdef glyph of the face.
+    pub fn load_notdef(&self, load_flags: LoadFlag) -> Result<Glyph, FontLoaderError> {
+	lock(&self.inner).value.load_notdef(load_flags)
+    }
+
+    /// Removes all the glyphs rendered so far from the cache.
+    pub fn clear_cache(&self) {
+	lock(&self.inner).value.clear_cache()
+    }
+
+    /// Limits the number of cached glyphs, evicting the oldest ones first. None lifts the limit.
+    pub fn set_cache_capacity(&self, capacity: Option<usize>) {
+	lock(&self.inner).value.set_cache_capacity(capacity)
+    }
+}
+
+/// An AtlasGenerator that can be shared between threads, generating one atlas at a time.
+pub struct SyncAtlasGenerator {
+    inner: Mutex<Isolated<AtlasGenerator>>,
+}
+
+impl SyncAtlasGenerator {
+    /// Creates a generator from the given font filepath, options and load mode, with a FreeType library of its own.
+    pub fn new<P>(font_filepath: P, options: AtlasGeneratorOption, load_mode: AtlasLoadMode) -> Result<Self, AtlasGeneratorError> where P: AsRef<Path> {
+	let font_system = FontSystem::new()?;
+	let generator = AtlasGenerator::new(&font_system, font_filepath, options, load_mode)?;
+
+	Ok(Self {
+	    inner: Mutex::new(Isolated { value: generator, font_system }),
+	})
+    }
+
+    /// Adds the font file used for the characters missing from the previous fonts.
+    pub fn add_fallback<P>(&self, font_filepath: P) -> Result<FontId, AtlasGeneratorError> where P: AsRef<Path> {
+	let mut inner = lock(&self.inner);
+	let font_loader = FontLoader::new(&inner.font_system, font_filepath)?;
+
+	Ok(inner.value.add_fallback(font_loader))
+    }
+
+    /// Returns a copy of the options the atlases are generated with.
+    pub fn options(&self) -> AtlasGeneratorOption {
+	lock(&self.inner).value.options().clone()
+    }
+
+    /// Replaces the options the next atlases are generated with, keeping the fonts loaded.
+    pub fn set_options(&self, options: AtlasGeneratorOption) {
+	lock(&self.inner).value.set_options(options)
+    }
+
+    /// Sets how the glyphs of the next atlases are rendered.
+    pub fn set_load_mode(&self, load_mode: AtlasLoadMode) {
+	lock(&self.inner).value.set_load_mode(load_mode)
+    }
+
+    /// Generate an atlas of size `size`.
+    pub fn generate(&self, size: u32) -> Result<FontAtlas, AtlasGeneratorError> {
+	lock(&self.inner).value.generate(size)
+    }
+
+    /// Renders a glyph of the primary font in the given mode.
+    pub fn load_glyph(&self, c: char, load_mode: AtlasLoadMode) -> Result<Arc<Glyph>, AtlasGeneratorError> {
+	lock(&self.inner).value.load_glyph(c, load_mode)
+    }
+}
+
+/// Runs `f` on the blocking thread pool of the current tokio runtime, forwarding its panics. A task cancelled before it ran,
+/// as happens when the runtime shuts down, returns `cancelled`.
+#[cfg(feature = "tokio")]
+async fn spawn_blocking<F, T, E>(f: F, cancelled: E) -> Result<T, E> where F: FnOnce() -> Result<T, E> + Send + 'static, T: Send + 'static, E: Send + 'static {
+    match tokio::task::spawn_blocking(f).await {
+	Ok(result) => result,
+	Err(error) => match error.try_into_panic() {
+	    Ok(panic) => std::panic::resume_unwind(panic),
+	    Err(_) => Err(cancelled),
+	},
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl SyncFontLoader {
+    /// Reads the given font file on the blocking thread pool, so the calling task isn't stalled.
+    pub async fn new_async<P>(font_filepath: P) -> Result<Self, FontLoaderError> where P: AsRef<Path> {
+	let font_filepath = font_filepath.as_ref().to_path_buf();
+	spawn_blocking(move || Self::new(font_filepath), FontLoaderError::Cancelled).await
+    }
+
+    /// Renders a glyph at the given size on the blocking thread pool.
+    pub async fn load_glyph_async(self: &Arc<Self>, c: char, size: u32, dpi: u32, load_flags: LoadFlag) -> Result<Arc<Glyph>, FontLoaderError> {
+	let font_loader = Arc::clone(self);
+	spawn_blocking(move || font_loader.load_glyph_sized(c, size, dpi, load_flags), FontLoaderError::Cancelled).await
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl SyncAtlasGenerator {
+    /// Reads the given font file on the blocking thread pool, so the calling task isn't stalled.
+    pub async fn new_async<P>(font_filepath: P, options: AtlasGeneratorOption, load_mode: AtlasLoadMode) -> Result<Self, AtlasGeneratorError> where P: AsRef<Path> {
+	let font_filepath = font_filepath.as_ref().to_path_buf();
+	spawn_blocking(move || Self::new(font_filepath, options, load_mode), AtlasGeneratorError::Cancelled).await
+    }
+
+    /// Generates an atlas of size `size` on the blocking thread pool.
+    pub async fn generate_async(self: &Arc<Self>, size: u32) -> Result<FontAtlas, AtlasGeneratorError> {
+	let generator = Arc::clone(self);
+	spawn_blocking(move || generator.generate(size), AtlasGeneratorError::Cancelled).await
+    }
+}
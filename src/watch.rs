@@ -0,0 +1,145 @@
+use std::fmt::{Debug, Display};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::Duration;
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use super::atlas::{AtlasGenerator, AtlasGeneratorError, AtlasGeneratorOption, AtlasLoadMode, FontAtlas};
+use super::charset::Charset;
+use super::loader::FontSystem;
+
+/// How long the watched files must stay unchanged before rebaking, as editors often write a file several times when saving it.
+const DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// A struct baking an atlas again each time its font or one of its charset files changes, until it is dropped.
+pub struct AtlasWatcher {
+    _watcher: RecommendedWatcher,
+}
+
+impl AtlasWatcher {
+    /// Bakes the font at `font_path` at `size`, the characters of `charset_files` being added to the charset of `options`,
+    /// and bakes it again each time one of these files changes. `callback` is called on a thread of the watcher with each new atlas,
+    /// the first one included, or with the error that prevented it.
+    pub fn new<P, F>(font_path: P, charset_files: &[PathBuf], options: AtlasGeneratorOption, load_mode: AtlasLoadMode, size: u32, mut callback: F) -> Result<Self, WatchError>
+    where P: AsRef<Path>, F: FnMut(Result<FontAtlas, WatchError>) + Send + 'static {
+	let font_path = font_path.as_ref().to_path_buf();
+	let charset_files = charset_files.to_vec();
+
+	let (sender, receiver) = mpsc::channel();
+	let mut watcher = notify::recommended_watcher(sender)?;
+
+	// Editors often save by replacing the file, which ends the watch of the file itself, so their directories are watched instead
+	let mut watched_files = Vec::new();
+	for path in std::iter::once(&font_path).chain(charset_files.iter()) {
+	    let directory = match path.parent() {
+		Some(directory) if !directory.as_os_str().is_empty() => directory.canonicalize()?,
+		_ => std::env::current_dir()?,
+	    };
+	    if !watched_files.iter().any(|file: &PathBuf| file.parent() == Some(&directory)) {
+		watcher.watch(&directory, RecursiveMode::NonRecursive)?;
+	    }
+	    watched_files.push(directory.join(path.file_name().unwrap_or_default()));
+	}
+
+	std::thread::spawn(move || {
+	    let bake = || bake(&font_path, &charset_files, &options, load_mode, size);
+	    callback(bake());
+
+	    // The channel is closed when the watcher is dropped
+	    while let Ok(event) = receiver.recv() {
+		let mut changed = Vec::from([event]);
+		loop {
+		    match receiver.recv_timeout(DEBOUNCE) {
+			Ok(event) => changed.push(event),
+			Err(RecvTimeoutError::Timeout) => break,
+			Err(RecvTimeoutError::Disconnected) => return,
+		    }
+		}
+
+		let mut rebake = false;
+		for event in changed {
+		    match event {
+			Ok(event) => rebake |= is_change_of(&event, &watched_files),
+			Err(e) => callback(Err(WatchError::NotifyError(e))),
+		    }
+		}
+
+		if rebake {
+		    callback(bake());
+		}
+	    }
+	});
+
+	Ok(Self { _watcher: watcher })
+    }
+}
+
+/// Returns true if `event` changed the content of one of `files`.
+fn is_change_of(event: &Event, files: &[PathBuf]) -> bool {
+    matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) && event.paths.iter().any(|path| files.contains(path))
+}
+
+/// Bakes an atlas with the characters of `options` and `charset_files`, loading the font again in case it changed.
+fn bake(font_path: &Path, charset_files: &[PathBuf], options: &AtlasGeneratorOption, load_mode: AtlasLoadMode, size: u32) -> Result<FontAtlas, WatchError> {
+    let mut charset = Charset::from_text(&options.charset);
+    for file in charset_files {
+	charset.add_file(file).map_err(|e| WatchError::CharsetError(file.clone(), e))?;
+    }
+
+    let font_system = FontSystem::new().map_err(AtlasGeneratorError::from)?;
+    let generator = AtlasGenerator::new(&font_system, font_path, options.clone().with_charset(&charset.to_string()), load_mode)?;
+
+    Ok(generator.generate(size)?)
+}
+
+/// Errors that can happen while watching and baking an atlas.
+pub enum WatchError {
+    NotifyError(notify::Error),
+    CharsetError(PathBuf, std::io::Error),
+    GeneratorError(AtlasGeneratorError),
+}
+
+impl From<notify::Error> for WatchError {
+    fn from(e: notify::Error) -> Self {
+	WatchError::NotifyError(e)
+    }
+}
+
+impl From<std::io::Error> for WatchError {
+    fn from(e: std::io::Error) -> Self {
+	WatchError::NotifyError(notify::Error::io(e))
+    }
+}
+
+impl From<AtlasGeneratorError> for WatchError {
+    fn from(e: AtlasGeneratorError) -> Self {
+	WatchError::GeneratorError(e)
+    }
+}
+
+impl Display for WatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+	match self {
+	    WatchError::NotifyError(e) => write!(f, "Can't watch the font files: {}", e),
+	    WatchError::CharsetError(path, e) => write!(f, "Can't read the charset file {}: {}", path.display(), e),
+	    WatchError::GeneratorError(e) => write!(f, "{}", e),
+	}
+    }
+}
+
+impl Debug for WatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+	write!(f, "{}", self)
+    }
+}
+
+impl std::error::Error for WatchError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+	match self {
+	    WatchError::NotifyError(e) => Some(e),
+	    WatchError::CharsetError(_, e) => Some(e),
+	    WatchError::GeneratorError(e) => Some(e),
+	}
+    }
+}
@@ -0,0 +1,75 @@
+#![cfg(feature = "ab-glyph")]
+
+use font::ab_font::AbFont;
+use font::atlas::{AtlasGenerator, AtlasLoadMode, FontAtlas, Padding, GLYPHS};
+use font::rasterizer::Rasterizer;
+
+/// The OFL licensed font shipped in the repository.
+const FONT_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/fonts/EBGaramond-Regular.ttf");
+
+const FONT_SIZE: u32 = 24 * 64;
+
+fn generate<R>(rasterizer: R, load_mode: AtlasLoadMode) -> FontAtlas where R: Rasterizer + 'static {
+    AtlasGenerator::builder()
+	.rasterizer(rasterizer)
+	.size(512, 512)
+	.padding(Padding::new(1, 1, 1, 1))
+	.mode(load_mode)
+	.notdef()
+	.build()
+	.unwrap()
+	.generate(FONT_SIZE)
+	.unwrap()
+}
+
+#[test]
+fn every_glyph_is_rendered_in_both_modes() {
+    for load_mode in [AtlasLoadMode::Gray, AtlasLoadMode::LCD] {
+	let atlas = generate(AbFont::new(FONT_PATH).unwrap(), load_mode);
+
+	assert_eq!(atlas.map.len(), GLYPHS.chars().count());
+	assert!(!atlas.notdef.as_ref().unwrap().is_blank());
+	assert!(atlas.lookup(' ').unwrap().is_blank());
+
+	for (c, entry) in atlas.map.iter().filter(|(c, _)| **c != ' ') {
+	    let position = entry.position();
+	    assert_eq!((position.width, position.height), (entry.metrics().width, entry.metrics().height), "{}", c);
+
+	    let inked = (position.top..position.top + position.height)
+		.flat_map(|y| (position.left..position.left + position.width).map(move |x| (x, y)))
+		.any(|(x, y)| atlas.buffer.get_pixel(x, y) != [0, 0, 0]);
+	    assert!(inked, "{} is empty", c);
+	}
+    }
+}
+
+#[test]
+fn missing_files_are_reported() {
+    assert!(AbFont::new("missing.ttf").is_err());
+    assert!(AbFont::from_vec(vec![0; 16], 0).is_err());
+}
+
+#[cfg(feature = "freetype")]
+#[test]
+fn metrics_are_close_to_freetype_ones() {
+    use font::loader::{FontLoader, FontSystem};
+
+    let font_system = FontSystem::new().unwrap();
+    let freetype = generate(FontLoader::new(&font_system, FONT_PATH).unwrap(), AtlasLoadMode::Gray);
+    let ab_glyph = generate(AbFont::new(FONT_PATH).unwrap(), AtlasLoadMode::Gray);
+
+    let (expected, line_metrics) = (freetype.line_metrics, ab_glyph.line_metrics);
+    assert_eq!((line_metrics.ascender, line_metrics.descender, line_metrics.line_height), (expected.ascender, expected.descender, expected.line_height));
+    assert_eq!((line_metrics.underline_position, line_metrics.strikeout_position), (expected.underline_position, expected.strikeout_position));
+
+    // Hinting moves the edges and advances of FreeType glyphs to the pixel grid, by up to a pixel
+    for (c, entry) in freetype.map.iter() {
+	let (expected, metrics) = (entry.metrics(), ab_glyph.map[c].metrics());
+
+	assert!((metrics.advance - expected.advance).abs() <= 1, "{}", c);
+	assert!((metrics.bearing_x - expected.bearing_x).abs() <= 1, "{}", c);
+	assert!((metrics.bearing_y - expected.bearing_y).abs() <= 1, "{}", c);
+	assert!((metrics.width as i32 - expected.width as i32).abs() <= 2, "{}", c);
+	assert!((metrics.height as i32 - expected.height as i32).abs() <= 2, "{}", c);
+    }
+}
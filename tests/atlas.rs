@@ -0,0 +1,875 @@
+#![cfg(feature = "freetype")]
+
+use font::{Glyph, GlyphMetrics, Rectangle};
+use font::pixels::{expand_gray, Background, PixelFormat, PixelFormatError, Pixels};
+use font::atlas::{draw_text, generate_anchored_buffers_from_text, generate_buffers_from_text, generate_indexed_buffers_from_text, layout_quads, AtlasGenerator, AtlasGeneratorError, AtlasGeneratorOption, AtlasLoadMode, FontAtlas, OverflowPolicy, Padding, TextVertex, GLYPHS};
+use font::charset::Charset;
+use font::compress::{decompress, Compression};
+use font::layout::{Anchor, TextLayout, SOFT_HYPHEN};
+use font::loader::{Encoding, FontLoader, FontLoaderError, FontSystem};
+use font::rasterizer::{DriverModule, DriverProperty};
+use font::rich_text::Color;
+
+/// The OFL licensed font shipped in the repository.
+const FONT_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/fonts/EBGaramond-Regular.ttf");
+
+const FONT_SIZE: u32 = 24 * 64;
+
+fn latin1() -> String {
+    (' '..='\u{ff}').filter(|c| !c.is_control()).collect()
+}
+
+fn generate(load_mode: AtlasLoadMode, padding: u32, charset: &str) -> FontAtlas {
+    let font_system = FontSystem::new().unwrap();
+    let options = AtlasGeneratorOption::new(512, 512, 72, Padding::new(padding, padding, padding, padding)).with_charset(charset);
+
+    AtlasGenerator::new(&font_system, FONT_PATH, options, load_mode).unwrap().generate(FONT_SIZE).unwrap()
+}
+
+/// Returns `rectangle` grown by `margin` pixels on every side.
+fn grow(rectangle: &Rectangle, margin: u32) -> (i64, i64, i64, i64) {
+    let margin = margin as i64;
+    (
+	rectangle.left as i64 - margin,
+	rectangle.top as i64 - margin,
+	(rectangle.left + rectangle.width) as i64 + margin,
+	(rectangle.top + rectangle.height) as i64 + margin,
+    )
+}
+
+fn overlap(a: (i64, i64, i64, i64), b: (i64, i64, i64, i64)) -> bool {
+    a.0 < b.2 && b.0 < a.2 && a.1 < b.3 && b.1 < a.3
+}
+
+fn visible_positions(atlas: &FontAtlas) -> Vec<(char, Rectangle)> {
+    let mut positions: Vec<(char, Rectangle)> = atlas.map.iter()
+	.filter(|(_, entry)| !entry.is_blank())
+	.map(|(c, entry)| (*c, *entry.position()))
+	.collect();
+    positions.sort_by_key(|(c, _)| *c);
+    positions
+}
+
+#[test]
+fn entries_are_inside_the_atlas_and_never_overlap() {
+    for load_mode in [AtlasLoadMode::Gray, AtlasLoadMode::LCD] {
+	let atlas = generate(load_mode, 0, &latin1());
+	let positions = visible_positions(&atlas);
+	assert!(!positions.is_empty());
+
+	for (index, (c, position)) in positions.iter().enumerate() {
+	    assert!(position.left + position.width <= atlas.width && position.top + position.height <= atlas.height, "{} is outside the atlas", c);
+
+	    for (other, other_position) in positions[index + 1..].iter() {
+		assert!(!overlap(grow(position, 0), grow(other_position, 0)), "{} overlaps {}", c, other);
+	    }
+	}
+    }
+}
+
+#[test]
+fn padding_is_left_empty_around_each_glyph() {
+    let padding = 2;
+    let atlas = generate(AtlasLoadMode::Gray, padding, &latin1());
+    let positions = visible_positions(&atlas);
+
+    for (index, (c, position)) in positions.iter().enumerate() {
+	let (left, top, right, bottom) = grow(position, padding);
+	assert!(left >= 0 && top >= 0 && right <= atlas.width as i64 && bottom <= atlas.height as i64, "the padding of {} is outside the atlas", c);
+
+	for (other, other_position) in positions[index + 1..].iter() {
+	    assert!(!overlap(grow(position, padding), grow(other_position, padding)), "the padding of {} overlaps {}", c, other);
+	}
+
+	// The padded rectangles don't overlap, so the ring around the glyph belongs to no other glyph and stays blank
+	for y in top..bottom {
+	    for x in left..right {
+		let inside = x >= position.left as i64 && x < (position.left + position.width) as i64
+		    && y >= position.top as i64 && y < (position.top + position.height) as i64;
+		if !inside {
+		    assert_eq!(atlas.buffer.get_pixel(x as u32, y as u32), [0, 0, 0], "the padding of {} isn't empty", c);
+		}
+	    }
+	}
+    }
+}
+
+#[test]
+fn metrics_are_consistent() {
+    let atlas = generate(AtlasLoadMode::Gray, 1, &latin1());
+    let line_metrics = atlas.line_metrics;
+
+    assert!(line_metrics.ascender > 0);
+    assert!(line_metrics.descender < 0);
+    assert!(line_metrics.line_height >= line_metrics.ascender - line_metrics.descender - 2);
+    assert!(line_metrics.underline_thickness > 0 && line_metrics.strikeout_thickness > 0);
+    assert!(line_metrics.underline_position < 0 && line_metrics.strikeout_position > 0);
+
+    for (c, entry) in atlas.map.iter() {
+	let (metrics, position) = (entry.metrics(), entry.position());
+
+	assert!(metrics.advance >= 0, "{} goes backward", c);
+	assert!(metrics.bearing_y <= line_metrics.ascender, "{} rises above the ascender", c);
+	assert!(metrics.bearing_y - metrics.height as i32 >= line_metrics.descender - 1, "{} goes below the descender", c);
+	assert!((position.width as i32 - metrics.width as i32).abs() <= 1, "the bitmap of {} isn't as wide as its metrics", c);
+	assert!((position.height as i32 - metrics.height as i32).abs() <= 1, "the bitmap of {} isn't as tall as its metrics", c);
+    }
+
+    assert!(atlas.lookup(' ').unwrap().is_blank());
+    assert!(atlas.advance(' ') > 0);
+    assert!(atlas.advance('W') > atlas.advance('i'));
+}
+
+#[test]
+fn lcd_glyphs_are_at_least_as_wide_as_gray_glyphs() {
+    let gray = generate(AtlasLoadMode::Gray, 1, GLYPHS);
+    let lcd = generate(AtlasLoadMode::LCD, 1, GLYPHS);
+
+    for (c, gray_entry) in gray.map.iter() {
+	let lcd_entry = &lcd.map[c];
+	let (gray_position, lcd_position) = (gray_entry.position(), lcd_entry.position());
+
+	// The LCD filter spreads the coverage sideways, so LCD bitmaps can only be wider, by a few pixels at most
+	assert!(lcd_position.width >= gray_position.width, "{} is narrower in LCD", c);
+	assert!(lcd_position.width <= gray_position.width + 3, "{} is much wider in LCD", c);
+	assert_eq!(lcd_position.height, gray_position.height, "{} has another height in LCD", c);
+	assert_eq!(lcd_entry.metrics().advance, gray_entry.metrics().advance, "{} has another advance in LCD", c);
+    }
+}
+
+#[test]
+fn every_character_of_the_charset_is_baked() {
+    let mut charset = Charset::from_text(GLYPHS);
+    charset.add_json_strings(r#"{"greeting": "Bonjour à tous, ça va ?", "naïve farewell": "Tschüß"}"#);
+    let atlas = generate(AtlasLoadMode::Gray, 1, &charset.to_string());
+
+    assert_eq!(atlas.map.len(), charset.len());
+    for c in charset.chars() {
+	assert!(atlas.map.contains_key(&c), "{} is missing", c);
+    }
+
+    // Keys of JSON objects aren't displayed, so their characters aren't added
+    assert!(!atlas.map.contains_key(&'ï'));
+    assert!(atlas.map.contains_key(&'ß') && atlas.map.contains_key(&'ç'));
+    assert!(!atlas.map.contains_key(&'Ω'));
+}
+
+#[test]
+fn json_escapes_are_decoded() {
+    let mut charset = Charset::new();
+    charset.add_json_strings(r#"["\r\b\f\/x", "\ud83d\n", "\ud83d\ude00"]"#);
+
+    // Control characters are never added, and a lone high surrogate is replaced
+    assert_eq!(charset.chars().collect::<String>(), "/x\u{fffd}😀");
+}
+
+#[test]
+fn missing_characters_fall_back_to_notdef() {
+    let font_system = FontSystem::new().unwrap();
+    let options = AtlasGeneratorOption::new(256, 256, 72, Padding::new(1, 1, 1, 1)).with_charset("abc ").with_notdef();
+    let atlas = AtlasGenerator::new(&font_system, FONT_PATH, options, AtlasLoadMode::Gray).unwrap().generate(FONT_SIZE).unwrap();
+
+    let notdef = atlas.notdef.as_ref().unwrap();
+    assert!(!notdef.is_blank());
+    assert_eq!(atlas.lookup('z').unwrap().position(), notdef.position());
+    assert_eq!(atlas.lookup('a').unwrap().position(), atlas.map[&'a'].position());
+
+    // Without it, they are left out unless the space glyph is asked for
+    let mut atlas = generate(AtlasLoadMode::Gray, 1, "abc ");
+    assert!(atlas.lookup('z').is_none());
+    assert_eq!(atlas.advance('z'), 0);
+    atlas.space_fallback = true;
+    assert_eq!(atlas.lookup('z').unwrap().position(), atlas.map[&' '].position());
+}
+
+#[test]
+fn glyphs_added_later_keep_the_atlas_consistent() {
+    let font_system = FontSystem::new().unwrap();
+    let options = AtlasGeneratorOption::new(512, 512, 72, Padding::new(1, 1, 1, 1)).with_charset("abc");
+    let generator = AtlasGenerator::new(&font_system, FONT_PATH, options, AtlasLoadMode::Gray).unwrap();
+
+    let mut atlas = generator.generate(FONT_SIZE).unwrap();
+    atlas.take_dirty_regions();
+    generator.add_glyphs(&mut atlas, "abcdéf").unwrap();
+
+    assert_eq!(atlas.map.len(), 6);
+    assert_eq!(atlas.take_dirty_regions().len(), 3);
+
+    let positions = visible_positions(&atlas);
+    for (index, (c, position)) in positions.iter().enumerate() {
+	for (other, other_position) in positions[index + 1..].iter() {
+	    assert!(!overlap(grow(position, 1), grow(other_position, 1)), "{} overlaps {}", c, other);
+	}
+    }
+}
+
+#[test]
+fn fonts_loaded_from_memory_match_font_files() {
+    let data = std::fs::read(FONT_PATH).unwrap();
+    let builder = || AtlasGenerator::builder().size(512, 512).padding(Padding::new(1, 1, 1, 1)).mode(AtlasLoadMode::Gray).charset(GLYPHS);
+
+    let from_file = builder().font(FONT_PATH).build().unwrap().generate(FONT_SIZE).unwrap();
+    let from_memory = builder().font_bytes(&data).build().unwrap().generate(FONT_SIZE).unwrap();
+
+    assert_eq!(visible_positions(&from_memory), visible_positions(&from_file));
+    assert!(from_memory.buffer.iter().eq(from_file.buffer.iter()));
+}
+
+#[test]
+fn lenient_generators_report_the_glyphs_left_out() {
+    let font_system = FontSystem::new().unwrap();
+    let charset = latin1();
+    let options = AtlasGeneratorOption::new(64, 64, 72, Padding::new(1, 1, 1, 1)).with_charset(&charset);
+
+    let strict = AtlasGenerator::new(&font_system, FONT_PATH, options.clone(), AtlasLoadMode::Gray).unwrap();
+    assert!(matches!(strict.generate(FONT_SIZE), Err(AtlasGeneratorError::InsertError(_))));
+
+    let lenient = AtlasGenerator::new(&font_system, FONT_PATH, options.with_lenient(), AtlasLoadMode::Gray).unwrap();
+    let atlas = lenient.generate(FONT_SIZE).unwrap();
+
+    assert!(!atlas.map.is_empty());
+    assert!(!atlas.failures.is_empty());
+    assert_eq!(atlas.map.len() + atlas.failures.len(), charset.chars().count());
+    for (c, error) in atlas.failures.iter() {
+	assert!(!atlas.map.contains_key(c));
+	assert!(matches!(error, AtlasGeneratorError::InsertError(_)));
+    }
+}
+
+#[test]
+fn overflowing_generators_retry_at_other_sizes() {
+    let font_system = FontSystem::new().unwrap();
+    let charset = latin1();
+    let options = AtlasGeneratorOption::new(128, 128, 72, Padding::new(1, 1, 1, 1)).with_charset(&charset);
+    let generate = |overflow: OverflowPolicy| {
+	let options = options.clone().with_overflow(overflow);
+	AtlasGenerator::new(&font_system, FONT_PATH, options, AtlasLoadMode::Gray).unwrap().generate(FONT_SIZE)
+    };
+
+    let shrunk = generate(OverflowPolicy::ShrinkFont { step: 2 * 64, min_size: 4 * 64 }).unwrap();
+    assert!(shrunk.font_size < FONT_SIZE);
+    assert_eq!((FONT_SIZE - shrunk.font_size) % (2 * 64), 0);
+    assert_eq!((shrunk.width, shrunk.height), (128, 128));
+    assert_eq!(shrunk.map.len(), charset.chars().count());
+
+    let grown = generate(OverflowPolicy::GrowAtlas { max_width: 1024, max_height: 1024 }).unwrap();
+    assert_eq!(grown.font_size, FONT_SIZE);
+    assert!(grown.width > 128 && grown.width.is_power_of_two() && grown.width == grown.height);
+    assert_eq!(grown.map.len(), charset.chars().count());
+
+    let capped = generate(OverflowPolicy::GrowAtlas { max_width: 160, max_height: 160 });
+    assert!(matches!(capped, Err(AtlasGeneratorError::InsertError(_))));
+}
+
+#[test]
+fn deterministic_atlases_ignore_the_charset_order() {
+    let font_system = FontSystem::new().unwrap();
+    let charset = latin1();
+    let reversed: String = charset.chars().rev().chain(charset.chars()).collect();
+    let generate = |charset: &str, options: AtlasGeneratorOption| {
+	let options = options.with_charset(charset);
+	AtlasGenerator::new(&font_system, FONT_PATH, options, AtlasLoadMode::Gray).unwrap().generate(FONT_SIZE).unwrap()
+    };
+    let options = AtlasGeneratorOption::new(512, 512, 72, Padding::new(1, 1, 1, 1));
+
+    let atlas = generate(&charset, options.clone());
+    assert_eq!(atlas.content_hash(), generate(&charset, options.clone()).content_hash());
+    assert_ne!(atlas.content_hash(), generate(&reversed, options.clone()).content_hash());
+
+    let deterministic = generate(&charset, options.clone().with_deterministic());
+    let shuffled = generate(&reversed, options.with_deterministic());
+    assert_eq!(deterministic.content_hash(), shuffled.content_hash());
+    assert_eq!(visible_positions(&deterministic), visible_positions(&shuffled));
+    assert!(deterministic.buffer.iter().eq(shuffled.buffer.iter()));
+}
+
+#[test]
+fn fingerprints_follow_the_generator_inputs() {
+    let font_system = FontSystem::new().unwrap();
+    let data = std::fs::read(FONT_PATH).unwrap();
+    let options = AtlasGeneratorOption::new(512, 512, 72, Padding::new(1, 1, 1, 1));
+    let from_file = |options: AtlasGeneratorOption| AtlasGenerator::new(&font_system, FONT_PATH, options, AtlasLoadMode::Gray).unwrap();
+
+    let generator = from_file(options.clone());
+    let fingerprint = generator.fingerprint(FONT_SIZE).unwrap();
+    assert_eq!(from_file(options.clone()).fingerprint(FONT_SIZE), Some(fingerprint));
+    assert_ne!(generator.fingerprint(FONT_SIZE + 64), Some(fingerprint));
+    assert_ne!(from_file(options.clone().with_charset("abc")).fingerprint(FONT_SIZE), Some(fingerprint));
+    assert_ne!(from_file(options.clone().with_rotation()).fingerprint(FONT_SIZE), Some(fingerprint));
+
+    // The same font data gives the same fingerprint however it is loaded, and faces loaded by the caller have none
+    let from_memory = FontLoader::from_vec(&font_system, data, 0).unwrap();
+    assert_eq!(AtlasGenerator::from_font_loader(from_memory, options.clone(), AtlasLoadMode::Gray).fingerprint(FONT_SIZE), Some(fingerprint));
+    let face = font_system.new_face(FONT_PATH, 0).unwrap();
+    assert_eq!(AtlasGenerator::from_font_loader(FontLoader::from_face(face), options, AtlasLoadMode::Gray).fingerprint(FONT_SIZE), None);
+
+    let mut atlas = generator.generate(FONT_SIZE).unwrap();
+    assert_eq!(atlas.fingerprint(), Some(fingerprint));
+    generator.add_glyphs(&mut atlas, "é").unwrap();
+    assert_eq!(atlas.fingerprint(), None);
+}
+
+#[test]
+fn extra_sizes_are_baked_in_the_same_atlas() {
+    let font_system = FontSystem::new().unwrap();
+    let (small, large) = (16 * 64, 32 * 64);
+    let options = AtlasGeneratorOption::new(512, 512, 72, Padding::new(1, 1, 1, 1)).with_charset(GLYPHS).with_extra_sizes(&[small, large]);
+    let generator = AtlasGenerator::new(&font_system, FONT_PATH, options, AtlasLoadMode::Gray).unwrap();
+
+    let mut atlas = generator.generate(FONT_SIZE).unwrap();
+    assert_eq!(atlas.sizes(), vec![small, FONT_SIZE, large]);
+    assert_eq!(atlas.nearest_size(20 * 64), FONT_SIZE);
+    assert_eq!(atlas.nearest_size(28 * 64), large);
+    assert_eq!(atlas.nearest_size(64), small);
+
+    let height = |size: u32| atlas.lookup_sized('W', size).unwrap().metrics().height;
+    assert!(height(small) < height(FONT_SIZE) && height(FONT_SIZE) < height(large));
+    assert!(atlas.line_metrics_sized(small).unwrap().line_height < atlas.line_metrics.line_height);
+    assert!(atlas.line_metrics_sized(20 * 64).is_none());
+
+    let width = |size: u32| TextLayout::new("Sized text", &atlas.at_size(size), None).glyphs().last().unwrap().x;
+    assert!(width(small) < width(FONT_SIZE) && width(FONT_SIZE) < width(large));
+
+    generator.add_glyphs(&mut atlas, "é").unwrap();
+    for size in atlas.sizes() {
+	assert!(!atlas.lookup_sized('é', size).unwrap().is_blank());
+    }
+
+    let positions: Vec<&Rectangle> = atlas.map.values().chain(atlas.sized_map.values())
+	.map(|entry| entry.position())
+	.filter(|position| position.width > 0 && position.height > 0)
+	.collect();
+    for (index, position) in positions.iter().enumerate() {
+	for other in positions[index + 1..].iter() {
+	    assert!(!overlap(grow(position, 1), grow(other, 1)));
+	}
+    }
+}
+
+#[test]
+fn scaled_atlases_are_baked_for_each_scale() {
+    let font_system = FontSystem::new().unwrap();
+    let options = AtlasGeneratorOption::new(256, 256, 72, Padding::new(1, 1, 1, 1)).with_charset(GLYPHS);
+    let generator = AtlasGenerator::new(&font_system, FONT_PATH, options, AtlasLoadMode::Gray).unwrap();
+
+    let scaled = generator.generate_scaled(FONT_SIZE, &[2.0, 1.0, 1.5, 0.0]).unwrap();
+    assert_eq!(scaled.scales(), vec![1.0, 1.5, 2.0]);
+
+    let sizes: Vec<(u32, u32, u32)> = scaled.atlases().iter().map(|(_, atlas)| (atlas.font_size, atlas.width, atlas.height)).collect();
+    assert_eq!(sizes, vec![(FONT_SIZE, 256, 256), (FONT_SIZE * 3 / 2, 384, 384), (FONT_SIZE * 2, 512, 512)]);
+    assert!(scaled.atlases().iter().all(|(_, atlas)| atlas.map.len() == GLYPHS.chars().count()));
+
+    assert_eq!(scaled.nearest_scale(1.25), Some(1.5));
+    assert_eq!(scaled.nearest_scale(3.0), Some(2.0));
+    assert_eq!(scaled.at_scale(0.5).unwrap().font_size, FONT_SIZE);
+
+    // Logical metrics stay close across scales while the glyphs get sharper
+    let (low, high) = (scaled.lookup('W', 1.0).unwrap(), scaled.lookup('W', 2.0).unwrap());
+    assert_eq!(high.scale, 2.0);
+    assert!((low.advance() - high.advance()).abs() <= 1.0);
+    assert!(high.entry.metrics().width > low.entry.metrics().width);
+    let uv = high.uv_rect();
+    assert!(uv[0] < uv[2] && uv[1] < uv[3] && uv[2] <= 1.0 && uv[3] <= 1.0);
+}
+
+#[test]
+fn pixel_sizes_match_the_point_sizes_at_the_same_resolution() {
+    let font_system = FontSystem::new().unwrap();
+    let generator = |dpi: u32| {
+	let options = AtlasGeneratorOption::new(512, 512, dpi, Padding::new(1, 1, 1, 1)).with_charset(GLYPHS);
+	AtlasGenerator::new(&font_system, FONT_PATH, options, AtlasLoadMode::Gray).unwrap()
+    };
+
+    // 24 points at 96 DPI are 32 pixels
+    let points = generator(96).generate(FONT_SIZE).unwrap();
+    let pixels = generator(96).generate_px(32).unwrap();
+    assert_eq!((pixels.font_size, pixels.dpi), (32 * 64, Some(72)));
+    assert_eq!(points.dpi, Some(96));
+    assert_eq!(pixels.line_metrics.line_height, points.line_metrics.line_height);
+    assert_eq!(pixels.map[&'W'].metrics().advance, points.map[&'W'].metrics().advance);
+    assert_eq!(pixels.buffer, points.buffer);
+
+    assert_eq!(generator(72).generate_px(24).unwrap().content_hash(), generator(72).generate(FONT_SIZE).unwrap().content_hash());
+}
+
+#[test]
+fn face_info_describes_the_font() {
+    let font_system = FontSystem::new().unwrap();
+    let info = FontLoader::new(&font_system, FONT_PATH).unwrap().info();
+
+    assert_eq!(info.family_name.as_deref(), Some("EB Garamond"));
+    assert_eq!(info.style_name.as_deref(), Some("Regular"));
+    assert_eq!(info.postscript_name.as_deref(), Some("EBGaramond-Regular"));
+    assert_eq!(info.units_per_em, 1000);
+    assert!(info.glyph_count > 256);
+    assert!(!info.bold && !info.italic);
+    assert!(info.scalable);
+}
+
+#[test]
+fn charmaps_can_be_listed_and_selected() {
+    let font_system = FontSystem::new().unwrap();
+    let font_loader = FontLoader::new(&font_system, concat!(env!("CARGO_MANIFEST_DIR"), "/resources/fonts/Ubuntu-R.ttf")).unwrap();
+
+    let charmaps = font_loader.charmaps();
+    let current = font_loader.charmap().unwrap();
+    assert_eq!((current.encoding, current.platform_id, current.encoding_id), (Encoding::Unicode, 3, 1));
+    assert_eq!(charmaps[current.index], current);
+
+    // Mac Roman encodes é as 0x8e
+    let e_acute = font_loader.glyph_index('\u{e9}');
+    let apple_roman = charmaps.iter().find(|charmap| charmap.encoding == Encoding::AppleRoman).unwrap();
+    font_loader.select_charmap(apple_roman.index).unwrap();
+    assert_eq!(font_loader.charmap(), Some(*apple_roman));
+    assert_eq!(font_loader.glyph_index('\u{8e}'), e_acute);
+
+    assert!(matches!(font_loader.select_charmap(charmaps.len()), Err(FontLoaderError::CharMapError(_, _))));
+}
+
+#[test]
+fn glyphs_can_be_loaded_by_name() {
+    let font_system = FontSystem::new().unwrap();
+    let font_loader = FontLoader::new(&font_system, FONT_PATH).unwrap();
+    font_loader.set_char_size(FONT_SIZE, 72).unwrap();
+
+    assert_eq!(font_loader.glyph_index_by_name("eacute"), Some(font_loader.glyph_index('é')));
+    assert_eq!(font_loader.glyph_index_by_name("missing-glyph"), None);
+
+    let by_name = font_loader.load_glyph_by_name("eacute", AtlasLoadMode::Gray.load_flags()).unwrap();
+    let by_char = font_loader.load_glyph('é', AtlasLoadMode::Gray.load_flags()).unwrap();
+    assert_eq!(by_name.bitmap, by_char.bitmap);
+    assert!(matches!(font_loader.load_glyph_by_name("missing-glyph", AtlasLoadMode::Gray.load_flags()), Err(FontLoaderError::GlyphNameNotFound(_))));
+}
+
+#[test]
+fn advances_match_the_rendered_glyphs() {
+    let font_system = FontSystem::new().unwrap();
+    let font_loader = FontLoader::new(&font_system, FONT_PATH).unwrap();
+    font_loader.set_char_size(FONT_SIZE, 72).unwrap();
+
+    let text = "Wavé fi";
+    let advances = font_loader.advances(text).unwrap();
+    assert_eq!(advances.len(), text.chars().count());
+    assert_eq!(font_loader.cached_glyph_count(), 0);
+
+    for (c, advance) in text.chars().zip(advances) {
+	let glyph = font_loader.load_glyph(c, AtlasLoadMode::Gray.load_flags()).unwrap();
+	assert_eq!(advance, glyph.metrics.advance as f32, "{}", c);
+    }
+}
+
+#[test]
+fn padding_ranges_override_the_padding_of_their_characters() {
+    let font_system = FontSystem::new().unwrap();
+    let options = AtlasGeneratorOption::new(512, 512, 72, Padding::new(0, 0, 0, 0)).with_charset(&latin1());
+    let padded = options.clone().with_padding_range('0'..='9', Padding::uniform(3)).with_padding_range('5'..='5', Padding::uniform(6));
+    assert_eq!(*padded.padding_for('5'), Padding::uniform(6));
+    assert_eq!(*padded.padding_for('7'), Padding::uniform(3));
+    assert_eq!(*padded.padding_for('a'), Padding::default());
+
+    let generator = AtlasGenerator::new(&font_system, FONT_PATH, padded, AtlasLoadMode::Gray).unwrap();
+    let unpadded = AtlasGenerator::new(&font_system, FONT_PATH, options, AtlasLoadMode::Gray).unwrap();
+    assert_ne!(generator.fingerprint(FONT_SIZE), unpadded.fingerprint(FONT_SIZE));
+
+    let mut atlas = generator.generate(FONT_SIZE).unwrap();
+    let margin = |c: char| match c {
+	'5' => 6,
+	'0'..='9' => 3,
+	_ => 0,
+    };
+
+    for repacked in [false, true] {
+	if repacked {
+	    atlas.repack().unwrap();
+	}
+
+	let positions = visible_positions(&atlas);
+	for (index, (c, position)) in positions.iter().enumerate() {
+	    for (other, other_position) in positions[index + 1..].iter() {
+		assert!(!overlap(grow(position, margin(*c)), grow(other_position, margin(*other))), "the padding of {} overlaps {}", c, other);
+	    }
+	}
+    }
+}
+
+#[test]
+fn paddings_can_be_built_and_read() {
+    let padding = Padding::new(1, 2, 3, 4);
+    assert_eq!((padding.left(), padding.right(), padding.top(), padding.bottom()), (1, 2, 3, 4));
+    assert_eq!((padding.horizontal(), padding.vertical()), (3, 7));
+
+    assert_eq!(Padding::uniform(2), Padding::new(2, 2, 2, 2));
+    assert_eq!(Padding::symmetric(1, 5), Padding::new(1, 1, 5, 5));
+    assert_eq!(Padding::default(), Padding::uniform(0));
+}
+
+#[test]
+fn entries_and_options_can_be_compared() {
+    let atlas = generate(AtlasLoadMode::Gray, 1, GLYPHS);
+    let again = generate(AtlasLoadMode::Gray, 1, GLYPHS);
+    assert_eq!(atlas.map, again.map);
+    assert_eq!(atlas.line_metrics, again.line_metrics);
+
+    let entry = atlas.map[&'a'];
+    assert_eq!(entry, again.map[&'a']);
+    assert_ne!(entry, atlas.map[&'b']);
+
+    let options = AtlasGeneratorOption::default();
+    assert_eq!(options, AtlasGeneratorOption::new(256, 256, 72, Padding::default()));
+    assert_ne!(options.clone().with_notdef(), options);
+}
+
+#[test]
+fn generators_can_change_their_options() {
+    let font_system = FontSystem::new().unwrap();
+    let options = AtlasGeneratorOption::new(512, 512, 72, Padding::uniform(1)).with_charset("abc");
+    let mut generator = AtlasGenerator::new(&font_system, FONT_PATH, options.clone(), AtlasLoadMode::Gray).unwrap();
+    let first = generator.generate(FONT_SIZE).unwrap();
+
+    generator.set_charset("xyz");
+    generator.set_dpi(144);
+    generator.set_load_mode(AtlasLoadMode::LCD);
+    generator.options_mut().padding = Padding::uniform(2);
+    assert_eq!(generator.load_mode(), AtlasLoadMode::LCD);
+    assert_eq!(generator.options().charset, "xyz");
+
+    let second = generator.generate(FONT_SIZE).unwrap();
+    assert_eq!(second.load_mode, AtlasLoadMode::LCD);
+    assert!(second.map.contains_key(&'x') && !second.map.contains_key(&'a'));
+    assert!(second.line_metrics.line_height > first.line_metrics.line_height);
+
+    // Going back to the first options gives the first atlas again
+    generator.set_options(options);
+    generator.set_load_mode(AtlasLoadMode::Gray);
+    assert_eq!(generator.generate(FONT_SIZE).unwrap().content_hash(), first.content_hash());
+}
+
+#[test]
+fn cropped_glyphs_keep_their_ink_in_place() {
+    let bitmap = Pixels::from_fn(6, 5, PixelFormat::Rgb8, |x, y| match (x, y) {
+	(2, 1) | (3, 3) => [255, 255, 255, 255],
+	_ => [0, 0, 0, 0],
+    });
+    let glyph = Glyph::new(GlyphMetrics::new(6, 5, 1, 4, 7), bitmap).cropped();
+    assert_eq!(glyph.metrics, GlyphMetrics::new(2, 3, 3, 3, 7));
+    assert_eq!(glyph.bitmap.dimensions(), (2, 3));
+    assert_eq!(Glyph::new(GlyphMetrics::new(3, 3, 0, 3, 4), Pixels::new(3, 3, PixelFormat::Rgb8)).cropped().metrics, GlyphMetrics::new(0, 0, 0, 3, 4));
+
+    let font_system = FontSystem::new().unwrap();
+    let generate = |options: AtlasGeneratorOption| {
+	AtlasGenerator::new(&font_system, FONT_PATH, options.with_charset(&latin1()), AtlasLoadMode::Gray).unwrap().generate(FONT_SIZE).unwrap()
+    };
+    let options = AtlasGeneratorOption::new(512, 512, 72, Padding::uniform(1));
+    let (full, cropped) = (generate(options.clone()), generate(options.with_cropping()));
+
+    // The ink of each glyph is drawn at the same place relative to its origin
+    let ink = |atlas: &FontAtlas, c: char| {
+	let entry = &atlas.map[&c];
+	let bounds = atlas.buffer.crop(entry.position()).ink_bounds()?;
+	let metrics = entry.metrics();
+	Some((metrics.bearing_x + bounds.left as i32, metrics.bearing_y - bounds.top as i32, bounds.width, bounds.height))
+    };
+    for c in latin1().chars() {
+	assert_eq!(ink(&full, c), ink(&cropped, c), "{}", c);
+
+	let entry = &cropped.map[&c];
+	if let Some(bounds) = cropped.buffer.crop(entry.position()).ink_bounds() {
+	    assert_eq!((bounds.left, bounds.top, bounds.width, bounds.height), (0, 0, entry.position().width, entry.position().height), "{}", c);
+	}
+    }
+}
+
+#[test]
+fn quad_iterators_yield_the_indexed_buffers_lazily() {
+    let atlas = generate(AtlasLoadMode::Gray, 1, &latin1());
+    let text = "Lazy quads\nfor every frame";
+    let (vertices, _) = generate_indexed_buffers_from_text(text, &atlas, 10, 200);
+
+    let quads: Vec<[TextVertex; 4]> = layout_quads(text, &atlas, 10, 200).map(|quad| quad.vertices()).collect();
+    assert_eq!(quads.concat(), vertices);
+
+    // Spaces and line breaks have no quad
+    let indices: Vec<usize> = layout_quads(text, &atlas, 0, 0).map(|quad| quad.index).collect();
+    assert_eq!(indices.len(), text.chars().filter(|c| !c.is_whitespace()).count());
+    assert!(indices.iter().all(|index| !text[*index..].starts_with(char::is_whitespace)));
+    assert_eq!(layout_quads(text, &atlas, 0, 0).nth(1).unwrap().index, 1);
+
+    let first = layout_quads(text, &atlas, 0, 0).with_color(Color::rgb(0, 0, 255)).next().unwrap();
+    assert_eq!(first.color, [0.0, 0.0, 1.0, 1.0]);
+}
+
+#[test]
+fn text_buffers_can_be_anchored() {
+    let atlas = generate(AtlasLoadMode::Gray, 1, &latin1());
+    let text = "Two\nlines";
+    let metrics = &atlas.line_metrics;
+    let width = text.split('\n').map(|line| line.chars().map(|c| atlas.advance(c)).sum::<i32>()).max().unwrap();
+
+    assert_eq!(generate_anchored_buffers_from_text(text, &atlas, 10, 20, Anchor::BaselineLeft), generate_buffers_from_text(text, &atlas, 10, 20));
+    assert_eq!(generate_anchored_buffers_from_text(text, &atlas, 0, 0, Anchor::TopLeft), generate_buffers_from_text(text, &atlas, 0, -metrics.ascender));
+    assert_eq!(
+	generate_anchored_buffers_from_text(text, &atlas, 0, 0, Anchor::BottomRight),
+	generate_buffers_from_text(text, &atlas, -width, 2 * metrics.line_height - metrics.ascender)
+    );
+}
+
+#[test]
+fn raw_bytes_describe_the_buffer() {
+    let atlas = generate(AtlasLoadMode::Gray, 1, &latin1());
+    let (bytes, width, height, stride, format) = atlas.as_raw();
+
+    assert_eq!((width, height, format), (512, 512, PixelFormat::Rgb8));
+    assert_eq!(stride, 512 * 3);
+    assert_eq!(bytes.len(), stride * height as usize);
+    assert_eq!(bytes, &atlas.buffer[..]);
+}
+
+#[test]
+fn rows_are_written_at_the_given_pitch() {
+    let atlas = generate(AtlasLoadMode::Gray, 1, &latin1());
+    let (bytes, _, height, stride, _) = atlas.as_raw();
+    let row_pitch = stride + 256;
+    let mut staging = vec![7; row_pitch * height as usize];
+
+    assert!(!atlas.write_into(&mut staging, stride - 1));
+    assert!(!atlas.write_into(&mut staging[..row_pitch * (height as usize - 1)], row_pitch));
+    assert!(staging.iter().all(|byte| *byte == 7));
+
+    assert!(atlas.write_into(&mut staging, row_pitch));
+    for (row, source) in bytes.chunks_exact(stride).enumerate() {
+	assert_eq!(&staging[row * row_pitch..row * row_pitch + stride], source);
+	assert!(staging[row * row_pitch + stride..(row + 1) * row_pitch].iter().all(|byte| *byte == 7));
+    }
+}
+
+#[test]
+fn gray_atlases_are_compressed_in_blocks() {
+    let atlas = generate(AtlasLoadMode::Gray, 1, "AgW@");
+    assert!(generate(AtlasLoadMode::LCD, 1, "AgW@").compressed(Compression::Bc4).is_none());
+
+    for compression in [Compression::Bc4, Compression::EacR11] {
+	let data = atlas.compressed(compression).unwrap();
+	assert_eq!(data.len(), atlas.buffer.len() / 3 / 16 * 8);
+	assert!(decompress(&data, atlas.width, atlas.height + 1, compression).is_none());
+
+	// Blocks interpolate between their extremes, so the coverage stays close
+	let decompressed = decompress(&data, atlas.width, atlas.height, compression).unwrap();
+	let error: i64 = atlas.buffer.chunks_exact(3).zip(decompressed.chunks_exact(3))
+	    .map(|(original, restored)| (original[0] as i64 - restored[0] as i64).abs())
+	    .sum();
+	assert!(error / (atlas.width * atlas.height) as i64 <= 2, "{:?}: mean error {}", compression, error);
+    }
+
+    // Partial blocks on the edges are stored whole
+    let pixels = Pixels::from_fn(5, 6, PixelFormat::Rgb8, |x, y| [(x * 40 + y) as u8; 4]);
+    let data = font::compress::compress(&pixels, Compression::Bc4);
+    assert_eq!(data.len(), 2 * 2 * 8);
+    assert_eq!(decompress(&data, 5, 6, Compression::Bc4).unwrap().dimensions(), (5, 6));
+}
+
+#[test]
+fn measured_atlases_have_the_metrics_without_pixels() {
+    let font_system = FontSystem::new().unwrap();
+    let options = AtlasGeneratorOption::new(512, 512, 72, Padding::uniform(1));
+    let generator = AtlasGenerator::new(&font_system, FONT_PATH, options, AtlasLoadMode::Gray).unwrap();
+    let charset = latin1();
+
+    let measured = generator.measure(&charset, FONT_SIZE).unwrap();
+    let generated = generate(AtlasLoadMode::Gray, 1, &charset);
+    assert!(measured.buffer.is_empty());
+    assert_eq!(measured.line_metrics, generated.line_metrics);
+
+    // Soft hyphens are only drawn at line breaks, as hyphens
+    assert!(measured.lookup(SOFT_HYPHEN).is_none());
+    for c in charset.chars().filter(|c| *c != SOFT_HYPHEN) {
+	let (measured, generated) = (measured.lookup(c).unwrap(), generated.lookup(c).unwrap());
+	assert_eq!(measured.metrics(), generated.metrics());
+	assert_eq!(measured.glyph_id(), generated.glyph_id());
+    }
+
+    // Layouts only need the metrics
+    let text = "Measured on a server";
+    assert_eq!(TextLayout::new(text, &measured, Some(100)).glyphs(), TextLayout::new(text, &generated, Some(100)).glyphs());
+
+    // Without pixels, repacking moves the boxes, added glyphs are measured and images are refused
+    let mut measured = measured;
+    assert!(!measured.has_pixels() && generated.has_pixels());
+    measured.repack().unwrap();
+    generator.add_glyphs(&mut measured, "€").unwrap();
+    assert_eq!(measured.lookup('€').unwrap().metrics(), generate(AtlasLoadMode::Gray, 1, "€").lookup('€').unwrap().metrics());
+    assert!(measured.insert_image("icon", &Pixels::new(4, 4, PixelFormat::Rgba8)).is_err());
+    assert!(measured.buffer.is_empty());
+}
+
+#[test]
+fn gamma_and_stem_darkening_darken_small_glyphs() {
+    let font_system = FontSystem::new().unwrap();
+    let generate_with = |options: AtlasGeneratorOption| {
+	AtlasGenerator::new(&font_system, FONT_PATH, options.with_charset("ailm"), AtlasLoadMode::Gray).unwrap().generate(11 * 64).unwrap()
+    };
+    let options = AtlasGeneratorOption::new(128, 128, 72, Padding::uniform(1));
+
+    let plain = generate_with(options.clone());
+    let darker = generate_with(options.clone().with_gamma(2.2));
+    assert_eq!(visible_positions(&plain), visible_positions(&darker));
+    assert!(plain.buffer.iter().zip(darker.buffer.iter()).all(|(plain, darker)| darker >= plain));
+    assert!(darker.buffer.iter().map(|value| *value as u32).sum::<u32>() > plain.buffer.iter().map(|value| *value as u32).sum::<u32>());
+
+    // TrueType fonts hinted by their own instructions aren't darkened, but the option still bakes them
+    let darkened = generate_with(options.with_stem_darkening());
+    assert_eq!(darkened.map.len(), plain.map.len());
+}
+
+#[test]
+fn driver_properties_are_set_before_rendering() {
+    let font_system = FontSystem::new().unwrap();
+    let options = AtlasGeneratorOption::new(256, 256, 72, Padding::uniform(1)).with_charset("Hinting");
+
+    let v35 = options.clone().with_driver_property(DriverProperty::InterpreterVersion(35));
+    let generator = AtlasGenerator::new(&font_system, FONT_PATH, v35, AtlasLoadMode::Gray).unwrap();
+    assert_eq!(generator.generate(FONT_SIZE).unwrap().map.len(), 5);
+    assert_ne!(generator.fingerprint(FONT_SIZE), AtlasGenerator::new(&font_system, FONT_PATH, options.clone(), AtlasLoadMode::Gray).unwrap().fingerprint(FONT_SIZE));
+
+    // The truetype driver doesn't darken stems
+    let unknown = options.with_driver_property(DriverProperty::NoStemDarkening(DriverModule::TrueType, false));
+    let generator = AtlasGenerator::new(&font_system, FONT_PATH, unknown, AtlasLoadMode::Gray).unwrap();
+    assert!(matches!(generator.generate(FONT_SIZE), Err(AtlasGeneratorError::LoadError(FontLoaderError::PropertyError(_)))));
+}
+
+#[test]
+fn glyph_hooks_process_bitmaps_before_packing() {
+    let font_system = FontSystem::new().unwrap();
+    let options = AtlasGeneratorOption::new(256, 256, 72, Padding::uniform(1)).with_charset("Hook");
+    let threshold = options.clone().with_glyph_hook(|bitmap: &mut Pixels, _metrics: &GlyphMetrics, c: char| {
+	assert!("Hook".contains(c));
+	for value in bitmap.iter_mut() {
+	    *value = if *value >= 128 { 255 } else { 0 };
+	}
+    });
+
+    let plain = AtlasGenerator::new(&font_system, FONT_PATH, options.clone(), AtlasLoadMode::Gray).unwrap().generate(FONT_SIZE).unwrap();
+    let generator = AtlasGenerator::new(&font_system, FONT_PATH, threshold, AtlasLoadMode::Gray).unwrap();
+    let thresholded = generator.generate(FONT_SIZE).unwrap();
+
+    assert_eq!(visible_positions(&plain), visible_positions(&thresholded));
+    assert!(thresholded.buffer.iter().all(|value| *value == 0 || *value == 255));
+    assert!(plain.buffer.iter().any(|value| *value != 0 && *value != 255));
+    assert!(generator.fingerprint(FONT_SIZE).is_none());
+
+    // Hooks can't resize the bitmaps, whether through their size or their bytes
+    let widen = options.clone().with_glyph_hook(|bitmap: &mut Pixels, _metrics: &GlyphMetrics, _c: char| bitmap.width += 1);
+    let generator = AtlasGenerator::new(&font_system, FONT_PATH, widen, AtlasLoadMode::Gray).unwrap();
+    assert!(matches!(generator.generate(FONT_SIZE), Err(AtlasGeneratorError::HookError(_))));
+    let truncate = options.with_glyph_hook(|bitmap: &mut Pixels, _metrics: &GlyphMetrics, _c: char| bitmap.data.truncate(1));
+    let generator = AtlasGenerator::new(&font_system, FONT_PATH, truncate, AtlasLoadMode::Gray).unwrap();
+    assert!(matches!(generator.generate(FONT_SIZE), Err(AtlasGeneratorError::HookError(_))));
+}
+
+#[test]
+fn backgrounds_fill_the_pixels_around_the_glyphs() {
+    let font_system = FontSystem::new().unwrap();
+    let options = AtlasGeneratorOption::new(128, 128, 72, Padding::uniform(2)).with_charset("Bg");
+    let generate_with = |background: Background| {
+	let options = options.clone().with_background(background);
+	AtlasGenerator::new(&font_system, FONT_PATH, options, AtlasLoadMode::Gray).unwrap().generate(FONT_SIZE).unwrap()
+    };
+
+    let plain = generate_with(Background::Transparent);
+    for background in [Background::MAGENTA, Background::checkerboard(4)] {
+	let mut atlas = generate_with(background);
+	assert_eq!(atlas.background, background);
+
+	// The glyphs are drawn over the background, which shows in their padding
+	for (_, position) in visible_positions(&atlas) {
+	    assert_eq!(atlas.buffer.crop(&position), plain.buffer.crop(&position));
+	    let (x, y) = (position.left - 1, position.top - 1);
+	    assert_eq!(atlas.buffer.get_pixel(x, y), &background.pixel(x, y)[..3]);
+	}
+
+	// Repacking keeps it
+	atlas.repack().unwrap();
+	assert_eq!(atlas.buffer.get_pixel(127, 127), &background.pixel(127, 127)[..3]);
+    }
+}
+
+#[test]
+fn uv_rects_can_be_inset_by_half_a_texel() {
+    let atlas = generate(AtlasLoadMode::Gray, 1, &latin1());
+    let size = (atlas.width, atlas.height);
+
+    for (c, position) in visible_positions(&atlas) {
+	let entry = &atlas.map[&c];
+	let [left, top, right, bottom] = entry.uv_rect(size, false);
+	assert_eq!(left * 512.0, position.left as f32);
+	assert_eq!(top * 512.0, position.top as f32);
+	assert_eq!(right * 512.0, (position.left + position.width) as f32);
+	assert_eq!(bottom * 512.0, (position.top + position.height) as f32);
+
+	let [inset_left, inset_top, inset_right, inset_bottom] = entry.uv_rect(size, true);
+	assert_eq!(inset_left * 512.0, position.left as f32 + 0.5);
+	assert_eq!(inset_top * 512.0, position.top as f32 + 0.5);
+	assert_eq!(inset_right * 512.0, (position.left + position.width) as f32 - 0.5);
+	assert_eq!(inset_bottom * 512.0, (position.top + position.height) as f32 - 0.5);
+    }
+
+    // Blank glyphs have no texels to inset
+    assert_eq!(atlas.map[&' '].uv_rect(size, true), atlas.map[&' '].uv_rect(size, false));
+}
+
+#[test]
+fn gray_values_are_expanded_to_the_three_channels() {
+    // Lengths around the block size, the trailing values being expanded one by one
+    for length in [0, 1, 15, 16, 17, 40] {
+	let gray: Vec<u8> = (0..length).map(|i| (i * 7) as u8).collect();
+	let mut rgb = vec![1; length * 3 + 2];
+	expand_gray(&gray, &mut rgb);
+
+	let expected: Vec<u8> = gray.iter().flat_map(|value| [*value; 3]).collect();
+	assert_eq!(&rgb[..length * 3], &expected[..]);
+	assert_eq!(&rgb[length * 3..], &[1, 1]);
+    }
+}
+
+#[test]
+fn text_is_only_drawn_in_rgba_images() {
+    let atlas = generate(AtlasLoadMode::Gray, 1, &latin1());
+    let layout = TextLayout::new("Text", &atlas, None);
+
+    let mut rgb = Pixels::new(64, 32, PixelFormat::Rgb8);
+    let error = draw_text(&mut rgb, 0, 0, &layout, &atlas, Color::default()).unwrap_err();
+    assert_eq!(error, PixelFormatError::new(PixelFormat::Rgba8, PixelFormat::Rgb8));
+    assert!(rgb.iter().all(|value| *value == 0));
+
+    let mut rgba = Pixels::new(64, 32, PixelFormat::Rgba8);
+    draw_text(&mut rgba, 0, 0, &layout, &atlas, Color::default()).unwrap();
+    assert!(rgba.iter().any(|value| *value != 0));
+}
+
+#[test]
+fn color_atlases_keep_the_alpha_of_their_glyphs() {
+    let mut gray = generate(AtlasLoadMode::Gray, 1, "Alpha");
+    let mut color = generate(AtlasLoadMode::Color, 1, "Alpha");
+    assert_eq!((gray.buffer.format, color.buffer.format), (PixelFormat::Rgb8, PixelFormat::Rgba8));
+    assert_eq!(visible_positions(&gray), visible_positions(&color));
+
+    // Coverage is stored as premultiplied white, and drawn the same
+    for (_, position) in visible_positions(&color) {
+	for y in position.top..position.top + position.height {
+	    for x in position.left..position.left + position.width {
+		assert_eq!(color.buffer.get_pixel(x, y), &[gray.buffer.get_pixel(x, y)[0]; 4]);
+	    }
+	}
+    }
+    let layout = TextLayout::new("Alpha", &color, None);
+    let (mut from_gray, mut from_color) = (Pixels::new(96, 32, PixelFormat::Rgba8), Pixels::new(96, 32, PixelFormat::Rgba8));
+    draw_text(&mut from_gray, 0, 0, &layout, &gray, Color::rgb(0, 128, 255)).unwrap();
+    draw_text(&mut from_color, 0, 0, &layout, &color, Color::rgb(0, 128, 255)).unwrap();
+    assert_eq!(from_gray, from_color);
+
+    // Images keep their alpha in color atlases only
+    let image = Pixels::from_raw(2, 1, PixelFormat::Rgba8, vec![255, 0, 0, 128, 0, 0, 255, 255]).unwrap();
+    let position = *color.insert_image("icon", &image).unwrap().position();
+    assert_eq!(&color.buffer.crop(&position).data, &[128, 0, 0, 128, 0, 0, 255, 255]);
+    let position = *gray.insert_image("icon", &image).unwrap().position();
+    assert_eq!(&gray.buffer.crop(&position).data, &[128, 0, 0, 0, 0, 255]);
+}
@@ -0,0 +1,112 @@
+#![cfg(all(feature = "freetype", feature = "bevy"))]
+
+use font::Rectangle;
+use font::atlas::{AtlasGenerator, AtlasGeneratorOption, AtlasLoadMode, FontAtlas, Padding, GLYPHS};
+use font::bevy::{AtlasAssets, AtlasTextures, FontAtlasPlugin, Text2dAtlas, TextureEvent};
+use font::loader::FontSystem;
+use font::rich_text::Color;
+
+use bevy::asset::AssetPlugin;
+use bevy::prelude::{App, AssetApp, Assets, Handle, Image, Mesh, MinimalPlugins, SpatialBundle};
+use bevy::sprite::{ColorMaterial, Mesh2dHandle};
+
+/// The OFL licensed font shipped in the repository.
+const FONT_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/fonts/EBGaramond-Regular.ttf");
+
+const FONT_SIZE: u32 = 24 * 64;
+
+fn generate() -> FontAtlas {
+    let font_system = FontSystem::new().unwrap();
+    let options = AtlasGeneratorOption::new(256, 256, 72, Padding::uniform(1)).with_charset(GLYPHS);
+
+    AtlasGenerator::new(&font_system, FONT_PATH, options, AtlasLoadMode::Gray).unwrap().generate(FONT_SIZE).unwrap()
+}
+
+#[test]
+fn textures_are_created_updated_and_released() {
+    let mut assets = AtlasAssets::new();
+    let handle = assets.add(generate());
+
+    let events = assets.texture_events();
+    assert_eq!(events.len(), 1);
+    match &events[0] {
+	TextureEvent::Created { handle: created, width, height, data } => {
+	    assert_eq!(*created, handle);
+	    assert_eq!((*width, *height), (256, 256));
+	    assert_eq!(data.len(), 256 * 256 * 4);
+	},
+	event => panic!("unexpected event {:?}", event),
+    }
+    assert!(assets.texture_events().is_empty());
+
+    let region = Rectangle::new(2, 3, 4, 5);
+    assets.get_mut(handle).unwrap().mark_dirty(region);
+    match assets.texture_events().as_slice() {
+	[TextureEvent::Updated { handle: updated, region: dirty, data }] => {
+	    assert_eq!((*updated, *dirty), (handle, region));
+	    assert_eq!(data.len(), 4 * 5 * 4);
+	},
+	events => panic!("unexpected events {:?}", events),
+    }
+
+    assert!(assets.remove(handle).is_some());
+    assert_eq!(assets.texture_events(), vec![TextureEvent::Released { handle }]);
+    assert!(assets.is_empty());
+
+    // An atlas removed before its texture was created leaves nothing to release
+    let handle = assets.add(generate());
+    assets.remove(handle);
+    assert!(assets.texture_events().is_empty());
+}
+
+#[test]
+fn text_meshes_are_drawn_with_y_up_and_linear_colors() {
+    let mut assets = AtlasAssets::new();
+    let handle = assets.add(generate());
+    let text = Text2dAtlas::new("Hi", handle).with_color(Color::new(255, 0, 0, 128));
+
+    let mesh = text.mesh(&assets).unwrap();
+    assert_eq!(mesh.positions.len(), 2 * 4);
+    assert_eq!(mesh.uvs.len(), 2 * 4);
+    assert_eq!(mesh.indices.len(), 2 * 6);
+    assert!(mesh.colors.iter().all(|color| *color == [1.0, 0.0, 0.0, 128.0 / 255.0]));
+
+    // The paragraph hangs below the origin, at its top-left corner
+    assert!(mesh.positions.iter().all(|position| position[0] >= 0.0 && position[1] <= 0.0));
+    assert!(mesh.positions.iter().any(|position| position[1] < 0.0));
+
+    assets.remove(handle);
+    assert_eq!(text.mesh(&assets), None);
+}
+
+#[test]
+fn the_plugin_uploads_atlases_and_meshes_texts() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default(), FontAtlasPlugin))
+	.init_asset::<Image>()
+	.init_asset::<Mesh>()
+	.init_asset::<ColorMaterial>();
+
+    let handle = app.world_mut().resource_mut::<AtlasAssets>().add(generate());
+    let entity = app.world_mut().spawn((Text2dAtlas::new("Hi", handle), SpatialBundle::default())).id();
+    app.update();
+
+    let image = app.world().resource::<AtlasTextures>().get(handle).unwrap().clone();
+    assert_eq!(app.world().resource::<Assets<Image>>().get(&image).unwrap().size().to_array(), [256, 256]);
+    let mesh = app.world().get::<Mesh2dHandle>(entity).unwrap().0.clone();
+    assert_eq!(app.world().resource::<Assets<Mesh>>().get(&mesh).unwrap().count_vertices(), 2 * 4);
+    let material = app.world().get::<Handle<ColorMaterial>>(entity).unwrap();
+    assert_eq!(app.world().resource::<Assets<ColorMaterial>>().get(material).unwrap().texture, Some(image.clone()));
+
+    // Changing the text gives the entity a new mesh
+    app.world_mut().get_mut::<Text2dAtlas>(entity).unwrap().text = String::from("Hello");
+    app.update();
+    let mesh = app.world().get::<Mesh2dHandle>(entity).unwrap().0.clone();
+    assert_eq!(app.world().resource::<Assets<Mesh>>().get(&mesh).unwrap().count_vertices(), 5 * 4);
+
+    // Removing the atlas drops its image
+    app.world_mut().resource_mut::<AtlasAssets>().remove(handle);
+    app.update();
+    assert!(app.world().resource::<AtlasTextures>().get(handle).is_none());
+    assert!(app.world().resource::<Assets<Image>>().get(&image).is_none());
+}
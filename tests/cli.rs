@@ -0,0 +1,117 @@
+#![cfg(feature = "cli")]
+
+use std::path::PathBuf;
+use std::process::Command;
+
+use font::atlas::{AtlasGenerator, AtlasLoadMode, Padding};
+
+/// The OFL licensed font shipped in the repository.
+const FONT_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/fonts/EBGaramond-Regular.ttf");
+
+/// Returns an empty directory of its own for `test`.
+fn output_directory(test: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("font-bake-{}-{}", test, std::process::id()));
+    let _ = std::fs::remove_dir_all(&directory);
+    std::fs::create_dir_all(&directory).unwrap();
+    directory
+}
+
+fn font_bake() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_font-bake"))
+}
+
+#[test]
+fn metadata_describes_the_baked_image() {
+    let directory = output_directory("metadata");
+    let (out, meta) = (directory.join("atlas.png"), directory.join("atlas.json"));
+
+    let status = font_bake()
+	.args(["--font", FONT_PATH, "--size", "24", "--charset", "latin1", "--padding", "1", "--mode", "gray"])
+	.arg("--out").arg(&out)
+	.arg("--meta").arg(&meta)
+	.status()
+	.unwrap();
+    assert!(status.success());
+
+    let meta: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&meta).unwrap()).unwrap();
+    let image = image::open(&out).unwrap().to_luma();
+
+    // The same atlas baked by the library gives the expected entries and pixels
+    let atlas = AtlasGenerator::builder()
+	.font(FONT_PATH)
+	.size(512, 512)
+	.padding(Padding::new(1, 1, 1, 1))
+	.mode(AtlasLoadMode::Gray)
+	.charset(&(' '..='~').chain('\u{a0}'..='\u{ff}').collect::<String>())
+	.deterministic()
+	.build()
+	.unwrap()
+	.generate(24 * 64)
+	.unwrap();
+
+    assert_eq!(meta["mode"], "gray");
+    assert_eq!(meta["width"], atlas.width);
+    assert_eq!(meta["height"], atlas.height);
+    assert_eq!(meta["ascender"], atlas.line_metrics.ascender);
+    assert_eq!(meta["descender"], atlas.line_metrics.descender);
+    assert_eq!(meta["line_height"], atlas.line_metrics.line_height);
+    assert_eq!(meta["hash"], format!("{:016x}", atlas.content_hash()));
+    assert_eq!(meta["fingerprint"], format!("{:016x}", atlas.fingerprint().unwrap()));
+    assert_eq!(image.dimensions(), (atlas.width, atlas.height));
+
+    let glyphs = meta["glyphs"].as_array().unwrap();
+    assert_eq!(glyphs.len(), atlas.map.len());
+
+    for glyph in glyphs {
+	let c = glyph["char"].as_str().unwrap().chars().next().unwrap();
+	assert_eq!(glyph["codepoint"], c as u32);
+
+	let entry = &atlas.map[&c];
+	let (position, metrics) = (entry.position(), entry.metrics());
+	assert_eq!(glyph["x"], position.left, "{}", c);
+	assert_eq!(glyph["y"], position.top, "{}", c);
+	assert_eq!(glyph["width"], position.width, "{}", c);
+	assert_eq!(glyph["height"], position.height, "{}", c);
+	assert_eq!(glyph["bearing_x"], metrics.bearing_x, "{}", c);
+	assert_eq!(glyph["bearing_y"], metrics.bearing_y, "{}", c);
+	assert_eq!(glyph["advance"], metrics.advance, "{}", c);
+	assert_eq!(glyph["rotated"], false, "{}", c);
+
+	for y in position.top..position.top + position.height {
+	    for x in position.left..position.left + position.width {
+		assert_eq!(image.get_pixel(x, y).0[0], atlas.buffer.get_pixel(x, y)[0], "{} at ({}, {})", c, x, y);
+	    }
+	}
+    }
+
+    let _ = std::fs::remove_dir_all(&directory);
+}
+
+#[test]
+fn errors_have_their_own_exit_code() {
+    let directory = output_directory("errors");
+
+    let missing_font = font_bake()
+	.args(["--font", "missing.ttf"])
+	.arg("--out").arg(directory.join("atlas.png"))
+	.status()
+	.unwrap();
+    assert_eq!(missing_font.code(), Some(3));
+
+    let too_small = font_bake()
+	.args(["--font", FONT_PATH, "--size", "64", "--width", "32", "--height", "32"])
+	.arg("--out").arg(directory.join("atlas.png"))
+	.status()
+	.unwrap();
+    assert_eq!(too_small.code(), Some(5));
+
+    // The flags of a single atlas would be ignored with a config, so clap rejects them
+    let config_with_flags = font_bake()
+	.arg("--config").arg(directory.join("atlases.toml"))
+	.args(["--size", "12"])
+	.status()
+	.unwrap();
+    assert_eq!(config_with_flags.code(), Some(2));
+
+    let _ = std::fs::remove_dir_all(&directory);
+}
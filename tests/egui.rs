@@ -0,0 +1,99 @@
+#![cfg(all(feature = "freetype", feature = "egui"))]
+
+use font::Rectangle;
+use font::atlas::{AtlasGenerator, AtlasGeneratorOption, AtlasLoadMode, FontAtlas, Padding, GLYPHS};
+use font::egui::{Galley, TextureDelta};
+use font::layout::LayoutOptions;
+use font::loader::FontSystem;
+use font::rich_text::Color;
+
+use epaint::{Color32, ImageData, ImageDelta, TextureId};
+
+/// The OFL licensed font shipped in the repository.
+const FONT_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/fonts/EBGaramond-Regular.ttf");
+
+const FONT_SIZE: u32 = 24 * 64;
+
+fn generate(load_mode: AtlasLoadMode) -> FontAtlas {
+    let font_system = FontSystem::new().unwrap();
+    let options = AtlasGeneratorOption::new(256, 256, 72, Padding::uniform(1)).with_charset(&format!("{}é", GLYPHS));
+
+    AtlasGenerator::new(&font_system, FONT_PATH, options, load_mode).unwrap().generate(FONT_SIZE).unwrap()
+}
+
+#[test]
+fn texture_deltas_cover_the_dirty_regions_in_premultiplied_white() {
+    let mut atlas = generate(AtlasLoadMode::Gray);
+
+    let deltas = atlas.egui_texture_deltas();
+    assert_eq!(deltas.len(), 1);
+    assert_eq!(deltas[0].pos, None);
+    assert_eq!(deltas[0].size, [256, 256]);
+    assert!(deltas[0].pixels.iter().all(|[r, g, b, a]| r == g && g == b && b == a));
+    assert!(deltas[0].pixels.iter().any(|pixel| pixel[3] == 255));
+    assert!(atlas.egui_texture_deltas().is_empty());
+
+    let region = Rectangle::new(2, 3, 4, 5);
+    atlas.mark_dirty(region);
+    let deltas = atlas.egui_texture_deltas();
+    assert_eq!(deltas, vec![TextureDelta::region(&atlas.buffer, &region, AtlasLoadMode::Gray)]);
+    assert_eq!(deltas[0].pos, Some([3, 2]));
+    assert_eq!(deltas[0].pixels.len(), 4 * 5);
+
+    // LCD coverage is averaged, egui blending a single alpha
+    let atlas = generate(AtlasLoadMode::LCD);
+    let delta = TextureDelta::full(&atlas.buffer, atlas.load_mode);
+    assert!(delta.pixels.iter().all(|[r, g, b, a]| r == g && g == b && b == a));
+}
+
+#[test]
+fn galleys_are_in_points_with_premultiplied_colors() {
+    let atlas = generate(AtlasLoadMode::Gray);
+    let text = "Hé\nworld";
+    let color = Color::new(255, 0, 0, 128);
+
+    let galley = Galley::new(text, &atlas, &LayoutOptions::new(), color, 2.0);
+    let line_height = atlas.line_metrics.line_height as f32 / 2.0;
+
+    assert_eq!(galley.meshes.len(), 1);
+    let mesh = &galley.meshes[0];
+    assert_eq!(mesh.vertices.len(), 7 * 4);
+    assert_eq!(mesh.indices.len(), 7 * 6);
+    assert!(mesh.vertices.iter().all(|vertex| vertex.color == [128, 0, 0, 128]));
+    assert!(mesh.vertices.iter().all(|vertex| (0.0..=1.0).contains(&vertex.uv[0]) && (0.0..=1.0).contains(&vertex.uv[1])));
+
+    assert_eq!(galley.rows.len(), 2);
+    assert_eq!(galley.rows[0].char_range, 0..2);
+    assert_eq!(galley.rows[1].char_range, 3..8);
+    assert_eq!(galley.rows[1].byte_range, 4..9);
+    assert_eq!(galley.rows[1].rect[1], line_height);
+    assert_eq!(galley.size[1], 2.0 * line_height);
+    assert!(mesh.vertices.iter().all(|vertex| vertex.pos[1] <= galley.size[1]));
+
+    // Cursors are in characters and points
+    assert_eq!(galley.cursor_at(0.0, line_height * 1.5), 3);
+    assert_eq!(galley.cursor_rect(3)[1], line_height);
+}
+
+#[test]
+fn deltas_and_meshes_convert_to_epaint() {
+    let mut atlas = generate(AtlasLoadMode::Gray);
+    atlas.egui_texture_deltas();
+
+    let region = Rectangle::new(2, 3, 4, 5);
+    atlas.mark_dirty(region);
+    let delta = ImageDelta::from(atlas.egui_texture_deltas().remove(0));
+    assert_eq!(delta.pos, Some([3, 2]));
+    match delta.image {
+	ImageData::Color(image) => assert_eq!(image.size, [4, 5]),
+	_ => panic!("the delta isn't a color image"),
+    }
+
+    let galley = Galley::new("Hi", &atlas, &LayoutOptions::new(), Color::new(255, 0, 0, 128), 1.0);
+    let mesh = galley.meshes[0].to_mesh(TextureId::User(7));
+    assert_eq!(mesh.texture_id, TextureId::User(7));
+    assert_eq!(mesh.indices, galley.meshes[0].indices);
+    assert_eq!(mesh.vertices.len(), galley.meshes[0].vertices.len());
+    assert!(mesh.vertices.iter().all(|vertex| vertex.color == Color32::from_rgba_premultiplied(128, 0, 0, 128)));
+    assert_eq!(mesh.vertices[0].pos.x, galley.meshes[0].vertices[0].pos[0]);
+}
@@ -0,0 +1,81 @@
+#![cfg(feature = "ffi")]
+
+use std::ffi::{CStr, CString};
+
+use font::ffi::*;
+
+/// The OFL licensed font shipped in the repository.
+const FONT_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/fonts/EBGaramond-Regular.ttf");
+
+#[test]
+fn atlases_are_generated_and_queried_through_the_c_api() {
+    let charset = CString::new("Hello").unwrap();
+    let data = std::fs::read(FONT_PATH).unwrap();
+
+    let mut options = font_generator_options_default();
+    options.width = 128;
+    options.height = 128;
+    options.padding = 1;
+    options.load_mode = FONT_LOAD_MODE_GRAY;
+    options.notdef = true;
+    options.charset = charset.as_ptr();
+
+    unsafe {
+	let generator = font_generator_new_from_memory(data.as_ptr(), data.len(), &options);
+	assert!(!generator.is_null());
+
+	let atlas = font_generator_generate(generator, 24 * 64);
+	assert!(!atlas.is_null());
+
+	let (mut width, mut height, mut length) = (0, 0, 0);
+	font_atlas_size(atlas, &mut width, &mut height);
+	let pixels = font_atlas_pixels(atlas, &mut length);
+	assert_eq!((width, height, length), (128, 128, 128 * 128 * 3));
+
+	let mut glyph = FontGlyph::default();
+	assert!(font_atlas_glyph(atlas, 'H' as u32, &mut glyph));
+	assert!(glyph.width > 0 && glyph.height > 0 && glyph.advance > 0);
+
+	// The glyph has been drawn where its entry says
+	let pixels = std::slice::from_raw_parts(pixels, length);
+	let inked = (glyph.y..glyph.y + glyph.height)
+	    .flat_map(|y| (glyph.x..glyph.x + glyph.width).map(move |x| (y * width + x) as usize * 3))
+	    .any(|offset| pixels[offset] > 0);
+	assert!(inked);
+
+	// Missing characters are given the .notdef glyph
+	let mut notdef = FontGlyph::default();
+	assert!(font_atlas_glyph(atlas, 'z' as u32, &mut notdef));
+	assert_ne!((notdef.x, notdef.y), (glyph.x, glyph.y));
+
+	let mut line_metrics = FontLineMetrics::default();
+	font_atlas_line_metrics(atlas, &mut line_metrics);
+	assert!(line_metrics.ascender > 0 && line_metrics.descender < 0);
+
+	font_atlas_free(atlas);
+	font_generator_free(generator);
+    }
+}
+
+#[test]
+fn errors_are_reported_through_the_last_error() {
+    let path = CString::new("missing.ttf").unwrap();
+    let options = font_generator_options_default();
+
+    unsafe {
+	assert!(font_generator_new(path.as_ptr(), &options).is_null());
+	assert!(!font_last_error().is_null());
+	assert_eq!(CStr::from_ptr(font_last_error()).to_str().unwrap(), "Can't load font face");
+
+	let unknown_mode = FontGeneratorOptions { load_mode: 7, ..options };
+	assert!(font_generator_new(path.as_ptr(), &unknown_mode).is_null());
+	assert_eq!(CStr::from_ptr(font_last_error()).to_str().unwrap(), "Unknown load mode 7");
+
+	assert!(font_generator_generate(std::ptr::null(), 24 * 64).is_null());
+	assert_eq!(CStr::from_ptr(font_last_error()).to_str().unwrap(), "The generator can't be null");
+
+	// Freeing null does nothing
+	font_generator_free(std::ptr::null_mut());
+	font_atlas_free(std::ptr::null_mut());
+    }
+}
@@ -0,0 +1,594 @@
+#![cfg(feature = "freetype")]
+
+use font::atlas::{AtlasGenerator, AtlasGeneratorOption, AtlasLoadMode, FontAtlas, GlyphAtlas, GlyphQuad, Padding, TextVertex};
+use font::layout::{Anchor, LayoutConfig, LayoutOptions, TextLayout, YAxis, NO_BREAK_SPACE, SOFT_HYPHEN};
+use font::layout_cache::LayoutCache;
+use font::loader::{FontLoader, FontSystem};
+use font::measure::TextMeasurer;
+use font::multi_atlas::{FontId, MultiFontAtlasGenerator};
+use font::rich_text::{Color, Decoration, MapTheme, RichText, TextStyle};
+use font::script::{itemize, sequence_glyph_name, Script};
+
+/// The OFL licensed font shipped in the repository.
+const FONT_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/fonts/EBGaramond-Regular.ttf");
+const FALLBACK_FONT_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/fonts/Ubuntu-R.ttf");
+
+const FONT_SIZE: u32 = 24 * 64;
+
+fn latin1_atlas() -> FontAtlas {
+    let font_system = FontSystem::new().unwrap();
+    let charset: String = (' '..='\u{ff}').filter(|c| !c.is_control()).collect();
+    let options = AtlasGeneratorOption::new(512, 512, 72, Padding::uniform(1)).with_charset(&charset).with_notdef();
+
+    AtlasGenerator::new(&font_system, FONT_PATH, options, AtlasLoadMode::Gray).unwrap().generate(FONT_SIZE).unwrap()
+}
+
+/// Returns the text of each line of `layout`.
+fn lines<'a>(layout: &TextLayout, text: &'a str) -> Vec<&'a str> {
+    layout.line_boundaries().iter().map(|line| &text[line.range.clone()]).collect()
+}
+
+/// Returns the left, bottom, right and top edges of the quad drawn by `vertices`.
+fn quad_bounds(vertices: &[TextVertex]) -> [f32; 4] {
+    vertices.iter().fold([f32::MAX, f32::MAX, f32::MIN, f32::MIN], |[left, bottom, right, top], vertex| {
+	let [x, y] = vertex.position;
+	[left.min(x), bottom.min(y), right.max(x), top.max(y)]
+    })
+}
+
+#[test]
+fn soft_hyphens_are_only_drawn_at_breaks() {
+    let atlas = latin1_atlas();
+    let text = format!("extra{}ordinary", SOFT_HYPHEN);
+    let unbroken = TextLayout::new(&text, &atlas, None);
+    let plain = TextLayout::new("extraordinary", &atlas, None);
+
+    // Without a break, the soft hyphen neither advances nor draws anything
+    assert_eq!(unbroken.line_count(), 1);
+    assert_eq!(unbroken.glyphs().last().unwrap().x, plain.glyphs().last().unwrap().x);
+    assert_eq!(unbroken.generate_buffers(&atlas, 0, 0), plain.generate_buffers(&atlas, 0, 0));
+
+    // A line broken at it ends with a hyphen
+    let max_width = plain.glyphs()[6].x as u32;
+    let broken = TextLayout::new(&text, &atlas, Some(max_width));
+    assert_eq!(lines(&broken, &text), vec![format!("extra{}", SOFT_HYPHEN).as_str(), "ordinary"]);
+
+    let hyphen = broken.glyphs().iter().find(|glyph| glyph.c == SOFT_HYPHEN).unwrap();
+    assert_eq!(hyphen.advance, atlas.advance('-'));
+    assert_eq!(hyphen.glyph_id, atlas.lookup('-').unwrap().glyph_id());
+    assert_eq!(broken.generate_buffers(&atlas, 0, 0).len(), text.chars().count() * 6);
+
+    // The atlas alone never draws them
+    assert!(atlas.lookup(SOFT_HYPHEN).is_none());
+    assert_eq!(atlas.advance(SOFT_HYPHEN), 0);
+}
+
+#[test]
+fn no_break_spaces_keep_words_together() {
+    let atlas = latin1_atlas();
+    let text = format!("a 10{}km", NO_BREAK_SPACE);
+    let max_width = TextLayout::new("a 10", &atlas, None).glyphs().last().unwrap().x as u32 + 4;
+
+    let layout = TextLayout::with_options(&text, &atlas, &LayoutOptions::new().with_max_width(max_width));
+    assert_eq!(lines(&layout, &text), vec!["a ", &text[2..]]);
+
+    let space = layout.glyphs().iter().find(|glyph| glyph.c == NO_BREAK_SPACE).unwrap();
+    assert_eq!(space.advance, atlas.advance(' '));
+}
+
+#[test]
+fn itemize_splits_scripts_and_keeps_common_characters_in_their_run() {
+    let text = "(abc) αβγ, где? 漢字";
+    let runs: Vec<(&str, Script)> = itemize(text).into_iter().map(|(range, script)| (&text[range], script)).collect();
+
+    assert_eq!(runs, vec![("(abc) ", Script::Latin), ("αβγ, ", Script::Greek), ("где? ", Script::Cyrillic), ("漢字", Script::Han)]);
+    assert_eq!(itemize("12 + 3"), vec![(0..6, Script::Common)]);
+    assert_eq!(Script::of('e'), Script::Latin);
+    assert_eq!(Script::of('\u{301}'), Script::Inherited);
+    assert!(itemize("").is_empty());
+}
+
+#[test]
+fn script_runs_use_the_first_font_supporting_them() {
+    let font_system = FontSystem::new().unwrap();
+    let latin: String = ('a'..='z').chain(Some(' ')).collect();
+    let greek: String = latin.chars().chain('α'..='ω').collect();
+
+    let mut generator = MultiFontAtlasGenerator::new(AtlasGeneratorOption::new(512, 512, 72, Padding::uniform(1)), AtlasLoadMode::Gray);
+    let serif = generator.add_font(FontLoader::new(&font_system, FONT_PATH).unwrap(), &latin);
+    let sans = generator.add_font(FontLoader::new(&font_system, FALLBACK_FONT_PATH).unwrap(), &greek);
+    let atlas = generator.generate(FONT_SIZE).unwrap();
+
+    let text = "abc αβγ abc";
+    let layout = TextLayout::with_font_chain(text, &atlas, &LayoutOptions::new(), &[serif, sans]);
+    let fonts: Vec<(char, FontId)> = layout.glyphs().iter().map(|glyph| (glyph.c, glyph.font)).collect();
+
+    assert_eq!(&fonts[..4], &[('a', serif), ('b', serif), ('c', serif), (' ', serif)]);
+    assert_eq!(&fonts[4..8], &[('α', sans), ('β', sans), ('γ', sans), (' ', sans)]);
+    assert_eq!(fonts[8], ('a', serif));
+
+    // The order of the chain decides between fonts supporting the same script
+    let layout = TextLayout::with_font_chain(text, &atlas, &LayoutOptions::new(), &[sans, serif]);
+    assert!(layout.glyphs().iter().all(|glyph| glyph.font == sans));
+}
+
+#[test]
+fn emoji_have_a_script_and_sequences_a_glyph_name() {
+    let text = "hi 👩\u{200d}💻!";
+    let runs: Vec<(&str, Script)> = itemize(text).into_iter().map(|(range, script)| (&text[range], script)).collect();
+
+    assert_eq!(runs, vec![("hi ", Script::Latin), ("👩\u{200d}💻!", Script::Emoji)]);
+    assert_eq!(sequence_glyph_name("👩\u{200d}💻"), "u1F469_200D_1F4BB");
+    assert_eq!(sequence_glyph_name("❤\u{fe0f}"), "u2764");
+}
+
+#[test]
+fn joiners_and_variation_selectors_are_never_drawn() {
+    let atlas = latin1_atlas();
+    let text = "a\u{fe0f}b\u{200d}c\u{fe0e}";
+    let layout = TextLayout::new(text, &atlas, None);
+
+    assert!(atlas.lookup('\u{200d}').is_none());
+    assert!(atlas.lookup('\u{fe0f}').is_none());
+    assert_eq!(layout.generate_buffers(&atlas, 0, 0).len(), 3 * 6);
+    assert_eq!(layout.glyphs().last().unwrap().x + layout.glyphs().last().unwrap().advance, TextLayout::new("abc", &atlas, None).glyphs()[2].x + atlas.advance('c'));
+}
+
+#[test]
+fn zwj_sequences_are_drawn_as_one_glyph_or_side_by_side() {
+    let mut atlas = latin1_atlas();
+    let sequence = "👩\u{200d}💻";
+    let text = format!("a{}b", sequence);
+
+    // Without a glyph for the sequence, the joined emoji are drawn one after the other, here with the .notdef glyph
+    let layout = TextLayout::new(&text, &atlas, None);
+    let notdef_advance = atlas.notdef.unwrap().metrics().advance;
+    let xs: Vec<i32> = layout.glyphs().iter().map(|glyph| glyph.x).collect();
+    let a = atlas.advance('a');
+    assert_eq!(xs, vec![0, a, a + notdef_advance, a + notdef_advance, a + 2 * notdef_advance]);
+    assert_eq!(layout.generate_buffers(&atlas, 0, 0).len(), 4 * 6);
+
+    // With one, the cluster is drawn once with its advance and own colors
+    let entry = atlas.lookup('x').unwrap().with_color(true).with_glyph_id(1234);
+    atlas.sequences.insert(String::from(sequence), entry);
+    assert_eq!(atlas.lookup_sequence("👩\u{fe0f}\u{200d}💻"), Some(&entry));
+
+    let layout = TextLayout::new(&text, &atlas, None);
+    let xs: Vec<i32> = layout.glyphs().iter().map(|glyph| glyph.x).collect();
+    assert_eq!(xs, vec![0, a, a + atlas.advance('x'), a + atlas.advance('x'), a + atlas.advance('x')]);
+    assert_eq!(layout.glyphs()[1].glyph_id, 1234);
+    assert_eq!(layout.glyph_entry(&atlas, &layout.glyphs()[1]), Some(&entry));
+    assert_eq!(layout.glyph_entry(&atlas, &layout.glyphs()[3]), None);
+
+    let red = Color::rgb(255, 0, 0);
+    let vertices = layout.generate_colored_buffers(&atlas, 0, 0, &[(0..text.len(), red)]);
+    assert_eq!(vertices.len(), 3 * 6);
+    assert_eq!(vertices[6].color, Color::default().to_f32());
+    assert_eq!(vertices[0].color, red.to_f32());
+}
+
+#[test]
+fn sequences_missing_from_the_fonts_are_left_out() {
+    let font_system = FontSystem::new().unwrap();
+    let options = AtlasGeneratorOption::new(256, 256, 72, Padding::uniform(1)).with_sequences(&["👩\u{200d}💻"]);
+    let atlas = AtlasGenerator::new(&font_system, FONT_PATH, options, AtlasLoadMode::Color).unwrap().generate(FONT_SIZE).unwrap();
+
+    assert!(atlas.sequences.is_empty());
+    assert!(atlas.failures.is_empty());
+    assert!(atlas.map.values().all(|entry| !entry.is_color()));
+}
+
+/// A vertex of an engine with 3D positions and 16 bits uvs.
+#[derive(Debug, PartialEq)]
+struct PackedVertex {
+    position: [f32; 3],
+    uv: [u16; 2],
+}
+
+#[test]
+fn vertex_emitters_get_the_quads_of_the_indexed_buffers() {
+    let atlas = latin1_atlas();
+    let text = "Héllo, world";
+    let layout = TextLayout::new(text, &atlas, None);
+    let (vertices, indices) = layout.generate_indexed_buffers(&atlas, 10, 20);
+
+    let mut quads = Vec::new();
+    let mut packed = Vec::new();
+    layout.emit_colored_vertices(&atlas, 10, 20, &[(1..2, Color::rgb(255, 0, 0))], &mut |quad: &GlyphQuad| {
+	quads.push(*quad);
+	packed.extend((0..4).map(|corner| PackedVertex {
+	    position: [quad.positions[corner][0], quad.positions[corner][1], 0.5],
+	    uv: quad.uvs[corner].map(|uv| (uv * 65535.0).round() as u16),
+	}));
+    });
+
+    // The space has no quad, and the index of each quad is the one of its glyph in the text
+    assert_eq!(quads.len(), vertices.len() / 4);
+    assert_eq!(packed.len(), vertices.len());
+    assert_eq!(quads.iter().map(|quad| quad.index).collect::<Vec<_>>(), vec![0, 1, 3, 4, 5, 6, 8, 9, 10, 11, 12]);
+    assert_eq!(quads[1].color, [1.0, 0.0, 0.0, 1.0]);
+    assert_eq!(&indices[..6], &GlyphQuad::triangle_corners());
+
+    for (quad, corners) in quads.iter().zip(vertices.chunks_exact(4)) {
+	let mut vertices = quad.vertices();
+	vertices.iter_mut().for_each(|vertex| vertex.color = corners[0].color);
+	assert_eq!(&vertices, corners);
+    }
+}
+
+#[test]
+fn justified_lines_are_stretched_to_the_maximum_width() {
+    let atlas = latin1_atlas();
+    let text = "the quick brown fox jumps over the lazy dog\nand sleeps";
+    let options = LayoutOptions::new().with_max_width(200);
+    let ragged = TextLayout::with_options(text, &atlas, &options);
+    let justified = TextLayout::with_options(text, &atlas, &options.clone().with_justify(true));
+
+    assert!(ragged.line_count() > 2);
+    assert_eq!(lines(&justified, text), lines(&ragged, text));
+
+    let last_line = justified.line_count() - 1;
+    for (line, boundary) in justified.line_boundaries().iter().enumerate() {
+	let ends_paragraph = line == last_line || text[boundary.range.end..].starts_with('\n');
+	let last = justified.glyphs().iter().rfind(|glyph| glyph.line == line && !glyph.c.is_whitespace()).unwrap();
+	match ends_paragraph {
+	    true => assert_eq!(boundary, &ragged.line_boundaries()[line]),
+	    false => {
+		assert_eq!(last.x + last.advance, 200);
+		assert_eq!(boundary.rectangle.width, 200.0);
+	    },
+	}
+    }
+
+    // Words move with their gaps
+    for (word, glyph) in justified.word_boundaries().iter().map(|word| (word, justified.glyphs().iter().find(|glyph| glyph.index == word.range.start).unwrap())) {
+	assert_eq!(word.rectangle.x, glyph.x as f32);
+    }
+}
+
+#[test]
+fn overflowing_words_are_hyphenated_to_fill_lines() {
+    let atlas = latin1_atlas();
+    let syllables = |word: &str| match word {
+	"extraordinary" => vec![5, 10],
+	_ => vec![],
+    };
+    let text = "an extraordinary day";
+    let width = |text: &str| TextLayout::new(text, &atlas, None).line_boundaries()[0].rectangle.width as u32;
+    let max_width = width("an extra-") + 1;
+
+    let plain = TextLayout::new(text, &atlas, Some(max_width));
+    assert_eq!(lines(&plain, text), vec!["an ", "extraordinary ", "day"]);
+
+    let options = LayoutOptions::new().with_max_width(max_width).with_hyphenator(syllables);
+    let layout = TextLayout::with_options(text, &atlas, &options);
+    assert_eq!(lines(&layout, text), vec!["an extra", "ordinary ", "day"]);
+    assert_eq!(layout.line_boundaries()[0].rectangle.width as u32, width("an extra-"));
+
+    // The hyphen is drawn after the break, and carets skip it
+    let hyphen = layout.glyphs().iter().find(|glyph| glyph.c == SOFT_HYPHEN).unwrap();
+    assert_eq!((hyphen.line, hyphen.index), (0, 7));
+    assert_eq!(layout.generate_buffers(&atlas, 0, 0).len(), (text.len() - 2 + 1) * 6);
+    assert_eq!(layout.caret_position(8).y, layout.line_boundaries()[1].rectangle.y);
+
+    // Alone on its line, a word is broken as many times as needed
+    let text = "extraordinary";
+    let options = LayoutOptions::new().with_max_width(width("extra-").max(width("ordin-")) + 1).with_hyphenator(syllables);
+    assert_eq!(lines(&TextLayout::with_options(text, &atlas, &options), text), vec!["extra", "ordin", "ary"]);
+}
+
+#[test]
+fn paragraphs_are_indented_within_their_margins() {
+    let atlas = latin1_atlas();
+    let text = "the quick brown fox jumps over the lazy dog\nit sleeps on under the old oak tree";
+    let options = LayoutOptions::new().with_max_width(200).with_margins(5, 15).with_first_line_indent(30).with_hanging_indent(10);
+    let layout = TextLayout::with_options(text, &atlas, &options);
+
+    assert!(layout.line_count() > 4);
+    for (line, boundary) in layout.line_boundaries().iter().enumerate() {
+	let first_line = boundary.range.start == 0 || text[..boundary.range.start].ends_with('\n');
+	let x = if first_line { 35 } else { 15 };
+	let first = layout.glyphs().iter().find(|glyph| glyph.line == line).unwrap();
+	let last = layout.glyphs().iter().rfind(|glyph| glyph.line == line && !glyph.c.is_whitespace()).unwrap();
+
+	assert_eq!(first.x, x);
+	assert_eq!(boundary.rectangle.x, x as f32);
+	assert_eq!(boundary.rectangle.x + boundary.rectangle.width, (last.x + last.advance) as f32);
+	assert!(last.x + last.advance <= 185);
+    }
+
+    // Justified lines end at the right margin
+    let justified = TextLayout::with_options(text, &atlas, &options.with_justify(true));
+    let first_line = &justified.line_boundaries()[0].rectangle;
+    assert_eq!((first_line.x, first_line.x + first_line.width), (35.0, 185.0));
+
+    // The caret of an empty paragraph is at its indent
+    let layout = TextLayout::with_options("a\n\nb", &atlas, &LayoutOptions::new().with_margins(5, 0).with_first_line_indent(30));
+    assert_eq!(layout.caret_position(2).x, 35.0);
+}
+
+#[test]
+fn layouts_clamped_to_a_number_of_lines_report_the_text_consumed() {
+    let atlas = latin1_atlas();
+    let text = "the quick brown fox jumps over the lazy dog\nand sleeps";
+    let options = LayoutOptions::new().with_max_width(200);
+    let full = TextLayout::with_options(text, &atlas, &options);
+    assert!(full.line_count() > 2);
+    assert_eq!((full.consumed(), full.is_clamped()), (text.len(), false));
+
+    let clamped = TextLayout::with_options(text, &atlas, &options.clone().with_max_lines(2));
+    assert_eq!(clamped.line_count(), 2);
+    assert_eq!(lines(&clamped, text), lines(&full, text)[..2].to_vec());
+    assert_eq!(clamped.consumed(), full.line_boundaries()[2].range.start);
+    assert!(clamped.is_clamped());
+    assert!(clamped.glyphs().iter().all(|glyph| glyph.line < 2 && glyph.index < clamped.consumed()));
+    assert!(clamped.word_boundaries().iter().all(|word| word.range.end <= clamped.consumed()));
+    assert_eq!(clamped.generate_buffers(&atlas, 0, 0), full.generate_buffers(&atlas, 0, 0)[..clamped.generate_buffers(&atlas, 0, 0).len()].to_vec());
+
+    // A text fitting in the maximum isn't clamped
+    let fitting = TextLayout::with_options(text, &atlas, &options.with_max_lines(full.line_count()));
+    assert_eq!((fitting.consumed(), fitting.is_clamped()), (text.len(), false));
+}
+
+#[test]
+fn anchors_place_the_paragraph_around_the_given_position() {
+    let atlas = latin1_atlas();
+    let text = "Anchored\nparagraph";
+    let layout = TextLayout::new(text, &atlas, None);
+    let (width, height) = layout.size();
+    let baseline = layout.glyphs()[0].y;
+    assert_eq!(height, 2 * layout.line_boundaries()[0].rectangle.height as i32);
+
+    let anchored = |anchor: Anchor, y_axis: YAxis, x: i32, y: i32| {
+	TextLayout::new(text, &atlas, None).with_config(LayoutConfig::default().with_y_axis(y_axis).with_anchor(anchor)).generate_buffers(&atlas, x, y)
+    };
+
+    assert_eq!(anchored(Anchor::Center, YAxis::Down, 100, 100), anchored(Anchor::TopLeft, YAxis::Down, 100 - width / 2, 100 - height / 2));
+    assert_eq!(anchored(Anchor::BottomRight, YAxis::Up, 0, 0), anchored(Anchor::TopLeft, YAxis::Up, -width, height));
+    assert_eq!(anchored(Anchor::BaselineLeft, YAxis::Up, 0, 0), anchored(Anchor::TopLeft, YAxis::Up, 0, baseline));
+    assert_eq!(anchored(Anchor::Baseline, YAxis::Down, 0, 0), anchored(Anchor::TopLeft, YAxis::Down, -width / 2, -baseline));
+}
+
+#[test]
+fn prefixes_of_the_glyphs_are_revealed_without_laying_out_again() {
+    let atlas = latin1_atlas();
+    let text = "Type\nwriter é";
+    let layout = TextLayout::new(text, &atlas, None);
+    let vertices = layout.generate_buffers(&atlas, 5, 5);
+    let (_, indices) = layout.generate_indexed_buffers(&atlas, 5, 5);
+
+    assert!(layout.prefix_vertices(&atlas, 5, 5, 0).is_empty());
+    assert_eq!(layout.prefix_vertices(&atlas, 5, 5, usize::MAX), vertices);
+    assert_eq!(layout.prefix_vertex_count(&atlas, layout.glyphs().len()), indices.len());
+
+    for glyph_count in 0..=layout.glyphs().len() {
+	let count = layout.prefix_vertex_count(&atlas, glyph_count);
+	assert_eq!(layout.prefix_vertices(&atlas, 5, 5, glyph_count), vertices[..count].to_vec());
+    }
+
+    // The space has no vertices, so revealing it doesn't draw anything more
+    let space = layout.glyphs().iter().position(|glyph| glyph.c == ' ').unwrap();
+    assert_eq!(layout.prefix_vertex_count(&atlas, space), layout.prefix_vertex_count(&atlas, space + 1));
+    assert_eq!(layout.glyphs()[space].index, text.find(' ').unwrap());
+}
+
+#[test]
+fn cached_layouts_are_dropped_when_the_atlas_changes() {
+    let mut atlas = latin1_atlas();
+    let mut cache = LayoutCache::new(LayoutOptions::new());
+    let text = "A static label";
+    let expected = TextLayout::new(text, &atlas, Some(60)).glyphs().to_vec();
+
+    assert_eq!(cache.layout(&atlas, text, FontId(0), FONT_SIZE, Some(60)).glyphs(), expected.as_slice());
+    cache.layout(&atlas, text, FontId(0), FONT_SIZE, Some(60));
+    cache.layout(&atlas, text, FontId(0), FONT_SIZE, None);
+    assert_eq!(cache.len(), 2);
+
+    // Only the layouts requested since the last eviction are kept
+    cache.evict_unused();
+    cache.layout(&atlas, text, FontId(0), FONT_SIZE, None);
+    cache.evict_unused();
+    assert_eq!(cache.len(), 1);
+
+    // Repacking moves the glyphs, so the layouts are made again
+    let generation = atlas.generation();
+    atlas.repack().unwrap();
+    assert!(atlas.generation() > generation);
+    cache.layout(&atlas, "Another label", FontId(0), FONT_SIZE, None);
+    assert_eq!(cache.len(), 1);
+
+    cache.clear();
+    assert!(cache.is_empty());
+}
+
+#[test]
+fn cached_layouts_are_dropped_for_another_atlas() {
+    let font_system = FontSystem::new().unwrap();
+    let mut cache = LayoutCache::new(LayoutOptions::new());
+    let text = "A static label";
+    let atlas = latin1_atlas();
+    cache.layout(&atlas, text, FontId(0), FONT_SIZE, None);
+
+    // A new atlas of another font at the same size has a generation of its own, even once the first one is dropped
+    drop(atlas);
+    let options = AtlasGeneratorOption::new(256, 256, 72, Padding::uniform(1)).with_charset(text);
+    let atlas = AtlasGenerator::new(&font_system, FALLBACK_FONT_PATH, options, AtlasLoadMode::Gray).unwrap().generate(FONT_SIZE).unwrap();
+    let expected = TextLayout::new(text, &atlas, None).glyphs().to_vec();
+    assert_eq!(cache.layout(&atlas, text, FontId(0), FONT_SIZE, None).glyphs(), expected.as_slice());
+    assert_eq!(cache.len(), 1);
+
+    // Multi-font atlases get one at creation
+    let mut generator = MultiFontAtlasGenerator::new(AtlasGeneratorOption::new(256, 256, 72, Padding::uniform(1)), AtlasLoadMode::Gray);
+    generator.add_font(FontLoader::new(&font_system, FONT_PATH).unwrap(), text);
+    let first = generator.generate(FONT_SIZE).unwrap();
+    let second = generator.generate(FONT_SIZE).unwrap();
+    assert_ne!(first.generation(), second.generation());
+    assert_ne!(first.generation(), atlas.generation());
+}
+
+#[test]
+fn pages_are_exported_as_the_layers_of_a_texture_array() {
+    let font_system = FontSystem::new().unwrap();
+    let charset: String = ('a'..='z').chain('A'..='Z').collect();
+    let mut generator = MultiFontAtlasGenerator::new(AtlasGeneratorOption::new(64, 64, 72, Padding::uniform(1)), AtlasLoadMode::Gray);
+    generator.add_font(FontLoader::new(&font_system, FONT_PATH).unwrap(), &charset);
+    let mut atlas = generator.generate(FONT_SIZE).unwrap();
+    assert!(atlas.pages.len() > 1);
+
+    let array = atlas.texture_array();
+    assert_eq!(array.layers as usize, atlas.pages.len());
+    assert_eq!(array.data.len(), array.layer_size() * atlas.pages.len());
+    for (index, page) in atlas.pages.iter().enumerate() {
+	assert_eq!(array.layer(index), Some(&page.buffer[..]));
+    }
+    assert!(array.layer(atlas.pages.len()).is_none());
+
+    // Every glyph was packed since the atlas was created, and nothing since the export
+    for entry in atlas.map.values().filter(|entry| !entry.is_blank()) {
+	assert!(array.dirty_regions[entry.page()].contains(entry.position()));
+    }
+    assert!(atlas.texture_array().dirty_regions.iter().all(Vec::is_empty));
+}
+
+#[test]
+fn measurers_wrap_text_like_atlases() {
+    let atlas = latin1_atlas();
+    let font_system = FontSystem::new().unwrap();
+    let mut measurer = TextMeasurer::new(FontLoader::new(&font_system, FONT_PATH).unwrap(), FONT_SIZE, 72).unwrap();
+    assert_eq!(measurer.line_metrics(), atlas.line_metrics);
+
+    let text = "Measured on a server, wrapped on the client\tidentically";
+    let options = LayoutOptions::new().with_max_width(120);
+    let measured = measurer.layout(text, &options).unwrap();
+    let rendered = TextLayout::with_options(text, &atlas, &options);
+    assert_eq!(measured.glyphs(), rendered.glyphs());
+    assert_eq!(lines(&measured, text), lines(&rendered, text));
+
+    // Kerning only changes the widths measured with it
+    let advances: i32 = "AV".chars().map(|c| atlas.advance(c)).sum();
+    assert_eq!(measurer.kerned_width("AV").unwrap(), advances + measurer.kerning('A', 'V').unwrap());
+}
+
+#[test]
+fn lines_share_the_baseline_of_their_tallest_font() {
+    let font_system = FontSystem::new().unwrap();
+    let charset: String = ('a'..='z').chain(Some(' ')).collect();
+    let mut generator = MultiFontAtlasGenerator::new(AtlasGeneratorOption::new(512, 512, 72, Padding::uniform(1)), AtlasLoadMode::Gray);
+    let small = generator.add_font(FontLoader::new(&font_system, FONT_PATH).unwrap(), &charset);
+    let large = generator.add_font_sized(FontLoader::new(&font_system, FONT_PATH).unwrap(), &charset, FONT_SIZE * 2);
+    let atlas = generator.generate(FONT_SIZE).unwrap();
+    let small_metrics = *atlas.font_line_metrics(small).unwrap();
+    let large_metrics = *atlas.font_line_metrics(large).unwrap();
+    assert!(large_metrics.ascender > small_metrics.ascender);
+
+    let style = |font: FontId| TextStyle::new(Color::default(), font, Decoration::default());
+    let mut rich_text = RichText::new();
+    rich_text.push("small ", style(small)).push("large", style(large)).push("\nsmall", style(small));
+    let layout = TextLayout::from_rich_text(&rich_text, &MapTheme::default(), &atlas, &LayoutOptions::new().with_paragraph_spacing(10));
+    let boundaries = layout.line_boundaries();
+    assert_eq!(boundaries.len(), 2);
+
+    // The first line is as tall as its largest font, the small glyphs sitting on the baseline of the large ones
+    assert_eq!(boundaries[0].rectangle.height, large_metrics.line_height as f32);
+    assert!(layout.glyphs().iter().filter(|glyph| glyph.line == 0).all(|glyph| glyph.y == large_metrics.ascender));
+
+    // The line after the break only has the small font, and starts after the paragraph spacing
+    let second_top = large_metrics.line_height + 10;
+    assert_eq!(boundaries[1].rectangle.y, second_top as f32);
+    assert_eq!(boundaries[1].rectangle.height, small_metrics.line_height as f32);
+    assert!(layout.glyphs().iter().filter(|glyph| glyph.line == 1).all(|glyph| glyph.y == second_top + small_metrics.ascender));
+}
+
+#[test]
+fn paragraph_spacing_only_follows_line_breaks() {
+    let atlas = latin1_atlas();
+    let line_height = atlas.line_metrics.line_height;
+    let text = "one two\nthree";
+    let max_width = TextLayout::new("one tw", &atlas, None).glyphs().last().unwrap().x as u32;
+
+    let layout = TextLayout::with_options(text, &atlas, &LayoutOptions::new().with_max_width(max_width).with_paragraph_spacing(10));
+    assert_eq!(lines(&layout, text), vec!["one ", "two", "three"]);
+
+    let tops: Vec<f32> = layout.line_boundaries().iter().map(|line| line.rectangle.y).collect();
+    assert_eq!(tops, vec![0.0, line_height as f32, (2 * line_height + 10) as f32]);
+}
+
+#[test]
+fn decorations_follow_the_font_metrics_along_merged_runs() {
+    let atlas = latin1_atlas();
+    let metrics = atlas.line_metrics;
+    let (red, blue) = (Color::rgb(255, 0, 0), Color::rgb(0, 0, 255));
+    let underlined = TextStyle::new(red, FontId(0), Decoration::new(true, false));
+
+    // Adjacent spans of the same style are underlined at once, the plain span isn't decorated
+    let mut rich_text = RichText::new();
+    rich_text.push("under", underlined)
+	.push("lined ", underlined)
+	.push("plain ", TextStyle::default())
+	.push("struck", TextStyle::new(blue, FontId(0), Decoration::new(false, true)));
+    let layout = TextLayout::from_rich_text(&rich_text, &MapTheme::default(), &atlas, &LayoutOptions::new());
+    let vertices = layout.generate_decoration_buffers(&atlas, 0, 0);
+    assert_eq!(vertices.len(), 12);
+
+    let glyph_x = |index: usize| layout.glyphs().iter().find(|glyph| glyph.index == index).unwrap().x;
+    let last = layout.glyphs().last().unwrap();
+    // With y up, a stroke centered `position` above the baseline of the first line
+    let stroke = |position: i32, thickness: i32| {
+	let top = metrics.ascender - position - thickness / 2;
+	((-top - thickness) as f32, -top as f32)
+    };
+
+    let (bottom, top) = stroke(metrics.underline_position, metrics.underline_thickness);
+    assert_eq!(quad_bounds(&vertices[..6]), [0.0, bottom, glyph_x(11) as f32, top]);
+    assert!(vertices[..6].iter().all(|vertex| vertex.color == red.to_f32() && vertex.uv == [0.0, 0.0]));
+
+    let (bottom, top) = stroke(metrics.strikeout_position, metrics.strikeout_thickness);
+    assert_eq!(quad_bounds(&vertices[6..]), [glyph_x(17) as f32, bottom, (last.x + last.advance) as f32, top]);
+    assert!(vertices[6..].iter().all(|vertex| vertex.color == blue.to_f32()));
+
+    // Runs of different colors are drawn separately, even when they touch
+    let mut rich_text = RichText::new();
+    rich_text.push("red", underlined).push("blue", TextStyle { color: blue, ..underlined });
+    let layout = TextLayout::from_rich_text(&rich_text, &MapTheme::default(), &atlas, &LayoutOptions::new());
+    let vertices = layout.generate_decoration_buffers(&atlas, 0, 0);
+    assert_eq!(vertices.len(), 12);
+    assert_eq!(quad_bounds(&vertices[..6])[2], quad_bounds(&vertices[6..])[0]);
+}
+
+#[test]
+fn right_to_left_runs_are_placed_from_the_right() {
+    let atlas = latin1_atlas();
+    // The Hebrew letters aren't in the atlas, so they all advance like its .notdef glyph
+    let text = "ab אבג דה cd";
+    let layout = TextLayout::new(text, &atlas, None);
+    let glyph = |index: usize| *layout.glyphs().iter().find(|glyph| glyph.index == index).unwrap();
+    let (space, letter) = (glyph(2), glyph(3));
+    assert!(letter.advance > 0);
+
+    // The run keeps the place it had left to right, its first letter at its right end and its words in reverse order
+    let run_start = space.x + space.advance;
+    assert_eq!(glyph(12).x, run_start);
+    assert_eq!(glyph(3).x + glyph(3).advance, glyph(14).x);
+    let run: Vec<i32> = [3, 5, 7, 9, 10, 12].iter().map(|index| glyph(*index).x).collect();
+    assert!(run.windows(2).all(|pair| pair[0] > pair[1]), "{:?}", run);
+    assert!([3, 5, 7, 9, 10, 12].iter().all(|index| glyph(*index).right_to_left));
+    assert!(!glyph(1).right_to_left && !glyph(14).right_to_left);
+    assert_eq!(layout.word_at(3).unwrap().rectangle.x, glyph(7).x as f32);
+
+    // Selecting across the start of the run covers its right end, apart from the left to right text
+    let rects = layout.selection_rects(0..5);
+    assert_eq!(rects.len(), 2);
+    assert_eq!((rects[0].x, rects[0].width), (glyph(0).x as f32, (run_start - glyph(0).x) as f32));
+    assert_eq!((rects[1].x, rects[1].width), (glyph(3).x as f32, letter.advance as f32));
+
+    // The caret before a right to left letter is on its right edge, and its left half hits the caret after it
+    assert_eq!(layout.caret_position(3).x, (glyph(3).x + letter.advance) as f32);
+    assert_eq!(layout.hit_test(glyph(3).x as f32 + 1.0, 0.0), 5);
+    assert_eq!(layout.hit_test((glyph(3).x + letter.advance) as f32 - 1.0, 0.0), 3);
+
+    // Numbers inside the run stay left to right, between the words around them
+    let layout = TextLayout::new("אב 12 גד", &atlas, None);
+    let x = |index: usize| layout.glyphs().iter().find(|glyph| glyph.index == index).unwrap().x;
+    assert!(x(10) < x(8) && x(8) < x(5) && x(5) < x(6) && x(6) < x(2) && x(2) < x(0));
+    assert!(!layout.glyphs().iter().find(|glyph| glyph.index == 5).unwrap().right_to_left);
+}
@@ -0,0 +1,138 @@
+use proptest::prelude::*;
+
+use font::{Node, NodeRef, Rectangle};
+use font::atlas::FontAtlas;
+
+fn overlap(a: &Rectangle, b: &Rectangle) -> bool {
+    a.left < b.left + b.width && b.left < a.left + a.width && a.top < b.top + b.height && b.top < a.top + a.height
+}
+
+fn inside(rectangle: &Rectangle, width: u32, height: u32) -> bool {
+    rectangle.left + rectangle.width <= width && rectangle.top + rectangle.height <= height
+}
+
+/// Checks that the rectangles are inside a `width` by `height` area and that no two of them overlap.
+fn check_packing(rectangles: &[Rectangle], width: u32, height: u32) -> Result<(), TestCaseError> {
+    for (index, rectangle) in rectangles.iter().enumerate() {
+	prop_assert!(inside(rectangle, width, height), "{:?} is outside the {}x{} area", rectangle, width, height);
+
+	for other in rectangles[index + 1..].iter() {
+	    prop_assert!(!overlap(rectangle, other), "{:?} overlaps {:?}", rectangle, other);
+	}
+    }
+
+    Ok(())
+}
+
+fn rectangle() -> impl Strategy<Value = Rectangle> {
+    (0u32..64, 0u32..64, 0u32..32, 0u32..32).prop_map(|(top, left, width, height)| Rectangle::new(top, left, width, height))
+}
+
+fn sizes() -> impl Strategy<Value = Vec<(u32, u32)>> {
+    prop::collection::vec((1u32..48, 1u32..48), 0..150)
+}
+
+proptest! {
+    #[test]
+    fn node_packs_inside_without_overlap(width in 1u32..300, height in 1u32..300, sizes in sizes()) {
+	let mut node = Node::new(Rectangle::new(0, 0, width, height));
+	let mut packed = Vec::new();
+
+	for (rectangle_width, rectangle_height) in sizes {
+	    if let Ok(rectangle) = node.insert(&Rectangle::new(0, 0, rectangle_width, rectangle_height)) {
+		prop_assert_eq!((rectangle.width, rectangle.height), (rectangle_width, rectangle_height));
+		packed.push(rectangle);
+	    }
+	}
+
+	check_packing(&packed, width, height)?;
+
+	// The occupied nodes of the tree are the packed rectangles
+	let mut occupied = Vec::new();
+	if node.occupied() {
+	    occupied.push(node.rectangle);
+	}
+	let mut pending: Vec<NodeRef> = node.children().iter().flatten().copied().collect();
+	while let Some(child) = pending.pop() {
+	    if child.occupied() {
+		prop_assert!(child.is_leaf());
+		occupied.push(child.rectangle());
+	    }
+	    pending.extend(child.children().iter().flatten());
+	}
+	occupied.sort_by_key(|rectangle| (rectangle.top, rectangle.left));
+	packed.sort_by_key(|rectangle| (rectangle.top, rectangle.left));
+	prop_assert_eq!(occupied, packed);
+    }
+
+    #[test]
+    fn rectangle_arithmetic_agrees_with_pixel_sets(a in rectangle(), b in rectangle()) {
+	let covers = |rectangle: &Rectangle, x: u32, y: u32| {
+	    rectangle.left <= x && x < rectangle.right() && rectangle.top <= y && y < rectangle.bottom()
+	};
+
+	prop_assert_eq!(a.intersection(&b), b.intersection(&a));
+	if !a.is_empty() || !b.is_empty() {
+	    prop_assert_eq!(a.union(&b), b.union(&a));
+	}
+	prop_assert!(a.union(&b).contains(&a) && a.union(&b).contains(&b));
+	prop_assert_eq!(a.contains(&b), a.union(&b) == a);
+
+	let mut common = 0;
+	for y in 0..96 {
+	    for x in 0..96 {
+		let both = covers(&a, x, y) && covers(&b, x, y);
+		prop_assert_eq!(a.intersection(&b).is_some_and(|intersection| covers(&intersection, x, y)), both);
+		common += both as u64;
+	    }
+	}
+	// Empty rectangles cover no pixel, so they intersect nothing
+	prop_assert_eq!(a.intersects(&b), common > 0);
+	prop_assert_eq!(a.intersection(&b).map_or(0, |intersection| intersection.area()), common);
+    }
+
+    #[test]
+    fn node_rejects_rectangles_larger_than_the_area(width in 1u32..300, height in 1u32..300, extra in 1u32..50) {
+	let mut node = Node::new(Rectangle::new(0, 0, width, height));
+
+	prop_assert!(node.insert(&Rectangle::new(0, 0, width + extra, 1)).is_err());
+	prop_assert!(node.insert(&Rectangle::new(0, 0, 1, height + extra)).is_err());
+	prop_assert_eq!(node.insert(&Rectangle::new(0, 0, width, height)).ok(), Some(Rectangle::new(0, 0, width, height)));
+	prop_assert!(node.insert(&Rectangle::new(0, 0, 1, 1)).is_err());
+    }
+
+    #[test]
+    fn reserved_regions_survive_repacking(width in 16u32..256, height in 16u32..256, sizes in sizes(), new_sizes in prop::collection::vec(1u32..16, 0..50)) {
+	let mut atlas = FontAtlas::new((width, height));
+	for (region_width, region_height) in sizes {
+	    let _ = atlas.reserve(region_width, region_height);
+	}
+
+	let reserved = atlas.reserved().to_vec();
+	check_packing(&reserved, width, height)?;
+
+	// Packing in another order can fail, which leaves the atlas as it was
+	let repacked = atlas.repack();
+	let regions_after = atlas.reserved().to_vec();
+	check_packing(&regions_after, width, height)?;
+
+	// Every region keeps its size, and its previous position maps to its new one
+	prop_assert_eq!(regions_after.len(), reserved.len());
+	for (before, after) in reserved.iter().zip(regions_after.iter()) {
+	    prop_assert_eq!((before.width, before.height), (after.width, after.height));
+	    match &repacked {
+		Ok(remap) => prop_assert_eq!(remap.get(before), Some(after)),
+		Err(_) => prop_assert_eq!(before, after),
+	    }
+	}
+
+	// New regions never overlap the kept ones
+	let mut regions = regions_after;
+	for size in new_sizes {
+	    if let Ok(region) = atlas.reserve(size, size) {
+		regions.push(region);
+	    }
+	}
+	check_packing(&regions, width, height)?;
+    }
+}
@@ -0,0 +1,103 @@
+use font::path::TextPath;
+
+fn assert_close(a: [f32; 2], b: [f32; 2]) {
+    assert!((a[0] - b[0]).abs() < 1e-3 && (a[1] - b[1]).abs() < 1e-3, "{:?} != {:?}", a, b);
+}
+
+#[test]
+fn paths_are_sampled_by_distance() {
+    let path = TextPath::from_points(&[[0.0, 0.0], [10.0, 0.0], [10.0, 10.0]]);
+
+    assert_eq!(path.length(), 20.0);
+    assert_eq!(path.sample(5.0), Some(([5.0, 0.0], [1.0, 0.0])));
+    assert_eq!(path.sample(15.0), Some(([10.0, 5.0], [0.0, 1.0])));
+    assert_eq!(path.sample(-1.0), None);
+    assert_eq!(path.sample(21.0), None);
+    assert_eq!(TextPath::new([0.0, 0.0]).sample(0.0), None);
+
+    // Curves are flattened, and ones with aligned control points are straight
+    let curve = TextPath::new([0.0, 0.0]).cubic_to([10.0, 0.0], [20.0, 0.0], [30.0, 0.0]);
+    assert!((curve.length() - 30.0).abs() < 1e-3);
+    assert!(curve.points().len() > 2);
+
+    let arc = TextPath::new([0.0, 0.0]).quadratic_to([50.0, 50.0], [100.0, 0.0]);
+    let (point, tangent) = arc.sample(arc.length() / 2.0).unwrap();
+    assert_close(point, [50.0, 25.0]);
+    assert!(tangent[0] > 0.99);
+}
+
+#[cfg(feature = "freetype")]
+mod layout {
+    use font::atlas::{AtlasGenerator, AtlasGeneratorOption, AtlasLoadMode, FontAtlas, GlyphQuad, Padding, GLYPHS};
+    use font::layout::{LayoutConfig, TextLayout};
+    use font::loader::FontSystem;
+    use font::path::TextPath;
+
+    use super::assert_close;
+
+    /// The OFL licensed font shipped in the repository.
+    const FONT_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/fonts/EBGaramond-Regular.ttf");
+
+    const FONT_SIZE: u32 = 24 * 64;
+
+    fn generate() -> FontAtlas {
+	let font_system = FontSystem::new().unwrap();
+	let options = AtlasGeneratorOption::new(256, 256, 72, Padding::uniform(1)).with_charset(GLYPHS);
+
+	AtlasGenerator::new(&font_system, FONT_PATH, options, AtlasLoadMode::Gray).unwrap().generate(FONT_SIZE).unwrap()
+    }
+
+    #[test]
+    fn text_on_a_straight_path_is_laid_on_its_baseline() {
+	let atlas = generate();
+	let layout = TextLayout::new("Map label", &atlas, None);
+	let baseline = layout.glyphs()[0].y;
+
+	let path = TextPath::from_points(&[[0.0, 0.0], [1000.0, 0.0]]);
+	let (on_path, indices) = layout.generate_indexed_buffers_on_path(&atlas, &path, 0.0);
+	let (flat, flat_indices) = layout.generate_indexed_buffers(&atlas, 0, baseline);
+
+	assert_eq!(indices, flat_indices);
+	for (vertex, flat) in on_path.iter().zip(flat.iter()) {
+	    assert_close(vertex.position, flat.position);
+	    assert_eq!(vertex.uv, flat.uv);
+	}
+    }
+
+    #[test]
+    fn glyphs_follow_the_tangent_and_stop_at_the_end_of_the_path() {
+	let atlas = generate();
+	let layout = TextLayout::new("Hi", &atlas, None);
+
+	// Going up, the glyphs are turned a quarter counter-clockwise and stand on the left of the path, without descenders here
+	let path = TextPath::from_points(&[[0.0, 0.0], [0.0, 1000.0]]);
+	let (vertices, _) = layout.generate_indexed_buffers_on_path(&atlas, &path, 10.0);
+	assert_eq!(vertices.len(), 2 * 4);
+	assert!(vertices.iter().all(|vertex| vertex.position[0] <= 1.0 && vertex.position[1] >= 9.0));
+	assert!(vertices[4].position[1] > vertices[0].position[1]);
+
+	// Past the end of the path, glyphs are left out
+	let width = layout.glyphs()[0].advance as f32;
+	let path = TextPath::from_points(&[[0.0, 0.0], [width, 0.0]]);
+	assert_eq!(layout.generate_indexed_buffers_on_path(&atlas, &path, 0.0).0.len(), 4);
+    }
+
+    #[test]
+    fn glyphs_on_fractional_positions_can_be_snapped_to_pixels() {
+	let atlas = generate();
+	let path = TextPath::from_points(&[[0.3, 0.4], [1000.3, 0.4]]);
+	let is_whole = |value: f32| value.fract() == 0.0;
+
+	let (blurry, _) = TextLayout::new("Crisp", &atlas, None).generate_indexed_buffers_on_path(&atlas, &path, 0.0);
+	assert!(blurry.iter().all(|vertex| !is_whole(vertex.position[0]) && !is_whole(vertex.position[1])));
+
+	let layout = TextLayout::new("Crisp", &atlas, None).with_config(LayoutConfig::default().with_pixel_snap(true));
+	let (crisp, _) = layout.generate_indexed_buffers_on_path(&atlas, &path, 0.0);
+	assert!(crisp.iter().all(|vertex| is_whole(vertex.position[0]) && is_whole(vertex.position[1])));
+	assert_eq!(crisp.iter().map(|vertex| vertex.uv).collect::<Vec<_>>(), blurry.iter().map(|vertex| vertex.uv).collect::<Vec<_>>());
+
+	// In points drawn at 2 pixels per point, corners land on half points
+	let quad = GlyphQuad { index: 0, positions: [[0.3, 0.8], [10.3, 0.8], [0.3, 20.8], [10.3, 20.8]], uvs: [[0.0; 2]; 4], color: [1.0; 4], page: 0 };
+	assert_eq!(quad.snapped(2.0).positions, [[0.5, 1.0], [10.5, 1.0], [0.5, 21.0], [10.5, 21.0]]);
+    }
+}
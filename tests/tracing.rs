@@ -0,0 +1,88 @@
+#![cfg(all(feature = "tracing", feature = "freetype"))]
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Level, Metadata, Subscriber};
+
+use font::atlas::{AtlasGenerator, AtlasGeneratorOption, AtlasLoadMode, Padding};
+use font::loader::FontSystem;
+
+/// The OFL licensed font shipped in the repository.
+const FONT_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/fonts/EBGaramond-Regular.ttf");
+
+const FONT_SIZE: u32 = 24 * 64;
+
+/// A subscriber keeping the names of the spans created and the levels of the events.
+#[derive(Default, Clone)]
+struct Recorder {
+    spans: Arc<Mutex<Vec<&'static str>>>,
+    events: Arc<Mutex<Vec<Level>>>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl Recorder {
+    fn span_count(&self, name: &str) -> usize {
+	self.spans.lock().unwrap().iter().filter(|span| **span == name).count()
+    }
+}
+
+impl Subscriber for Recorder {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+	true
+    }
+
+    fn new_span(&self, attributes: &Attributes) -> Id {
+	self.spans.lock().unwrap().push(attributes.metadata().name());
+	Id::from_u64(self.next_id.fetch_add(1, Ordering::Relaxed) + 1)
+    }
+
+    fn record(&self, _span: &Id, _values: &Record) {}
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event) {
+	self.events.lock().unwrap().push(*event.metadata().level());
+    }
+
+    fn enter(&self, _span: &Id) {}
+
+    fn exit(&self, _span: &Id) {}
+}
+
+#[test]
+fn generation_is_traced_glyph_by_glyph() {
+    let font_system = FontSystem::new().unwrap();
+    let options = AtlasGeneratorOption::new(512, 512, 72, Padding::new(0, 0, 0, 0)).with_charset("abc").with_notdef();
+    let recorder = Recorder::default();
+
+    tracing::subscriber::with_default(recorder.clone(), || {
+	let generator = AtlasGenerator::new(&font_system, FONT_PATH, options, AtlasLoadMode::Gray).unwrap();
+	generator.generate(FONT_SIZE).unwrap();
+    });
+
+    assert_eq!(recorder.span_count("new_face"), 1);
+    assert_eq!(recorder.span_count("generate"), 1);
+    assert_eq!(recorder.span_count("pack_char"), 3);
+    assert_eq!(recorder.span_count("rasterize"), 3);
+    assert_eq!(recorder.span_count("finalize"), 1);
+    assert!(!recorder.events.lock().unwrap().contains(&Level::ERROR));
+}
+
+#[test]
+fn glyphs_that_fail_are_reported() {
+    let font_system = FontSystem::new().unwrap();
+    let options = AtlasGeneratorOption::new(4, 4, 72, Padding::new(0, 0, 0, 0)).with_charset("W");
+    let recorder = Recorder::default();
+
+    let result = tracing::subscriber::with_default(recorder.clone(), || {
+	let generator = AtlasGenerator::new(&font_system, FONT_PATH, options, AtlasLoadMode::Gray).unwrap();
+	generator.generate(FONT_SIZE).map(|_| ())
+    });
+
+    assert!(result.is_err());
+    assert_eq!(recorder.span_count("finalize"), 0);
+    // Both the glyph and the generation report the error
+    assert_eq!(recorder.events.lock().unwrap().iter().filter(|level| **level == Level::ERROR).count(), 2);
+}